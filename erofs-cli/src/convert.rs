@@ -1,8 +1,16 @@
-use std::{fs::File, os::unix::fs::PermissionsExt, time::UNIX_EPOCH};
+use std::{
+    collections::{HashMap, hash_map},
+    ffi::OsStr,
+    fs::File,
+    os::unix::{ffi::OsStrExt, fs::PermissionsExt},
+    path::PathBuf,
+    time::UNIX_EPOCH,
+};
 
 use anyhow::{Context, Result};
 use clap::Args;
-use erofs_rs::{EroFS, backend::MmapImage};
+use erofs_rs::{DirEntry, EroFS, types::Inode};
+use glob::{MatchOptions, Pattern};
 use tar::Header;
 
 #[derive(Args, Debug)]
@@ -14,38 +22,133 @@ pub struct ConvertArgs {
     output: String,
     #[clap(short, long)]
     format: Option<String>,
+    /// Skip entries matching a glob pattern. Patterns are matched against
+    /// the archived path and the file name; excluded directories are not
+    /// descended into. Names that are not valid UTF-8 are matched with each
+    /// invalid sequence replaced by U+FFFD, so they can't be excluded by
+    /// their exact bytes. May be given more than once.
+    #[clap(short, long)]
+    exclude: Vec<String>,
 }
 
 pub fn convert(args: ConvertArgs) -> Result<()> {
-    let image = MmapImage::new_from_path(args.path)?;
-    let fs = EroFS::new(image)?;
+    let fs = EroFS::open_path(args.path)?;
+    let excludes = args
+        .exclude
+        .iter()
+        .map(|pattern| Pattern::new(pattern).with_context(|| format!("invalid pattern {pattern}")))
+        .collect::<Result<Vec<_>>>()?;
 
     let out_file = File::create(args.output)?;
     let mut tar = tar::Builder::new(out_file);
+    // First path archived for each multiply linked inode, by nid.
+    let mut links = HashMap::new();
 
-    for entry in fs.walk_dir(args.root)? {
+    // Sorted so that the archive doesn't depend on how the image was built.
+    let walk = fs
+        .walk_dir(args.root)?
+        .sort_by_name()
+        .resolve_symlinks(true)
+        .filter_entry(|entry| !is_excluded(&excludes, &entry.dir_entry));
+    for entry in walk {
         let entry = entry.context("read entry failed")?;
+        // Names are archived as stored, even if they are not valid UTF-8.
+        let path = entry.dir_entry.path();
+        let path = PathBuf::from(OsStr::from_bytes(path.strip_prefix("/")?.as_bytes()));
 
+        // Like GNU tar, skip sockets: archives cannot represent them.
+        if entry.inode.is_socket() {
+            eprintln!("{}: socket ignored", path.display());
+            continue;
+        }
         let mut header = Header::new_gnu();
-        header.set_path(entry.dir_entry.path().strip_prefix("/")?.to_string())?;
+        header.set_path(&path)?;
         header.set_mode(entry.inode.permissions().mode());
-        if let Some(time) = entry.inode.modified() {
+        header.set_uid(entry.inode.uid().into());
+        header.set_gid(entry.inode.gid().into());
+        if let Some(time) = entry.inode.modified().or_else(|| fs.build_time()) {
             header.set_mtime(time.duration_since(UNIX_EPOCH)?.as_secs());
         }
 
+        // Every name of a multiply linked inode after the first is archived
+        // as a hard link to the first, whatever the inode's type.
+        if !entry.inode.is_dir() && entry.inode.nlink() > 1 {
+            match links.entry(entry.inode.nid()) {
+                hash_map::Entry::Occupied(target) => {
+                    header.set_entry_type(tar::EntryType::Link);
+                    header.set_link_name(target.get())?;
+                    header.set_size(0);
+                    header.set_cksum();
+                    tar.append(&header, std::io::empty())?;
+                    continue;
+                }
+                hash_map::Entry::Vacant(slot) => {
+                    slot.insert(path);
+                }
+            }
+        }
+
         if entry.dir_entry.file_type().is_dir() {
             header.set_entry_type(tar::EntryType::Directory);
             header.set_size(0);
             header.set_cksum();
             tar.append(&header, std::io::empty())?;
+        } else if let Some(target) = &entry.link_target {
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_link_name(OsStr::from_bytes(target.as_bytes()))?;
+            header.set_size(0);
+            header.set_cksum();
+            tar.append(&header, std::io::empty())?;
+        } else if let Some(entry_type) = special_entry_type(&entry.inode) {
+            header.set_entry_type(entry_type);
+            if let Some((major, minor)) = entry.inode.device_id() {
+                header.set_device_major(major)?;
+                header.set_device_minor(minor)?;
+            }
+            header.set_size(0);
+            header.set_cksum();
+            tar.append(&header, std::io::empty())?;
         } else {
             header.set_entry_type(tar::EntryType::Regular);
             header.set_size(entry.inode.data_size() as u64);
             header.set_cksum();
 
+            // The file is copied through its reader one block at a time, so
+            // memory use doesn't grow with the file size.
             tar.append(&header, fs.open_inode_file(entry.inode)?)?;
         }
     }
 
     Ok(())
 }
+
+/// Whether `entry` matches any of the `--exclude` patterns, comparing
+/// against the lossy UTF-8 form of its path and name.
+fn is_excluded(excludes: &[Pattern], entry: &DirEntry) -> bool {
+    if excludes.is_empty() {
+        return false;
+    }
+    let path = entry.path();
+    let path = path.to_string_lossy();
+    let path = path.trim_start_matches('/');
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::new()
+    };
+    excludes.iter().any(|pattern| {
+        pattern.matches_with(path, options) || pattern.matches_with(entry.file_name(), options)
+    })
+}
+
+/// Tar entry type of device nodes and fifos, which carry no data.
+fn special_entry_type(inode: &Inode) -> Option<tar::EntryType> {
+    if inode.is_block_device() {
+        Some(tar::EntryType::Block)
+    } else if inode.is_char_device() {
+        Some(tar::EntryType::Char)
+    } else if inode.is_fifo() {
+        Some(tar::EntryType::Fifo)
+    } else {
+        None
+    }
+}
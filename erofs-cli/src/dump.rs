@@ -4,13 +4,11 @@ use clap::Args;
 use erofs_rs::{
     EroFS,
     r#async::EroFS as AsyncEroFS,
-    backend::MmapImage,
     backend::OpendalImage,
     types::{SB_EXTSLOT_SIZE, SuperBlock},
 };
 use opendal::{Operator, services};
 use url::{Position, Url};
-use uuid::Uuid;
 
 #[derive(Args, Debug)]
 pub struct DumpArgs {
@@ -27,7 +25,7 @@ pub struct DumpArgs {
 // Filesystem sb_size:                           128
 // Filesystem inode count:                       516
 // Filesystem created:                           Fri Dec  5 00:48:29 2025
-// Filesystem features:                          sb_csum mtime xattr_filter
+// Filesystem features:                          sb_chksum mtime xattr_filter
 // Filesystem UUID:                              71bd9ab4-fb8c-47b4-986c-5c901ad547c7
 
 pub async fn dump(args: DumpArgs) -> Result<()> {
@@ -39,8 +37,7 @@ pub async fn dump(args: DumpArgs) -> Result<()> {
         let fs = AsyncEroFS::new(image).await?;
         fs.super_block().to_owned()
     } else {
-        let image = MmapImage::new_from_path(args.path)?;
-        let fs = EroFS::new(image)?;
+        let fs = EroFS::open_path(args.path)?;
         fs.super_block().to_owned()
     };
 
@@ -50,7 +47,7 @@ pub async fn dump(args: DumpArgs) -> Result<()> {
     );
     println!(
         "Filesystem blocksize:                         {}",
-        1 << block.blk_size_bits
+        block.block_size()
     );
     println!(
         "Filesystem blocks:                            {}",
@@ -87,15 +84,28 @@ pub async fn dump(args: DumpArgs) -> Result<()> {
         "Filesystem created:                           {}",
         created
     );
+    let features = block
+        .compat_features()
+        .iter_names()
+        .map(|(name, _)| name)
+        .chain(block.incompat_features().iter_names().map(|(name, _)| name))
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>();
     println!(
         "Filesystem features:                          {}",
-        block.feature_compat
+        features.join(" ")
     );
 
     println!(
         "Filesystem UUID:                              {}",
-        Uuid::from_bytes(block.uuid)
+        block.uuid_string()
     );
+    if !block.volume_name().is_empty() {
+        println!(
+            "Filesystem volume name:                       {}",
+            block.volume_name()
+        );
+    }
 
     Ok(())
 }
@@ -1,12 +1,10 @@
-use std::os::unix::fs::PermissionsExt;
-
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use chrono::{DateTime, Datelike, Local};
 use clap::{Args, Subcommand};
 use erofs_rs::{
-    EroFS,
+    DirEntry, EroFS,
     r#async::EroFS as AsyncEroFS,
-    backend::{AsyncImage, Image, MmapImage, OpendalImage},
+    backend::{AsyncImage, Image, OpendalImage},
     types::Inode,
 };
 use opendal::{Operator, services};
@@ -26,6 +24,12 @@ enum InspectSubcommands {
     Ls {
         #[clap(default_value = "/")]
         path: String,
+        /// Show the mode, size and modification time of each entry
+        #[clap(short, long)]
+        long: bool,
+        /// List subdirectories recursively
+        #[clap(short = 'R', long)]
+        recursive: bool,
     },
     Cat {
         path: String,
@@ -42,16 +46,23 @@ pub async fn inspect(args: InspectArgs) -> Result<()> {
         let fs = AsyncEroFS::new(image).await?;
 
         match args.operation {
-            InspectSubcommands::Ls { path } => ls_async(&fs, &path).await?,
+            InspectSubcommands::Ls {
+                path,
+                long,
+                recursive,
+            } => ls_async(&fs, &path, long, recursive).await?,
             InspectSubcommands::Cat { path } => cat_async(&fs, &path).await?,
         }
     } else {
         // Sync path for local files
-        let image = MmapImage::new_from_path(args.image)?;
-        let fs = EroFS::new(image)?;
+        let fs = EroFS::open_path(args.image)?;
 
         match args.operation {
-            InspectSubcommands::Ls { path } => ls(&fs, &path)?,
+            InspectSubcommands::Ls {
+                path,
+                long,
+                recursive,
+            } => ls(&fs, &path, long, recursive)?,
             InspectSubcommands::Cat { path } => cat(&fs, &path)?,
         }
     }
@@ -59,34 +70,6 @@ pub async fn inspect(args: InspectArgs) -> Result<()> {
     Ok(())
 }
 
-fn format_mode(inode: &Inode) -> String {
-    let mut res = String::with_capacity(10);
-    res.push(if inode.is_dir() { 'd' } else { '-' });
-
-    let masks = [
-        (0o400, 'r'),
-        (0o200, 'w'),
-        (0o100, 'x'), // User
-        (0o040, 'r'),
-        (0o020, 'w'),
-        (0o010, 'x'), // Group
-        (0o004, 'r'),
-        (0o002, 'w'),
-        (0o001, 'x'), // Other
-    ];
-
-    let mode = inode.permissions().mode();
-    for (mask, char) in masks {
-        if mode & mask != 0 {
-            res.push(char);
-        } else {
-            res.push('-');
-        }
-    }
-
-    res
-}
-
 fn format_size(inode: &Inode) -> String {
     let size = inode.data_size();
     if size < 1024 {
@@ -115,70 +98,96 @@ fn format_time(inode: &Inode) -> String {
     }
 }
 
-fn ls<I: Image>(fs: &EroFS<I>, path: &str) -> Result<()> {
-    let read_dir = fs
-        .read_dir(path)
-        .with_context(|| format!("failed to read directory: {}", path))?;
+/// Prints a listed entry: its name, or its path below `root` when listing
+/// recursively, preceded by its mode, size and modification time in the
+/// long format.
+fn print_entry(root: &str, entry: &DirEntry, inode: &Inode, long: bool, recursive: bool) {
+    let name = if recursive {
+        let path = entry.path();
+        path.strip_prefix(root)
+            .map_or_else(|_| path.to_string_lossy(), |p| p.to_string_lossy())
+            .into_owned()
+    } else {
+        entry.file_name().to_string()
+    };
 
-    for entry in read_dir {
-        let entry = entry.with_context(|| "failed to read directory entry")?;
-        let inode = entry.inode;
+    if long {
         println!(
             "{} {:>8} {} {}",
-            format_mode(&inode),
-            format_size(&inode),
-            format_time(&inode),
-            entry.dir_entry.file_name()
+            inode.mode_string(),
+            format_size(inode),
+            format_time(inode),
+            name
         );
+    } else {
+        println!("{}", name);
+    }
+}
+
+fn ls<I: Image>(fs: &EroFS<I>, path: &str, long: bool, recursive: bool) -> Result<()> {
+    let walk_dir = fs
+        .walk_dir(path)
+        .with_context(|| format!("failed to read directory: {}", path))?;
+    let walk_dir = if recursive {
+        walk_dir
+    } else {
+        walk_dir.max_depth(1)
+    };
+
+    for entry in walk_dir {
+        let entry = entry.with_context(|| "failed to read directory entry")?;
+        print_entry(path, &entry.dir_entry, &entry.inode, long, recursive);
     }
 
     Ok(())
 }
 
 fn cat<I: Image>(fs: &EroFS<I>, path: &str) -> Result<()> {
+    if fs.is_dir(path) {
+        bail!("{}: is a directory", path);
+    }
     let mut file = fs
         .open(path)
         .with_context(|| format!("failed to open file: {}", path))?;
 
-    if file.size() > 1024 * 1024 {
-        return Err(anyhow::anyhow!("file too large to output (>{} MiB)", 1));
-    }
-
-    std::io::copy(&mut file, &mut std::io::stdout())?;
+    // The file is copied a block at a time, so its size doesn't matter.
+    std::io::copy(&mut file, &mut std::io::stdout().lock())?;
     Ok(())
 }
 
-async fn ls_async<I: AsyncImage>(fs: &AsyncEroFS<I>, path: &str) -> Result<()> {
-    let mut read_dir = fs
-        .read_dir(path)
+async fn ls_async<I: AsyncImage>(
+    fs: &AsyncEroFS<I>,
+    path: &str,
+    long: bool,
+    recursive: bool,
+) -> Result<()> {
+    let walk_dir = fs
+        .walk_dir(path)
         .await
         .with_context(|| format!("failed to read directory: {}", path))?;
+    let mut walk_dir = if recursive {
+        walk_dir
+    } else {
+        walk_dir.max_depth(1)
+    };
 
-    while let Some(result) = read_dir.next_entry().await {
+    while let Some(result) = walk_dir.next_entry().await {
         let entry = result.with_context(|| "failed to read directory entry")?;
-        let inode = entry.inode;
-        println!(
-            "{} {:>8} {} {}",
-            format_mode(&inode),
-            format_size(&inode),
-            format_time(&inode),
-            entry.dir_entry.file_name()
-        );
+        print_entry(path, &entry.dir_entry, &entry.inode, long, recursive);
     }
 
     Ok(())
 }
 
 async fn cat_async<I: AsyncImage>(fs: &AsyncEroFS<I>, path: &str) -> Result<()> {
+    if fs.is_dir(path).await {
+        bail!("{}: is a directory", path);
+    }
     let mut file = fs
         .open(path)
         .await
         .with_context(|| format!("failed to open file: {}", path))?;
 
-    if file.size() > 1024 * 1024 {
-        return Err(anyhow::anyhow!("file too large to output (>{} MiB)", 1));
-    }
-
     let mut buffer = vec![0u8; 4096];
     let mut stdout = std::io::stdout();
 
@@ -0,0 +1,371 @@
+#[path = "../../erofs/tests/common/mod.rs"]
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use common::{Data, ImageBuilder, S_IFBLK, S_IFCHR, S_IFIFO, S_IFSOCK};
+
+/// Writes `img` to a scratch file named after the calling test.
+fn write_image(name: &str, img: &[u8]) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("image.erofs");
+    fs::write(&path, img).unwrap();
+    path
+}
+
+/// Runs `convert` on `img` and returns the archived entries as
+/// `(path, header, contents)`.
+fn convert(name: &str, img: &[u8], args: &[&str]) -> Vec<(String, tar::Header, Vec<u8>)> {
+    let image = write_image(name, img);
+    let output = image.with_extension("tar");
+    let status = Command::new(env!("CARGO_BIN_EXE_erofs-cli"))
+        .arg("convert")
+        .arg(&image)
+        .arg("--output")
+        .arg(&output)
+        .args(args)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let mut archive = tar::Archive::new(fs::File::open(&output).unwrap());
+    archive
+        .entries()
+        .unwrap()
+        .map(|entry| {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().to_string_lossy().into_owned();
+            let header = entry.header().clone();
+            let mut data = Vec::new();
+            std::io::Read::read_to_end(&mut entry, &mut data).unwrap();
+            (path, header, data)
+        })
+        .collect()
+}
+
+/// Runs `inspect` on `img` with `args` and returns its output lines.
+fn inspect(name: &str, img: &[u8], args: &[&str]) -> Vec<String> {
+    let image = write_image(name, img);
+    let output = Command::new(env!("CARGO_BIN_EXE_erofs-cli"))
+        .arg("inspect")
+        .arg("--image")
+        .arg(&image)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn convert_archives_special_files() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let sda1 = builder.special(S_IFBLK, 0x801);
+    let tty = builder.special(S_IFCHR, 0x401);
+    let fifo = builder.special(S_IFIFO, 0);
+    let socket = builder.special(S_IFSOCK, 0);
+    builder.dir(
+        root,
+        root,
+        &[
+            ("fifo", fifo),
+            ("sda1", sda1),
+            ("socket", socket),
+            ("tty1", tty),
+        ],
+    );
+    let img = builder.build();
+
+    let entries = convert("convert_archives_special_files", &img, &[]);
+    let kinds: Vec<_> = entries
+        .iter()
+        .map(|(path, header, _)| (path.as_str(), header.entry_type()))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            ("fifo", tar::EntryType::Fifo),
+            ("sda1", tar::EntryType::Block),
+            ("tty1", tar::EntryType::Char),
+        ]
+    );
+
+    let (_, sda1, _) = &entries[1];
+    assert_eq!(sda1.device_major().unwrap(), Some(8));
+    assert_eq!(sda1.device_minor().unwrap(), Some(1));
+}
+
+#[test]
+fn convert_archives_hard_links() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let bin = builder.reserve();
+    let busybox = builder.file(Data::Inline(b"#!busybox".to_vec()));
+    builder.dir(root, root, &[("bin", bin), ("sh", busybox)]);
+    builder.dir(bin, root, &[("busybox", busybox), ("ls", busybox)]);
+    let img = builder.build();
+
+    let entries = convert("convert_archives_hard_links", &img, &[]);
+    let summary: Vec<_> = entries
+        .iter()
+        .map(|(path, header, data)| {
+            let link = header.link_name().unwrap().map(|p| p.display().to_string());
+            (path.as_str(), header.entry_type(), link, data.as_slice())
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("bin", tar::EntryType::Directory, None, &b""[..]),
+            ("bin/busybox", tar::EntryType::Regular, None, b"#!busybox"),
+            (
+                "bin/ls",
+                tar::EntryType::Link,
+                Some("bin/busybox".to_string()),
+                b""
+            ),
+            (
+                "sh",
+                tar::EntryType::Link,
+                Some("bin/busybox".to_string()),
+                b""
+            ),
+        ]
+    );
+}
+
+#[test]
+fn convert_exclude_prunes_directories() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let etc = builder.reserve();
+    let cache = builder.reserve();
+    let nested = builder.reserve();
+    let hostname = builder.file(Data::Inline(b"erofs\n".to_vec()));
+    let swap = builder.file(Data::Inline(b"swap".to_vec()));
+    let blob = builder.file(Data::Inline(b"blob".to_vec()));
+    builder.dir(root, root, &[("cache", cache), ("etc", etc)]);
+    builder.dir(etc, root, &[("hostname", hostname), ("hostname.swp", swap)]);
+    builder.dir(cache, root, &[("blob", blob), ("nested", nested)]);
+    builder.dir(nested, cache, &[("blob", blob)]);
+    let img = builder.build();
+
+    let paths = |args: &[&str]| -> Vec<String> {
+        convert("convert_exclude_prunes_directories", &img, args)
+            .into_iter()
+            .map(|(path, _, _)| path)
+            .collect()
+    };
+    assert_eq!(
+        paths(&["--exclude", "cache", "--exclude", "*.swp"]),
+        ["etc", "etc/hostname"]
+    );
+    // Patterns with a separator match the whole path, and `*` doesn't
+    // cross directories.
+    assert_eq!(
+        paths(&["--exclude", "cache/*"]),
+        ["cache", "etc", "etc/hostname", "etc/hostname.swp"]
+    );
+}
+
+#[test]
+fn convert_exclude_matches_lossy_names() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let bad = builder.file(Data::Inline(b"bad".to_vec()));
+    let ok = builder.file(Data::Inline(b"ok".to_vec()));
+    builder.dir_bytes(root, root, &[(b"bad\xff", bad), (b"ok", ok)]);
+    let img = builder.build();
+
+    let paths: Vec<_> = convert(
+        "convert_exclude_matches_lossy_names",
+        &img,
+        &["--exclude", "bad\u{fffd}"],
+    )
+    .into_iter()
+    .map(|(path, _, _)| path)
+    .collect();
+    assert_eq!(paths, ["ok"]);
+}
+
+#[test]
+fn convert_archives_symlinks() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let etc = builder.reserve();
+    let hostname = builder.file(Data::Inline(b"erofs\n".to_vec()));
+    let relative = builder.symlink("etc/hostname");
+    let absolute = builder.symlink("/etc");
+    builder.dir(
+        root,
+        root,
+        &[("etc", etc), ("hostname", relative), ("link", absolute)],
+    );
+    builder.dir(etc, root, &[("hostname", hostname)]);
+    let img = builder.build();
+
+    let entries = convert("convert_archives_symlinks", &img, &[]);
+    let summary: Vec<_> = entries
+        .iter()
+        .map(|(path, header, _)| {
+            let link = header.link_name().unwrap().map(|p| p.display().to_string());
+            (path.as_str(), header.entry_type(), link)
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("etc", tar::EntryType::Directory, None),
+            ("etc/hostname", tar::EntryType::Regular, None),
+            (
+                "hostname",
+                tar::EntryType::Symlink,
+                Some("etc/hostname".to_string())
+            ),
+            ("link", tar::EntryType::Symlink, Some("/etc".to_string())),
+        ]
+    );
+}
+
+#[test]
+fn convert_keeps_ownership() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let compact = builder.file(Data::Inline(b"compact\n".to_vec()));
+    let extended = builder.file(Data::Inline(b"extended\n".to_vec()));
+    builder.dir(root, root, &[("compact", compact), ("extended", extended)]);
+    builder
+        .owner(compact, 1000, 100)
+        .owner(extended, 100_000, 70_000)
+        .mtime(extended, 0, 0);
+    let img = builder.build();
+
+    let entries = convert("convert_keeps_ownership", &img, &[]);
+    let owners: Vec<_> = entries
+        .iter()
+        .map(|(path, header, _)| (path.as_str(), header.uid().unwrap(), header.gid().unwrap()))
+        .collect();
+    assert_eq!(
+        owners,
+        [("compact", 1000, 100), ("extended", 100_000, 70_000)]
+    );
+}
+
+#[test]
+fn convert_archives_names_as_stored() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let file = builder.file(Data::Inline(b"latin-1\n".to_vec()));
+    builder.dir_bytes(root, root, &[(b"caf\xe9", file)]);
+    let img = builder.build();
+
+    let entries = convert("convert_archives_names_as_stored", &img, &[]);
+    let names: Vec<_> = entries
+        .iter()
+        .map(|(_, header, data)| (header.path_bytes().into_owned(), data.clone()))
+        .collect();
+    assert_eq!(names, [(b"caf\xe9".to_vec(), b"latin-1\n".to_vec())]);
+}
+
+#[test]
+fn inspect_ls_long_and_recursive() {
+    let img = common::sample();
+    let lines = inspect("inspect_ls_long_and_recursive", &img, &["ls"]);
+    assert_eq!(lines, ["empty", "etc", "hostname"]);
+
+    let lines = inspect("inspect_ls_long_and_recursive", &img, &["ls", "-R", "/etc"]);
+    assert_eq!(lines, ["hostname", "motd"]);
+    let lines = inspect("inspect_ls_long_and_recursive", &img, &["ls", "-R"]);
+    assert_eq!(
+        lines,
+        ["empty", "etc", "etc/hostname", "etc/motd", "hostname"]
+    );
+
+    let lines = inspect("inspect_ls_long_and_recursive", &img, &["ls", "-l", "/"]);
+    let long: Vec<_> = lines
+        .iter()
+        .map(|line| {
+            let fields: Vec<_> = line.split_whitespace().collect();
+            (fields[0], fields[1], *fields.last().unwrap())
+        })
+        .collect();
+    assert_eq!(
+        long,
+        [
+            ("drwxr-xr-x", "27B", "empty"),
+            ("drwxr-xr-x", "63B", "etc"),
+            ("lrwxrwxrwx", "12B", "hostname"),
+        ]
+    );
+}
+
+#[test]
+fn inspect_cat_streams_large_files() {
+    let data = common::random_bytes(2 << 20, 5);
+    let img = common::single_file(&mut ImageBuilder::new(12), Data::Plain(data.clone()));
+    let image = write_image("inspect_cat_streams_large_files", &img);
+    let cat = |path: &str| {
+        Command::new(env!("CARGO_BIN_EXE_erofs-cli"))
+            .arg("inspect")
+            .arg("--image")
+            .arg(&image)
+            .args(["cat", path])
+            .output()
+            .unwrap()
+    };
+
+    let output = cat("/file");
+    assert!(output.status.success());
+    assert!(output.stdout == data);
+
+    let output = cat("/");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("/: is a directory"));
+}
+
+#[test]
+fn convert_archives_hard_links_of_any_type() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let fifo = builder.special(S_IFIFO, 0);
+    let link = builder.symlink("target");
+    let socket = builder.special(S_IFSOCK, 0);
+    builder.dir(
+        root,
+        root,
+        &[
+            ("fifo", fifo),
+            ("fifo2", fifo),
+            ("link", link),
+            ("link2", link),
+            ("socket", socket),
+        ],
+    );
+    let img = builder.build();
+
+    let entries = convert("convert_archives_hard_links_of_any_type", &img, &[]);
+    let summary: Vec<_> = entries
+        .iter()
+        .map(|(path, header, _)| {
+            let link = header.link_name().unwrap().map(|p| p.display().to_string());
+            (path.as_str(), header.entry_type(), link)
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("fifo", tar::EntryType::Fifo, None),
+            ("fifo2", tar::EntryType::Link, Some("fifo".to_string())),
+            ("link", tar::EntryType::Symlink, Some("target".to_string())),
+            ("link2", tar::EntryType::Link, Some("link".to_string())),
+        ]
+    );
+}
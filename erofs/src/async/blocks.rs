@@ -0,0 +1,48 @@
+use bytes::Bytes;
+
+use super::EroFS;
+use crate::Result;
+use crate::backend::AsyncImage;
+use crate::types::Inode;
+
+/// An async iterator over the data blocks of a file, each returned as
+/// owned [`Bytes`].
+///
+/// Created by [`EroFS::blocks`]. The iterator holds its own clone of the
+/// filesystem rather than borrowing it, so it can be moved into a spawned
+/// task and held across await points. Every block is as long as the block
+/// size except the last, which is cut at the end of the file. After an
+/// error the iterator is exhausted.
+#[derive(Debug, Clone)]
+pub struct Blocks<I: AsyncImage> {
+    erofs: EroFS<I>,
+    inode: Inode,
+    offset: usize,
+}
+
+impl<I: AsyncImage> Blocks<I> {
+    pub(crate) fn new(erofs: EroFS<I>, inode: Inode) -> Self {
+        Self {
+            erofs,
+            inode,
+            offset: 0,
+        }
+    }
+
+    pub async fn next_block(&mut self) -> Option<Result<Bytes>> {
+        if self.offset >= self.inode.data_size() {
+            return None;
+        }
+
+        let block = self
+            .erofs
+            .read_inode_block(&self.inode, self.offset)
+            .await
+            .map(Bytes::from);
+        self.offset = match block {
+            Ok(_) => self.offset + self.erofs.block_size(),
+            Err(_) => self.inode.data_size(),
+        };
+        Some(block)
+    }
+}
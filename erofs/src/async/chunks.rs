@@ -0,0 +1,22 @@
+use super::EroFS;
+use crate::backend::AsyncImage;
+use crate::filesystem::ChunkScan;
+use crate::{ChunkRef, Result};
+
+/// An async iterator over the chunk index table of a file, in file order.
+///
+/// Created by [`EroFS::chunks`]. Item `i` describes chunk `i` of the file.
+pub struct Chunks<'a, I: AsyncImage> {
+    erofs: &'a EroFS<I>,
+    scan: ChunkScan,
+}
+
+impl<'a, I: AsyncImage> Chunks<'a, I> {
+    pub(crate) fn new(erofs: &'a EroFS<I>, scan: ChunkScan) -> Self {
+        Self { erofs, scan }
+    }
+
+    pub async fn next_chunk(&mut self) -> Option<Result<ChunkRef>> {
+        self.erofs.next_chunk(&mut self.scan).await
+    }
+}
@@ -1,11 +1,20 @@
 use alloc::vec::Vec;
-use typed_path::{UnixPath, UnixPathBuf};
+use typed_path::UnixPathBuf;
 
 use super::EroFS;
 use crate::backend::AsyncImage;
 use crate::dirent::{DirEntry, DirentBlock};
-use crate::{Result, types::Inode};
+use crate::{
+    AsUnixPath, Result,
+    types::{Dirent, Inode},
+};
 
+/// An async iterator over the entries of a directory, excluding `.` and `..`.
+///
+/// Created by [`EroFS::read_dir_from`]. Entries are decoded lazily, one
+/// directory block at a time: only the block holding the next entry is
+/// kept, and a name is only copied out when its entry is yielded, so memory
+/// use stays flat however large the directory is.
 pub struct ReadDir<'a, I: AsyncImage> {
     dir: UnixPathBuf,
     inode: Inode,
@@ -15,29 +24,58 @@ pub struct ReadDir<'a, I: AsyncImage> {
 }
 
 impl<'a, I: AsyncImage> ReadDir<'a, I> {
-    pub(crate) async fn new<P: AsRef<UnixPath>>(
+    pub(crate) async fn new<P: AsUnixPath>(
         erofs: &'a EroFS<I>,
         inode: Inode,
         dir: P,
     ) -> Result<Self> {
-        let block_data = erofs.read_inode_block(&inode, 0).await?;
-        let dirent_block = DirentBlock::new(dir.as_ref().to_path_buf(), block_data)?;
+        Self::new_at(erofs, inode, dir, 0).await
+    }
+
+    pub(crate) async fn new_at<P: AsUnixPath>(
+        erofs: &'a EroFS<I>,
+        inode: Inode,
+        dir: P,
+        cookie: u64,
+    ) -> Result<Self> {
+        let block_size = erofs.block_size();
+        let (offset, index) = match cookie as usize {
+            cookie if cookie < inode.data_size() => (
+                cookie - cookie % block_size,
+                cookie % block_size / Dirent::size(),
+            ),
+            // Past the end: park on the last block with nothing left to yield.
+            _ => (
+                inode.data_size().saturating_sub(1) / block_size * block_size,
+                usize::MAX,
+            ),
+        };
+
+        let block_data = erofs.read_inode_block(&inode, offset).await?;
+        let mut dirent_block = DirentBlock::new(block_data)?;
+        dirent_block.seek(index)?;
         Ok(Self {
-            dir: dir.as_ref().to_path_buf(),
+            dir: dir.as_unix_path().to_path_buf(),
             inode,
             erofs,
             dirent_block,
-            offset: 0,
+            offset,
         })
     }
 
+    /// Returns an opaque position that resumes listing right after the last
+    /// entry yielded, for use with [`EroFS::read_dir_from`].
+    pub fn cookie(&self) -> u64 {
+        (self.offset + self.dirent_block.position() * Dirent::size()) as u64
+    }
+
     pub async fn next_entry(&mut self) -> Result<Option<DirEntry>> {
         if self.offset >= self.inode.data_size() {
             return Ok(None);
         }
 
         while self.offset < self.inode.data_size() {
-            match self.dirent_block.next_entry()? {
+            match self.dirent_block.next_entry(&self.dir)? {
                 Some(entry) => return Ok(Some(entry)),
                 None => {
                     self.offset += self.dirent_block.block_size();
@@ -46,7 +84,7 @@ impl<'a, I: AsyncImage> ReadDir<'a, I> {
                             .erofs
                             .read_inode_block(&self.inode, self.offset)
                             .await?;
-                        self.dirent_block = DirentBlock::new(self.dir.clone(), block_data)?;
+                        self.dirent_block = DirentBlock::new(block_data)?;
                     }
                 }
             }
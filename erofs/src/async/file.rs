@@ -6,6 +6,7 @@ use super::EroFS;
 use crate::Result;
 use crate::backend::AsyncImage;
 use crate::types::Inode;
+use typed_path::{UnixPath, UnixPathBuf};
 
 /// An async handle to a file within an EROFS filesystem.
 ///
@@ -14,6 +15,7 @@ use crate::types::Inode;
 pub struct File<'a, I: AsyncImage> {
     inode: Inode,
     erofs: &'a EroFS<I>,
+    path: Option<UnixPathBuf>,
     offset: usize,
     buf: Option<Bytes>,
 }
@@ -23,16 +25,49 @@ impl<'a, I: AsyncImage> File<'a, I> {
         Self {
             inode,
             erofs,
+            path: None,
             offset: 0,
             buf: None,
         }
     }
 
+    pub(crate) fn with_path(mut self, path: UnixPathBuf) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    /// Returns the path the file was opened by, as it was given, or `None`
+    /// if it was opened from an inode.
+    ///
+    /// This is meant for error and log messages; paths through symlinks
+    /// are not resolved.
+    pub fn path(&self) -> Option<&UnixPath> {
+        self.path.as_deref()
+    }
+
+    /// Returns the inode of the file.
+    pub fn inode(&self) -> &Inode {
+        &self.inode
+    }
+
     /// Returns the size of the file in bytes.
     pub fn size(&self) -> usize {
         self.inode.data_size()
     }
 
+    /// Reads bytes starting at `offset` into `buf` without moving the
+    /// position used by [`read`](Self::read).
+    ///
+    /// Reads are clamped to the end of the file, so fewer than `buf.len()`
+    /// bytes are returned only when the range extends past [`size`](Self::size).
+    pub async fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let len = buf.len().min(self.size().saturating_sub(offset));
+        self.erofs
+            .read_inode_at(&self.inode, offset, &mut buf[..len])
+            .await?;
+        Ok(len)
+    }
+
     /// Asynchronously reads file contents into `buf`.
     ///
     /// Returns the number of bytes read, or `0` if EOF has been reached.
@@ -41,8 +76,9 @@ impl<'a, I: AsyncImage> File<'a, I> {
             return Ok(0);
         }
 
+        let block_mask = self.erofs.block_size() - 1;
         if let Some(ref data) = self.buf {
-            let offset = self.offset % self.erofs.block_size();
+            let offset = self.offset & block_mask;
             let data_remaining = data.len().saturating_sub(offset);
             let n = cmp::min(buf.len(), data_remaining);
             buf[..n].copy_from_slice(&data[offset..offset + n]);
@@ -53,21 +89,15 @@ impl<'a, I: AsyncImage> File<'a, I> {
             return Ok(n);
         }
 
-        let block_size = self.erofs.block_size();
         let cur_offset = self.offset;
         let block = self.erofs.read_inode_block(&self.inode, cur_offset).await?;
-        if buf.len() >= block.len() {
-            let n = block.len();
-            buf[..n].copy_from_slice(&block);
-            self.offset += n;
-            Ok(n)
-        } else {
-            let offset = cur_offset % block_size;
-            let n = cmp::min(buf.len(), block.len().saturating_sub(offset));
-            buf[..n].copy_from_slice(&block[offset..offset + n]);
+        let offset = cur_offset & block_mask;
+        let n = cmp::min(buf.len(), block.len().saturating_sub(offset));
+        buf[..n].copy_from_slice(&block[offset..offset + n]);
+        if offset + n < block.len() {
             self.buf = Some(Bytes::from(block));
-            self.offset += n;
-            Ok(n)
         }
+        self.offset += n;
+        Ok(n)
     }
 }
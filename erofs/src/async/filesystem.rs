@@ -1,74 +1,676 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
 use alloc::format;
+use alloc::string::{String, ToString};
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
 use alloc::vec::Vec;
-use typed_path::Component;
+use core::cmp;
 
-use bytes::Buf;
-use typed_path::{UnixComponent, UnixPath};
+use bytes::Bytes;
+use typed_path::{UnixPath, UnixPathBuf};
 
+use super::blocks::Blocks;
+use super::chunks::Chunks;
+use super::dirent::ReadDir;
 use super::file::File;
+use super::inodes::Inodes;
 use super::walkdir::WalkDir;
-use crate::backend::AsyncImage;
+use super::xattrs::SharedXattrs;
+use crate::backend::{AsyncImage, OffsetImage};
+#[cfg(feature = "std")]
+use crate::cache::{ExtentCache, PathCache};
 use crate::dirent;
-use crate::filesystem::{BlockPlan, EroFSCore};
+use crate::filesystem::{BlockPlan, ChunkScan, EroFSCore, InodeScan, PathWalk, SharedXattrScan};
+use crate::fsck::LinkCounts;
+use crate::manifest::ManifestBuilder;
 use crate::types::*;
-use crate::{Error, Result};
+use crate::xattr;
+use crate::zmap::{Extent, ExtentFormat, ZMap};
+use crate::{
+    AsUnixPath, CheckError, ChunkRef, Error, FsStats, FsckCode, FsckReport, Index, ManifestEntry,
+    Metadata, PhysicalExtent, Result, WalkError, Xattrs,
+};
 
 /// The async entry point for reading EROFS filesystem images.
 ///
 /// `EroFS` provides async methods to traverse directories, open files, and access
 /// filesystem metadata from EROFS images.
+///
+/// Like the sync [`EroFS`](crate::EroFS), it is [`Send`] and [`Sync`], since
+/// [`AsyncImage`] requires both and its caches are guarded by a mutex.
+/// Clones share the caches, so spawned tasks can each take one.
 #[derive(Debug, Clone)]
 pub struct EroFS<I: AsyncImage> {
     image: I,
     core: EroFSCore,
+    #[cfg(feature = "std")]
+    cache: Option<Arc<ExtentCache>>,
+    #[cfg(feature = "std")]
+    path_cache: Option<Arc<PathCache>>,
 }
 
 impl<I: AsyncImage> EroFS<I> {
     /// Creates a new async `EroFS` instance from an async backend image source.
+    ///
+    /// Async backends don't report their length, so unlike
+    /// [`EroFS::new`](crate::EroFS::new) this can't detect a truncated image
+    /// up front; reads past its end fail when they happen instead.
     pub async fn new(image: I) -> Result<Self> {
         let mut super_block = vec![0u8; SuperBlock::size()];
         image
             .read_exact_at(&mut super_block, SUPER_BLOCK_OFFSET)
             .await?;
-        let core = EroFSCore::new(&super_block)?;
-        Ok(Self { image, core })
+        let mut core = EroFSCore::new(&super_block)?;
+        if let Some((offset, len)) = core.compr_configs_range() {
+            let mut configs = vec![0u8; len];
+            image.read_exact_at(&mut configs, offset).await?;
+            core.parse_compr_configs(&configs)?;
+        }
+        if let Some((offset, len)) = core.device_table_range() {
+            let mut devices = vec![0u8; len];
+            image.read_exact_at(&mut devices, offset).await?;
+            core.parse_devices(&devices)?;
+        }
+        Ok(Self {
+            image,
+            core,
+            #[cfg(feature = "std")]
+            cache: None,
+            #[cfg(feature = "std")]
+            path_cache: None,
+        })
+    }
+
+    /// Creates a new async `EroFS` instance that keeps up to `capacity` bytes
+    /// of decompressed data in an LRU cache.
+    ///
+    /// See [`EroFS::with_cache`](crate::EroFS::with_cache); a `capacity` of
+    /// `0` disables the cache.
+    #[cfg(feature = "std")]
+    pub async fn with_cache(image: I, capacity: usize) -> Result<Self> {
+        let mut fs = Self::new(image).await?;
+        if capacity > 0 {
+            fs.cache = Some(Arc::new(ExtentCache::new(capacity)));
+        }
+        Ok(fs)
+    }
+
+    /// Keeps up to `capacity` resolved paths in an LRU cache.
+    ///
+    /// See [`EroFS::with_path_cache`](crate::EroFS::with_path_cache); a
+    /// `capacity` of `0` disables the cache.
+    #[cfg(feature = "std")]
+    pub fn with_path_cache(mut self, capacity: usize) -> Self {
+        self.path_cache = (capacity > 0).then(|| Arc::new(PathCache::new(capacity)));
+        self
+    }
+
+    /// Sets whether file reads verify each decoded block against a data
+    /// checksum stored in the image.
+    ///
+    /// See [`EroFS::verify_data`](crate::EroFS::verify_data): EROFS images
+    /// carry no data checksums, so this is a no-op on them.
+    pub fn verify_data(mut self, verify: bool) -> Self {
+        self.core.verify_data = verify;
+        self
+    }
+
+    /// Returns whether data verification was requested with
+    /// [`verify_data`](Self::verify_data).
+    pub fn verifies_data(&self) -> bool {
+        self.core.verify_data
     }
 
     /// Recursively walks a directory tree starting from the given path.
-    pub async fn walk_dir(&self, root: impl AsRef<UnixPath>) -> Result<WalkDir<'_, I>> {
-        WalkDir::new(self, root.as_ref()).await
+    pub async fn walk_dir(&self, root: impl AsUnixPath) -> Result<WalkDir<'_, I>> {
+        WalkDir::new(self, root.as_unix_path()).await
+    }
+
+    /// Returns an async iterator over every inode of the image, together with
+    /// its nid.
+    ///
+    /// Unlike [`walk_dir`](Self::walk_dir), this doesn't follow the directory
+    /// tree: it scans the metadata area in on-disk nid order, so inodes that
+    /// no directory entry refers to are found as well and no paths are built.
+    /// Slots are recognized by checking that they decode to an inode
+    /// consistent with the image, and the scan stops once the number of
+    /// inodes recorded in the superblock has been found. Images that don't
+    /// record it are scanned to the end, and data blocks of compressed or
+    /// chunk-based files may then be mistaken for inodes.
+    pub fn inodes(&self) -> Inodes<'_, I> {
+        Inodes::new(self, InodeScan::new(&self.core))
+    }
+
+    /// Returns totals over every inode of the image, see [`FsStats`].
+    ///
+    /// This is a full metadata walk in `O(inodes)`: every inode found by
+    /// [`inodes`](Self::inodes) is read, along with the chunk table or
+    /// compression map of inodes that have one. File data is neither read
+    /// nor decompressed. Hard-linked files are counted once.
+    pub async fn stats(&self) -> Result<FsStats> {
+        let mut stats = FsStats::default();
+        let mut inodes = self.inodes();
+        while let Some(inode) = inodes.next_inode().await {
+            let (nid, inode) = inode?;
+            let physical = self.physical_size(&inode).await?;
+            if self.core.is_packed_inode(nid) {
+                stats.add_packed(physical);
+            } else {
+                stats.add(&inode, physical);
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Walks the whole image once and returns an [`Index`] of every path.
+    pub async fn index(&self) -> Result<Index> {
+        let mut index = Index::default();
+        index.insert(
+            UnixPathBuf::from("/"),
+            self.core.super_block.root_nid as u64,
+            DirentFileType::Directory,
+        );
+        let mut walk_dir = self.walk_dir("/").await?;
+        while let Some(entry) = walk_dir.next_entry().await {
+            let entry = entry?;
+            index.insert(
+                entry.dir_entry.path(),
+                entry.dir_entry.nid(),
+                entry.dir_entry.file_type(),
+            );
+        }
+        Ok(index)
+    }
+
+    /// Checks the whole image for consistency in a single walk.
+    ///
+    /// Unlike the other methods, problems don't abort the check: every one
+    /// found is recorded in the returned [`FsckReport`], which makes this
+    /// suitable for gating generated images in CI.
+    ///
+    /// Every directory entry must refer to a readable inode of the type it
+    /// records, the data of every inode reached must lie within the image
+    /// with a chunk table or compressed indexes that decode, and link
+    /// counts must match the entries found.
+    ///
+    /// # Errors
+    ///
+    /// Only fails if the root directory itself cannot be read.
+    pub async fn fsck(&self) -> Result<FsckReport> {
+        let mut report = FsckReport::default();
+        report.check_features(&self.core.super_block);
+        match self.verify_superblock().await {
+            Ok(()) => {}
+            Err(e @ Error::ChecksumMismatch { .. }) => {
+                report.push(FsckCode::ChecksumMismatch, "/", None, e.to_string())
+            }
+            Err(e) => report.push(FsckCode::Unreadable, "/", None, e.to_string()),
+        }
+
+        let root = self.root_inode().await?;
+        let root_nid = root.id();
+        let mut links = LinkCounts::default();
+        links.visit("/", &root);
+        let ranges = self.data_ranges(&root).await;
+        report.check_data(&self.core, "/", &root, ranges);
+        if let Some(packed_nid) = self.core.packed_nid() {
+            match self.get_inode(packed_nid).await {
+                Ok(packed) => {
+                    let ranges = self.data_ranges(&packed).await;
+                    report.check_data(&self.core, "/", &packed, ranges);
+                }
+                Err(e) => report.push(FsckCode::Unreadable, "/", Some(packed_nid), e.to_string()),
+            }
+        }
+
+        let mut stack = vec![(root, root_nid, UnixPathBuf::from("/"))];
+        while let Some((dir, parent_nid, dir_path)) = stack.pop() {
+            let dir_path_str = dir_path.to_string_lossy();
+            let block_count = dir.data_size().div_ceil(self.core.block_size);
+            for i in 0..block_count {
+                let dirents = self
+                    .read_inode_block(&dir, i * self.core.block_size)
+                    .await
+                    .and_then(|block| dirent::read_owned_dirents(&block));
+                let dirents = match dirents {
+                    Ok(dirents) => dirents,
+                    Err(e) => {
+                        report.push(
+                            FsckCode::Unreadable,
+                            &dir_path_str,
+                            Some(dir.id()),
+                            format!("directory block {}: {}", i, e),
+                        );
+                        continue;
+                    }
+                };
+
+                for (dirent, name) in dirents {
+                    let nid = dirent.nid;
+                    links.reference(nid);
+                    if report.check_dot_entry(&dir_path_str, &name, nid, dir.id(), parent_nid) {
+                        continue;
+                    }
+
+                    let path = dir_path.join(&name);
+                    let path_str = path.to_string_lossy();
+                    let inode = match self.get_inode(nid).await {
+                        Ok(inode) => inode,
+                        Err(e) => {
+                            report.push(FsckCode::Unreadable, &path_str, Some(nid), e.to_string());
+                            continue;
+                        }
+                    };
+                    report.check_inode(&path_str, dirent.file_type, &inode);
+                    if !links.visit(&path_str, &inode) {
+                        if inode.is_dir() {
+                            report.push(
+                                FsckCode::DirectoryCycle,
+                                &path_str,
+                                Some(nid),
+                                "directory already reached through another path".to_string(),
+                            );
+                        }
+                        continue;
+                    }
+
+                    let ranges = self.data_ranges(&inode).await;
+                    report.check_data(&self.core, &path_str, &inode, ranges);
+                    if inode.is_dir() {
+                        stack.push((inode, dir.id(), path.clone()));
+                    }
+                }
+            }
+        }
+
+        links.check(&mut report);
+        Ok(report)
+    }
+
+    /// Checks the whole image for consistency, returning every problem
+    /// found.
+    ///
+    /// This is [`fsck`](Self::fsck) for callers that only need the list of
+    /// problems: an empty list means the image is consistent.
+    pub async fn check(&self) -> Result<Vec<CheckError>> {
+        Ok(self.fsck().await?.problems)
+    }
+
+    /// Lists every entry extracting the image would write, flagging those
+    /// that would be unsafe to write as they are.
+    ///
+    /// Only metadata is read: the walk goes through every directory from
+    /// the root, which is not listed itself, and reads symlink targets but
+    /// no file data. Entries with a name that isn't a single path
+    /// component, symlinks pointing outside of the tree, special files,
+    /// duplicate paths and directory cycles are reported in
+    /// [`ManifestEntry::issues`] instead of failing the walk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a directory or inode can't be read.
+    pub async fn manifest(&self) -> Result<Vec<ManifestEntry>> {
+        let mut manifest = ManifestBuilder::default();
+        let mut walk_dir = self.walk_dir("/").await?.resolve_symlinks(true);
+        while let Some(entry) = walk_dir.next_entry().await {
+            match entry {
+                Ok(entry) => {
+                    let path = entry.dir_entry.path();
+                    let escapes = match &entry.link_target {
+                        Some(target) => self.symlink_escapes(&path, target).await?,
+                        None => false,
+                    };
+                    manifest.push(
+                        path,
+                        entry.dir_entry.file_name_bytes(),
+                        &entry.inode,
+                        entry.link_target,
+                        escapes,
+                        false,
+                    );
+                }
+                Err(Error::Walk(WalkError::Cycle { path, nid })) => {
+                    let path = UnixPathBuf::from(path);
+                    let name = path.file_name().unwrap_or_default().to_vec();
+                    let inode = self.get_inode(nid).await?;
+                    manifest.push(path, &name, &inode, None, false, true);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(manifest.finish())
     }
 
     /// Lists the immediate contents of a directory.
-    pub async fn read_dir(&self, path: impl AsRef<UnixPath>) -> Result<WalkDir<'_, I>> {
-        Ok(WalkDir::new(self, path.as_ref()).await?.max_depth(1))
+    pub async fn read_dir(&self, path: impl AsUnixPath) -> Result<WalkDir<'_, I>> {
+        Ok(WalkDir::new(self, path.as_unix_path()).await?.max_depth(1))
+    }
+
+    /// Lists a directory starting at `cookie`, as returned by [`ReadDir::cookie`].
+    ///
+    /// A cookie of `0` starts from the beginning. Since only the inode is
+    /// known, entry paths are relative to the listed directory.
+    pub async fn read_dir_from(&self, dir: Inode, cookie: u64) -> Result<ReadDir<'_, I>> {
+        if !dir.is_dir() {
+            return Err(Error::inode_not_a_directory(dir.id()));
+        }
+
+        ReadDir::new_at(self, dir, "", cookie).await
+    }
+
+    /// Looks up the entry called `name` in the directory with node id
+    /// `parent_nid` and returns its inode, like a FUSE `lookup`.
+    ///
+    /// `name` is a single component compared byte for byte, and symlinks
+    /// are not followed. Directories store `.` and `..` entries, so those
+    /// resolve to the directory itself and its parent.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NotADirectory`] if `parent_nid` is not a directory
+    /// and [`Error::NotFound`] if it has no entry called `name`.
+    pub async fn lookup(&self, parent_nid: u64, name: impl AsRef<[u8]>) -> Result<Inode> {
+        let name = name.as_ref();
+        let dir = self.inode_at(parent_nid).await?;
+        if !dir.is_dir() {
+            return Err(Error::inode_not_a_directory(parent_nid));
+        }
+        let dirent = self
+            .lookup_child(&dir, name)
+            .await?
+            .ok_or_else(|| Error::child_not_found(parent_nid, name))?;
+        self.inode_at(dirent.nid).await
+    }
+
+    /// Lists the directory with node id `nid`, like a FUSE `readdir`.
+    ///
+    /// This is [`read_dir_from`](Self::read_dir_from) from the start, so
+    /// entry paths are relative to the directory. To resume a listing, pass
+    /// the [`ReadDir::cookie`] to `read_dir_from` instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `nid` is not a directory.
+    pub async fn readdir_at(&self, nid: u64) -> Result<ReadDir<'_, I>> {
+        self.read_dir_from(self.inode_at(nid).await?, 0).await
+    }
+
+    /// Returns the dirent array of block `block_index` of the directory
+    /// `dir`, as stored on disk.
+    ///
+    /// This is a lower-level view than [`read_dir`](Self::read_dir), meant
+    /// for diagnosing the layout of a directory: every dirent of the block
+    /// is returned in order, including `.` and `..`, with its raw
+    /// [`name_off`](Dirent::name_off), nid and file type. The number of
+    /// entries is the first name offset divided by [`Dirent::size`]. Names
+    /// are neither read nor validated, so this also works on blocks that
+    /// `read_dir` rejects as corrupt.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `dir` is not a directory, or if `block_index` is
+    /// past its last block.
+    pub async fn dir_block_entries(&self, dir: &Inode, block_index: usize) -> Result<Vec<Dirent>> {
+        if !dir.is_dir() {
+            return Err(Error::inode_not_a_directory(dir.id()));
+        }
+
+        let block = self
+            .read_inode_block(dir, block_index * self.core.block_size)
+            .await?;
+        dirent::read_raw_dirents(&block)
     }
 
     /// Opens a file at the given path for reading.
     ///
-    /// The returned [`File`] provides an async [`read`](File::read) method.
+    /// Symlinks are followed, including the last component. The returned
+    /// [`File`] provides an async [`read`](File::read) method.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path doesn't exist, a symlink on it is
+    /// dangling, it goes through more than [`MAX_SYMLINK_FOLLOWS`] symlinks,
+    /// or it is not a regular file.
+    ///
+    /// [`MAX_SYMLINK_FOLLOWS`]: crate::MAX_SYMLINK_FOLLOWS
+    pub async fn open(&self, path: impl AsUnixPath) -> Result<File<'_, I>> {
+        let inode = self.get_path_inode(path.as_unix_path()).await?;
+
+        let file = self.open_inode_file(inode)?;
+        Ok(file.with_path(path.as_unix_path().to_path_buf()))
+    }
+
+    /// Opens a file like [`open`](Self::open), without following a symlink
+    /// in the last component.
+    pub async fn open_nofollow(&self, path: impl AsUnixPath) -> Result<File<'_, I>> {
+        let (nid, _) = self.lookup_path(path.as_unix_path(), false).await?;
+
+        let file = self.open_inode_file(self.get_inode(nid).await?)?;
+        Ok(file.with_path(path.as_unix_path().to_path_buf()))
+    }
+
+    /// Opens a file like [`open`](Self::open), comparing the names on
+    /// `path` case-insensitively.
+    ///
+    /// This is meant for images made from case-insensitive filesystems.
+    /// Names are compared by the lowercase mapping of their characters, or
+    /// of their ASCII letters if either one isn't valid UTF-8. Symlink
+    /// targets are resolved the same way.
+    ///
+    /// Directory entries are sorted by their exact names, so instead of
+    /// binary-searching each directory on the way, this reads all of its
+    /// entries. Lookups take time linear in the size of the directories,
+    /// and the path cache isn't used.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::AmbiguousPath`] if a name on `path` matches several
+    /// entries of its directory, and otherwise the same errors as
+    /// [`open`](Self::open).
+    pub async fn open_ci(&self, path: impl AsUnixPath) -> Result<File<'_, I>> {
+        let inode = self.get_path_inode_ci(path.as_unix_path()).await?;
+        let file = self.open_inode_file(inode)?;
+        Ok(file.with_path(path.as_unix_path().to_path_buf()))
+    }
+
+    /// Reads the whole file at `path` into memory, following symlinks like
+    /// [`open`](Self::open).
+    ///
+    /// The buffer is sized up front from the inode, and the file's data is
+    /// decoded straight into it, each compressed physical cluster once.
+    /// Sizes that can't be allocated fail with [`Error::OutOfMemory`]
+    /// instead of aborting.
+    pub async fn read(&self, path: impl AsUnixPath) -> Result<Bytes> {
+        let file = self.open(path).await?;
+        let mut data = EroFSCore::data_buffer(file.size())?;
+        self.read_inode_at(file.inode(), 0, &mut data).await?;
+        Ok(Bytes::from(data))
+    }
+
+    /// Reads the first `n` bytes of the file at `path`, or all of it if it
+    /// is shorter, following symlinks like [`open`](Self::open).
+    ///
+    /// Only the blocks holding those bytes are read or decompressed.
+    pub async fn read_head(&self, path: impl AsUnixPath, n: usize) -> Result<Bytes> {
+        let file = self.open(path).await?;
+        let mut data = EroFSCore::data_buffer(n.min(file.size()))?;
+        self.read_inode_at(file.inode(), 0, &mut data).await?;
+        Ok(Bytes::from(data))
+    }
+
+    /// Returns an async iterator over the data blocks of `inode`, each as
+    /// owned [`Bytes`].
+    ///
+    /// The iterator keeps its own clone of the filesystem instead of
+    /// borrowing `self`, so it can be moved into a spawned task. To get the
+    /// whole file at once, use [`read`](Self::read).
+    pub fn blocks(&self, inode: &Inode) -> Blocks<I>
+    where
+        I: Clone,
+    {
+        Blocks::new(self.clone(), *inode)
+    }
+
+    /// Reads the whole file at `path` into a string, like
+    /// [`read`](Self::read).
+    ///
+    /// Returns [`Error::InvalidUtf8`] if the contents are not valid UTF-8.
+    pub async fn read_to_string(&self, path: impl AsUnixPath) -> Result<String> {
+        let data = Vec::from(self.read(path).await?);
+        String::from_utf8(data).map_err(|e| Error::InvalidUtf8(e.utf8_error()))
+    }
+
+    /// Returns the absolute path `path` resolves to, with `.` and `..`
+    /// applied and every symlink on it followed, including the last
+    /// component.
+    ///
+    /// Relative paths and symlink targets are taken from the root, and the
+    /// result only goes through directories that exist in the image, so it
+    /// can't point outside of it. This makes it suitable for checking where
+    /// an entry of an untrusted image would be written when extracting it.
     ///
     /// # Errors
     ///
-    /// Returns an error if the path doesn't exist or is not a regular file.
-    pub async fn open(&self, path: impl AsRef<UnixPath>) -> Result<File<'_, I>> {
-        let inode = self
-            .get_path_inode(path.as_ref())
+    /// Returns [`Error::NotFound`] if a component doesn't exist,
+    /// [`Error::NotADirectory`] if a component other than the last is not a
+    /// directory, [`Error::PathEscapesRoot`] if a `..` goes above the root,
+    /// and [`Error::SymlinkLoop`] if more than [`MAX_SYMLINK_FOLLOWS`]
+    /// symlinks are followed.
+    ///
+    /// [`MAX_SYMLINK_FOLLOWS`]: crate::MAX_SYMLINK_FOLLOWS
+    pub async fn canonicalize(&self, path: impl AsUnixPath) -> Result<UnixPathBuf> {
+        self.walk_path(path.as_unix_path(), true, false)
             .await?
-            .ok_or_else(|| Error::PathNotFound(path.as_ref().to_string_lossy().into_owned()))?;
+            .canonical_path()
+    }
+
+    /// Returns the target of the symlink at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path doesn't exist or is not a symlink.
+    pub async fn read_link(&self, path: impl AsUnixPath) -> Result<UnixPathBuf> {
+        let (nid, _) = self.lookup_path(path.as_unix_path(), false).await?;
 
-        self.open_inode_file(inode)
+        let inode = self.get_inode(nid).await?;
+        if !inode.is_symlink() {
+            return Err(Error::NotASymlink(
+                path.as_unix_path().to_string_lossy().into_owned(),
+            ));
+        }
+        self.read_inode_link(&inode).await
+    }
+
+    /// Returns the target of a symlink inode.
+    ///
+    /// Targets longer than `PATH_MAX` (4096 bytes) are rejected as
+    /// [`Error::CorruptedData`].
+    pub async fn read_inode_link(&self, inode: &Inode) -> Result<UnixPathBuf> {
+        if !inode.is_symlink() {
+            return Err(Error::NotASymlink(format!("inode {}", inode.id())));
+        }
+        self.core.check_symlink_size(inode)?;
+
+        let mut target = Vec::with_capacity(inode.data_size());
+        let block_count = inode.data_size().div_ceil(self.core.block_size);
+        for i in 0..block_count {
+            target.extend_from_slice(
+                &self
+                    .read_inode_block(inode, i * self.core.block_size)
+                    .await?,
+            );
+        }
+        Ok(UnixPathBuf::from(target))
+    }
+
+    /// Returns the metadata of the entry at `path`, following symlinks.
+    ///
+    /// Unlike [`open`](Self::open) this works for every kind of entry.
+    pub async fn metadata(&self, path: impl AsUnixPath) -> Result<Metadata> {
+        let inode = self.get_path_inode(path.as_unix_path()).await?;
+        Ok(Metadata::new(inode))
+    }
+
+    /// Returns the metadata of the entry at `path` without following a
+    /// symlink in the last component.
+    pub async fn symlink_metadata(&self, path: impl AsUnixPath) -> Result<Metadata> {
+        let (nid, _) = self.lookup_path(path.as_unix_path(), false).await?;
+        Ok(Metadata::new(self.get_inode(nid).await?))
+    }
+
+    /// Returns the metadata of the entry at `path` like
+    /// [`metadata`](Self::metadata), comparing the names on `path`
+    /// case-insensitively as [`open_ci`](Self::open_ci) does.
+    pub async fn metadata_ci(&self, path: impl AsUnixPath) -> Result<Metadata> {
+        Ok(Metadata::new(
+            self.get_path_inode_ci(path.as_unix_path()).await?,
+        ))
+    }
+
+    /// Returns the file type of the entry at `path`.
+    ///
+    /// The type is taken from the parent's directory entry, so the target
+    /// inode is only read for the root or when the entry type is unknown.
+    pub async fn file_type(&self, path: impl AsUnixPath) -> Result<DirentFileType> {
+        let (nid, file_type) = self.lookup_path(path.as_unix_path(), false).await?;
+
+        match file_type {
+            Some(file_type) if file_type != DirentFileType::Unknown => Ok(file_type),
+            _ => Ok(self.get_inode(nid).await?.file_type().into()),
+        }
+    }
+
+    /// Returns `true` if `path` points at an existing entry, following
+    /// symlinks like [`open`](Self::open).
+    ///
+    /// Errors are treated as the entry not existing. Use
+    /// [`metadata`](Self::metadata) to tell them apart.
+    pub async fn exists(&self, path: impl AsUnixPath) -> bool {
+        self.metadata(path).await.is_ok()
+    }
+
+    /// Returns `true` if `path` points at a directory, following symlinks.
+    ///
+    /// Errors are treated as `false`, like [`exists`](Self::exists).
+    pub async fn is_dir(&self, path: impl AsUnixPath) -> bool {
+        self.metadata(path)
+            .await
+            .is_ok_and(|metadata| metadata.is_dir())
+    }
+
+    /// Returns `true` if `path` points at a regular file, following
+    /// symlinks.
+    ///
+    /// Errors are treated as `false`, like [`exists`](Self::exists).
+    pub async fn is_file(&self, path: impl AsUnixPath) -> bool {
+        self.metadata(path)
+            .await
+            .is_ok_and(|metadata| metadata.is_file())
+    }
+
+    /// Opens the regular file with node id `nid`.
+    ///
+    /// See [`inode_at`](Self::inode_at) for how `nid` is validated.
+    pub async fn open_inode_by_nid(&self, nid: u64) -> Result<File<'_, I>> {
+        self.open_inode_file(self.inode_at(nid).await?)
     }
 
     /// Opens a file from an inode directly.
     ///
     /// This is useful when you already have an inode from directory traversal.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::IsASymlink`] for a symlink, whose target is read
+    /// with [`read_inode_link`](Self::read_inode_link) instead, and
+    /// [`Error::NotARegularFile`] for any other inode that isn't a regular
+    /// file.
     pub fn open_inode_file(&self, inode: Inode) -> Result<File<'_, I>> {
-        if !inode.is_file() {
-            return Err(Error::NotAFile(format!(
-                "inode {} is not a regular file",
-                inode.id()
-            )));
+        if inode.is_symlink() {
+            return Err(Error::IsASymlink(format!("inode {}", inode.id())));
+        } else if !inode.is_file() {
+            return Err(Error::NotARegularFile(format!("inode {}", inode.id())));
         }
 
         Ok(File::new(inode, self))
@@ -79,17 +681,446 @@ impl<I: AsyncImage> EroFS<I> {
         &self.core.super_block
     }
 
-    pub(crate) fn block_size(&self) -> usize {
+    /// Returns the time the image was built, as recorded in the superblock.
+    ///
+    /// Compact inodes carry no timestamp of their own, and the kernel reports
+    /// this time for them instead. Returns `None` if the time can't be
+    /// represented as a `SystemTime`.
+    #[cfg(feature = "std")]
+    pub fn build_time(&self) -> Option<std::time::SystemTime> {
+        let sb = &self.core.super_block;
+        system_time(sb.build_time, sb.build_time_ns)
+    }
+
+    /// Returns the time the image was built as `(seconds, nanoseconds)`
+    /// since the Unix epoch, as recorded in the superblock.
+    ///
+    /// Compact inodes carry no timestamp of their own, and the kernel reports
+    /// this time for them instead.
+    #[cfg(not(feature = "std"))]
+    pub fn build_time(&self) -> (u64, u32) {
+        let sb = &self.core.super_block;
+        (sb.build_time, sb.build_time_ns)
+    }
+
+    /// Verifies the superblock checksum.
+    ///
+    /// Images without the `sb_csum` feature carry no checksum and always
+    /// pass. Verification is opt-in so opening an image stays cheap.
+    pub async fn verify_superblock(&self) -> Result<()> {
+        let Some((offset, len)) = self.core.superblock_checksum_range() else {
+            return Ok(());
+        };
+        let mut data = vec![0u8; len];
+        self.image.read_exact_at(&mut data, offset).await?;
+        self.core.verify_superblock_checksum(&data)
+    }
+
+    /// Returns every compression algorithm needed to fully read this image.
+    ///
+    /// The set is derived from the superblock, so it is cheap enough to be
+    /// used as a preflight check right after opening the image. An empty set
+    /// means the image contains no compressed data.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the superblock declares an unknown algorithm.
+    pub fn required_algorithms(&self) -> Result<BTreeSet<CompressionAlgo>> {
+        self.core.required_algorithms()
+    }
+
+    /// Returns the compression algorithm and logical cluster size of
+    /// `inode`, or `None` if its data is not compressed.
+    ///
+    /// Only the inode's map header is read, so this works whether or not
+    /// the decompressor for the algorithm is enabled. A file stored as a
+    /// whole in the packed inode reports the compression of the packed
+    /// inode.
+    pub async fn compression(&self, inode: &Inode) -> Result<Option<Compression>> {
+        let Some(map_offset) = self.core.map_header_offset(inode) else {
+            return Ok(None);
+        };
+        let mut header = vec![0u8; MapHeader::size()];
+        self.image.read_exact_at(&mut header, map_offset).await?;
+        if let Some(compression) = ZMap::compression(&self.core, &header)? {
+            return Ok(Some(compression));
+        }
+
+        let packed_nid = self.core.super_block.packed_nid;
+        if inode.id() == packed_nid {
+            return Err(Error::CorruptedData(format!(
+                "packed inode {} is stored in itself",
+                packed_nid
+            )));
+        }
+        let packed = self.inode_at(packed_nid).await?;
+        Box::pin(self.compression(&packed)).await
+    }
+
+    /// Returns where the image stores the data of `inode`, in file order.
+    ///
+    /// This is the mapping reads go through, without decoding any data:
+    /// one extent per run of blocks of a flat inode and its inline tail,
+    /// per chunk of a chunk-based one and per physical cluster of a
+    /// compressed one. Holes have no extent, and neither have tails stored
+    /// as fragments of the packed inode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the chunk or compressed mapping can't be decoded.
+    pub async fn extents(&self, inode: &Inode) -> Result<Vec<PhysicalExtent>> {
+        match inode.layout()? {
+            Layout::FlatPlain | Layout::FlatInline => self.core.flat_extents(inode),
+            Layout::ChunkBased => {
+                self.core.check_chunk_format(inode)?;
+                let (table_offset, addr_len, chunk_size) = self.core.chunk_table(inode);
+                let mut addr = vec![0u8; addr_len];
+                let mut extents = Vec::new();
+                for i in 0..inode.data_size().div_ceil(chunk_size) {
+                    self.image
+                        .read_exact_at(&mut addr, table_offset + i * addr_len)
+                        .await?;
+                    extents.extend(self.core.chunk_extent(inode, i, &addr, self.image_len())?);
+                }
+                Ok(extents)
+            }
+            Layout::CompressedFull | Layout::CompressedCompact => {
+                let (map_offset, map_data) = self.zmap_data(inode).await?;
+                let zmap = ZMap::new(&self.core, inode, map_offset, &map_data)?;
+                Ok(zmap
+                    .extents(0, inode.data_size())?
+                    .iter()
+                    .filter(|extent| extent.format != ExtentFormat::Fragment)
+                    .map(|extent| self.core.zmap_extent(extent))
+                    .collect())
+            }
+        }
+    }
+
+    /// Returns the chunk index table of a chunk-based `inode`, one entry per
+    /// chunk in file order.
+    ///
+    /// Entries are reported as stored, without checking that their blocks
+    /// lie within the image or its devices; use
+    /// [`extents`](Self::extents) for the mapping reads go through. Chunk
+    /// `i` covers the file bytes starting at `i` times the chunk size, and
+    /// chunks shared by deduplicated files have equal entries. Inodes with
+    /// any other layout have no chunk index and yield no entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the chunk format of `inode` isn't supported.
+    pub async fn chunks(&self, inode: &Inode) -> Result<Chunks<'_, I>> {
+        Ok(Chunks::new(self, ChunkScan::new(&self.core, inode)?))
+    }
+
+    /// Returns the extra devices (blobs) this image expects besides itself,
+    /// as recorded in its device table.
+    ///
+    /// The list is empty for single-device images. Chunks stored on an extra
+    /// device can only be read when the device is mapped into the image's
+    /// block address space; otherwise reading them fails with
+    /// [`Error::UnsupportedFeature`].
+    pub fn devices(&self) -> &[DeviceSlot] {
+        &self.core.devices
+    }
+
+    /// Returns the extended attributes of `inode`.
+    ///
+    /// Both inline and shared attributes are decoded; an inode without
+    /// xattrs yields an empty iterator.
+    pub async fn xattrs(&self, inode: &Inode) -> Result<Xattrs> {
+        let Some((offset, size)) = self.core.xattr_range(inode) else {
+            return Ok(Xattrs::default());
+        };
+        let mut data = vec![0u8; size];
+        self.image.read_exact_at(&mut data, offset).await?;
+        let (shared, mut xattrs) = xattr::parse_inline(&data)?;
+
+        for id in shared {
+            xattrs.push(self.shared_xattr(id).await?);
+        }
+        Ok(Xattrs::new(xattrs))
+    }
+
+    /// Returns the IDs of the shared extended attributes `inode` refers to.
+    ///
+    /// See [`EroFS::shared_xattr_ids`](crate::EroFS::shared_xattr_ids).
+    pub async fn shared_xattr_ids(&self, inode: &Inode) -> Result<Vec<u32>> {
+        let Some((offset, size)) = self.core.xattr_range(inode) else {
+            return Ok(Vec::new());
+        };
+        let mut data = vec![0u8; size];
+        self.image.read_exact_at(&mut data, offset).await?;
+        xattr::parse_shared_ids(&data)
+    }
+
+    /// Decodes the entry `id` of the shared xattr pool into a
+    /// `(name, value)` pair.
+    pub async fn shared_xattr(&self, id: u32) -> Result<(String, Bytes)> {
+        let offset = self.core.shared_xattr_offset(id);
+        let mut entry = vec![0u8; XattrEntry::size()];
+        self.image.read_exact_at(&mut entry, offset).await?;
+        entry.resize(xattr::entry_len(&entry)?, 0);
+        self.image.read_exact_at(&mut entry, offset).await?;
+        xattr::parse_entry(&entry)
+    }
+
+    /// Returns an async iterator over the entries of the shared xattr pool,
+    /// by ID.
+    ///
+    /// See [`EroFS::shared_xattrs`](crate::EroFS::shared_xattrs).
+    pub fn shared_xattrs(&self) -> SharedXattrs<'_, I> {
+        SharedXattrs::new(self, SharedXattrScan::new(&self.core))
+    }
+
+    /// Returns the next entry found by `scan`, see
+    /// [`shared_xattrs`](Self::shared_xattrs).
+    pub(crate) async fn next_shared_xattr(
+        &self,
+        scan: &mut SharedXattrScan,
+    ) -> Option<Result<(u32, (String, Bytes))>> {
+        let (id, offset) = scan.next_entry(&self.core)?;
+        let mut entry = vec![0u8; XattrEntry::size()];
+        let len = match self.image.read_exact_at(&mut entry, offset).await {
+            Ok(_) => scan.accept(&entry),
+            Err(e) => Err(e),
+        };
+        let read = match len {
+            Ok(Some(len)) => {
+                entry.resize(len, 0);
+                self.image.read_exact_at(&mut entry, offset).await
+            }
+            Ok(None) => return None,
+            Err(e) => Err(e),
+        };
+        match read {
+            Ok(_) => Some(xattr::parse_entry(&entry).map(|xattr| (id, xattr))),
+            Err(e) => {
+                scan.finish();
+                Some(Err(e))
+            }
+        }
+    }
+
+    /// Returns the value of the extended attribute `name` of `inode`, or
+    /// `None` if it is not set.
+    pub async fn get_xattr(&self, inode: &Inode, name: &str) -> Result<Option<Bytes>> {
+        Ok(self
+            .xattrs(inode)
+            .await?
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value))
+    }
+
+    /// Returns the block size of the image in bytes, between 512 bytes and
+    /// 64 KiB.
+    pub fn block_size(&self) -> usize {
         self.core.block_size
     }
 
+    /// Returns the base-2 logarithm of the [block size](Self::block_size),
+    /// between 9 and 16.
+    pub fn block_size_bits(&self) -> u8 {
+        self.core.super_block.blk_size_bits
+    }
+
+    /// Returns the length of the image in bytes.
+    ///
+    /// Async backends don't report their length, so this is the length the
+    /// superblock claims for the image and its mapped devices.
+    pub fn image_len(&self) -> u64 {
+        self.core.image_len()
+    }
+
+    /// Returns the number of blocks in the image, based on
+    /// [`image_len`](Self::image_len).
+    pub fn block_count(&self) -> u64 {
+        self.core.block_count(self.core.image_len())
+    }
+
+    /// Returns `true` if block `blkaddr` starts within the image, based on
+    /// [`image_len`](Self::image_len).
+    ///
+    /// See [`EroFS::block_address_valid`](crate::EroFS::block_address_valid).
+    pub fn block_address_valid(&self, blkaddr: u64) -> bool {
+        self.core
+            .block_address_valid(blkaddr, self.core.image_len())
+    }
+
+    /// Reads the inode of the root directory, whose nid is recorded in the
+    /// superblock.
+    ///
+    /// This is the entry point for traversing the image by hand with
+    /// [`inode_at`](Self::inode_at) and [`read_dir_from`](Self::read_dir_from).
+    pub async fn root_inode(&self) -> Result<Inode> {
+        self.inode_at(self.core.super_block.root_nid as u64).await
+    }
+
+    /// Lists the root directory, like `read_dir_from(root_inode, 0)`.
+    ///
+    /// Entry paths are relative to the root.
+    pub async fn root_dir(&self) -> Result<ReadDir<'_, I>> {
+        let root = self.root_inode().await?;
+        self.read_dir_from(root, 0).await
+    }
+
+    /// Returns the packed inode, which holds the tails of files stored as
+    /// fragments, or `None` if the image has none.
+    ///
+    /// The packed inode is a regular file that no directory refers to. Its
+    /// node id is recorded in the superblock, and is only meaningful when
+    /// the image uses the fragments feature.
+    pub async fn fragment_inode(&self) -> Result<Option<Inode>> {
+        match self.core.packed_nid() {
+            Some(nid) => self.inode_at(nid).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads `len` bytes at `offset` of the data of the packed inode, or
+    /// returns `None` if the image has none, see
+    /// [`fragment_inode`](Self::fragment_inode).
+    ///
+    /// Offsets are those fragment references use, so this reads back the
+    /// tail of a file stored as a fragment without going through the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::OutOfRange`] if the range extends past the end of
+    /// the packed inode, and otherwise fails like reading a file.
+    pub async fn read_packed(&self, offset: usize, len: usize) -> Result<Option<Vec<u8>>> {
+        let Some(packed) = self.fragment_inode().await? else {
+            return Ok(None);
+        };
+        self.core.check_packed_range(&packed, offset, len)?;
+        let mut data = vec![0u8; len];
+        File::new(packed, self).read_at(offset, &mut data).await?;
+        Ok(Some(data))
+    }
+
+    /// Same as [`inode_at`](Self::inode_at).
     pub async fn get_inode(&self, nid: u64) -> Result<Inode> {
-        let offset = self.core.get_inode_offset(nid) as usize;
+        self.inode_at(nid).await
+    }
+
+    /// Reads the inode with node id `nid` directly, bypassing path lookup.
+    ///
+    /// This allows following on-disk references such as
+    /// [`DirEntry::nid`](crate::DirEntry::nid) by hand.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidInode`] if `nid` cannot be addressed or lies
+    /// past the end of the image given by the superblock, or the backend's
+    /// error if the image is shorter than that.
+    pub async fn inode_at(&self, nid: u64) -> Result<Inode> {
+        let offset = self.core.inode_offset(nid)?;
         let mut buf = vec![0u8; InodeExtended::size()];
         self.image.read_exact_at(&mut buf, offset).await?;
         self.core.parse_inode(&buf, nid)
     }
 
+    /// Returns the next inode found by `scan`, see [`inodes`](Self::inodes).
+    pub(crate) async fn next_scanned_inode(
+        &self,
+        scan: &mut InodeScan,
+    ) -> Option<Result<(u64, Inode)>> {
+        while let Some((nid, offset)) = scan.next_slot(&self.core) {
+            let mut buf = vec![0u8; InodeExtended::size()];
+            if let Err(e) = self.image.read_exact_at(&mut buf, offset).await {
+                return Some(Err(e));
+            }
+            let inode = self
+                .core
+                .parse_inode(&buf, nid)
+                .ok()
+                .filter(|inode| self.core.is_plausible_inode(inode));
+            let Some(inode) = inode else {
+                scan.skip();
+                continue;
+            };
+
+            let mut map_header = vec![0u8; MapHeader::size()];
+            if let Some(offset) = self.core.map_header_offset(&inode)
+                && let Err(e) = self.image.read_exact_at(&mut map_header, offset).await
+            {
+                return Some(Err(e));
+            }
+            let meta_len = self
+                .core
+                .inode_meta_len(&inode, &map_header)
+                // Resume right after the inode and its xattrs if the data
+                // following them can't be sized, e.g. a corrupted map header.
+                .unwrap_or_else(|_| inode.size() + inode.xattr_len());
+            scan.accept(&self.core, &inode, meta_len);
+            return Some(Ok((nid, inode)));
+        }
+        None
+    }
+
+    /// Returns the next entry walked by `scan`, see [`chunks`](Self::chunks).
+    pub(crate) async fn next_chunk(&self, scan: &mut ChunkScan) -> Option<Result<ChunkRef>> {
+        let (offset, len) = scan.next_entry()?;
+        let mut addr = vec![0u8; len];
+        if let Err(e) = self.image.read_exact_at(&mut addr, offset).await {
+            return Some(Err(e));
+        }
+        Some(self.core.chunk_ref(&addr))
+    }
+
+    /// Bytes of the image taken up by the data of `inode`, see
+    /// [`FsStats::physical_bytes`].
+    async fn physical_size(&self, inode: &Inode) -> Result<u64> {
+        Ok(self
+            .data_ranges(inode)
+            .await?
+            .iter()
+            .map(|(_, len)| len)
+            .sum())
+    }
+
+    /// Image byte ranges holding the data of `inode`, as `(offset, len)`
+    /// pairs. Fragments stored in the packed inode are left out. Fails if
+    /// the chunk or compressed mapping can't be decoded.
+    async fn data_ranges(&self, inode: &Inode) -> Result<Vec<(u64, u64)>> {
+        match inode.layout()? {
+            Layout::FlatPlain | Layout::FlatInline => self.core.flat_data_ranges(inode),
+            Layout::ChunkBased => {
+                self.core.check_chunk_format(inode)?;
+                let (table_offset, addr_len, chunk_size) = self.core.chunk_table(inode);
+                let mut addr = vec![0u8; addr_len];
+                let mut ranges = Vec::new();
+                for (i, start) in (0..inode.data_size()).step_by(chunk_size).enumerate() {
+                    self.image
+                        .read_exact_at(&mut addr, table_offset + i * addr_len)
+                        .await?;
+                    if let Some(block) =
+                        self.core
+                            .chunk_block(inode.id(), i, &addr, 0, self.image_len())?
+                    {
+                        let len = (inode.data_size() - start).min(chunk_size);
+                        ranges.push((
+                            block << self.core.super_block.blk_size_bits,
+                            len.next_multiple_of(self.core.block_size) as u64,
+                        ));
+                    }
+                }
+                Ok(ranges)
+            }
+            Layout::CompressedFull | Layout::CompressedCompact => {
+                let (map_offset, map_data) = self.zmap_data(inode).await?;
+                let zmap = ZMap::new(&self.core, inode, map_offset, &map_data)?;
+                Ok(zmap
+                    .extents(0, inode.data_size())?
+                    .iter()
+                    .filter(|extent| extent.format != ExtentFormat::Fragment)
+                    .map(|extent| (extent.pa as u64, extent.plen as u64))
+                    .collect())
+            }
+        }
+    }
+
     pub(crate) async fn read_inode_block(&self, inode: &Inode, offset: usize) -> Result<Vec<u8>> {
         match self.core.plan_inode_block_read(inode, offset)? {
             BlockPlan::Direct { offset, size } => {
@@ -106,57 +1137,340 @@ impl<I: AsyncImage> EroFS<I> {
             }
             BlockPlan::Chunked {
                 addr_offset,
+                addr_len,
                 chunk_fixed,
                 chunk_size,
                 data_size,
                 chunk_index,
             } => {
-                let mut addr_buf = vec![0u8; 4];
-                self.image.read_exact_at(&mut addr_buf, addr_offset).await?;
-                let chunk_addr = (&addr_buf[..]).get_i32_le();
+                let mut chunk_addr = vec![0u8; addr_len];
+                self.image
+                    .read_exact_at(&mut chunk_addr, addr_offset)
+                    .await?;
+
+                let chunk_block = self.core.chunk_block(
+                    inode.id(),
+                    chunk_index,
+                    &chunk_addr,
+                    chunk_fixed,
+                    self.image_len(),
+                )?;
 
                 let (offset, size) = self.core.resolve_chunk_read(
-                    chunk_addr,
+                    chunk_block,
                     chunk_fixed,
                     chunk_size,
                     data_size,
                     chunk_index,
                 )?;
                 let mut buf = vec![0u8; size];
-                self.image.read_exact_at(&mut buf, offset).await?;
+                if let Some(offset) = offset {
+                    self.image.read_exact_at(&mut buf, offset).await?;
+                }
                 Ok(buf)
             }
+            BlockPlan::Compressed {
+                map_offset,
+                start,
+                size,
+            } => {
+                let mut header = vec![0u8; MapHeader::size()];
+                self.image.read_exact_at(&mut header, map_offset).await?;
+                let mut map_data = vec![0u8; ZMap::data_len(&self.core, inode, &header)?];
+                self.image.read_exact_at(&mut map_data, map_offset).await?;
+                let zmap = ZMap::new(&self.core, inode, map_offset, &map_data)?;
+
+                let mut block = vec![0u8; size];
+                for extent in zmap.extents(start, start + size)? {
+                    self.read_extent(&extent, start, &mut block).await?;
+                }
+                Ok(block)
+            }
         }
     }
 
-    pub(crate) async fn get_path_inode(&self, path: &UnixPath) -> Result<Option<Inode>> {
-        let mut nid = self.core.super_block.root_nid as u64;
-
-        let path = path.normalize();
-        'outer: for part in path.components() {
-            if part == UnixComponent::RootDir {
-                continue;
+    /// Reads the data of `inode` starting at `offset` into all of `buf`,
+    /// which must not extend past the end of the file.
+    ///
+    /// Compressed data is mapped once for the whole range, so each physical
+    /// cluster is decoded once however many blocks of `buf` it fills.
+    pub(crate) async fn read_inode_at(
+        &self,
+        inode: &Inode,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<()> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+        if let Layout::CompressedFull | Layout::CompressedCompact = inode.layout()? {
+            let (map_offset, map_data) = self.zmap_data(inode).await?;
+            let zmap = ZMap::new(&self.core, inode, map_offset, &map_data)?;
+            for extent in zmap.extents(offset, offset + buf.len())? {
+                self.read_extent(&extent, offset, buf).await?;
             }
+            return Ok(());
+        }
 
-            let inode = self.get_inode(nid).await?;
-            let block_count = inode.data_size().div_ceil(self.core.block_size);
-            if block_count == 0 {
-                return Ok(None);
+        // Block sizes are powers of two, so masking finds the offset within
+        // a block.
+        let block_mask = self.block_size() - 1;
+        let mut n = 0;
+        while n < buf.len() {
+            let pos = offset + n;
+            let block = self.read_inode_block(inode, pos).await?;
+            let start = pos & block_mask;
+            let m = (buf.len() - n).min(block.len().saturating_sub(start));
+            if m == 0 {
+                return Err(Error::CorruptedData(format!(
+                    "short block at offset {}",
+                    pos
+                )));
             }
+            buf[n..n + m].copy_from_slice(&block[start..start + m]);
+            n += m;
+        }
+        Ok(())
+    }
 
-            for i in 0..block_count {
-                let block = self
-                    .read_inode_block(&inode, i * self.core.block_size)
-                    .await?;
-                if let Some(found_nid) = dirent::find_nodeid_by_name(part.as_bytes(), &block)? {
-                    nid = found_nid;
-                    continue 'outer;
+    /// Reads the compression map of the compressed `inode`, returning its
+    /// image offset along with the raw map.
+    async fn zmap_data(&self, inode: &Inode) -> Result<(usize, Vec<u8>)> {
+        let map_offset = ZMap::header_offset(&self.core, inode);
+        let mut header = vec![0u8; MapHeader::size()];
+        self.image.read_exact_at(&mut header, map_offset).await?;
+        let mut map_data = vec![0u8; ZMap::data_len(&self.core, inode, &header)?];
+        self.image.read_exact_at(&mut map_data, map_offset).await?;
+        Ok((map_offset, map_data))
+    }
+
+    /// Decodes `extent` and copies its part of the logical range starting at
+    /// `start` into `buf`, going through the extent cache if there is one.
+    async fn read_extent(&self, extent: &Extent, start: usize, buf: &mut [u8]) -> Result<()> {
+        if extent.format == ExtentFormat::Fragment {
+            // Reading the packed inode goes through this function again.
+            return Box::pin(self.read_fragment(extent, start, buf)).await;
+        }
+        #[cfg(feature = "std")]
+        if let Some(cache) = &self.cache {
+            let data = match cache.get(extent) {
+                Some(data) => data,
+                None => {
+                    let data = Bytes::from(self.decode_extent(extent).await?);
+                    cache.insert(*extent, data.clone());
+                    data
                 }
+            };
+            self.core.copy_extent(extent, &data, start, buf);
+            return Ok(());
+        }
+
+        let data = self.decode_extent(extent).await?;
+        self.core.copy_extent(extent, &data, start, buf);
+        Ok(())
+    }
+
+    async fn decode_extent(&self, extent: &Extent) -> Result<Vec<u8>> {
+        let mut input = vec![0u8; extent.plen];
+        self.image.read_exact_at(&mut input, extent.pa).await?;
+        Ok(self.core.decode_extent(extent, &input)?.into_owned())
+    }
+
+    /// Reads the part of the fragment `extent` overlapping the logical range
+    /// starting at `start` into `buf`, straight from the packed inode.
+    async fn read_fragment(&self, extent: &Extent, start: usize, buf: &mut [u8]) -> Result<()> {
+        let Some((from, to)) = EroFSCore::extent_overlap(extent, start, buf.len()) else {
+            return Ok(());
+        };
+        let packed = self.inode_at(self.core.super_block.packed_nid).await?;
+        let offset = EroFSCore::fragment_offset(extent, from)?;
+        let buf = &mut buf[from - start..to - start];
+        if File::new(packed, self).read_at(offset, buf).await? != buf.len() {
+            return Err(Error::CorruptedData(format!(
+                "fragment of {} bytes at {} exceeds the packed inode",
+                extent.llen, extent.pa
+            )));
+        }
+        Ok(())
+    }
+
+    /// Resolves `path` to its inode, following symlinks.
+    pub(crate) async fn get_path_inode(&self, path: &UnixPath) -> Result<Inode> {
+        let (nid, _) = self.lookup_path(path, true).await?;
+        self.get_inode(nid).await
+    }
+
+    /// Resolves `path` to its inode with case-insensitive lookups, following
+    /// symlinks.
+    async fn get_path_inode_ci(&self, path: &UnixPath) -> Result<Inode> {
+        let (nid, _) = self.walk_path(path, true, true).await?.finish();
+        self.get_inode(nid).await
+    }
+
+    /// Finds the entries of the directory `dir` whose names match `name`
+    /// case-insensitively, together with those names.
+    async fn find_children_ci(&self, dir: &Inode, name: &[u8]) -> Result<Vec<(Dirent, Vec<u8>)>> {
+        let mut found = Vec::new();
+        for block_index in 0..dir.data_size().div_ceil(self.core.block_size) {
+            let block = self
+                .read_inode_block(dir, block_index * self.core.block_size)
+                .await?;
+            found.extend(dirent::find_dirents_ignore_case(name, &block)?);
+        }
+        Ok(found)
+    }
+
+    /// Looks up the entry called `name` in the directory `dir`.
+    ///
+    /// Directory entries are sorted by name across blocks, so this
+    /// binary-searches the blocks by their first name and then the entries
+    /// within the only block that can hold `name`.
+    async fn lookup_child(&self, dir: &Inode, name: &[u8]) -> Result<Option<Dirent>> {
+        let mut lo = 0;
+        let mut hi = dir.data_size().div_ceil(self.core.block_size);
+        let mut candidate = None;
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let block = self
+                .read_inode_block(dir, mid * self.core.block_size)
+                .await?;
+            if dirent::cmp_first_name(&block, name)? == cmp::Ordering::Greater {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+                candidate = Some(block);
             }
+        }
+
+        let Some(block) = candidate else {
             return Ok(None);
+        };
+        dirent::find_dirent_by_name(name, &block)
+    }
+
+    /// Resolves `path` to the nid of its last component, together with the
+    /// file type recorded in the parent directory entry (`None` for the root).
+    ///
+    /// Symlinks met along the way are followed; the last component is only
+    /// followed if `follow` is set.
+    async fn lookup_path(
+        &self,
+        path: &UnixPath,
+        follow: bool,
+    ) -> Result<(u64, Option<DirentFileType>)> {
+        #[cfg(feature = "std")]
+        if let Some(cache) = &self.path_cache {
+            if let Some(target) = cache.get(path, follow) {
+                return Ok(target);
+            }
+            let target = self.resolve_path(path, follow).await?;
+            cache.insert(path, follow, target);
+            return Ok(target);
         }
+        self.resolve_path(path, follow).await
+    }
 
-        let inode = self.get_inode(nid).await?;
-        Ok(Some(inode))
+    /// Walks the directories on `path` for [`lookup_path`](Self::lookup_path).
+    async fn resolve_path(
+        &self,
+        path: &UnixPath,
+        follow: bool,
+    ) -> Result<(u64, Option<DirentFileType>)> {
+        Ok(self.walk_path(path, follow, false).await?.finish())
+    }
+
+    /// Walks the directories on `path`, following symlinks like
+    /// [`lookup_path`](Self::lookup_path).
+    ///
+    /// With `ignore_case`, names are compared case-insensitively and the
+    /// walk enters the entries under their stored names.
+    async fn walk_path(
+        &self,
+        path: &UnixPath,
+        follow: bool,
+        ignore_case: bool,
+    ) -> Result<PathWalk> {
+        let mut walk = PathWalk::new(self.core.super_block.root_nid as u64, path);
+        while let Some(name) = walk.next_name() {
+            let inode = self.get_inode(walk.dir()).await?;
+            if !inode.is_dir() {
+                return Err(walk.not_a_directory());
+            }
+            let (dirent, name) = if ignore_case {
+                let mut found = self.find_children_ci(&inode, &name).await?;
+                if found.len() > 1 {
+                    return Err(walk.ambiguous());
+                }
+                found.pop().ok_or_else(|| walk.not_found(&name))?
+            } else {
+                let dirent = self
+                    .lookup_child(&inode, &name)
+                    .await?
+                    .ok_or_else(|| walk.not_found(&name))?;
+                (dirent, name)
+            };
+
+            let nid = dirent.nid;
+            let file_type = dirent.file_type.try_into()?;
+            if (follow || !walk.is_last())
+                && matches!(file_type, DirentFileType::Symlink | DirentFileType::Unknown)
+            {
+                let inode = self.get_inode(nid).await?;
+                if inode.is_symlink() {
+                    walk.follow(&self.read_inode_link(&inode).await?)?;
+                    continue;
+                }
+            }
+            walk.enter(name, nid, file_type);
+        }
+
+        Ok(walk)
+    }
+
+    /// Whether the symlink at `path` points outside of the tree once the
+    /// image is extracted, resolving `target` through the symlinks of the
+    /// image like the host would, see [`manifest`](Self::manifest).
+    async fn symlink_escapes(&self, path: &UnixPath, target: &UnixPath) -> Result<bool> {
+        if target.is_absolute() {
+            return Ok(true);
+        }
+        let dir = path.parent().unwrap_or_else(|| UnixPath::new("/"));
+        let mut walk = PathWalk::new(self.core.super_block.root_nid as u64, &dir.join(target));
+        let mut stopped_at = None;
+        while let Some(name) = walk.next_name() {
+            // The host stops resolving at a name looked up in something
+            // that isn't a directory or missing from it.
+            let inode = self.get_inode(walk.dir()).await?;
+            if !inode.is_dir() {
+                stopped_at = Some(name);
+                break;
+            }
+            let Some(dirent) = self.lookup_child(&inode, &name).await? else {
+                stopped_at = Some(name);
+                break;
+            };
+
+            let file_type = dirent.file_type.try_into()?;
+            if matches!(file_type, DirentFileType::Symlink | DirentFileType::Unknown) {
+                let inode = self.get_inode(dirent.nid).await?;
+                if inode.is_symlink() {
+                    // Symlink loops don't resolve on the host either.
+                    if walk.follow(&self.read_inode_link(&inode).await?).is_err() {
+                        break;
+                    }
+                    continue;
+                }
+            }
+            walk.enter(name, dirent.nid, file_type);
+        }
+        Ok(walk.leaves_root(stopped_at.as_deref()))
+    }
+}
+
+impl<I: AsyncImage> EroFS<OffsetImage<I>> {
+    /// Creates a new async `EroFS` instance for an image embedded `offset`
+    /// bytes into `image`, see [`OffsetImage`].
+    pub async fn new_at_offset(image: I, offset: usize) -> Result<Self> {
+        Self::new(OffsetImage::new(image, offset)).await
     }
 }
@@ -0,0 +1,23 @@
+use super::EroFS;
+use crate::Result;
+use crate::backend::AsyncImage;
+use crate::filesystem::InodeScan;
+use crate::types::Inode;
+
+/// An async iterator over every inode of the image, in on-disk nid order.
+///
+/// Created by [`EroFS::inodes`]. Each item is a `(nid, inode)` pair.
+pub struct Inodes<'a, I: AsyncImage> {
+    erofs: &'a EroFS<I>,
+    scan: InodeScan,
+}
+
+impl<'a, I: AsyncImage> Inodes<'a, I> {
+    pub(crate) fn new(erofs: &'a EroFS<I>, scan: InodeScan) -> Self {
+        Self { erofs, scan }
+    }
+
+    pub async fn next_inode(&mut self) -> Option<Result<(u64, Inode)>> {
+        self.erofs.next_scanned_inode(&mut self.scan).await
+    }
+}
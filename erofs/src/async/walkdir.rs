@@ -1,17 +1,37 @@
-use alloc::vec::Vec;
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::cmp::Ordering;
 
 use super::EroFS;
 use super::dirent::ReadDir;
+use super::file::File;
 use crate::backend::AsyncImage;
 use crate::dirent::DirEntry;
-use crate::{Error, Result, types::Inode};
-use typed_path::UnixPath;
+use crate::{AsUnixPath, Error, Result, WalkError, types::Inode};
+use typed_path::UnixPathBuf;
 
 /// An async iterator for recursively walking a directory tree.
 pub struct WalkDir<'a, I: AsyncImage> {
     erofs: &'a EroFS<I>,
-    dir_stack: Vec<(usize, ReadDir<'a, I>)>,
+    /// The directories being read, each with the depth of its entries
+    /// and its nid: the ancestors of the entries it yields.
+    dir_stack: Vec<(usize, u64, DirEntries<'a, I>)>,
+    min_depth: usize,
     max_depth: usize,
+    skip_whiteouts: bool,
+    resolve_symlinks: bool,
+    filter: Option<EntryFilter<'a>>,
+    sorter: Option<EntrySorter<'a>>,
+}
+
+type EntryFilter<'a> = Box<dyn FnMut(&WalkDirEntry) -> bool + Send + 'a>;
+type EntrySorter<'a> = Box<dyn FnMut(&DirEntry, &DirEntry) -> Ordering + Send + 'a>;
+
+/// The remaining entries of a directory on the walk's stack.
+enum DirEntries<'a, I: AsyncImage> {
+    /// Entries read from the image as the walk goes.
+    Unsorted(ReadDir<'a, I>),
+    /// All entries of the directory, read up front and sorted.
+    Sorted(vec::IntoIter<DirEntry>),
 }
 
 /// A single entry returned by [`WalkDir`].
@@ -22,28 +42,41 @@ pub struct WalkDirEntry {
     pub dir_entry: DirEntry,
     /// The inode containing file metadata.
     pub inode: Inode,
+    /// The target of a symlink, if the walk was told to
+    /// [`resolve_symlinks`](WalkDir::resolve_symlinks). `None` for every
+    /// other entry.
+    pub link_target: Option<UnixPathBuf>,
+}
+
+impl WalkDirEntry {
+    /// Returns `true` if the entry is an overlayfs whiteout.
+    ///
+    /// See [`Inode::is_whiteout`].
+    pub fn is_whiteout(&self) -> bool {
+        self.inode.is_whiteout()
+    }
 }
 
 impl<'a, I: AsyncImage> WalkDir<'a, I> {
-    pub(crate) async fn new(erofs: &'a EroFS<I>, root: impl AsRef<UnixPath>) -> Result<Self> {
-        let read_dir = {
-            let inode = erofs
-                .get_path_inode(root.as_ref())
-                .await?
-                .ok_or_else(|| Error::PathNotFound(root.as_ref().to_string_lossy().into_owned()))?;
+    pub(crate) async fn new(erofs: &'a EroFS<I>, root: impl AsUnixPath) -> Result<Self> {
+        let (nid, read_dir) = {
+            let inode = erofs.get_path_inode(root.as_unix_path()).await?;
 
             if !inode.file_type().is_dir() {
-                return Err(Error::NotADirectory(
-                    root.as_ref().to_string_lossy().into_owned(),
-                ));
+                return Err(Error::not_a_directory(root.as_unix_path()));
             }
 
-            ReadDir::new(erofs, inode, root).await?
+            (inode.id(), ReadDir::new(erofs, inode, root).await?)
         };
         Ok(WalkDir {
             erofs,
-            dir_stack: vec![(1, read_dir)],
+            dir_stack: vec![(1, nid, DirEntries::Unsorted(read_dir))],
+            min_depth: 0,
             max_depth: 0,
+            skip_whiteouts: false,
+            resolve_symlinks: false,
+            filter: None,
+            sorter: None,
         })
     }
 
@@ -56,35 +89,162 @@ impl<'a, I: AsyncImage> WalkDir<'a, I> {
         self
     }
 
+    /// Sets the minimum depth of the entries that are returned.
+    ///
+    /// Entries shallower than `depth` are skipped, but the walk still
+    /// descends into them unless [`filter_entry`](Self::filter_entry)
+    /// rejects them. A depth of 0 or 1 (the default is 0) returns
+    /// every entry.
+    pub fn min_depth(mut self, depth: usize) -> Self {
+        self.min_depth = depth;
+        self
+    }
+
+    /// Skips overlayfs whiteouts instead of yielding them.
+    ///
+    /// See [`WalkDir::skip_whiteouts`](crate::WalkDir::skip_whiteouts).
+    pub fn skip_whiteouts(mut self, skip: bool) -> Self {
+        self.skip_whiteouts = skip;
+        self
+    }
+
+    /// Reads the target of each symlink the walk yields into
+    /// [`WalkDirEntry::link_target`].
+    ///
+    /// See [`WalkDir::resolve_symlinks`](crate::WalkDir::resolve_symlinks).
+    pub fn resolve_symlinks(mut self, resolve: bool) -> Self {
+        self.resolve_symlinks = resolve;
+        self
+    }
+
+    /// Skips the entries for which `predicate` returns `false`.
+    ///
+    /// The predicate runs before a directory is opened, so rejecting a
+    /// directory prunes its whole subtree without reading it. It also runs
+    /// on the entries [`min_depth`](Self::min_depth) keeps from being
+    /// yielded, so those can be pruned too.
+    pub fn filter_entry<P>(mut self, predicate: P) -> Self
+    where
+        P: FnMut(&WalkDirEntry) -> bool + Send + 'a,
+    {
+        self.filter = Some(Box::new(predicate));
+        self
+    }
+
+    /// Yields the entries of each directory in the order given by
+    /// `compare`, instead of the order they are stored in.
+    ///
+    /// See [`WalkDir::sort_by`](crate::WalkDir::sort_by).
+    pub fn sort_by<F>(mut self, compare: F) -> Self
+    where
+        F: FnMut(&DirEntry, &DirEntry) -> Ordering + Send + 'a,
+    {
+        self.sorter = Some(Box::new(compare));
+        self
+    }
+
+    /// Yields the entries of each directory sorted by file name.
+    ///
+    /// See [`WalkDir::sort_by_name`](crate::WalkDir::sort_by_name).
+    pub fn sort_by_name(self) -> Self {
+        self.sort_by(|a, b| a.file_name_bytes().cmp(b.file_name_bytes()))
+    }
+
+    /// Turns the walk into one over the regular files of the tree, each
+    /// paired with its path and opened for reading.
+    ///
+    /// Directories, symlinks and special files are skipped; the depth
+    /// limits, filter and sort order set on the walk still apply. Files are
+    /// opened one at a time as the walk reaches them, so memory use stays
+    /// the same as for the walk itself however many files are read.
+    pub fn files(self) -> Files<'a, I> {
+        Files { walk: self }
+    }
+
     async fn get_walk_dir_entry(
         &mut self,
         dir_entry: DirEntry,
         depth: usize,
-    ) -> Result<WalkDirEntry> {
+    ) -> Result<Option<WalkDirEntry>> {
         let inode = self.erofs.get_inode(dir_entry.nid()).await?;
-
-        if (depth < self.max_depth || self.max_depth == 0) && dir_entry.file_type().is_dir() {
-            let child_dir = ReadDir::new(self.erofs, inode, dir_entry.path()).await?;
-            self.dir_stack.push((depth + 1, child_dir));
-        }
-
-        Ok(WalkDirEntry {
+        let mut entry = WalkDirEntry {
             depth,
             dir_entry,
             inode,
-        })
+            link_target: None,
+        };
+
+        if self.skip_whiteouts && entry.is_whiteout() {
+            return Ok(None);
+        }
+
+        if self.resolve_symlinks && entry.inode.is_symlink() {
+            entry.link_target = Some(self.erofs.read_inode_link(&entry.inode).await?);
+        }
+        if self.filter.as_mut().is_some_and(|filter| !filter(&entry)) {
+            return Ok(None);
+        }
+
+        if (depth < self.max_depth || self.max_depth == 0) && entry.dir_entry.file_type().is_dir() {
+            // Directory hardlinks only exist in crafted images, but entering
+            // one that leads back to an ancestor would loop forever. The same
+            // directory reached through sibling paths is walked each time.
+            let nid = entry.inode.id();
+            if self
+                .dir_stack
+                .iter()
+                .any(|&(_, ancestor, _)| ancestor == nid)
+            {
+                return Err(WalkError::Cycle {
+                    path: entry.dir_entry.path().to_string_lossy().into_owned(),
+                    nid,
+                }
+                .into());
+            }
+            let child_dir = ReadDir::new(self.erofs, entry.inode, entry.dir_entry.path()).await?;
+            self.dir_stack
+                .push((depth + 1, nid, DirEntries::Unsorted(child_dir)));
+        }
+
+        Ok((depth >= self.min_depth).then_some(entry))
     }
 
     pub async fn next_entry(&mut self) -> Option<Result<WalkDirEntry>> {
         loop {
             let (depth, next_item) = {
-                let (depth, dir) = self.dir_stack.last_mut()?;
-                let next = dir.next_entry().await;
+                let (depth, _, dir) = self.dir_stack.last_mut()?;
+                if let (Some(sorter), DirEntries::Unsorted(read_dir)) =
+                    (&mut self.sorter, &mut *dir)
+                {
+                    let mut entries = Vec::new();
+                    loop {
+                        match read_dir.next_entry().await {
+                            Ok(Some(entry)) => entries.push(entry),
+                            Ok(None) => break,
+                            Err(e) => {
+                                // The directory can't be sorted without all
+                                // of its entries, so it is skipped as a whole.
+                                self.dir_stack.pop();
+                                return Some(Err(e));
+                            }
+                        }
+                    }
+                    entries.sort_by(|a, b| sorter(a, b));
+                    *dir = DirEntries::Sorted(entries.into_iter());
+                }
+                let next = match dir {
+                    DirEntries::Unsorted(read_dir) => read_dir.next_entry().await,
+                    DirEntries::Sorted(entries) => Ok(entries.next()),
+                };
                 (*depth, next)
             };
 
             match next_item {
-                Ok(Some(entry)) => return Some(self.get_walk_dir_entry(entry, depth).await),
+                Ok(Some(entry)) => match self.get_walk_dir_entry(entry, depth).await {
+                    Ok(Some(entry)) => return Some(Ok(entry)),
+                    Ok(None) => {}
+                    Err(e) => return Some(Err(e)),
+                },
                 Ok(None) => {
                     self.dir_stack.pop();
                 }
@@ -93,3 +253,27 @@ impl<'a, I: AsyncImage> WalkDir<'a, I> {
         }
     }
 }
+
+/// An async iterator over the regular files of a directory tree and their
+/// paths.
+///
+/// Created by [`WalkDir::files`].
+pub struct Files<'a, I: AsyncImage> {
+    walk: WalkDir<'a, I>,
+}
+
+impl<'a, I: AsyncImage> Files<'a, I> {
+    pub async fn next_file(&mut self) -> Option<Result<(UnixPathBuf, File<'a, I>)>> {
+        loop {
+            match self.walk.next_entry().await? {
+                Ok(entry) if entry.inode.is_file() => {
+                    let path = entry.dir_entry.path();
+                    let file = File::new(entry.inode, self.walk.erofs).with_path(path.clone());
+                    return Some(Ok((path, file)));
+                }
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
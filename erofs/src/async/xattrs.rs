@@ -0,0 +1,28 @@
+use alloc::string::String;
+
+use bytes::Bytes;
+
+use super::EroFS;
+use crate::Result;
+use crate::backend::AsyncImage;
+use crate::filesystem::SharedXattrScan;
+
+/// An async iterator over the entries of the shared xattr pool, in ID
+/// order.
+///
+/// Created by [`EroFS::shared_xattrs`]. Each item is an `(id, (name, value))`
+/// pair.
+pub struct SharedXattrs<'a, I: AsyncImage> {
+    erofs: &'a EroFS<I>,
+    scan: SharedXattrScan,
+}
+
+impl<'a, I: AsyncImage> SharedXattrs<'a, I> {
+    pub(crate) fn new(erofs: &'a EroFS<I>, scan: SharedXattrScan) -> Self {
+        Self { erofs, scan }
+    }
+
+    pub async fn next_xattr(&mut self) -> Option<Result<(u32, (String, Bytes))>> {
+        self.erofs.next_shared_xattr(&mut self.scan).await
+    }
+}
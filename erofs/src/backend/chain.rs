@@ -0,0 +1,176 @@
+use alloc::vec::Vec;
+use core::ops::{Bound, RangeBounds};
+
+use bytes::{Bytes, BytesMut};
+
+use super::Image;
+use super::lent::LentBuffers;
+
+/// A backend presenting several backends back to back as one image.
+///
+/// Use this for images split across several files or blobs, e.g. ones
+/// reassembled from chunked downloads, without concatenating them into one
+/// buffer first. Reads within a single part are passed through to it,
+/// which keeps zero-copy backends zero-copy. Available only when the `std`
+/// feature is enabled.
+///
+/// # Memory usage
+///
+/// A range straddling two or more parts has to be copied together. Like
+/// with [`FileImage`](super::FileImage), such ranges requested through
+/// [`Image::get`] are kept until the `ChainImage` is dropped, while
+/// [`Image::get_owned`] copies them into the returned buffer every time.
+/// [`Image::get_borrowed`] lends nothing for them, so file data straddling
+/// parts is read with `get_owned`. EROFS reads rarely straddle parts split
+/// at block boundaries.
+///
+/// # Examples
+///
+/// ```no_run
+/// use erofs_rs::{EroFS, backend::{ChainImage, MmapImage}};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let parts = ["image.erofs.0", "image.erofs.1", "image.erofs.2"]
+///     .into_iter()
+///     .map(MmapImage::new_from_path)
+///     .collect::<Result<Vec<_>, _>>()?;
+/// let fs = EroFS::new(ChainImage::new(parts))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ChainImage<I> {
+    parts: Vec<I>,
+    /// The offset each part ends at, in the same order as `parts`.
+    ends: Vec<u64>,
+    /// Buffers handed out by `get` for ranges straddling parts.
+    lent: LentBuffers,
+}
+
+impl<I: Image> ChainImage<I> {
+    /// Creates a new `ChainImage` made of `parts`, in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use erofs_rs::backend::{ChainImage, Image, SliceImage};
+    ///
+    /// let image = ChainImage::new([SliceImage::new(b"Hello, "), SliceImage::new(b"world!")]);
+    /// assert_eq!(image.len(), 13);
+    /// assert_eq!(image.get(0..5), Some(&b"Hello"[..]));
+    /// assert_eq!(image.get(5..9), Some(&b", wo"[..]));
+    /// assert_eq!(image.get(10..14), None);
+    /// ```
+    pub fn new<T: IntoIterator<Item = I>>(parts: T) -> Self {
+        let parts: Vec<I> = parts.into_iter().collect();
+        let ends = parts
+            .iter()
+            .scan(0, |end, part| {
+                *end += part.len();
+                Some(*end)
+            })
+            .collect();
+        Self {
+            parts,
+            ends,
+            lent: LentBuffers::default(),
+        }
+    }
+}
+
+impl<I> ChainImage<I> {
+    /// Returns the parts making up the image.
+    pub fn parts(&self) -> &[I] {
+        &self.parts
+    }
+
+    /// Consumes the `ChainImage`, returning its parts.
+    pub fn into_inner(self) -> Vec<I> {
+        self.parts
+    }
+
+    /// Resolves `range` against the image, returning `None` if any part of
+    /// it lies outside.
+    fn bounds<R: RangeBounds<usize>>(&self, range: R) -> Option<(usize, usize)> {
+        let len = usize::try_from(self.ends.last().copied().unwrap_or(0)).ok()?;
+        let start = match range.start_bound() {
+            Bound::Included(&s) => s,
+            Bound::Excluded(&s) => s.checked_add(1)?,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&e) => e.checked_add(1)?,
+            Bound::Excluded(&e) => e,
+            Bound::Unbounded => len,
+        };
+        (start <= end && end <= len).then_some((start, end))
+    }
+
+    /// Splits `start..end` into `(part, local_start, local_end)` pieces, one
+    /// per part it covers.
+    fn pieces(&self, start: usize, end: usize) -> impl Iterator<Item = (&I, usize, usize)> {
+        let first = self.ends.partition_point(|&e| e <= start as u64);
+        let mut pos = start;
+        (first..self.parts.len()).map_while(move |i| {
+            if pos >= end {
+                return None;
+            }
+            let part_start = i.checked_sub(1).map_or(0, |prev| self.ends[prev] as usize);
+            let piece_end = end.min(self.ends[i] as usize);
+            let piece = (&self.parts[i], pos - part_start, piece_end - part_start);
+            pos = piece_end;
+            Some(piece)
+        })
+    }
+}
+
+impl<I: Image> Image for ChainImage<I> {
+    fn get<R: RangeBounds<usize>>(&self, range: R) -> Option<&[u8]> {
+        let (start, end) = self.bounds(range)?;
+        let Some((part, local_start, local_end)) = self.pieces(start, end).next() else {
+            return Some(&[]);
+        };
+        if local_end - local_start == end - start {
+            return part.get(local_start..local_end);
+        }
+
+        self.lent.get_or_fill(start, end, || {
+            let mut buf = Vec::with_capacity(end - start);
+            for (part, local_start, local_end) in self.pieces(start, end) {
+                buf.extend_from_slice(part.get(local_start..local_end)?);
+            }
+            Some(buf)
+        })
+    }
+
+    fn get_owned<R: RangeBounds<usize>>(&self, range: R) -> Option<Bytes> {
+        let (start, end) = self.bounds(range)?;
+        let Some((part, local_start, local_end)) = self.pieces(start, end).next() else {
+            return Some(Bytes::new());
+        };
+        if local_end - local_start == end - start {
+            return part.get_owned(local_start..local_end);
+        }
+
+        let mut buf = BytesMut::with_capacity(end - start);
+        for (part, local_start, local_end) in self.pieces(start, end) {
+            buf.extend_from_slice(&part.get_owned(local_start..local_end)?);
+        }
+        Some(buf.freeze())
+    }
+
+    fn get_borrowed<R: RangeBounds<usize>>(&self, range: R) -> Option<&[u8]> {
+        let (start, end) = self.bounds(range)?;
+        let Some((part, local_start, local_end)) = self.pieces(start, end).next() else {
+            return Some(&[]);
+        };
+        if local_end - local_start != end - start {
+            return None;
+        }
+        part.get_borrowed(local_start..local_end)
+    }
+
+    fn len(&self) -> u64 {
+        self.ends.last().copied().unwrap_or(0)
+    }
+}
@@ -0,0 +1,163 @@
+use alloc::vec::Vec;
+#[cfg(not(unix))]
+use std::sync::{Mutex, PoisonError};
+use std::{
+    fs, io,
+    ops::{Bound, RangeBounds},
+    path,
+};
+
+use bytes::Bytes;
+
+use super::Image;
+use super::lent::LentBuffers;
+
+/// A file backend for EROFS images that reads with positioned I/O instead
+/// of memory mapping.
+///
+/// Use this where mapping the image is not possible or not safe, e.g. when
+/// it lives on a network filesystem where a failed page fault would kill
+/// the process instead of returning an error. Available only when the
+/// `std` feature is enabled.
+///
+/// # Memory usage
+///
+/// [`Image::get`] lends out slices that live as long as the image, which a
+/// file can't provide by itself. Every range requested through `get` is
+/// therefore read into a buffer that is kept until the `FileImage` is
+/// dropped, and later requests within a cached range are served from it.
+/// [`EroFS`](crate::EroFS) only reads metadata such as inodes and
+/// compression maps this way, so memory usage grows with the amount of
+/// metadata that has been read.
+///
+/// [`Image::get_owned`] reads straight from the file into the returned
+/// buffer and never touches the cache. File data, including directory
+/// blocks, is read this way, since [`Image::get_borrowed`] lends nothing.
+///
+/// # Examples
+///
+/// ```no_run
+/// use erofs_rs::{EroFS, backend::FileImage};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let image = FileImage::new_from_path("image.erofs")?;
+/// let fs = EroFS::new(image)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FileImage {
+    file: fs::File,
+    len: u64,
+    /// Buffers handed out by `get`.
+    lent: LentBuffers,
+    /// Serializes the seek and read pair on platforms without positioned
+    /// reads.
+    #[cfg(not(unix))]
+    seek_lock: Mutex<()>,
+}
+
+impl FileImage {
+    /// Creates a new `FileImage` reading from `file`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use erofs_rs::backend::FileImage;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let file = File::open("image.erofs")?;
+    /// let image = FileImage::new(file)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(file: fs::File) -> io::Result<Self> {
+        let len = file.metadata()?.len();
+        Ok(Self {
+            file,
+            len,
+            lent: LentBuffers::default(),
+            #[cfg(not(unix))]
+            seek_lock: Mutex::default(),
+        })
+    }
+
+    /// Creates a new `FileImage` by opening the file at the given path.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::backend::FileImage;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let image = FileImage::new_from_path("image.erofs")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_from_path<P: AsRef<path::Path>>(path: P) -> io::Result<Self> {
+        Self::new(fs::File::open(path)?)
+    }
+
+    /// Resolves `range` against the image, returning `None` if any part of
+    /// it lies outside.
+    fn bounds<R: RangeBounds<usize>>(&self, range: R) -> Option<(usize, usize)> {
+        let len = usize::try_from(self.len).ok()?;
+        let start = match range.start_bound() {
+            Bound::Included(&s) => s,
+            Bound::Excluded(&s) => s + 1,
+            Bound::Unbounded => 0,
+        };
+
+        let end = match range.end_bound() {
+            Bound::Included(&e) => e + 1,
+            Bound::Excluded(&e) => e,
+            Bound::Unbounded => len,
+        };
+        (start <= end && end <= len).then_some((start, end))
+    }
+
+    fn read_range(&self, start: usize, end: usize) -> Option<Vec<u8>> {
+        let mut buf = vec![0u8; end - start];
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileExt;
+            self.file.read_exact_at(&mut buf, start as u64).ok()?;
+        }
+        #[cfg(not(unix))]
+        {
+            use std::io::{Read, Seek, SeekFrom};
+            let _guard = self
+                .seek_lock
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let mut file = &self.file;
+            file.seek(SeekFrom::Start(start as u64)).ok()?;
+            file.read_exact(&mut buf).ok()?;
+        }
+
+        Some(buf)
+    }
+}
+
+impl Image for FileImage {
+    fn get<R: RangeBounds<usize>>(&self, range: R) -> Option<&[u8]> {
+        let (start, end) = self.bounds(range)?;
+        self.lent
+            .get_or_fill(start, end, || self.read_range(start, end))
+    }
+
+    fn get_owned<R: RangeBounds<usize>>(&self, range: R) -> Option<Bytes> {
+        let (start, end) = self.bounds(range)?;
+        self.read_range(start, end).map(Bytes::from)
+    }
+
+    fn get_borrowed<R: RangeBounds<usize>>(&self, _range: R) -> Option<&[u8]> {
+        None
+    }
+
+    fn len(&self) -> u64 {
+        self.len
+    }
+}
@@ -0,0 +1,50 @@
+use alloc::{boxed::Box, collections::BTreeMap, vec::Vec};
+use std::sync::{Mutex, PoisonError};
+
+/// Buffers lent out by backends that can't borrow [`Image::get`] ranges
+/// from the image itself, keyed by their `(start, end)` range.
+///
+/// Buffers are kept until the owner is dropped, so memory usage grows with
+/// the amount of data that has been lent out.
+///
+/// [`Image::get`]: super::Image::get
+#[derive(Debug, Default)]
+pub(super) struct LentBuffers(Mutex<Buffers>);
+
+type Buffers = BTreeMap<(usize, usize), Box<[u8]>>;
+
+impl LentBuffers {
+    /// Returns the bytes of `start..end`, calling `fill` to produce them if
+    /// no buffer lent out before covers the range. Fails if `fill` does, or
+    /// returns a buffer of any other length.
+    pub(super) fn get_or_fill<F>(&self, start: usize, end: usize, fill: F) -> Option<&[u8]>
+    where
+        F: FnOnce() -> Option<Vec<u8>>,
+    {
+        let mut buffers = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+
+        // The buffer starting closest before `start` is the only one worth
+        // checking; reads of the same structure share their start.
+        let lent = buffers
+            .range(..=(start, usize::MAX))
+            .next_back()
+            .filter(|((s, e), _)| *s <= start && end <= *e)
+            .map(|((s, _), buf)| (buf.as_ptr(), start - s));
+        let (ptr, offset) = match lent {
+            Some(found) => found,
+            None => {
+                let buf = fill().filter(|buf| buf.len() == end - start)?;
+                let buf = buf.into_boxed_slice();
+                let ptr = buf.as_ptr();
+                buffers.insert((start, end), buf);
+                (ptr, 0)
+            }
+        };
+        drop(buffers);
+
+        // SAFETY: buffers live on the heap and are only freed when `self` is
+        // dropped, so they outlive the returned borrow even after the lock
+        // is released or the map is rebalanced.
+        Some(unsafe { core::slice::from_raw_parts(ptr.add(offset), end - start) })
+    }
+}
@@ -4,7 +4,7 @@ use std::{
     path,
 };
 
-use memmap2::Mmap;
+use memmap2::{Mmap, MmapOptions};
 
 use super::Image;
 
@@ -126,4 +126,60 @@ impl MmapImage {
         let file = fs::File::open(path)?;
         Self::new_from_file(&file)
     }
+
+    /// Creates a new `MmapImage` by memory-mapping the given file and
+    /// pre-faulting the whole mapping.
+    ///
+    /// The file is read in up front (`MAP_POPULATE` on Linux), so later
+    /// accesses don't block on page faults. This suits callers that walk the
+    /// whole image right after opening it, at the cost of reading parts that
+    /// may never be used. Other platforms map the file as
+    /// [`new_from_file`](Self::new_from_file) does.
+    ///
+    /// # Safety
+    ///
+    /// This function creates a memory-mapped view of the file. The caller must
+    /// ensure that the file is not modified while the mapping is active.
+    pub fn new_from_file_populated(file: &fs::File) -> io::Result<Self> {
+        let mmap = unsafe { MmapOptions::new().populate().map(file)? };
+        Ok(Self(mmap))
+    }
+
+    /// Creates a new `MmapImage` by opening and memory-mapping a file at the
+    /// given path, pre-faulting the whole mapping like
+    /// [`new_from_file_populated`](Self::new_from_file_populated).
+    ///
+    /// # Safety
+    ///
+    /// This function creates a memory-mapped view of the file. The caller must
+    /// ensure that the file is not modified while the mapping is active.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{EroFS, backend::MmapImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::new(MmapImage::new_from_path_populated("image.erofs")?)?;
+    /// for entry in fs.walk_dir("/")? {
+    ///     println!("{}", entry?.dir_entry.path().to_string_lossy());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_from_path_populated<P: AsRef<path::Path>>(path: P) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        Self::new_from_file_populated(&file)
+    }
+
+    /// Tells the kernel the mapping will be read sequentially
+    /// (`MADV_SEQUENTIAL`), so it reads ahead more aggressively and drops
+    /// pages soon after they were read.
+    ///
+    /// Useful when streaming every file out of the image once, e.g. while
+    /// extracting it. Only available on Unix.
+    #[cfg(unix)]
+    pub fn advise_sequential(&self) -> io::Result<()> {
+        self.0.advise(memmap2::Advice::Sequential)
+    }
 }
@@ -4,7 +4,14 @@
 //! from different sources:
 //!
 //! - [`MmapImage`]: Memory-mapped files (requires `std` feature)
+//! - [`FileImage`]: Files read with positioned I/O (requires `std` feature)
 //! - [`SliceImage`]: Raw byte slices (available in `no_std` mode)
+//! - [`VecImage`]: Owned in-memory data (available in `no_std` mode)
+//! - [`BytesImage`]: Shared in-memory [`Bytes`](bytes::Bytes), an alias of
+//!   [`VecImage`] (available in `no_std` mode)
+//! - [`OffsetImage`]: An image embedded at an offset within another backend
+//! - [`ChainImage`]: An image split across several backends (requires `std`
+//!   feature)
 //!
 //! The [`Image`] trait defines the common interface that all backend implementations
 //! must implement.
@@ -33,9 +40,23 @@
 //! ```
 
 use binrw::io::Cursor;
+use bytes::Bytes;
 use core::{future::Future, ops};
 
-use super::Result;
+use super::{Error, Result};
+
+#[cfg(feature = "std")]
+mod chain;
+#[cfg(feature = "std")]
+pub use chain::ChainImage;
+
+#[cfg(feature = "std")]
+mod file;
+#[cfg(feature = "std")]
+pub use file::FileImage;
+
+#[cfg(feature = "std")]
+mod lent;
 
 #[cfg(feature = "std")]
 mod mmap;
@@ -47,13 +68,23 @@ mod opendal;
 #[cfg(all(feature = "std", feature = "opendal"))]
 pub use opendal::OpendalImage;
 
+mod offset;
+pub use offset::OffsetImage;
+
 mod slice;
 pub use slice::SliceImage;
 
+mod vec;
+pub use vec::{BytesImage, VecImage};
+
 /// A trait for accessing EROFS image data from various sources.
 ///
 /// This trait provides a common interface for reading data from different
 /// backend types, enabling zero-copy access where possible.
+///
+/// [`EroFS`](crate::EroFS) is [`Send`] and [`Sync`] if the backend is, so
+/// backends that need interior mutability, like [`FileImage`], should use
+/// thread-safe types for it.
 pub trait Image {
     /// Gets a slice of data at the specified range.
     ///
@@ -71,10 +102,104 @@ pub trait Image {
     /// ```
     fn get<R: ops::RangeBounds<usize>>(&self, range: R) -> Option<&[u8]>;
 
+    /// Gets an owned copy of the data at the specified range.
+    ///
+    /// Returns `None` if the range is out of bounds. The default copies the
+    /// slice returned by [`get`](Self::get); backends that can't lend out
+    /// slices of the image, like [`FileImage`], read straight into the
+    /// returned buffer instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use erofs_rs::backend::{Image, SliceImage};
+    ///
+    /// let image = SliceImage::new(b"Hello, world!");
+    /// assert_eq!(image.get_owned(7..12).as_deref(), Some(&b"world"[..]));
+    /// ```
+    fn get_owned<R: ops::RangeBounds<usize>>(&self, range: R) -> Option<Bytes> {
+        self.get(range).map(Bytes::copy_from_slice)
+    }
+
+    /// Gets a slice of data at the specified range, if the backend can lend
+    /// it out without keeping a copy.
+    ///
+    /// Returns `None` if the range is out of bounds, and always for backends
+    /// that can't borrow from the image itself, like [`FileImage`], which
+    /// are read with [`get_owned`](Self::get_owned) instead. File data is
+    /// read through this, so that reading a whole image doesn't keep it in
+    /// memory. The default is [`get`](Self::get), for backends whose slices
+    /// point into the image.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use erofs_rs::backend::{Image, SliceImage};
+    ///
+    /// let image = SliceImage::new(b"Hello, world!");
+    /// assert_eq!(image.get_borrowed(0..5), Some(&b"Hello"[..]));
+    /// ```
+    fn get_borrowed<R: ops::RangeBounds<usize>>(&self, range: R) -> Option<&[u8]> {
+        self.get(range)
+    }
+
+    /// Gets a reference to the `N` bytes at `offset` as a fixed-size array.
+    ///
+    /// Returns `None` if any of the bytes lie outside the image. This suits
+    /// reading fixed-size fields, where the length is checked by the type
+    /// rather than by a hand-computed range.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use erofs_rs::backend::{Image, SliceImage};
+    ///
+    /// let image = SliceImage::new(&[0x78, 0x56, 0x34, 0x12, 0xff]);
+    /// let value = image.get_array::<4>(0).map(|b| u32::from_le_bytes(*b));
+    /// assert_eq!(value, Some(0x1234_5678));
+    /// assert_eq!(image.get_array::<4>(2), None);
+    /// assert_eq!(image.get_array::<2>(usize::MAX), None);
+    /// ```
+    fn get_array<const N: usize>(&self, offset: usize) -> Option<&[u8; N]> {
+        self.get(offset..offset.checked_add(N)?)?.try_into().ok()
+    }
+
+    /// Gets the `len` bytes at `offset`, failing with
+    /// [`Error::OutOfBounds`] if any of them lie outside the image.
+    ///
+    /// Unlike [`get`](Self::get), the error records where the read landed
+    /// and how long the image is, which tells a truncated image apart from
+    /// a corrupted offset.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use erofs_rs::{Error, backend::{Image, SliceImage}};
+    ///
+    /// let image = SliceImage::new(b"Hello, world!");
+    /// assert_eq!(image.read_exact_at(7, 5).unwrap(), b"world");
+    /// assert!(matches!(
+    ///     image.read_exact_at(10, 8),
+    ///     Err(Error::OutOfBounds { offset: 10, len: 8, image_len: 13 })
+    /// ));
+    /// ```
+    fn read_exact_at(&self, offset: usize, len: usize) -> Result<&[u8]> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.get(offset..end))
+            .ok_or(Error::OutOfBounds {
+                offset,
+                len,
+                image_len: self.len(),
+            })
+    }
+
     /// Gets a cursor for reading data starting at the specified offset.
     ///
     /// This is a convenience method for creating a `Cursor` that can be used
-    /// with binary parsing libraries like `binrw`.
+    /// with binary parsing libraries like `binrw`. Returns `None` if `offset`
+    /// is past the end of the image; the cursor covers everything from
+    /// `offset` to the end.
     fn get_cursor(&self, offset: usize) -> Option<Cursor<&[u8]>> {
         self.get(offset..).map(Cursor::new)
     }
@@ -0,0 +1,103 @@
+use super::{AsyncImage, Image};
+use crate::Result;
+use bytes::Bytes;
+use core::ops::{self, Bound};
+
+/// A backend exposing the part of another backend that starts at a fixed
+/// offset.
+///
+/// Some containers, like Android super partitions and OTA packages, embed an
+/// EROFS image somewhere inside a larger file. Wrapping the container's
+/// backend in an `OffsetImage` makes every read relative to where the image
+/// starts, so the superblock is found at `offset + 1024` and block addresses
+/// resolve within the embedded image, without copying it out first. Reads
+/// are passed through to the wrapped backend, which keeps zero-copy backends
+/// zero-copy.
+///
+/// Works with both [`Image`] and [`AsyncImage`] backends.
+///
+/// # Examples
+///
+/// ```no_run
+/// use erofs_rs::{EroFS, backend::{MmapImage, OffsetImage}};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let container = MmapImage::new_from_path("super.img")?;
+/// let fs = EroFS::new(OffsetImage::new(container, 0x10_0000))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct OffsetImage<I> {
+    inner: I,
+    offset: usize,
+}
+
+impl<I> OffsetImage<I> {
+    /// Creates a new `OffsetImage` starting `offset` bytes into `inner`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use erofs_rs::backend::{Image, OffsetImage, SliceImage};
+    ///
+    /// let image = OffsetImage::new(SliceImage::new(b"headerEROFS"), 6);
+    /// assert_eq!(image.get(..), Some(&b"EROFS"[..]));
+    /// assert_eq!(image.len(), 5);
+    /// ```
+    pub fn new(inner: I, offset: usize) -> Self {
+        Self { inner, offset }
+    }
+
+    /// Returns the offset within the wrapped backend the image starts at.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Consumes the `OffsetImage`, returning the wrapped backend.
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    /// Translates `range` into the wrapped backend, or `None` if it
+    /// overflows.
+    fn shift<R: ops::RangeBounds<usize>>(&self, range: R) -> Option<(Bound<usize>, Bound<usize>)> {
+        let start = match range.start_bound() {
+            Bound::Included(&s) => Bound::Included(s.checked_add(self.offset)?),
+            Bound::Excluded(&s) => Bound::Excluded(s.checked_add(self.offset)?),
+            Bound::Unbounded => Bound::Included(self.offset),
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&e) => Bound::Included(e.checked_add(self.offset)?),
+            Bound::Excluded(&e) => Bound::Excluded(e.checked_add(self.offset)?),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        Some((start, end))
+    }
+}
+
+impl<I: Image> Image for OffsetImage<I> {
+    fn get<R: ops::RangeBounds<usize>>(&self, range: R) -> Option<&[u8]> {
+        self.inner.get(self.shift(range)?)
+    }
+
+    fn get_owned<R: ops::RangeBounds<usize>>(&self, range: R) -> Option<Bytes> {
+        self.inner.get_owned(self.shift(range)?)
+    }
+
+    fn get_borrowed<R: ops::RangeBounds<usize>>(&self, range: R) -> Option<&[u8]> {
+        self.inner.get_borrowed(self.shift(range)?)
+    }
+
+    fn len(&self) -> u64 {
+        self.inner.len().saturating_sub(self.offset as u64)
+    }
+}
+
+impl<I: AsyncImage> AsyncImage for OffsetImage<I> {
+    async fn read_exact_at(&self, buf: &mut [u8], offset: usize) -> Result<usize> {
+        self.inner
+            .read_exact_at(buf, offset.saturating_add(self.offset))
+            .await
+    }
+}
@@ -0,0 +1,95 @@
+use super::Image;
+use alloc::vec::Vec;
+use bytes::Bytes;
+use core::ops;
+
+/// An owned in-memory backend for EROFS images.
+///
+/// Unlike [`SliceImage`](super::SliceImage), the image data is owned by the
+/// backend, so an `EroFS<VecImage>` has no lifetime to thread through and
+/// can be stored or moved freely. The data is kept as [`Bytes`], so
+/// [`Image::get_owned`] hands out shared views of it instead of copies.
+/// Available in `no_std` mode.
+///
+/// # Examples
+///
+/// ```no_run
+/// use erofs_rs::{EroFS, backend::VecImage};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let data = std::fs::read("image.erofs")?;
+/// let fs = EroFS::new(VecImage::new(data))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct VecImage(Bytes);
+
+/// An in-memory backend over a shared [`Bytes`] buffer.
+///
+/// This is [`VecImage`] under the name that says what it holds: wrapping
+/// `Bytes` that were already downloaded or read elsewhere neither copies
+/// them nor borrows them, and cloning the backend only bumps a reference
+/// count. Available in `no_std` mode.
+///
+/// # Examples
+///
+/// ```no_run
+/// use bytes::Bytes;
+/// use erofs_rs::{EroFS, backend::BytesImage};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let data = Bytes::from(std::fs::read("image.erofs")?);
+/// let fs = EroFS::new(BytesImage::from(data.clone()))?;
+/// # Ok(())
+/// # }
+/// ```
+pub type BytesImage = VecImage;
+
+impl VecImage {
+    /// Creates a new `VecImage` owning `data`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use erofs_rs::backend::{Image, VecImage};
+    ///
+    /// let image = VecImage::new(vec![0u8; 1024]);
+    /// assert_eq!(image.len(), 1024);
+    /// ```
+    pub fn new<B: Into<Bytes>>(data: B) -> Self {
+        Self(data.into())
+    }
+
+    /// Consumes the `VecImage`, returning the image data.
+    pub fn into_inner(self) -> Bytes {
+        self.0
+    }
+}
+
+impl From<Vec<u8>> for VecImage {
+    fn from(data: Vec<u8>) -> Self {
+        Self::new(data)
+    }
+}
+
+impl From<Bytes> for VecImage {
+    fn from(data: Bytes) -> Self {
+        Self(data)
+    }
+}
+
+impl Image for VecImage {
+    fn get<R: ops::RangeBounds<usize>>(&self, range: R) -> Option<&[u8]> {
+        self.0
+            .get((range.start_bound().cloned(), range.end_bound().cloned()))
+    }
+
+    fn get_owned<R: ops::RangeBounds<usize>>(&self, range: R) -> Option<Bytes> {
+        self.get(range).map(|data| self.0.slice_ref(data))
+    }
+
+    fn len(&self) -> u64 {
+        self.0.len() as u64
+    }
+}
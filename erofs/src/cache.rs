@@ -0,0 +1,197 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use std::sync::{Mutex, PoisonError};
+
+use bytes::Bytes;
+use typed_path::UnixPath;
+
+use crate::types::DirentFileType;
+use crate::zmap::Extent;
+
+/// A least-recently-used cache of decoded extents.
+///
+/// Compressed data is decoded a whole physical cluster at a time, so
+/// reading a file block by block would otherwise decode every cluster once
+/// per block it spans.
+#[derive(Debug)]
+pub struct ExtentCache {
+    /// Upper bound on the total size of cached data, in bytes.
+    capacity: usize,
+    inner: Mutex<Lru<Extent, Bytes>>,
+}
+
+impl ExtentCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::default(),
+        }
+    }
+
+    pub fn get(&self, extent: &Extent) -> Option<Bytes> {
+        self.inner
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(extent)
+    }
+
+    pub fn insert(&self, extent: Extent, data: Bytes) {
+        if data.len() > self.capacity {
+            return;
+        }
+
+        let size = data.len();
+        self.inner
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(extent, data, size, self.capacity);
+    }
+}
+
+/// How the data of a single [`EroFS::read_at_traced`](crate::EroFS::read_at_traced)
+/// call was served.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReadTrace {
+    /// Number of bytes read into the buffer.
+    pub bytes: usize,
+    /// Compressed extents whose decoded data was found in the extent cache.
+    pub cache_hits: usize,
+    /// Compressed extents that had to be decoded, which is all of them on
+    /// a filesystem without a cache.
+    pub cache_misses: usize,
+}
+
+/// The nid a path resolves to and the file type recorded for it, as
+/// returned by path lookups.
+pub type PathTarget = (u64, Option<DirentFileType>);
+
+/// A least-recently-used cache of resolved paths.
+///
+/// Resolving a path reads every directory on it starting from the root.
+/// The image is read-only, so a resolved path never goes stale and entries
+/// only leave the cache when it is full.
+#[derive(Debug)]
+pub struct PathCache {
+    /// Upper bound on the number of cached paths.
+    capacity: usize,
+    /// Keyed by the path as given and whether its last component was
+    /// followed.
+    inner: Mutex<Lru<(Vec<u8>, bool), PathTarget>>,
+}
+
+impl PathCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::default(),
+        }
+    }
+
+    pub fn get(&self, path: &UnixPath, follow: bool) -> Option<PathTarget> {
+        self.inner
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&(path.as_bytes().to_vec(), follow))
+    }
+
+    pub fn insert(&self, path: &UnixPath, follow: bool, target: PathTarget) {
+        self.inner
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert((path.as_bytes().to_vec(), follow), target, 1, self.capacity);
+    }
+}
+
+#[derive(Debug)]
+struct Lru<K, V> {
+    /// Values with their size and last use.
+    entries: BTreeMap<K, (V, usize, u64)>,
+    /// Keys ordered from least to most recently used.
+    order: BTreeMap<u64, K>,
+    tick: u64,
+    size: usize,
+}
+
+impl<K, V> Default for Lru<K, V> {
+    fn default() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+            size: 0,
+        }
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Lru<K, V> {
+    fn get(&mut self, key: &K) -> Option<V> {
+        let (value, _, tick) = self.entries.get_mut(key)?;
+        let key = self.order.remove(tick)?;
+        self.tick += 1;
+        *tick = self.tick;
+        let value = value.clone();
+        self.order.insert(self.tick, key);
+        Some(value)
+    }
+
+    /// Inserts `value` counting `size` towards `capacity`, evicting the
+    /// least recently used entries to make room.
+    fn insert(&mut self, key: K, value: V, size: usize, capacity: usize) {
+        if let Some((_, old_size, tick)) = self.entries.remove(&key) {
+            self.order.remove(&tick);
+            self.size -= old_size;
+        }
+        while self.size + size > capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some((_, old_size, _)) = self.entries.remove(&oldest) {
+                self.size -= old_size;
+            }
+        }
+
+        self.tick += 1;
+        self.size += size;
+        self.order.insert(self.tick, key.clone());
+        self.entries.insert(key, (value, size, self.tick));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zmap::ExtentFormat;
+
+    fn extent(la: usize) -> Extent {
+        Extent {
+            la,
+            llen: 4,
+            pa: la,
+            plen: 4,
+            format: ExtentFormat::Shifted,
+            partial: false,
+        }
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = ExtentCache::new(8);
+        cache.insert(extent(0), Bytes::from_static(b"aaaa"));
+        cache.insert(extent(1), Bytes::from_static(b"bbbb"));
+        assert_eq!(cache.get(&extent(0)).as_deref(), Some(&b"aaaa"[..]));
+
+        cache.insert(extent(2), Bytes::from_static(b"cccc"));
+        assert_eq!(cache.get(&extent(1)), None);
+        assert_eq!(cache.get(&extent(0)).as_deref(), Some(&b"aaaa"[..]));
+        assert_eq!(cache.get(&extent(2)).as_deref(), Some(&b"cccc"[..]));
+    }
+
+    #[test]
+    fn skips_data_larger_than_capacity() {
+        let cache = ExtentCache::new(8);
+        cache.insert(extent(0), Bytes::from_static(b"aaaa"));
+        cache.insert(extent(1), Bytes::from_static(b"too large"));
+        assert_eq!(cache.get(&extent(1)), None);
+        assert_eq!(cache.get(&extent(0)).as_deref(), Some(&b"aaaa"[..]));
+    }
+}
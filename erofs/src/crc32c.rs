@@ -0,0 +1,45 @@
+/// Lookup table for the reflected CRC32C (Castagnoli) polynomial.
+const TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x82f6_3b78
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Updates `crc` with `data`, without the final inversion.
+///
+/// This matches the kernel's `crc32c()`, which EROFS checksums are
+/// computed with starting from `!0`.
+pub fn update(mut crc: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        crc = TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_value() {
+        assert_eq!(!update(!0, b"123456789"), 0xe306_9283);
+        assert_eq!(
+            update(update(!0, b"1234"), b"56789"),
+            update(!0, b"123456789")
+        );
+    }
+}
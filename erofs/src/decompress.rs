@@ -0,0 +1,297 @@
+use alloc::{borrow::Cow, collections::BTreeSet, string::ToString, vec::Vec};
+
+use binrw::{BinRead, io::Cursor};
+
+use crate::types::{
+    CompressionAlgo, DEFLATE_MAX_WINDOWBITS, DeflateConfig, LZMA_MAX_DICT_SIZE, LzmaConfig,
+    ZSTD_MAX_DICT_SIZE, ZstdConfig,
+};
+use crate::zmap::{Extent, ExtentFormat};
+use crate::{Error, Result};
+
+/// Decoder parameters recorded in the compression configs that follow the
+/// superblock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Configs {
+    pub lzma: Option<LzmaConfig>,
+    pub deflate: Option<DeflateConfig>,
+    pub zstd: Option<ZstdConfig>,
+}
+
+impl Configs {
+    /// Parses the configs of `algos` from `data`, which starts right after
+    /// the superblock and its extension slots.
+    ///
+    /// Each config is a little-endian `u16` length followed by its payload,
+    /// aligned to 4 bytes and stored in the order of the algorithm bits.
+    pub fn parse(algos: &BTreeSet<CompressionAlgo>, data: &[u8]) -> Result<Self> {
+        let mut configs = Self::default();
+        let mut pos = 0usize;
+        for &algo in algos {
+            pos = pos.next_multiple_of(4);
+            let len = data
+                .get(pos..pos + 2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]))
+                .ok_or_else(|| Error::CorruptedData("truncated compression configs".to_string()))?;
+            // A zero length stands for the maximum of 65536 bytes.
+            let len = if len == 0 { 1 << 16 } else { len as usize };
+            let payload = data.get(pos + 2..pos + 2 + len).ok_or_else(|| {
+                Error::CorruptedData(format!("truncated {} compression config", algo.name()))
+            })?;
+            pos += 2 + len;
+
+            match algo {
+                CompressionAlgo::Lzma => {
+                    let config = LzmaConfig::read(&mut Cursor::new(payload))?;
+                    if config.format != 0 || config.dict_size > LZMA_MAX_DICT_SIZE {
+                        return Err(Error::CorruptedData(format!(
+                            "invalid lzma config: format {}, dict size {}",
+                            config.format, config.dict_size
+                        )));
+                    }
+                    configs.lzma = Some(config);
+                }
+                CompressionAlgo::Deflate => {
+                    let config = DeflateConfig::read(&mut Cursor::new(payload))?;
+                    if config.windowbits > DEFLATE_MAX_WINDOWBITS {
+                        return Err(Error::CorruptedData(format!(
+                            "invalid deflate config: window bits {}",
+                            config.windowbits
+                        )));
+                    }
+                    configs.deflate = Some(config);
+                }
+                CompressionAlgo::Zstd => {
+                    let config = ZstdConfig::read(&mut Cursor::new(payload))?;
+                    if config.format != 0 || config.window_size()? > ZSTD_MAX_DICT_SIZE as u64 {
+                        return Err(Error::CorruptedData(format!(
+                            "invalid zstd config: format {}, window log {}",
+                            config.format, config.windowlog
+                        )));
+                    }
+                    configs.zstd = Some(config);
+                }
+                _ => {}
+            }
+        }
+        Ok(configs)
+    }
+}
+
+/// Decodes the physical cluster `input` of `extent` into its `llen` bytes.
+///
+/// When `zero_padding` is set, compressed data is aligned to the end of the
+/// pcluster and the leading zeros must be skipped before decoding.
+pub fn decode_extent<'a>(
+    extent: &Extent,
+    input: &'a [u8],
+    zero_padding: bool,
+    block_size: usize,
+    configs: &Configs,
+) -> Result<Cow<'a, [u8]>> {
+    let llen = extent.llen;
+    match extent.format {
+        // Fragments are read from the packed inode as they are.
+        ExtentFormat::Shifted | ExtentFormat::Fragment => input
+            .get(..llen)
+            .map(Cow::Borrowed)
+            .ok_or_else(|| Error::CorruptedData("plain extent exceeds its pcluster".to_string())),
+        ExtentFormat::Interlaced => {
+            // The block is rotated so that logical offset `la` lands at
+            // `la % block_size`: the head comes from the end of the input.
+            let head_len = block_size - extent.la % block_size;
+            let start = input.len().checked_sub(head_len).ok_or_else(|| {
+                Error::CorruptedData("interlaced extent exceeds its pcluster".to_string())
+            })?;
+            let head = &input[start..start + head_len.min(llen)];
+            let rest = input.get(..llen - head.len()).ok_or_else(|| {
+                Error::CorruptedData("interlaced extent exceeds its pcluster".to_string())
+            })?;
+
+            let mut output = Vec::with_capacity(llen);
+            output.extend_from_slice(head);
+            output.extend_from_slice(rest);
+            Ok(Cow::Owned(output))
+        }
+        ExtentFormat::Compressed(algo) => {
+            let input = if zero_padding {
+                strip_zero_padding(input, block_size)?
+            } else {
+                input
+            };
+            decompress(algo, input, llen, extent.partial, configs).map(Cow::Owned)
+        }
+    }
+}
+
+/// Skips the zeros padding compressed data to the end of its first block.
+fn strip_zero_padding(input: &[u8], block_size: usize) -> Result<&[u8]> {
+    let window = input.len().min(block_size);
+    input[..window]
+        .iter()
+        .position(|&b| b != 0)
+        .map(|i| &input[i..])
+        .ok_or_else(|| Error::CorruptedData("compressed cluster contains only padding".to_string()))
+}
+
+/// Decompresses `input` into exactly `len` bytes. If `partial` is set the
+/// stream may hold more data than needed, and the excess is dropped.
+#[cfg_attr(
+    not(all(
+        feature = "lz4",
+        feature = "lzma",
+        feature = "deflate",
+        feature = "zstd"
+    )),
+    allow(unused_variables)
+)]
+fn decompress(
+    algo: CompressionAlgo,
+    input: &[u8],
+    len: usize,
+    partial: bool,
+    configs: &Configs,
+) -> Result<Vec<u8>> {
+    match algo {
+        #[cfg(feature = "lz4")]
+        CompressionAlgo::Lz4 => decompress_lz4(input, len, partial),
+        #[cfg(feature = "lzma")]
+        CompressionAlgo::Lzma => {
+            let config = configs.lzma.ok_or_else(|| {
+                Error::CorruptedData("lzma cluster without an lzma config".to_string())
+            })?;
+            decompress_lzma(input, len, config.dict_size)
+        }
+        #[cfg(feature = "deflate")]
+        CompressionAlgo::Deflate => decompress_deflate(input, len),
+        #[cfg(feature = "zstd")]
+        CompressionAlgo::Zstd => {
+            let config = configs.zstd.ok_or_else(|| {
+                Error::CorruptedData("zstd cluster without a zstd config".to_string())
+            })?;
+            decompress_zstd(input, len, config.window_size()?)
+        }
+        // Unreachable only when every decompressor is enabled.
+        #[allow(unreachable_patterns)]
+        algo => Err(Error::UnsupportedCompression(algo.name().to_string())),
+    }
+}
+
+#[cfg(feature = "lz4")]
+fn decompress_lz4(input: &[u8], len: usize, partial: bool) -> Result<Vec<u8>> {
+    use lz4_flex::block::DecompressError;
+
+    let mut output = vec![0u8; len];
+    loop {
+        match lz4_flex::block::decompress_into(input, &mut output) {
+            Ok(n) if n == len || (partial && n > len) => {
+                output.truncate(len);
+                return Ok(output);
+            }
+            Ok(n) => {
+                return Err(Error::CorruptedData(format!(
+                    "lz4 cluster decoded to {} bytes, expected {}",
+                    n, len
+                )));
+            }
+            // The full stream length of a partially referenced cluster is
+            // unknown; LZ4 can expand at most 255 times.
+            Err(DecompressError::OutputTooSmall { .. })
+                if partial && output.len() < input.len() * 255 =>
+            {
+                output.resize(output.len() * 2, 0);
+            }
+            Err(e) => return Err(Error::CorruptedData(format!("lz4: {}", e))),
+        }
+    }
+}
+
+/// Decodes a MicroLZMA stream, a raw LZMA1 stream whose first byte holds the
+/// inverted properties byte in place of the always-zero first byte of the
+/// range coder. The stream has no end marker, so it is read up to `len`.
+#[cfg(feature = "lzma")]
+fn decompress_lzma(input: &[u8], len: usize, dict_size: u32) -> Result<Vec<u8>> {
+    #[cfg(not(feature = "std"))]
+    use lzma_rust2::Read;
+    #[cfg(feature = "std")]
+    use std::io::Read;
+
+    let (&props, rest) = input
+        .split_first()
+        .ok_or_else(|| Error::CorruptedData("empty lzma cluster".to_string()))?;
+    let mut stream = Vec::with_capacity(input.len());
+    stream.push(0);
+    stream.extend_from_slice(rest);
+
+    let mut output = vec![0u8; len];
+    lzma_rust2::LzmaReader::new_with_props(stream.as_slice(), len as u64, !props, dict_size, None)
+        .and_then(|mut reader| reader.read_exact(&mut output))
+        .map_err(|e| Error::CorruptedData(format!("lzma: {:?}", e)))?;
+    Ok(output)
+}
+
+/// Decodes the first `len` bytes of a raw DEFLATE stream, without a zlib
+/// header or trailer.
+#[cfg(feature = "deflate")]
+fn decompress_deflate(input: &[u8], len: usize) -> Result<Vec<u8>> {
+    use miniz_oxide::inflate::TINFLStatus;
+    use miniz_oxide::inflate::core::{DecompressorOxide, decompress, inflate_flags};
+
+    let mut output = vec![0u8; len];
+    let mut decompressor = DecompressorOxide::new();
+    let (status, _, n) = decompress(
+        &mut decompressor,
+        input,
+        &mut output,
+        0,
+        inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+    );
+    match status {
+        // The stream may go on past the part this extent covers.
+        TINFLStatus::Done | TINFLStatus::HasMoreOutput if n == len => Ok(output),
+        TINFLStatus::Done | TINFLStatus::HasMoreOutput => Err(Error::CorruptedData(format!(
+            "deflate cluster decoded to {} bytes, expected {}",
+            n, len
+        ))),
+        status => Err(Error::CorruptedData(format!("deflate: {:?}", status))),
+    }
+}
+
+/// Decodes the first `len` bytes of a zstd frame whose window may not exceed
+/// `max_window`, as recorded in the image's zstd config.
+#[cfg(feature = "zstd")]
+fn decompress_zstd(input: &[u8], len: usize, max_window: u64) -> Result<Vec<u8>> {
+    use ruzstd::decoding::StreamingDecoder;
+    use ruzstd::io::Read;
+
+    if let Some(window) = zstd_window_size(input)
+        && window > max_window
+    {
+        return Err(Error::CorruptedData(format!(
+            "zstd window of {} bytes exceeds the configured {} bytes",
+            window, max_window
+        )));
+    }
+
+    let mut output = vec![0u8; len];
+    StreamingDecoder::new(input)
+        .map_err(|e| Error::CorruptedData(format!("zstd: {}", e)))?
+        .read_exact(&mut output)
+        .map_err(|e| Error::CorruptedData(format!("zstd: {}", e)))?;
+    Ok(output)
+}
+
+/// Reads the window size from the header of the zstd frame at the start of
+/// `input`. Single-segment frames have no window descriptor and yield `None`.
+#[cfg(feature = "zstd")]
+fn zstd_window_size(input: &[u8]) -> Option<u64> {
+    const SINGLE_SEGMENT: u8 = 1 << 5;
+
+    let descriptor = *input.get(4)?;
+    if descriptor & SINGLE_SEGMENT != 0 {
+        return None;
+    }
+    let window = *input.get(5)?;
+    let base = 1u64 << (10 + (window >> 3));
+    Some(base + (base / 8) * (window & 7) as u64)
+}
@@ -1,31 +1,35 @@
-use alloc::string::{String, ToString};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
 use core::{cmp, hint};
 
 use binrw::{BinRead, io::Cursor};
-use typed_path::UnixPathBuf;
+use typed_path::{UnixPath, UnixPathBuf};
 
 use crate::{
     Error, Result,
     types::{Dirent, DirentFileType},
 };
 
-pub fn find_nodeid_by_name(name: &[u8], data: &[u8]) -> Result<Option<u64>> {
-    let dirent = read_nth_dirent(data, 0)?;
-    let n = dirent.name_off as usize / Dirent::size();
-    if n <= 2 {
-        // Only "." and ".."
+/// Binary-searches a directory block for the entry called `name`.
+///
+/// Entries are sorted by name within a block, with `.` and `..` taking
+/// their place in that order like any other name.
+pub fn find_dirent_by_name(name: &[u8], data: &[u8]) -> Result<Option<Dirent>> {
+    let n = dirent_count(data)?;
+    if n == 0 {
         return Ok(None);
     }
 
-    let offset = 2;
-    let mut size = n - offset;
+    let mut size = n;
     let mut base = 0usize;
     while size > 1 {
         let half = size / 2;
         let mid = base + half;
 
         let cmp = {
-            let (_, entry_name) = read_nth_id_name(data, mid + offset, n)?;
+            let (_, entry_name) = read_nth_dirent_name(data, mid, n)?;
             entry_name.cmp(name)
         };
         base = hint::select_unpredictable(cmp == cmp::Ordering::Greater, base, mid);
@@ -33,66 +37,169 @@ pub fn find_nodeid_by_name(name: &[u8], data: &[u8]) -> Result<Option<u64>> {
         size -= half;
     }
 
-    let (inner_nid, cmp) = {
-        let (nid, entry_name) = read_nth_id_name(data, base + offset, n)?;
+    let (inner_dirent, cmp) = {
+        let (dirent, entry_name) = read_nth_dirent_name(data, base, n)?;
         let cmp = entry_name.cmp(name);
-        (nid, cmp)
+        (dirent, cmp)
     };
     if cmp != cmp::Ordering::Equal {
         return Ok(None);
     }
 
-    Ok(Some(inner_nid))
+    Ok(Some(inner_dirent))
+}
+
+/// Finds every entry of a directory block whose name matches `name`
+/// case-insensitively, see [`eq_ignore_case`].
+///
+/// Entries are sorted by their exact names, so this has to check them all.
+pub fn find_dirents_ignore_case(name: &[u8], data: &[u8]) -> Result<Vec<(Dirent, Vec<u8>)>> {
+    let n = dirent_count(data)?;
+    let mut found = Vec::new();
+    for i in 0..n {
+        let (dirent, entry_name) = read_nth_dirent_name(data, i, n)?;
+        if eq_ignore_case(entry_name, name) {
+            found.push((dirent, entry_name.to_vec()));
+        }
+    }
+    Ok(found)
 }
 
-fn read_nth_id_name(data: &[u8], n: usize, max: usize) -> Result<(u64, &[u8])> {
+/// Compares two names case-insensitively: by the lowercase mapping of
+/// their characters if both are valid UTF-8, and of their ASCII letters
+/// otherwise.
+pub fn eq_ignore_case(a: &[u8], b: &[u8]) -> bool {
+    match (core::str::from_utf8(a), core::str::from_utf8(b)) {
+        (Ok(a), Ok(b)) => a
+            .chars()
+            .flat_map(char::to_lowercase)
+            .eq(b.chars().flat_map(char::to_lowercase)),
+        _ => a.eq_ignore_ascii_case(b),
+    }
+}
+
+/// Compares the first entry name of a directory block with `name`.
+///
+/// Names are sorted across the blocks of a directory as well, so this tells
+/// whether `name` can only be found in this block or a later one
+/// (`Less`/`Equal`) or only in an earlier one (`Greater`).
+pub fn cmp_first_name(data: &[u8], name: &[u8]) -> Result<cmp::Ordering> {
+    let n = dirent_count(data)?;
+    if n == 0 {
+        return Err(Error::CorruptDirectory("empty directory block".to_string()));
+    }
+    let (_, first) = read_nth_dirent_name(data, 0, n)?;
+    Ok(first.cmp(name))
+}
+
+/// Decodes every dirent of a directory block, including `.` and `..`.
+pub fn read_owned_dirents(data: &[u8]) -> Result<Vec<(Dirent, Vec<u8>)>> {
+    let n = dirent_count(data)?;
+    (0..n)
+        .map(|i| read_nth_dirent_name(data, i, n).map(|(dirent, name)| (dirent, name.to_vec())))
+        .collect()
+}
+
+/// Decodes the dirent array of a directory block as stored, without
+/// reading or checking the names.
+pub fn read_raw_dirents(data: &[u8]) -> Result<Vec<Dirent>> {
+    let n = read_nth_dirent(data, 0)?.name_off as usize / Dirent::size();
+    (0..n).map(|i| read_nth_dirent(data, i)).collect()
+}
+
+fn read_nth_dirent_name(data: &[u8], n: usize, max: usize) -> Result<(Dirent, &[u8])> {
     let dirent = read_nth_dirent(data, n)?;
     let name_start = dirent.name_off as usize;
-    let name_end = if n < max - 1 {
-        let dirent = read_nth_dirent(data, n + 1)?;
-        dirent.name_off as usize
-    } else {
+    let last = n == max - 1;
+    let name_end = if last {
         data.len()
+    } else {
+        read_nth_dirent(data, n + 1)?.name_off as usize
     };
 
-    if name_end < name_start || name_end > data.len() {
-        return Err(Error::CorruptedData(
-            "invalid directory entry name offset".to_string(),
-        ));
-    }
-    let name = &data[name_start..name_end];
-    if let Some(i) = name.iter().position(|&b| b == 0) {
-        // Trim trailing null bytes
-        return Ok((dirent.nid, &name[..i]));
-    }
-
-    Ok((dirent.nid, name))
+    Ok((dirent, dirent_name(data, name_start, name_end, last)?))
 }
 
 pub fn read_nth_dirent(data: &[u8], n: usize) -> Result<Dirent> {
     let start = n * Dirent::size();
     let slice = data
         .get(start..)
-        .ok_or_else(|| Error::OutOfBounds("failed to get inode data".to_string()))?;
+        .ok_or_else(|| Error::CorruptDirectory(format!("dirent {} is outside its block", n)))?;
     let dirent = Dirent::read(&mut Cursor::new(slice))?;
     Ok(dirent)
 }
 
+/// Returns the number of dirents in a directory block.
+///
+/// The names follow the dirent array, so the first name offset gives its
+/// length.
+fn dirent_count(data: &[u8]) -> Result<usize> {
+    let name_off = read_nth_dirent(data, 0)?.name_off as usize;
+    if !name_off.is_multiple_of(Dirent::size()) || name_off > data.len() {
+        return Err(Error::CorruptDirectory(format!(
+            "first name offset {} doesn't end the dirent array",
+            name_off
+        )));
+    }
+    Ok(name_off / Dirent::size())
+}
+
+/// Returns the name stored in `data[start..end]`, checking that it is a
+/// single non-empty path component.
+///
+/// Only the `last` name of a block may be followed by NUL padding, which
+/// runs up to the end of the block. A NUL anywhere else, a `/` or an empty
+/// name means the block is corrupt, and is rejected rather than turned into
+/// a path that could point somewhere else.
+fn dirent_name(data: &[u8], start: usize, end: usize, last: bool) -> Result<&[u8]> {
+    let name = data.get(start..end).ok_or_else(|| {
+        Error::CorruptDirectory(format!("name at {}..{} is outside its block", start, end))
+    })?;
+    let name = match name.iter().position(|&b| b == 0) {
+        Some(i) if last && name[i..].iter().all(|&b| b == 0) => &name[..i],
+        Some(_) => {
+            return Err(Error::CorruptDirectory(format!(
+                "name \"{}\" contains a NUL byte",
+                name.escape_ascii()
+            )));
+        }
+        None => name,
+    };
+    if name.is_empty() {
+        return Err(Error::CorruptDirectory(format!(
+            "empty name at offset {}",
+            start
+        )));
+    }
+    if name.contains(&b'/') {
+        return Err(Error::CorruptDirectory(format!(
+            "name \"{}\" contains a slash",
+            name.escape_ascii()
+        )));
+    }
+    Ok(name)
+}
+
+/// A cursor over the dirents of one directory block.
+///
+/// A block holds an array of fixed-size dirents followed by their names,
+/// packed back to back in the same order, so the name of each entry ends
+/// where the next one starts. Entries are decoded one at a time and only
+/// the dirent that starts the next name is kept, so walking a block never
+/// allocates more than the name of the entry being returned.
 #[derive(Debug)]
 pub struct DirentBlock<D: AsRef<[u8]>> {
     data: D,
-    root: UnixPathBuf,
     dirent: Dirent,
     i: usize,
     n: usize,
 }
 
 impl<D: AsRef<[u8]>> DirentBlock<D> {
-    pub(crate) fn new(root: UnixPathBuf, data: D) -> Result<Self> {
+    pub(crate) fn new(data: D) -> Result<Self> {
         let dirent = read_nth_dirent(data.as_ref(), 0)?;
-        let n = dirent.name_off as usize / Dirent::size();
+        let n = dirent_count(data.as_ref())?;
         Ok(Self {
-            root,
             data,
             dirent,
             i: 0,
@@ -104,38 +211,63 @@ impl<D: AsRef<[u8]>> DirentBlock<D> {
         self.data.as_ref().len()
     }
 
-    pub(crate) fn next_entry(&mut self) -> Result<Option<DirEntry>> {
+    /// Number of dirents of this block left to decode, `.` and `..`
+    /// included.
+    pub(crate) fn remaining(&self) -> usize {
+        self.n - self.i
+    }
+
+    /// Index of the next dirent to be decoded in this block.
+    pub(crate) fn position(&self) -> usize {
+        self.i
+    }
+
+    /// Moves to the `i`-th dirent of this block, clamped to the entry count.
+    pub(crate) fn seek(&mut self, i: usize) -> Result<()> {
+        self.i = i.min(self.n);
+        if self.i < self.n {
+            self.dirent = read_nth_dirent(self.data.as_ref(), self.i)?;
+        }
+        Ok(())
+    }
+
+    /// Decodes the next entry other than `.` and `..`, with its path under
+    /// `dir`.
+    pub(crate) fn next_entry(&mut self, dir: &UnixPath) -> Result<Option<DirEntry>> {
         let data = self.data.as_ref();
         while self.i < self.n {
             let dirent = self.dirent;
-            let name_start = dirent.name_off as usize;
-            let name_end = if self.i < self.n - 1 {
+            let last = self.i == self.n - 1;
+            let name_end = if last {
+                data.len()
+            } else {
                 let dirent = read_nth_dirent(data, self.i + 1)?;
                 self.dirent = dirent;
                 dirent.name_off as usize
-            } else {
-                data.len()
             };
 
-            if name_end < name_start || name_end > data.len() {
-                return Err(Error::CorruptedData(
-                    "invalid directory entry name offset".to_string(),
-                ));
-            }
-
+            let name = dirent_name(data, dirent.name_off as usize, name_end, last)?;
             self.i += 1;
-            let name: String = String::from_utf8_lossy(&data[name_start..name_end])
-                .trim_end_matches('\0')
-                .into();
-            if name.as_str() == "." || name.as_str() == ".." {
+            if name == b"." || name == b".." {
                 continue;
             }
 
+            let (file_name, raw_name) = match String::from_utf8(name.to_vec()) {
+                Ok(file_name) => (file_name, None),
+                Err(e) => {
+                    let raw_name = e.into_bytes();
+                    (
+                        String::from_utf8_lossy(&raw_name).into_owned(),
+                        Some(raw_name),
+                    )
+                }
+            };
             let entry = DirEntry {
-                dir: self.root.clone(),
+                dir: dir.to_path_buf(),
                 nid: dirent.nid,
                 file_type: dirent.file_type.try_into()?,
-                file_name: name,
+                file_name,
+                raw_name,
             };
             return Ok(Some(entry));
         }
@@ -143,41 +275,88 @@ impl<D: AsRef<[u8]>> DirentBlock<D> {
     }
 }
 
-impl<D: AsRef<[u8]>> Iterator for DirentBlock<D> {
-    type Item = Result<DirEntry>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.i >= self.n {
-            None
-        } else {
-            self.next_entry().transpose()
-        }
-    }
-}
-
 /// A directory entry within an EROFS filesystem.
+///
+/// With the `serde` feature it serializes as a struct with the `path`,
+/// `file_name`, `nid` and `file_type` of the entry. The path and name are
+/// serialized as strings, with invalid UTF-8 replaced like
+/// [`file_name`](Self::file_name) does.
 #[derive(Debug, Clone)]
 pub struct DirEntry {
     pub(crate) dir: UnixPathBuf,
     pub(crate) nid: u64,
     pub(crate) file_type: DirentFileType,
     pub(crate) file_name: String,
+    /// The name as stored, kept only when it is not valid UTF-8 and
+    /// `file_name` had to replace parts of it.
+    pub(crate) raw_name: Option<Vec<u8>>,
 }
 
 impl DirEntry {
     /// Returns the file type of this entry.
+    ///
+    /// The type is stored in the directory entry itself, so this doesn't
+    /// read the inode.
     pub fn file_type(&self) -> DirentFileType {
         self.file_type
     }
 
+    /// Returns `true` if this entry is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.file_type.is_dir()
+    }
+
+    /// Returns `true` if this entry is a regular file.
+    pub fn is_file(&self) -> bool {
+        self.file_type.is_file()
+    }
+
+    /// Returns `true` if this entry is a symbolic link.
+    pub fn is_symlink(&self) -> bool {
+        self.file_type.is_symlink()
+    }
+
+    /// Returns `true` if this entry is a block device.
+    pub fn is_block_device(&self) -> bool {
+        self.file_type.is_block_device()
+    }
+
+    /// Returns `true` if this entry is a character device.
+    pub fn is_char_device(&self) -> bool {
+        self.file_type.is_char_device()
+    }
+
+    /// Returns `true` if this entry is a FIFO.
+    pub fn is_fifo(&self) -> bool {
+        self.file_type.is_fifo()
+    }
+
+    /// Returns `true` if this entry is a socket.
+    pub fn is_socket(&self) -> bool {
+        self.file_type.is_socket()
+    }
+
     /// Returns the file name of this entry.
+    ///
+    /// Names that are not valid UTF-8 have the invalid sequences replaced
+    /// with `U+FFFD`; use [`file_name_bytes`](Self::file_name_bytes) for
+    /// the name as stored.
     pub fn file_name(&self) -> &str {
         &self.file_name
     }
 
-    /// Returns the full path of this entry.
+    /// Returns the file name of this entry as stored in the image.
+    pub fn file_name_bytes(&self) -> &[u8] {
+        self.raw_name
+            .as_deref()
+            .unwrap_or(self.file_name.as_bytes())
+    }
+
+    /// Returns the full path of this entry, made of the names as stored,
+    /// so it can be passed back to path lookups even if it is not valid
+    /// UTF-8.
     pub fn path(&self) -> UnixPathBuf {
-        self.dir.join(&self.file_name)
+        self.dir.join(self.file_name_bytes())
     }
 
     /// Returns the node ID (inode number) of this entry.
@@ -185,3 +364,21 @@ impl DirEntry {
         self.nid
     }
 }
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for DirEntry {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> core::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let path = self.path();
+        let mut state = serializer.serialize_struct("DirEntry", 4)?;
+        state.serialize_field("path", &*path.to_string_lossy())?;
+        state.serialize_field("file_name", self.file_name())?;
+        state.serialize_field("nid", &self.nid())?;
+        state.serialize_field("file_type", &self.file_type())?;
+        state.end()
+    }
+}
@@ -1,5 +1,8 @@
 use alloc::string::String;
+use core::fmt;
+
 use thiserror::Error;
+use typed_path::UnixPath;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -12,30 +15,96 @@ pub enum Error {
     #[error("invalid layout: {0}")]
     InvalidLayout(u8),
 
-    #[error("path not found: {0}")]
-    PathNotFound(String),
+    #[error("invalid inode: nid {0} is outside the metadata area")]
+    InvalidInode(u64),
+
+    /// A component of `path` doesn't exist in its directory.
+    #[error("path not found: {path} (no entry named {component})")]
+    NotFound { path: String, component: String },
+
+    #[error("not a regular file: {0}")]
+    NotARegularFile(String),
+
+    #[error("{0} is a symlink, use read_link to read its target")]
+    IsASymlink(String),
+
+    /// `component` of `path` was used as a directory but isn't one.
+    #[error("not a directory: {path}{}", ComponentNote(.path, .component))]
+    NotADirectory { path: String, component: String },
+
+    #[error("not a symlink: {0}")]
+    NotASymlink(String),
+
+    #[error("path escapes the root directory: {0}")]
+    PathEscapesRoot(String),
+
+    #[error("ambiguous path: {0} matches several entries case-insensitively")]
+    AmbiguousPath(String),
 
-    #[error("not a file: {0}")]
-    NotAFile(String),
+    #[error("out of bounds: {len} bytes at offset {offset} exceed the {image_len}-byte image")]
+    OutOfBounds {
+        offset: usize,
+        len: usize,
+        image_len: u64,
+    },
 
-    #[error("not a directory: {0}")]
-    NotADirectory(String),
+    #[error("truncated image: expected at least {expected_min} bytes, got {actual}")]
+    TruncatedImage { expected_min: u64, actual: u64 },
 
-    #[error("out of bounds: {0}")]
-    OutOfBounds(String),
+    #[error("image too large: {len} bytes, at most {max} allowed")]
+    ImageTooLarge { len: u64, max: u64 },
+
+    #[error("out of memory: cannot allocate {0} bytes")]
+    OutOfMemory(usize),
 
     #[error("binread error: {0}")]
     BinRead(#[from] binrw::Error),
 
+    #[error("invalid seek to a negative or overflowing position")]
+    InvalidSeek,
+
     #[error("out of range {0} of {1}")]
     OutOfRange(usize, usize),
 
     #[error("{0} not supported yet")]
     NotSupported(String),
 
+    #[error("unsupported compression algorithm: {0}")]
+    UnsupportedCompression(String),
+
+    #[error("unsupported feature: {0}")]
+    UnsupportedFeature(String),
+
+    #[error("unsupported block size: 2^{0} bytes")]
+    UnsupportedBlockSize(u8),
+
     #[error("corrupted data: {0}")]
     CorruptedData(String),
 
+    #[error("corrupt directory: {0}")]
+    CorruptDirectory(String),
+
+    #[error("checksum mismatch: expected {expected:#010x}, got {actual:#010x}")]
+    ChecksumMismatch { expected: u32, actual: u32 },
+
+    #[error(
+        "corrupt chunk index {chunk_index} of inode {inode_nid}: block {blkaddr} is past the end of the image"
+    )]
+    CorruptChunkIndex {
+        inode_nid: u64,
+        chunk_index: usize,
+        blkaddr: u64,
+    },
+
+    #[error(transparent)]
+    Walk(#[from] WalkError),
+
+    #[error("invalid UTF-8: {0}")]
+    InvalidUtf8(core::str::Utf8Error),
+
+    #[error("too many levels of symbolic links: {0}")]
+    SymlinkLoop(String),
+
     #[cfg(feature = "std")]
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
@@ -46,3 +115,104 @@ pub enum Error {
 }
 
 pub type Result<T> = core::result::Result<T, Error>;
+
+/// An error that stops a [`WalkDir`](crate::WalkDir) from entering a
+/// directory, returned as [`Error::Walk`].
+#[derive(Debug, Error)]
+pub enum WalkError {
+    /// The directory at `path` is one of its own ancestors, which only
+    /// happens with directory hardlinks in crafted images.
+    #[error("directory cycle detected at {path} (nid {nid})")]
+    Cycle { path: String, nid: u64 },
+}
+
+impl Error {
+    /// Returns [`Error::NotADirectory`] for a `path` whose last component
+    /// isn't a directory.
+    pub(crate) fn not_a_directory(path: &UnixPath) -> Self {
+        let component = path.file_name().unwrap_or(path.as_bytes());
+        Self::NotADirectory {
+            path: path.to_string_lossy().into_owned(),
+            component: String::from_utf8_lossy(component).into_owned(),
+        }
+    }
+
+    /// Returns [`Error::NotFound`] for the entry `name` missing from the
+    /// directory inode `nid`, which isn't reached through a path.
+    pub(crate) fn child_not_found(nid: u64, name: &[u8]) -> Self {
+        let component = String::from_utf8_lossy(name).into_owned();
+        Self::NotFound {
+            path: alloc::format!("inode {nid}/{component}"),
+            component,
+        }
+    }
+
+    /// Returns [`Error::NotADirectory`] for the inode `nid`, which isn't
+    /// reached through a path.
+    pub(crate) fn inode_not_a_directory(nid: u64) -> Self {
+        let path = alloc::format!("inode {nid}");
+        Self::NotADirectory {
+            component: path.clone(),
+            path,
+        }
+    }
+}
+
+/// Names the failing component of a [`Error::NotADirectory`] path, unless
+/// that is the whole path.
+struct ComponentNote<'a>(&'a String, &'a String);
+
+impl fmt::Display for ComponentNote<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 == self.1 {
+            return Ok(());
+        }
+        write!(f, " ({} is not a directory)", self.1)
+    }
+}
+
+/// Converts into an [`std::io::Error`] of the closest matching kind.
+///
+/// The original error is kept as the source, so it can be recovered with
+/// [`std::io::Error::get_ref`] or [`std::io::Error::into_inner`] and a
+/// downcast to [`Error`].
+#[cfg(feature = "std")]
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> Self {
+        use std::io::ErrorKind;
+
+        let kind = match &err {
+            Error::NotFound { .. } => ErrorKind::NotFound,
+            Error::NotADirectory { .. } => ErrorKind::NotADirectory,
+            Error::NotARegularFile(_)
+            | Error::IsASymlink(_)
+            | Error::NotASymlink(_)
+            | Error::PathEscapesRoot(_)
+            | Error::AmbiguousPath(_)
+            | Error::InvalidSeek => ErrorKind::InvalidInput,
+            Error::OutOfBounds { .. } | Error::OutOfRange(..) | Error::TruncatedImage { .. } => {
+                ErrorKind::UnexpectedEof
+            }
+            Error::NotSupported(_)
+            | Error::UnsupportedCompression(_)
+            | Error::UnsupportedFeature(_)
+            | Error::UnsupportedBlockSize(_) => ErrorKind::Unsupported,
+            Error::InvalidSuperblock(_)
+            | Error::InvalidDirentFileType(_)
+            | Error::InvalidLayout(_)
+            | Error::InvalidInode(_)
+            | Error::BinRead(_)
+            | Error::CorruptedData(_)
+            | Error::CorruptDirectory(_)
+            | Error::ChecksumMismatch { .. }
+            | Error::CorruptChunkIndex { .. }
+            | Error::Walk(_)
+            | Error::InvalidUtf8(_) => ErrorKind::InvalidData,
+            Error::ImageTooLarge { .. } => ErrorKind::FileTooLarge,
+            Error::OutOfMemory(_) => ErrorKind::OutOfMemory,
+            Error::Io(e) => e.kind(),
+            _ => ErrorKind::Other,
+        };
+        Self::new(kind, err)
+    }
+}
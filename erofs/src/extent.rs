@@ -0,0 +1,43 @@
+/// A range of file data and where the image stores it, returned by
+/// [`EroFS::extents`](crate::EroFS::extents).
+///
+/// Files sharing data, such as chunks deduplicated across files, have
+/// extents starting at the same `device` and `phys_block`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PhysicalExtent {
+    /// Device holding the data: 0 for the image itself, or the 1-based
+    /// index of an extra device in [`EroFS::devices`](crate::EroFS::devices)
+    /// that isn't mapped into the image's block address space.
+    pub device: u16,
+    /// Block of `device` the data starts in. Tails stored inline right
+    /// after their inode start partway through this block.
+    pub phys_block: u64,
+    /// Start offset of the range within the file.
+    pub logical_offset: u64,
+    /// Length of the range within the file.
+    pub logical_len: u64,
+    /// Length of the data on `device`, which is smaller than `logical_len`
+    /// for compressed ranges and rounded up to whole blocks for others
+    /// that aren't stored inline.
+    pub len: u64,
+}
+
+/// An entry of the chunk index table of a chunk-based file, as stored,
+/// returned by [`EroFS::chunks`](crate::EroFS::chunks).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChunkRef {
+    /// The chunk isn't stored and reads as zeroes.
+    Hole,
+    /// The chunk is stored from the start of a block.
+    Mapped {
+        /// Device id of the entry: 0 for the image itself, or the 1-based
+        /// index of an extra device in
+        /// [`EroFS::devices`](crate::EroFS::devices). Always 0 for tables of
+        /// plain block addresses.
+        device: u16,
+        /// Block address of the entry, relative to `device`. For extra
+        /// devices mapped into the image, this isn't offset by their
+        /// mapped block address.
+        block: u64,
+    },
+}
@@ -1,11 +1,39 @@
-use alloc::{format, string::ToString};
+use alloc::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
 
 use binrw::BinRead;
-use binrw::BinReaderExt;
 use binrw::io::Cursor;
+use rustix::fs::FileType;
+use typed_path::{Component, UnixComponent, UnixPath, UnixPathBuf};
 
+use crate::crc32c;
+use crate::decompress;
 use crate::types::*;
-use crate::{Error, Result};
+use crate::zmap::{Extent, ZMap};
+use crate::{ChunkRef, Error, PhysicalExtent, Result};
+
+/// Incompatible features this crate knows how to read.
+pub const SUPPORTED_FEATURE_INCOMPAT: u32 = FEATURE_INCOMPAT_ZERO_PADDING
+    | FEATURE_INCOMPAT_COMPR_CFGS
+    | FEATURE_INCOMPAT_CHUNKED_FILE
+    | FEATURE_INCOMPAT_DEVICE_TABLE
+    | FEATURE_INCOMPAT_ZTAILPACKING
+    | FEATURE_INCOMPAT_FRAGMENTS;
+
+/// Smallest length an image can have: the superblock and the area before it.
+pub const MIN_IMAGE_LEN: u64 = (SUPER_BLOCK_OFFSET + SuperBlock::size()) as u64;
+
+/// Maximum number of symlinks followed while resolving a single path,
+/// matching Linux's `MAXSYMLINKS`.
+pub const MAX_SYMLINK_FOLLOWS: usize = 40;
+
+/// Longest symlink target accepted, matching Linux's `PATH_MAX`.
+const SYMLINK_MAX: usize = 4096;
 
 /// Shared core data and pure computation logic for EROFS filesystem.
 ///
@@ -15,6 +43,11 @@ use crate::{Error, Result};
 pub struct EroFSCore {
     pub(crate) super_block: SuperBlock,
     pub(crate) block_size: usize,
+    pub(crate) compr_configs: decompress::Configs,
+    pub(crate) devices: Vec<DeviceSlot>,
+    /// Whether decoded data is checked against per-block checksums, see
+    /// `EroFS::verify_data`.
+    pub(crate) verify_data: bool,
 }
 
 /// Describes a planned block read operation.
@@ -25,15 +58,384 @@ pub enum BlockPlan {
     /// A direct read: read `size` bytes at `offset`.
     Direct { offset: usize, size: usize },
     /// A two-phase read for chunk-based layout:
-    /// 1. Read 4 bytes at `addr_offset` to get chunk address
-    /// 2. Call `resolve_chunk_read()` with the chunk address
+    /// 1. Read `addr_len` bytes at `addr_offset` to get the chunk address,
+    ///    a 4-byte block address or an 8-byte `ChunkIndex`
+    /// 2. Decode them with `chunk_block()` and call `resolve_chunk_read()`
     Chunked {
         addr_offset: usize,
+        addr_len: usize,
         chunk_fixed: usize,
         chunk_size: usize,
         data_size: usize,
         chunk_index: usize,
     },
+    /// A multi-phase read for compressed layouts:
+    /// 1. Read the map header at `map_offset` and call `ZMap::data_len()`
+    /// 2. Read that many bytes at `map_offset` and build a `ZMap`
+    /// 3. Fill a `size`-byte buffer with every extent overlapping the logical
+    ///    range starting at `start`, using `decode_extent()` and `copy_extent()`
+    Compressed {
+        map_offset: usize,
+        start: usize,
+        size: usize,
+    },
+}
+
+/// The state of a path resolution that may follow symlinks.
+///
+/// Used by both sync and async implementations: the caller looks up every
+/// name returned by `next_name()` in the directory `dir()`, then either
+/// `enter()`s the entry found or `follow()`s it when it is a symlink.
+pub struct PathWalk {
+    path: String,
+    /// Nids from the root down to the current entry.
+    nids: Vec<u64>,
+    /// Names of the entries in `nids` below the root.
+    names: Vec<Vec<u8>>,
+    /// Components still to resolve, the next one last.
+    pending: Vec<Vec<u8>>,
+    file_type: Option<DirentFileType>,
+    follows: usize,
+    /// Whether a `..` tried to go above the root.
+    escaped: bool,
+    /// Whether an absolute symlink target was followed.
+    followed_absolute: bool,
+}
+
+impl PathWalk {
+    pub(crate) fn new(root_nid: u64, path: &UnixPath) -> Self {
+        let mut walk = Self {
+            path: path.to_string_lossy().into_owned(),
+            nids: vec![root_nid],
+            names: Vec::new(),
+            pending: Vec::new(),
+            file_type: None,
+            follows: 0,
+            escaped: false,
+            followed_absolute: false,
+        };
+        walk.push_path(path);
+        walk
+    }
+
+    fn push_path(&mut self, path: &UnixPath) {
+        let start = self.pending.len();
+        self.pending.extend(
+            path.components()
+                .filter(|c| *c != UnixComponent::RootDir)
+                .map(|c| c.as_bytes().to_vec()),
+        );
+        self.pending[start..].reverse();
+    }
+
+    /// Returns the next name to look up, applying `.` and `..` on the way.
+    ///
+    /// After an entry known not to be a directory, `.` and `..` are returned
+    /// as they are, for the lookup in that entry to fail.
+    pub(crate) fn next_name(&mut self) -> Option<Vec<u8>> {
+        while let Some(name) = self.pending.pop() {
+            let in_dir = self
+                .file_type
+                .is_none_or(|t| matches!(t, DirentFileType::Directory | DirentFileType::Unknown));
+            match name.as_slice() {
+                b"." | b".." if !in_dir => return Some(name),
+                b"." => {}
+                b".." => {
+                    if self.nids.len() > 1 {
+                        self.nids.pop();
+                        self.names.pop();
+                    } else {
+                        self.escaped = true;
+                    }
+                    self.file_type = None;
+                }
+                _ => return Some(name),
+            }
+        }
+        None
+    }
+
+    /// Whether the name last returned by `next_name()` ends the path.
+    pub(crate) fn is_last(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// The nid of the directory to look the next name up in.
+    pub(crate) fn dir(&self) -> u64 {
+        *self.nids.last().unwrap_or(&0)
+    }
+
+    pub(crate) fn enter(&mut self, name: Vec<u8>, nid: u64, file_type: DirentFileType) {
+        self.nids.push(nid);
+        self.names.push(name);
+        self.file_type = Some(file_type);
+    }
+
+    /// Continues the walk at `target`, relative to the directory holding
+    /// the symlink unless it is absolute.
+    pub(crate) fn follow(&mut self, target: &UnixPath) -> Result<()> {
+        self.follows += 1;
+        if self.follows > MAX_SYMLINK_FOLLOWS {
+            return Err(Error::SymlinkLoop(self.path.clone()));
+        }
+
+        if target.is_absolute() {
+            self.followed_absolute = true;
+            self.nids.truncate(1);
+            self.names.clear();
+        }
+        self.file_type = None;
+        self.push_path(target);
+        Ok(())
+    }
+
+    /// Returns the nid the path resolved to, together with the file type
+    /// recorded in its directory entry (`None` for the root).
+    pub(crate) fn finish(self) -> (u64, Option<DirentFileType>) {
+        (self.dir(), self.file_type)
+    }
+
+    /// Returns the absolute path the walk ended at, made of the names of
+    /// the entries actually entered.
+    ///
+    /// Fails if a `..` went above the root on the way, which `next_name()`
+    /// otherwise treats as staying at the root.
+    pub(crate) fn canonical_path(self) -> Result<UnixPathBuf> {
+        if self.escaped {
+            return Err(Error::PathEscapesRoot(self.path));
+        }
+        let mut path = UnixPathBuf::from("/");
+        for name in &self.names {
+            path.push(name);
+        }
+        Ok(path)
+    }
+
+    /// Whether the path leaves the tree once the image is extracted and the
+    /// host resolves it: a `..` went above the root, an absolute symlink
+    /// target was followed, or the components left to resolve climb above
+    /// the root on their own.
+    ///
+    /// A walk stopped early at the name `stopped_at`, which is missing or
+    /// was looked up in something that isn't a directory, has it and the
+    /// components after it judged by their `.` and `..` alone.
+    pub(crate) fn leaves_root(&self, stopped_at: Option<&[u8]>) -> bool {
+        if self.escaped || self.followed_absolute {
+            return true;
+        }
+        let mut depth = self.names.len();
+        let rest = self.pending.iter().rev().map(Vec::as_slice);
+        for name in stopped_at.into_iter().chain(rest) {
+            match name {
+                b"." => {}
+                b".." => match depth.checked_sub(1) {
+                    Some(parent) => depth = parent,
+                    None => return true,
+                },
+                _ => depth += 1,
+            }
+        }
+        false
+    }
+
+    /// Returns the error for a name looked up in something that is not a
+    /// directory.
+    ///
+    /// That is the entry entered last, or the root if none was.
+    pub(crate) fn not_a_directory(&self) -> Error {
+        let component = self.names.last().map_or(b"/".as_slice(), Vec::as_slice);
+        Error::NotADirectory {
+            path: self.path.clone(),
+            component: String::from_utf8_lossy(component).into_owned(),
+        }
+    }
+
+    /// Returns the error for a name missing from the directory it was
+    /// looked up in.
+    pub(crate) fn not_found(&self, name: &[u8]) -> Error {
+        Error::NotFound {
+            path: self.path.clone(),
+            component: String::from_utf8_lossy(name).into_owned(),
+        }
+    }
+
+    /// Returns the error for a name matching several entries of a
+    /// directory case-insensitively.
+    pub(crate) fn ambiguous(&self) -> Error {
+        Error::AmbiguousPath(self.path.clone())
+    }
+}
+
+/// A linear scan over the inode slots of the metadata area, in nid order.
+///
+/// Used by both sync and async inode iterators: the caller parses the slot
+/// returned by `next_slot()`, then either `accept()`s the inode found there
+/// or `skip()`s the slot.
+#[derive(Debug, Clone)]
+pub struct InodeScan {
+    nid: u64,
+    /// End offset of the image.
+    end: usize,
+    /// Inodes left to find, according to the superblock.
+    remaining: u64,
+    /// Block ranges holding the data of the inodes found so far.
+    data_blocks: BTreeMap<u64, u64>,
+}
+
+impl InodeScan {
+    pub(crate) fn new(core: &EroFSCore) -> Self {
+        let sb = &core.super_block;
+        Self {
+            nid: 0,
+            end: usize::try_from(core.block_offset(sb.blocks)).unwrap_or(usize::MAX),
+            // Images that don't record an inode count are scanned to the end.
+            remaining: if sb.inos == 0 { u64::MAX } else { sb.inos },
+            data_blocks: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the nid and image offset of the next slot to examine, or
+    /// `None` once every inode has been found or the image is exhausted.
+    pub(crate) fn next_slot(&mut self, core: &EroFSCore) -> Option<(u64, usize)> {
+        while self.remaining > 0 {
+            let offset = core.inode_offset(self.nid).ok()?;
+            if offset.checked_add(InodeCompact::size())? > self.end {
+                return None;
+            }
+
+            // Data blocks may be interleaved with metadata; never look for
+            // inodes in blocks known to hold file data.
+            let block = (offset / core.block_size) as u64;
+            match self.data_blocks.range(..=block).next_back() {
+                Some((_, &end)) if block < end => {
+                    let meta_offset = core.block_offset(core.super_block.meta_blk_addr);
+                    self.nid = ((end << core.super_block.blk_size_bits) - meta_offset)
+                        / InodeCompact::size() as u64;
+                }
+                _ => return Some((self.nid, offset)),
+            }
+        }
+        None
+    }
+
+    /// Moves past a slot that doesn't hold an inode.
+    pub(crate) fn skip(&mut self) {
+        self.nid += 1;
+    }
+
+    /// Moves past `inode` and the `meta_len` bytes of metadata it occupies.
+    pub(crate) fn accept(&mut self, core: &EroFSCore, inode: &Inode, meta_len: usize) {
+        self.remaining -= 1;
+        self.nid += meta_len.div_ceil(InodeCompact::size()).max(1) as u64;
+        if let Some((start, count)) = core.flat_data_blocks(inode) {
+            self.data_blocks.insert(start, start + count);
+        }
+    }
+}
+
+/// A linear scan over the entries of the shared xattr area, in ID order.
+///
+/// Used by both sync and async shared xattr iterators: the caller reads the
+/// entry header at the offset returned by `next_entry()` and passes it to
+/// `accept()`, which moves past the entry, or calls `finish()` if it can't
+/// be read.
+#[derive(Debug, Clone)]
+pub struct SharedXattrScan {
+    id: u32,
+    /// End offset of the area.
+    end: usize,
+}
+
+impl SharedXattrScan {
+    pub(crate) fn new(core: &EroFSCore) -> Self {
+        let sb = &core.super_block;
+        // The superblock doesn't record where the area ends, only that the
+        // image does, and that the inodes follow it if they come later.
+        let mut end = core.block_offset(sb.blocks);
+        if sb.meta_blk_addr > sb.xattr_blk_addr {
+            end = end.min(core.block_offset(sb.meta_blk_addr));
+        }
+        // Block 0 holds the superblock, so an address of 0 means no area.
+        if sb.xattr_blk_addr == 0 {
+            end = 0;
+        }
+        Self {
+            id: 0,
+            end: usize::try_from(end).unwrap_or(usize::MAX),
+        }
+    }
+
+    /// Returns the ID and image offset of the next entry, or `None` once
+    /// the area is exhausted.
+    pub(crate) fn next_entry(&self, core: &EroFSCore) -> Option<(u32, usize)> {
+        let offset = core.shared_xattr_offset(self.id);
+        (offset.checked_add(XattrEntry::size())? <= self.end).then_some((self.id, offset))
+    }
+
+    /// Moves past the entry starting with `header` and returns its length,
+    /// or `None` if the zeros padding the area to the end of its block
+    /// start there.
+    pub(crate) fn accept(&mut self, header: &[u8]) -> Result<Option<usize>> {
+        let entry = XattrEntry::read(&mut Cursor::new(header))?;
+        if entry.name_len == 0 {
+            self.finish();
+            return Ok(None);
+        }
+        let len = XattrEntry::size() + entry.name_len as usize + entry.value_len as usize;
+        // Entries are aligned to 4 bytes, the unit of IDs.
+        self.id = self.id.saturating_add(len.div_ceil(4) as u32);
+        Ok(Some(len))
+    }
+
+    /// Ends the scan, as when an entry can't be read.
+    pub(crate) fn finish(&mut self) {
+        self.end = 0;
+    }
+}
+
+/// Progress of a walk over the chunk index table of an inode, see
+/// `EroFS::chunks`. Inodes that aren't chunk-based have no entries.
+#[derive(Debug, Clone)]
+pub struct ChunkScan {
+    /// Image offset of the next entry.
+    offset: usize,
+    /// Size of each entry.
+    addr_len: usize,
+    /// Entries left to walk.
+    remaining: usize,
+}
+
+impl ChunkScan {
+    pub(crate) fn new(core: &EroFSCore, inode: &Inode) -> Result<Self> {
+        if inode.layout()? != Layout::ChunkBased {
+            return Ok(Self {
+                offset: 0,
+                addr_len: 0,
+                remaining: 0,
+            });
+        }
+        core.check_chunk_format(inode)?;
+        let (offset, addr_len, chunk_size) = core.chunk_table(inode);
+        Ok(Self {
+            offset,
+            addr_len,
+            remaining: inode.data_size().div_ceil(chunk_size),
+        })
+    }
+
+    /// Returns the image offset and size of the next entry, or `None` once
+    /// every chunk has been walked.
+    pub(crate) fn next_entry(&mut self) -> Option<(usize, usize)> {
+        self.remaining = self.remaining.checked_sub(1)?;
+        let entry = (self.offset, self.addr_len);
+        self.offset += self.addr_len;
+        Some(entry)
+    }
+
+    /// Returns the number of entries left to walk.
+    pub(crate) const fn remaining(&self) -> usize {
+        self.remaining
+    }
 }
 
 impl EroFSCore {
@@ -54,10 +456,17 @@ impl EroFSCore {
             )));
         }
 
-        if !(9..=24).contains(&blk_size_bits) {
+        if !(MIN_BLOCK_SIZE_BITS..=MAX_BLOCK_SIZE_BITS).contains(&blk_size_bits) {
+            return Err(Error::UnsupportedBlockSize(blk_size_bits));
+        }
+
+        let blocks = super_block.blocks;
+        if super_block.meta_blk_addr >= blocks
+            || (super_block.xattr_blk_addr != 0 && super_block.xattr_blk_addr >= blocks)
+        {
             return Err(Error::InvalidSuperblock(format!(
-                "invalid block size bits: {}",
-                blk_size_bits
+                "metadata block {} or xattr block {} is past the {} blocks of the image",
+                super_block.meta_blk_addr, super_block.xattr_blk_addr, blocks
             )));
         }
 
@@ -65,14 +474,244 @@ impl EroFSCore {
         Ok(Self {
             super_block,
             block_size,
+            compr_configs: decompress::Configs::default(),
+            devices: Vec::new(),
+            verify_data: false,
         })
     }
 
-    /// Parse an inode from raw bytes.
+    /// Checks that the target of the symlink `inode` is no longer than
+    /// `PATH_MAX`, before a buffer is sized from its untrusted size.
+    pub(crate) fn check_symlink_size(&self, inode: &Inode) -> Result<()> {
+        if inode.data_size() > SYMLINK_MAX {
+            return Err(Error::CorruptedData(format!(
+                "symlink inode {} has a {}-byte target, at most {} allowed",
+                inode.id(),
+                inode.data_size(),
+                SYMLINK_MAX
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns [`Error::TruncatedImage`] if an image of `actual` bytes is too
+    /// short to hold the superblock, the root inode and the start of the
+    /// shared xattr pool.
+    pub(crate) fn check_image_len(&self, actual: u64) -> Result<()> {
+        let root_end = self.inode_offset(self.super_block.root_nid as u64)? as u64
+            + InodeCompact::size() as u64;
+        let xattr_end = self.shared_xattr_offset(0) as u64 + XattrEntry::size() as u64;
+        let expected_min = root_end.max(xattr_end).max(MIN_IMAGE_LEN);
+        if actual < expected_min {
+            return Err(Error::TruncatedImage {
+                expected_min,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Length of the area starting at `SUPER_BLOCK_OFFSET` that belongs to
+    /// the superblock: the rest of its block, or a whole block when blocks
+    /// are smaller than the superblock offset.
+    fn super_block_area_len(&self) -> usize {
+        if self.block_size > SUPER_BLOCK_OFFSET {
+            self.block_size - SUPER_BLOCK_OFFSET
+        } else {
+            self.block_size
+        }
+    }
+
+    /// Byte range covered by the superblock checksum, or `None` if the image
+    /// doesn't carry one.
+    pub(crate) fn superblock_checksum_range(&self) -> Option<(usize, usize)> {
+        if self.super_block.feature_compat & FEATURE_COMPAT_SB_CHKSUM == 0 {
+            return None;
+        }
+        Some((SUPER_BLOCK_OFFSET, self.super_block_area_len()))
+    }
+
+    /// Byte range holding the compression configs, or `None` if the image
+    /// doesn't carry any. They follow the superblock and its extension slots
+    /// within the superblock area.
+    pub(crate) fn compr_configs_range(&self) -> Option<(usize, usize)> {
+        if self.super_block.feature_incompat & FEATURE_INCOMPAT_COMPR_CFGS == 0 {
+            return None;
+        }
+        let sb_size = SuperBlock::size() + self.super_block.ext_slots as usize * SB_EXTSLOT_SIZE;
+        let len = self.super_block_area_len().saturating_sub(sb_size);
+        Some((SUPER_BLOCK_OFFSET + sb_size, len))
+    }
+
+    /// Parse the compression configs from `data`, the bytes of the range
+    /// returned by `compr_configs_range()`.
+    pub(crate) fn parse_compr_configs(&mut self, data: &[u8]) -> Result<()> {
+        self.compr_configs = decompress::Configs::parse(&self.required_algorithms()?, data)?;
+        Ok(())
+    }
+
+    /// Verify the superblock checksum over `data`, the bytes of the range
+    /// returned by `superblock_checksum_range()`.
+    pub(crate) fn verify_superblock_checksum(&self, data: &[u8]) -> Result<()> {
+        // The checksum field itself (bytes 4..8) is computed as zero.
+        let actual = [&data[..4], &[0; 4], &data[8..]]
+            .iter()
+            .fold(!0, |crc, part| crc32c::update(crc, part));
+        let expected = self.super_block.checksum;
+        if actual != expected {
+            return Err(Error::ChecksumMismatch { expected, actual });
+        }
+        Ok(())
+    }
+
+    /// Collect the compression algorithms declared by the superblock.
+    pub(crate) fn required_algorithms(&self) -> Result<BTreeSet<CompressionAlgo>> {
+        let sb = &self.super_block;
+        let mut algos = BTreeSet::new();
+        if sb.feature_incompat & FEATURE_INCOMPAT_COMPR_CFGS == 0 {
+            // Without compression configs `compr_algs` holds `lz4_max_distance`
+            // and LZ4 is the only possible algorithm. mkfs.erofs marks images
+            // containing compressed inodes with the zero padding feature.
+            if sb.feature_incompat & FEATURE_INCOMPAT_ZERO_PADDING != 0 {
+                algos.insert(CompressionAlgo::Lz4);
+            }
+            return Ok(algos);
+        }
+
+        for bit in 0..u16::BITS as u8 {
+            if sb.compr_algs & (1 << bit) != 0 {
+                algos.insert(bit.try_into()?);
+            }
+        }
+        Ok(algos)
+    }
+
+    /// Byte range of the device table, or `None` if there are no extra devices.
+    pub(crate) fn device_table_range(&self) -> Option<(usize, usize)> {
+        let count = self.super_block.extra_devices as usize;
+        if count == 0 {
+            return None;
+        }
+        let offset = self.super_block.devt_slot_off as usize * DEVT_SLOT_SIZE;
+        Some((offset, count * DeviceSlot::size()))
+    }
+
+    /// Parse the device table from `data`, the bytes of the range returned
+    /// by `device_table_range()`.
+    pub(crate) fn parse_devices(&mut self, data: &[u8]) -> Result<()> {
+        let mut cursor = Cursor::new(data);
+        self.devices = (0..self.super_block.extra_devices)
+            .map(|_| Ok(DeviceSlot::read(&mut cursor)?))
+            .collect::<Result<_>>()?;
+        Ok(())
+    }
+
+    /// Translate block `blkaddr` of device `device_id` into a block of the
+    /// image.
+    ///
+    /// Data on extra devices is only reachable when every device is mapped
+    /// into the image's block address space (a flattened multi-device
+    /// image). Otherwise it lives in a separate blob this crate can't read,
+    /// which is reported instead of returning whatever the image holds at
+    /// that address.
+    pub(crate) fn map_device_block(&self, device_id: u16, blkaddr: u32) -> Result<u64> {
+        let flat = self.devices_flat();
+        let device_id = self.mask_device_id(device_id);
+        if device_id == 0 {
+            if !flat
+                && let Some(dev) = self.devices.iter().find(|dev| {
+                    dev.mapped_blkaddr != 0
+                        && (dev.mapped_blkaddr..dev.mapped_blkaddr.saturating_add(dev.blocks))
+                            .contains(&blkaddr)
+                })
+            {
+                return Err(Error::UnsupportedFeature(format!(
+                    "block {} is on extra device {}",
+                    blkaddr,
+                    String::from_utf8_lossy(dev.tag())
+                )));
+            }
+            return Ok(blkaddr as u64);
+        }
+
+        let dev = self.device(device_id)?;
+        if !flat {
+            return Err(Error::UnsupportedFeature(format!(
+                "data on extra device {}",
+                String::from_utf8_lossy(dev.tag())
+            )));
+        }
+        Ok(dev.mapped_blkaddr as u64 + blkaddr as u64)
+    }
+
+    /// Whether every extra device is mapped into the image's block address
+    /// space, which holds trivially for single-device images.
+    fn devices_flat(&self) -> bool {
+        self.devices.iter().all(|dev| dev.mapped_blkaddr != 0)
+    }
+
+    /// Like the kernel, ignores device id bits beyond the device count.
+    fn mask_device_id(&self, device_id: u16) -> u16 {
+        // Rounded up in u32, as with 32768 or more extra devices the next
+        // power of two no longer fits in a u16.
+        let mask = (self.devices.len() as u32 + 1).next_power_of_two() - 1;
+        device_id & mask as u16
+    }
+
+    /// Returns the extra device with the nonzero id `device_id`.
+    fn device(&self, device_id: u16) -> Result<&DeviceSlot> {
+        self.devices
+            .get(device_id as usize - 1)
+            .ok_or_else(|| Error::CorruptedData(format!("unknown device id {}", device_id)))
+    }
+
+    /// Byte range of the inline xattr region of `inode`, or `None` if it has
+    /// no xattrs.
+    pub(crate) fn xattr_range(&self, inode: &Inode) -> Option<(usize, usize)> {
+        let size = inode.xattr_len();
+        if size == 0 {
+            return None;
+        }
+        let offset = self.get_inode_offset(inode.id()) as usize + inode.size();
+        Some((offset, size))
+    }
+
+    /// Offset of the shared xattr entry with the given `id`.
+    pub(crate) fn shared_xattr_offset(&self, id: u32) -> usize {
+        self.block_offset(self.super_block.xattr_blk_addr) as usize + id as usize * 4
+    }
+
+    /// Returns the image offset of inode `nid`, or [`Error::InvalidInode`]
+    /// if it cannot be addressed or a compact inode there would run past
+    /// the end of the image given by the superblock.
+    pub(crate) fn inode_offset(&self, nid: u64) -> Result<usize> {
+        nid.checked_mul(InodeCompact::size() as u64)
+            .and_then(|off| off.checked_add(self.block_offset(self.super_block.meta_blk_addr)))
+            .filter(|off| {
+                off.checked_add(InodeCompact::size() as u64)
+                    .is_some_and(|end| end <= self.image_len())
+            })
+            .and_then(|off| usize::try_from(off).ok())
+            .ok_or(Error::InvalidInode(nid))
+    }
+
+    /// Parses inode `nid` from `data`, which starts at the inode's offset and
+    /// extends at most to the end of the image.
     pub(crate) fn parse_inode(&self, data: &[u8], nid: u64) -> Result<Inode> {
+        let layout = data
+            .get(..2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .ok_or(Error::InvalidInode(nid))?;
+        let size = if Inode::is_compact_format(layout) {
+            InodeCompact::size()
+        } else {
+            InodeExtended::size()
+        };
+        if data.len() < size {
+            return Err(Error::InvalidInode(nid));
+        }
+
         let mut inode_buf = Cursor::new(data);
-        let layout: u16 = inode_buf.read_le()?;
-        inode_buf.set_position(0);
         if Inode::is_compact_format(layout) {
             let inode = InodeCompact::read(&mut inode_buf)?;
             Ok(Inode::Compact((nid, inode)))
@@ -82,6 +721,278 @@ impl EroFSCore {
         }
     }
 
+    /// Checks that an inode found by scanning the metadata area is
+    /// consistent with the image.
+    pub(crate) fn is_plausible_inode(&self, inode: &Inode) -> bool {
+        let Ok(layout) = inode.layout() else {
+            return false;
+        };
+        if inode.file_type() == FileType::Unknown || inode.nlink() == 0 {
+            return false;
+        }
+
+        if let Some((start, count)) = self.flat_data_blocks(inode)
+            && start + count > self.super_block.blocks as u64
+        {
+            return false;
+        }
+        // Inline data never crosses a block boundary.
+        if layout == Layout::FlatInline {
+            let offset = self.get_inode_offset(inode.id()) as usize % self.block_size;
+            let tail = self.inline_tail_len(inode);
+            if offset + inode.size() + inode.xattr_len() + tail > self.block_size {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Returns the start and count of the data blocks of a flat inode, or
+    /// `None` if it has none.
+    fn flat_data_blocks(&self, inode: &Inode) -> Option<(u64, u64)> {
+        let count = inode.data_size().div_ceil(self.block_size) as u64;
+        let count = match inode.layout().ok()? {
+            Layout::FlatPlain => count,
+            Layout::FlatInline => count.saturating_sub(1),
+            _ => return None,
+        };
+        (count != 0).then(|| (inode.raw_block_addr() as u64, count))
+    }
+
+    /// Number of blocks data may be addressed in: those of the image and of
+    /// any extra device mapped after them.
+    pub(crate) fn addressable_blocks(&self) -> u64 {
+        self.devices
+            .iter()
+            .map(|dev| dev.mapped_blkaddr as u64 + dev.blocks as u64)
+            .fold(self.super_block.blocks as u64, u64::max)
+    }
+
+    /// Length in bytes the superblock claims for the image and its mapped
+    /// devices, for backends that can't tell their own length.
+    pub(crate) fn image_len(&self) -> u64 {
+        self.addressable_blocks() << self.super_block.blk_size_bits
+    }
+
+    /// Number of blocks in an image of `image_len` bytes, counting a
+    /// partial last block.
+    pub(crate) fn block_count(&self, image_len: u64) -> u64 {
+        image_len.div_ceil(self.block_size as u64)
+    }
+
+    /// Whether block `blkaddr` starts within an image of `image_len` bytes.
+    pub(crate) fn block_address_valid(&self, blkaddr: u64, image_len: u64) -> bool {
+        blkaddr < self.block_count(image_len)
+    }
+
+    /// Image byte ranges holding the data of a flat inode, as
+    /// `(offset, len)` pairs.
+    pub(crate) fn flat_data_ranges(&self, inode: &Inode) -> Result<Vec<(u64, u64)>> {
+        let mut ranges = Vec::new();
+        if let Some((start, count)) = self.flat_data_blocks(inode) {
+            ranges.push((
+                start << self.super_block.blk_size_bits,
+                count * self.block_size as u64,
+            ));
+        }
+        if inode.layout()? == Layout::FlatInline {
+            let offset =
+                self.get_inode_offset(inode.id()) + (inode.size() + inode.xattr_len()) as u64;
+            let tail = self.inline_tail_len(inode) as u64;
+            // Inline data never crosses a block boundary.
+            if offset % self.block_size as u64 + tail > self.block_size as u64 {
+                return Err(Error::CorruptedData(format!(
+                    "inline data of {} bytes crosses a block boundary",
+                    tail
+                )));
+            }
+            ranges.push((offset, tail));
+        }
+        Ok(ranges)
+    }
+
+    /// Physical extents of a flat inode, see `EroFS::extents`.
+    pub(crate) fn flat_extents(&self, inode: &Inode) -> Result<Vec<PhysicalExtent>> {
+        let size = inode.data_size() as u64;
+        let mut logical_offset = 0;
+        Ok(self
+            .flat_data_ranges(inode)?
+            .into_iter()
+            .map(|(offset, len)| {
+                let logical_len = len.min(size - logical_offset);
+                let extent = PhysicalExtent {
+                    device: 0,
+                    phys_block: offset >> self.super_block.blk_size_bits,
+                    logical_offset,
+                    logical_len,
+                    len,
+                };
+                logical_offset += logical_len;
+                extent
+            })
+            .collect())
+    }
+
+    /// Physical extent of chunk `chunk_index` of a chunk-based inode, which
+    /// starts at `logical_offset` and whose address `chunk_block()` decodes
+    /// from `chunk_addr`, or `None` for a hole.
+    ///
+    /// Unlike reads, chunks on an extra device that isn't mapped into the
+    /// image are reported rather than rejected.
+    pub(crate) fn chunk_extent(
+        &self,
+        inode: &Inode,
+        chunk_index: usize,
+        chunk_addr: &[u8],
+        image_len: u64,
+    ) -> Result<Option<PhysicalExtent>> {
+        let (_, _, chunk_size) = self.chunk_table(inode);
+        let logical_offset = (chunk_index * chunk_size) as u64;
+        let logical_len = (inode.data_size() as u64)
+            .saturating_sub(logical_offset)
+            .min(chunk_size as u64);
+        let extent = |device, phys_block| PhysicalExtent {
+            device,
+            phys_block,
+            logical_offset,
+            logical_len,
+            len: logical_len.next_multiple_of(self.block_size as u64),
+        };
+
+        if chunk_addr.len() == ChunkIndex::size() && !self.devices_flat() {
+            let index = ChunkIndex::read(&mut Cursor::new(chunk_addr))?;
+            let device_id = self.mask_device_id(index.device_id);
+            if index.blkaddr != NULL_ADDR && device_id != 0 {
+                self.device(device_id)?;
+                return Ok(Some(extent(device_id, index.blkaddr as u64)));
+            }
+        }
+        Ok(self
+            .chunk_block(inode.id(), chunk_index, chunk_addr, 0, image_len)?
+            .map(|block| extent(0, block)))
+    }
+
+    /// Decode an entry of the chunk index table as stored, see
+    /// `EroFS::chunks`.
+    pub(crate) fn chunk_ref(&self, chunk_addr: &[u8]) -> Result<ChunkRef> {
+        let (device, blkaddr) = if chunk_addr.len() == ChunkIndex::size() {
+            let index = ChunkIndex::read(&mut Cursor::new(chunk_addr))?;
+            (self.mask_device_id(index.device_id), index.blkaddr)
+        } else {
+            let addr = chunk_addr
+                .try_into()
+                .map(u32::from_le_bytes)
+                .map_err(|_| Error::CorruptedData("invalid chunk address".to_string()))?;
+            (0, addr)
+        };
+        if blkaddr == NULL_ADDR {
+            return Ok(ChunkRef::Hole);
+        }
+        Ok(ChunkRef::Mapped {
+            device,
+            block: blkaddr as u64,
+        })
+    }
+
+    /// Physical extent of a decoded extent of a compressed inode, see
+    /// `EroFS::extents`.
+    pub(crate) fn zmap_extent(&self, extent: &Extent) -> PhysicalExtent {
+        PhysicalExtent {
+            device: 0,
+            phys_block: extent.pa as u64 >> self.super_block.blk_size_bits,
+            logical_offset: extent.la as u64,
+            logical_len: extent.llen as u64,
+            len: extent.plen as u64,
+        }
+    }
+
+    /// Image byte range holding all of the data of `inode`, as
+    /// `(offset, len)`, if it is stored uncompressed in one piece: a flat
+    /// inode whose data is either all in blocks or all inline.
+    pub(crate) fn contiguous_data_range(&self, inode: &Inode) -> Option<(usize, usize)> {
+        if !matches!(inode.layout().ok()?, Layout::FlatPlain | Layout::FlatInline) {
+            return None;
+        }
+        match self.flat_data_ranges(inode).ok()?.as_slice() {
+            [] => Some((0, 0)),
+            [(offset, _)] => Some((usize::try_from(*offset).ok()?, inode.data_size())),
+            _ => None,
+        }
+    }
+
+    /// Whether the packed inode holding fragments is `nid`.
+    pub(crate) fn is_packed_inode(&self, nid: u64) -> bool {
+        self.packed_nid() == Some(nid)
+    }
+
+    /// The nid of the packed inode holding fragments, if the image has one.
+    pub(crate) fn packed_nid(&self) -> Option<u64> {
+        (self.super_block.feature_incompat & FEATURE_INCOMPAT_FRAGMENTS != 0)
+            .then_some(self.super_block.packed_nid)
+    }
+
+    /// Checks that `len` bytes at `offset` lie within the data of the packed
+    /// inode `packed`.
+    pub(crate) fn check_packed_range(
+        &self,
+        packed: &Inode,
+        offset: usize,
+        len: usize,
+    ) -> Result<()> {
+        let end = offset.saturating_add(len);
+        if end > packed.data_size() {
+            return Err(Error::OutOfRange(end, packed.data_size()));
+        }
+        Ok(())
+    }
+
+    /// Size of the tail of a flat inline inode stored right after it.
+    fn inline_tail_len(&self, inode: &Inode) -> usize {
+        let block_count = inode.data_size().div_ceil(self.block_size);
+        match block_count {
+            0 => 0,
+            n => self.block_len(inode.data_size(), n - 1),
+        }
+    }
+
+    /// Offset of the map header of a compressed inode, or `None` for other
+    /// layouts.
+    pub(crate) fn map_header_offset(&self, inode: &Inode) -> Option<usize> {
+        inode
+            .layout()
+            .is_ok_and(|layout| layout.is_compressed())
+            .then(|| ZMap::header_offset(self, inode))
+    }
+
+    /// Returns the number of bytes of the metadata area used by `inode`:
+    /// the inode itself, its xattrs and the inline data or indexes following
+    /// them.
+    ///
+    /// `map_header` must hold the bytes at `map_header_offset()` for
+    /// compressed inodes, and is ignored otherwise.
+    pub(crate) fn inode_meta_len(&self, inode: &Inode, map_header: &[u8]) -> Result<usize> {
+        let len = inode.size() + inode.xattr_len();
+        match inode.layout()? {
+            Layout::FlatPlain => Ok(len),
+            Layout::FlatInline => Ok(len + self.inline_tail_len(inode)),
+            Layout::ChunkBased => {
+                let chunk_format = ChunkBasedFormat::new(inode.raw_block_addr());
+                let chunk_bits = chunk_format.chunk_size_bits() + self.super_block.blk_size_bits;
+                let chunk_count = inode.data_size().div_ceil(1 << chunk_bits);
+                if chunk_format.is_indexes() {
+                    Ok(len.next_multiple_of(8) + chunk_count * 8)
+                } else {
+                    Ok(len + chunk_count * 4)
+                }
+            }
+            Layout::CompressedFull | Layout::CompressedCompact => {
+                let header_offset =
+                    ZMap::header_offset(self, inode) - self.get_inode_offset(inode.id()) as usize;
+                Ok(header_offset + ZMap::data_len(self, inode, map_header)?)
+            }
+        }
+    }
+
     /// Plan a block read operation for the given inode and offset.
     ///
     /// Returns a `BlockPlan` describing what bytes to read.
@@ -96,7 +1007,9 @@ impl EroFSCore {
                     return Err(Error::OutOfRange(block_index, block_count));
                 }
 
-                let size = inode.data_size();
+                // Block math must follow the EROFS block size, which is
+                // independent of the host page size used by mmap backends.
+                let size = self.block_len(inode.data_size(), block_index);
                 let offset = self.block_offset(inode.raw_block_addr()) as usize
                     + (block_index * self.block_size);
                 Ok(BlockPlan::Direct { offset, size })
@@ -111,8 +1024,8 @@ impl EroFSCore {
                 if block_count != 0 && block_index == block_count - 1 {
                     // tail block
                     let inode_offset = self.get_inode_offset(inode.id());
-                    let buf_size = inode.data_size() % self.block_size;
-                    let offset = inode_offset as usize + inode.size() + inode.xattr_size();
+                    let buf_size = self.block_len(inode.data_size(), block_index);
+                    let offset = inode_offset as usize + inode.size() + inode.xattr_len();
                     return Ok(BlockPlan::Direct {
                         offset,
                         size: buf_size,
@@ -125,36 +1038,31 @@ impl EroFSCore {
                 Ok(BlockPlan::Direct { offset, size: len })
             }
             Layout::CompressedFull | Layout::CompressedCompact => {
-                Err(Error::NotSupported("compressed compact layout".to_string()))
-            }
-            Layout::ChunkBased => {
-                let chunk_format = ChunkBasedFormat::new(inode.raw_block_addr());
-                if !chunk_format.is_valid() {
-                    return Err(Error::CorruptedData(format!(
-                        "invalid chunk based format {}",
-                        inode.raw_block_addr()
-                    )));
-                } else if chunk_format.is_indexes() {
-                    return Err(Error::NotSupported(
-                        "chunk based format with indexes".to_string(),
-                    ));
+                let block_count = inode.data_size().div_ceil(self.block_size);
+                let block_index = offset / self.block_size;
+                if block_index >= block_count {
+                    return Err(Error::OutOfRange(block_index, block_count));
                 }
 
-                let chunk_bits = chunk_format.chunk_size_bits() + self.super_block.blk_size_bits;
-                let chunk_size = 1usize << chunk_bits;
+                Ok(BlockPlan::Compressed {
+                    map_offset: ZMap::header_offset(self, inode),
+                    start: block_index * self.block_size,
+                    size: self.block_len(inode.data_size(), block_index),
+                })
+            }
+            Layout::ChunkBased => {
+                self.check_chunk_format(inode)?;
+                let (table_offset, addr_len, chunk_size) = self.chunk_table(inode);
                 let chunk_count = inode.data_size().div_ceil(chunk_size);
-                let chunk_index = offset >> chunk_bits;
+                let chunk_index = offset / chunk_size;
                 let chunk_fixed = offset % chunk_size / self.block_size;
                 if chunk_index >= chunk_count {
                     return Err(Error::OutOfRange(chunk_index, chunk_count));
                 }
 
-                let inode_offset = self.get_inode_offset(inode.id());
-                let addr_offset =
-                    inode_offset as usize + inode.size() + inode.xattr_size() + (chunk_index * 4);
-
                 Ok(BlockPlan::Chunked {
-                    addr_offset,
+                    addr_offset: table_offset + chunk_index * addr_len,
+                    addr_len,
                     chunk_fixed,
                     chunk_size,
                     data_size: inode.data_size(),
@@ -164,24 +1072,100 @@ impl EroFSCore {
         }
     }
 
+    /// Checks that the chunk format of a chunk-based inode is one this crate
+    /// understands.
+    pub(crate) fn check_chunk_format(&self, inode: &Inode) -> Result<()> {
+        if !ChunkBasedFormat::new(inode.raw_block_addr()).is_valid() {
+            return Err(Error::CorruptedData(format!(
+                "invalid chunk based format {}",
+                inode.raw_block_addr()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Locate the chunk address table of a chunk-based inode: its image
+    /// offset, the size of each entry and the size of each chunk.
+    pub(crate) fn chunk_table(&self, inode: &Inode) -> (usize, usize, usize) {
+        let chunk_format = ChunkBasedFormat::new(inode.raw_block_addr());
+        let chunk_bits = chunk_format.chunk_size_bits() + self.super_block.blk_size_bits;
+
+        // Chunk indexes are aligned to their size, plain block addresses
+        // directly follow the inode.
+        let inode_offset = self.get_inode_offset(inode.id());
+        let offset = inode_offset as usize + inode.size() + inode.xattr_len();
+        let (offset, addr_len) = if chunk_format.is_indexes() {
+            (
+                offset.next_multiple_of(ChunkIndex::size()),
+                ChunkIndex::size(),
+            )
+        } else {
+            (offset, 4)
+        };
+        (offset, addr_len, 1 << chunk_bits)
+    }
+
+    /// Decode the `addr_len` bytes read from `addr_offset` in a `Chunked`
+    /// plan into the image block the chunk starts at, or `None` for a hole.
+    ///
+    /// Chunks whose block `chunk_fixed` starts at or past byte `image_len`
+    /// are reported as [`Error::CorruptChunkIndex`] for chunk `chunk_index`
+    /// of inode `inode_nid`. Sync images pass the length their backend
+    /// reports, while async backends can't report one and pass the length
+    /// the superblock gives, see [`image_len`](Self::image_len).
+    pub(crate) fn chunk_block(
+        &self,
+        inode_nid: u64,
+        chunk_index: usize,
+        chunk_addr: &[u8],
+        chunk_fixed: usize,
+        image_len: u64,
+    ) -> Result<Option<u64>> {
+        let block = if chunk_addr.len() == ChunkIndex::size() {
+            let index = ChunkIndex::read(&mut Cursor::new(chunk_addr))?;
+            if index.blkaddr == NULL_ADDR {
+                return Ok(None);
+            }
+            self.map_device_block(index.device_id, index.blkaddr)?
+        } else {
+            // Like the kernel, only the null address marks a hole; 0 is an
+            // ordinary block address.
+            let addr = chunk_addr
+                .try_into()
+                .map(u32::from_le_bytes)
+                .map_err(|_| Error::CorruptedData("invalid chunk address".to_string()))?;
+            if addr == NULL_ADDR {
+                return Ok(None);
+            }
+            addr as u64
+        };
+
+        let read_block = block.saturating_add(chunk_fixed as u64);
+        if !self.block_address_valid(read_block, image_len) {
+            return Err(Error::CorruptChunkIndex {
+                inode_nid,
+                chunk_index,
+                blkaddr: read_block,
+            });
+        }
+        Ok(Some(block))
+    }
+
     /// Resolve the final read offset and size for a chunk-based block read.
     ///
-    /// `chunk_addr` is the i32 value read from `addr_offset` in the `Chunked` plan.
+    /// `chunk_block` is what `chunk_block()` decoded from the `addr_len`
+    /// bytes read from `addr_offset` in the `Chunked` plan.
     /// `chunk_size` is the full chunk size in bytes (may span multiple blocks).
+    /// The offset is `None` if the chunk is a hole, which reads as `size`
+    /// zeros.
     pub(crate) fn resolve_chunk_read(
         &self,
-        chunk_addr: i32,
+        chunk_block: Option<u64>,
         chunk_fixed: usize,
         chunk_size: usize,
         data_size: usize,
         chunk_index: usize,
-    ) -> Result<(usize, usize)> {
-        if chunk_addr <= 0 {
-            return Err(Error::CorruptedData(
-                "sparse chunks are not supported".to_string(),
-            ));
-        }
-
+    ) -> Result<(Option<usize>, usize)> {
         let file_byte_offset = chunk_index * chunk_size + chunk_fixed * self.block_size;
         let remaining = data_size.saturating_sub(file_byte_offset);
         let read_size = remaining.min(self.block_size);
@@ -190,10 +1174,74 @@ impl EroFSCore {
             return Err(Error::OutOfRange(file_byte_offset, data_size));
         }
 
-        let offset = self.block_offset(chunk_addr as u32 + chunk_fixed as u32) as usize;
+        let offset = chunk_block.map(|chunk_block| {
+            ((chunk_block + chunk_fixed as u64) << self.super_block.blk_size_bits) as usize
+        });
         Ok((offset, read_size))
     }
 
+    /// Decode `extent` from its physical cluster `input`.
+    pub(crate) fn decode_extent<'a>(
+        &self,
+        extent: &Extent,
+        input: &'a [u8],
+    ) -> Result<Cow<'a, [u8]>> {
+        let zero_padding = self.super_block.feature_incompat & FEATURE_INCOMPAT_ZERO_PADDING != 0;
+        decompress::decode_extent(
+            extent,
+            input,
+            zero_padding,
+            self.block_size,
+            &self.compr_configs,
+        )
+    }
+
+    /// Copy the part of the decoded `data` of `extent` overlapping the
+    /// logical range starting at `start` into `buf`.
+    pub(crate) fn copy_extent(&self, extent: &Extent, data: &[u8], start: usize, buf: &mut [u8]) {
+        if let Some((from, to)) = Self::extent_overlap(extent, start, buf.len()) {
+            buf[from - start..to - start].copy_from_slice(&data[from - extent.la..to - extent.la]);
+        }
+    }
+
+    /// The logical range `from..to` shared by `extent` and the `len` bytes
+    /// starting at `start`, or `None` if they don't overlap.
+    pub(crate) fn extent_overlap(
+        extent: &Extent,
+        start: usize,
+        len: usize,
+    ) -> Option<(usize, usize)> {
+        let from = extent.la.max(start);
+        let to = (extent.la + extent.llen).min(start + len);
+        (from < to).then_some((from, to))
+    }
+
+    /// Offset within the packed inode of the byte at logical offset `from`
+    /// of the fragment `extent`.
+    pub(crate) fn fragment_offset(extent: &Extent, from: usize) -> Result<usize> {
+        extent.pa.checked_add(from - extent.la).ok_or_else(|| {
+            Error::CorruptedData(format!("fragment offset {} out of range", extent.pa))
+        })
+    }
+
+    /// Allocates a zeroed buffer of `len` bytes for file data, failing
+    /// with [`Error::OutOfMemory`] instead of aborting when the size read
+    /// from an inode can't be allocated.
+    pub(crate) fn data_buffer(len: usize) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        buf.try_reserve_exact(len)
+            .map_err(|_| Error::OutOfMemory(len))?;
+        buf.resize(len, 0);
+        Ok(buf)
+    }
+
+    /// Number of bytes of a `data_size`-long file stored in block `block_index`.
+    pub(crate) fn block_len(&self, data_size: usize, block_index: usize) -> usize {
+        data_size
+            .saturating_sub(block_index * self.block_size)
+            .min(self.block_size)
+    }
+
     pub(crate) fn get_inode_offset(&self, nid: u64) -> u64 {
         self.block_offset(self.super_block.meta_blk_addr) + (nid * InodeCompact::size() as u64)
     }
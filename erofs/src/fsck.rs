@@ -0,0 +1,224 @@
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::filesystem::EroFSCore;
+use crate::types::{DirentFileType, Inode, SuperBlock};
+use crate::{Error, Result};
+
+/// Machine-readable kind of a problem found by [`EroFS::fsck`](crate::EroFS::fsck).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FsckCode {
+    /// The superblock sets incompatible feature bits this crate doesn't know.
+    UnsupportedFeature,
+    /// The superblock checksum doesn't match its contents.
+    ChecksumMismatch,
+    /// An inode uses a data layout that cannot be read.
+    UnsupportedLayout,
+    /// An inode or directory block could not be read or parsed.
+    Unreadable,
+    /// A directory entry's file type disagrees with its inode's mode.
+    FileTypeMismatch,
+    /// A directory's `.` entry doesn't point to the directory itself.
+    SelfMismatch,
+    /// A directory's `..` entry doesn't point to its parent.
+    ParentMismatch,
+    /// A directory is reachable through more than one path.
+    DirectoryCycle,
+    /// The data of an inode cannot be located: its inline data, chunk
+    /// table or compressed indexes are inconsistent.
+    CorruptMapping,
+    /// An inode's data lies past the blocks of the image.
+    DataOutOfRange,
+    /// An inode's link count disagrees with the directory entries referring
+    /// to it.
+    LinkCountMismatch,
+}
+
+/// A single problem found by [`EroFS::fsck`](crate::EroFS::fsck).
+#[derive(Debug, Clone)]
+pub struct FsckProblem {
+    /// What kind of problem was found.
+    pub code: FsckCode,
+    /// Path of the offending entry, or `/` for image-wide problems.
+    pub path: String,
+    /// Node ID of the offending inode, if the problem concerns one.
+    pub nid: Option<u64>,
+    /// Human-readable details.
+    pub message: String,
+}
+
+/// A single problem found by [`EroFS::check`](crate::EroFS::check).
+pub type CheckError = FsckProblem;
+
+/// The result of a full consistency check.
+#[derive(Debug, Clone, Default)]
+pub struct FsckReport {
+    /// Every problem found, in discovery order.
+    pub problems: Vec<FsckProblem>,
+}
+
+impl FsckReport {
+    /// Returns `true` if no problem was found.
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub(crate) fn check_features(&mut self, super_block: &SuperBlock) {
+        let unsupported = super_block.unsupported_features();
+        if !unsupported.is_empty() {
+            self.push(
+                FsckCode::UnsupportedFeature,
+                "/",
+                None,
+                format!("unsupported incompatible features {}", unsupported),
+            );
+        }
+    }
+
+    /// Checks a `.` or `..` entry of the directory `dir_nid`. Returns `false`
+    /// if `name` is a regular entry.
+    pub(crate) fn check_dot_entry(
+        &mut self,
+        dir_path: &str,
+        name: &[u8],
+        nid: u64,
+        dir_nid: u64,
+        parent_nid: u64,
+    ) -> bool {
+        let (expected, code) = match name {
+            b"." => (dir_nid, FsckCode::SelfMismatch),
+            b".." => (parent_nid, FsckCode::ParentMismatch),
+            _ => return false,
+        };
+        if nid != expected {
+            self.push(
+                code,
+                dir_path,
+                Some(dir_nid),
+                format!("points to nid {}, expected {}", nid, expected),
+            );
+        }
+        true
+    }
+
+    /// Checks an inode against the directory entry that references it.
+    pub(crate) fn check_inode(&mut self, path: &str, dirent_file_type: u8, inode: &Inode) {
+        let nid = Some(inode.id());
+        let on_disk = DirentFileType::from(inode.file_type());
+        if DirentFileType::try_from(dirent_file_type).ok() != Some(on_disk) {
+            self.push(
+                FsckCode::FileTypeMismatch,
+                path,
+                nid,
+                format!(
+                    "dirent type {} but inode is {:?}",
+                    dirent_file_type, on_disk
+                ),
+            );
+        }
+
+        if let Err(e) = inode.layout() {
+            self.push(FsckCode::UnsupportedLayout, path, nid, e.to_string());
+        }
+    }
+
+    /// Checks the `(offset, len)` image ranges holding the data of `inode`,
+    /// as located by `ranges`, against the blocks of the image.
+    pub(crate) fn check_data(
+        &mut self,
+        core: &EroFSCore,
+        path: &str,
+        inode: &Inode,
+        ranges: Result<Vec<(u64, u64)>>,
+    ) {
+        let nid = Some(inode.id());
+        let ranges = match ranges {
+            Ok(ranges) => ranges,
+            // Already reported by `check_inode`.
+            Err(_) if inode.layout().is_err() => return,
+            Err(e @ (Error::UnsupportedFeature(_) | Error::UnsupportedCompression(_))) => {
+                self.push(FsckCode::UnsupportedLayout, path, nid, e.to_string());
+                return;
+            }
+            Err(e) => {
+                self.push(FsckCode::CorruptMapping, path, nid, e.to_string());
+                return;
+            }
+        };
+
+        let image_len = core.image_len();
+        for (offset, len) in ranges {
+            if offset.saturating_add(len) > image_len {
+                self.push(
+                    FsckCode::DataOutOfRange,
+                    path,
+                    nid,
+                    format!(
+                        "{} bytes at offset {} exceed the {}-byte image",
+                        len, offset, image_len
+                    ),
+                );
+            }
+        }
+    }
+
+    pub(crate) fn push(&mut self, code: FsckCode, path: &str, nid: Option<u64>, message: String) {
+        self.problems.push(FsckProblem {
+            code,
+            path: path.into(),
+            nid,
+            message,
+        });
+    }
+}
+
+/// Link counts of the inodes reached by a directory walk, compared to the
+/// directory entries referring to them.
+///
+/// Every entry counts, `.` and `..` included, which gives directories their
+/// usual count of two plus one per subdirectory.
+#[derive(Debug, Default)]
+pub struct LinkCounts {
+    /// Entries referring to each nid, with the path and link count of the
+    /// inode once it has been read.
+    inodes: BTreeMap<u64, (u32, Option<(String, u32)>)>,
+}
+
+impl LinkCounts {
+    /// Records a directory entry referring to `nid`.
+    pub(crate) fn reference(&mut self, nid: u64) {
+        self.inodes.entry(nid).or_default().0 += 1;
+    }
+
+    /// Records `inode` as reached at `path`. Returns `false` if it was
+    /// already reached through another entry.
+    pub(crate) fn visit(&mut self, path: &str, inode: &Inode) -> bool {
+        let (_, seen) = self.inodes.entry(inode.id()).or_default();
+        if seen.is_some() {
+            return false;
+        }
+        *seen = Some((path.into(), inode.nlink()));
+        true
+    }
+
+    /// Reports every visited inode whose link count doesn't match the
+    /// entries found.
+    pub(crate) fn check(self, report: &mut FsckReport) {
+        for (nid, (references, seen)) in self.inodes {
+            if let Some((path, nlink)) = seen
+                && nlink != references
+            {
+                report.push(
+                    FsckCode::LinkCountMismatch,
+                    &path,
+                    Some(nid),
+                    format!("link count {}, expected {}", nlink, references),
+                );
+            }
+        }
+    }
+}
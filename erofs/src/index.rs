@@ -0,0 +1,65 @@
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as Map;
+#[cfg(feature = "std")]
+use std::collections::HashMap as Map;
+
+use typed_path::{UnixPath, UnixPathBuf};
+
+use crate::AsUnixPath;
+use crate::types::DirentFileType;
+
+/// A fully materialized map of every path in an EROFS image.
+///
+/// Created by [`EroFS::index`](crate::EroFS::index). Lookups never touch the
+/// image again, trading memory for speed on query-heavy workloads. With
+/// the `std` feature paths are kept in a hash map and looked up in `O(1)`;
+/// without it, in a B-tree and in `O(log n)`.
+#[derive(Debug, Clone, Default)]
+pub struct Index {
+    entries: Map<UnixPathBuf, (u64, DirentFileType)>,
+}
+
+impl Index {
+    pub(crate) fn insert(&mut self, path: UnixPathBuf, nid: u64, file_type: DirentFileType) {
+        self.entries.insert(path, (nid, file_type));
+    }
+
+    fn get<P: AsUnixPath>(&self, path: P) -> Option<&(u64, DirentFileType)> {
+        self.entries.get(&path.as_unix_path().normalize())
+    }
+
+    /// Returns the node ID of the entry at `path`.
+    pub fn lookup<P: AsUnixPath>(&self, path: P) -> Option<u64> {
+        self.get(path).map(|(nid, _)| *nid)
+    }
+
+    /// Returns `true` if `path` exists in the image.
+    pub fn exists<P: AsUnixPath>(&self, path: P) -> bool {
+        self.get(path).is_some()
+    }
+
+    /// Returns the file type of the entry at `path`.
+    pub fn file_type<P: AsUnixPath>(&self, path: P) -> Option<DirentFileType> {
+        self.get(path).map(|(_, file_type)| *file_type)
+    }
+
+    /// Returns the number of indexed paths, including the root.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if nothing has been indexed.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterates over all indexed paths, in no particular order.
+    ///
+    /// Paths are made of the names as stored, which need not be valid
+    /// UTF-8.
+    pub fn iter(&self) -> impl Iterator<Item = (&UnixPath, u64, DirentFileType)> {
+        self.entries
+            .iter()
+            .map(|(path, (nid, file_type))| (path.as_path(), *nid, *file_type))
+    }
+}
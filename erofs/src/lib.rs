@@ -16,10 +16,9 @@
 //!
 //! ```no_run
 //! use std::io::Read;
-//! use erofs_rs::{EroFS, backend::MmapImage};
+//! use erofs_rs::EroFS;
 //!
-//! let image = MmapImage::new_from_path("image.erofs").unwrap();
-//! let fs = EroFS::new(image).unwrap();
+//! let fs = EroFS::open_path("image.erofs").unwrap();
 //!
 //! // Read a file
 //! let mut file = fs.open("/etc/passwd").unwrap();
@@ -51,8 +50,21 @@ extern crate alloc;
 #[cfg(feature = "std")]
 extern crate std;
 
+#[cfg(feature = "std")]
+pub(crate) mod cache;
+pub(crate) mod crc32c;
+pub(crate) mod decompress;
 pub(crate) mod dirent;
+pub(crate) mod extent;
 pub(crate) mod filesystem;
+pub(crate) mod fsck;
+pub(crate) mod index;
+pub(crate) mod manifest;
+pub(crate) mod metadata;
+pub(crate) mod path;
+pub(crate) mod stats;
+pub(crate) mod xattr;
+pub(crate) mod zmap;
 
 pub mod r#async;
 pub mod backend;
@@ -60,6 +72,17 @@ mod error;
 pub mod sync;
 pub mod types;
 
+#[cfg(feature = "std")]
+pub use cache::ReadTrace;
 pub use dirent::DirEntry;
 pub use error::*;
-pub use sync::{EroFS, ReadDir, WalkDir, WalkDirEntry};
+pub use extent::{ChunkRef, PhysicalExtent};
+pub use filesystem::MAX_SYMLINK_FOLLOWS;
+pub use fsck::{CheckError, FsckCode, FsckProblem, FsckReport};
+pub use index::Index;
+pub use manifest::{ManifestEntry, ManifestIssue};
+pub use metadata::Metadata;
+pub use path::AsUnixPath;
+pub use stats::FsStats;
+pub use sync::{Blocks, Chunks, EroFS, Files, Inodes, ReadDir, WalkDir, WalkDirEntry};
+pub use xattr::Xattrs;
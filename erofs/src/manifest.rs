@@ -0,0 +1,110 @@
+use alloc::{collections::BTreeSet, vec::Vec};
+
+use typed_path::UnixPathBuf;
+
+use crate::types::{DirentFileType, Inode};
+
+/// Why extracting an entry of [`EroFS::manifest`](crate::EroFS::manifest)
+/// as it is would be unsafe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ManifestIssue {
+    /// The name is empty, `.` or `..`, or contains a `/` or NUL byte, so it
+    /// can't be written as a single entry of its directory. Names with a
+    /// `/` would be written outside of it.
+    UnsafeName,
+    /// A symlink whose target is absolute or climbs above the root with
+    /// `..`, so following it once extracted leaves the extracted tree.
+    /// Targets are resolved through the symlinks of the image, like the
+    /// host would once it is extracted.
+    SymlinkEscapes,
+    /// A device node, FIFO or socket, which includes overlayfs whiteouts.
+    /// These can't be created without privileges, if at all.
+    SpecialFile,
+    /// An earlier entry has the same path, so writing this one would
+    /// replace it.
+    DuplicatePath,
+    /// A directory that is one of its own ancestors, which only happens in
+    /// crafted images. Its entries are not listed again.
+    DirectoryCycle,
+}
+
+/// An entry of an image as extraction would write it, returned by
+/// [`EroFS::manifest`](crate::EroFS::manifest).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Path of the entry from the root of the image.
+    pub path: UnixPathBuf,
+    /// Node ID of the entry's inode.
+    pub nid: u64,
+    /// Type of the entry, taken from its inode.
+    pub file_type: DirentFileType,
+    /// Size of the entry's data in bytes.
+    pub size: u64,
+    /// Permission bits, including the setuid, setgid and sticky bits.
+    pub permissions: u16,
+    /// Target of the entry if it is a symlink.
+    pub link_target: Option<UnixPathBuf>,
+    /// Every reason extracting this entry as it is would be unsafe, in the
+    /// order the variants of [`ManifestIssue`] are declared.
+    pub issues: Vec<ManifestIssue>,
+}
+
+impl ManifestEntry {
+    /// Returns `true` if no issue was found with this entry.
+    pub fn is_safe(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Collects the entries of a walk into a manifest, see `EroFS::manifest`.
+#[derive(Debug, Default)]
+pub struct ManifestBuilder {
+    paths: BTreeSet<UnixPathBuf>,
+    entries: Vec<ManifestEntry>,
+}
+
+impl ManifestBuilder {
+    /// Adds the entry named `name` at `path`, which a walk reached directly
+    /// unless `cycle` is set. `link_escapes` tells whether `link_target`
+    /// resolves outside of the tree.
+    pub(crate) fn push(
+        &mut self,
+        path: UnixPathBuf,
+        name: &[u8],
+        inode: &Inode,
+        link_target: Option<UnixPathBuf>,
+        link_escapes: bool,
+        cycle: bool,
+    ) {
+        let mut issues = Vec::new();
+        if matches!(name, b"" | b"." | b"..") || name.iter().any(|&b| b == b'/' || b == 0) {
+            issues.push(ManifestIssue::UnsafeName);
+        }
+        if link_escapes {
+            issues.push(ManifestIssue::SymlinkEscapes);
+        }
+        if !inode.is_dir() && !inode.is_file() && !inode.is_symlink() {
+            issues.push(ManifestIssue::SpecialFile);
+        }
+        if !self.paths.insert(path.clone()) {
+            issues.push(ManifestIssue::DuplicatePath);
+        }
+        if cycle {
+            issues.push(ManifestIssue::DirectoryCycle);
+        }
+
+        self.entries.push(ManifestEntry {
+            path,
+            nid: inode.id(),
+            file_type: DirentFileType::from(inode.file_type()),
+            size: inode.data_size() as u64,
+            permissions: inode.mode() & 0o7777,
+            link_target,
+            issues,
+        });
+    }
+
+    pub(crate) fn finish(self) -> Vec<ManifestEntry> {
+        self.entries
+    }
+}
@@ -0,0 +1,194 @@
+#[cfg(feature = "std")]
+use std::{fs::Permissions, time::SystemTime};
+
+use alloc::string::String;
+
+use crate::types::{DirentFileType, Inode};
+
+/// Metadata about an entry, as returned by
+/// [`EroFS::metadata`](crate::EroFS::metadata).
+///
+/// This is the counterpart of [`std::fs::Metadata`]: it is available for
+/// every kind of entry, including directories, symlinks and device nodes,
+/// without opening it.
+///
+/// With the `serde` feature it serializes as a struct with the values of
+/// `file_type`, `size`, `mode`, `uid`, `gid`, `nlink`, `nid` and
+/// `device_id` under those names. The modification time is serialized as
+/// `mtime`, in seconds since the Unix epoch, and `mtime_nsec`, the
+/// nanoseconds past that second; both are `None` for compact inodes, which
+/// carry no timestamp.
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    inode: Inode,
+}
+
+impl Metadata {
+    pub(crate) fn new(inode: Inode) -> Self {
+        Self { inode }
+    }
+
+    /// Returns the type of the entry.
+    pub fn file_type(&self) -> DirentFileType {
+        self.inode.file_type().into()
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.inode.is_dir()
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.inode.is_file()
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.inode.is_symlink()
+    }
+
+    pub fn is_block_device(&self) -> bool {
+        self.inode.is_block_device()
+    }
+
+    pub fn is_char_device(&self) -> bool {
+        self.inode.is_char_device()
+    }
+
+    pub fn is_fifo(&self) -> bool {
+        self.inode.is_fifo()
+    }
+
+    pub fn is_socket(&self) -> bool {
+        self.inode.is_socket()
+    }
+
+    /// Returns `true` if this is an overlayfs whiteout.
+    ///
+    /// See [`Inode::is_whiteout`].
+    pub fn is_whiteout(&self) -> bool {
+        self.inode.is_whiteout()
+    }
+
+    /// Returns the `(major, minor)` device number of a device node.
+    pub fn device_id(&self) -> Option<(u32, u32)> {
+        self.inode.device_id()
+    }
+
+    /// Returns the size of the entry's data in bytes.
+    ///
+    /// For symlinks this is the length of the target.
+    pub fn size(&self) -> usize {
+        self.inode.data_size()
+    }
+
+    /// Returns the full mode, including the file type bits.
+    pub fn mode(&self) -> u16 {
+        self.inode.mode()
+    }
+
+    /// Returns the mode in the style of `ls -l`, see
+    /// [`Inode::mode_string`].
+    pub fn mode_string(&self) -> String {
+        self.inode.mode_string()
+    }
+
+    #[cfg(feature = "std")]
+    pub fn permissions(&self) -> Permissions {
+        self.inode.permissions()
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn permissions(&self) -> u16 {
+        self.inode.permissions()
+    }
+
+    pub fn uid(&self) -> u32 {
+        self.inode.uid()
+    }
+
+    pub fn gid(&self) -> u32 {
+        self.inode.gid()
+    }
+
+    /// Returns the number of hard links to the entry.
+    ///
+    /// Entries with more than one link share their [`nid`](Self::nid), so
+    /// archivers only need to track those to store each inode once.
+    pub fn nlink(&self) -> u32 {
+        self.inode.nlink()
+    }
+
+    /// Returns the node ID of the entry, which identifies hard links.
+    pub fn nid(&self) -> u64 {
+        self.inode.nid()
+    }
+
+    /// Returns the modification time, which only extended inodes carry.
+    ///
+    /// For compact inodes the kernel reports the image's
+    /// [`build_time`](crate::EroFS::build_time) instead.
+    #[cfg(feature = "std")]
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.inode.modified()
+    }
+
+    /// Returns the last access time. EROFS records a single timestamp per
+    /// inode, so this is the modification time.
+    #[cfg(feature = "std")]
+    pub fn accessed(&self) -> Option<SystemTime> {
+        self.inode.accessed()
+    }
+
+    /// Returns the creation time. EROFS records a single timestamp per
+    /// inode, so this is the modification time.
+    #[cfg(feature = "std")]
+    pub fn created(&self) -> Option<SystemTime> {
+        self.inode.created()
+    }
+
+    /// Returns the modification time as `(seconds, nanoseconds)`, which only
+    /// extended inodes carry.
+    #[cfg(not(feature = "std"))]
+    pub fn modified(&self) -> Option<(u64, u32)> {
+        self.inode.modified()
+    }
+
+    /// Returns the last access time as `(seconds, nanoseconds)`, which is
+    /// the modification time.
+    #[cfg(not(feature = "std"))]
+    pub fn accessed(&self) -> Option<(u64, u32)> {
+        self.inode.accessed()
+    }
+
+    /// Returns the creation time as `(seconds, nanoseconds)`, which is the
+    /// modification time.
+    #[cfg(not(feature = "std"))]
+    pub fn created(&self) -> Option<(u64, u32)> {
+        self.inode.created()
+    }
+
+    /// Returns the underlying inode.
+    pub fn inode(&self) -> &Inode {
+        &self.inode
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Metadata {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mtime = self.inode.mtime();
+        let mut state = serializer.serialize_struct("Metadata", 10)?;
+        state.serialize_field("file_type", &self.file_type())?;
+        state.serialize_field("size", &self.size())?;
+        state.serialize_field("mode", &self.mode())?;
+        state.serialize_field("uid", &self.uid())?;
+        state.serialize_field("gid", &self.gid())?;
+        state.serialize_field("nlink", &self.nlink())?;
+        state.serialize_field("nid", &self.nid())?;
+        state.serialize_field("device_id", &self.device_id())?;
+        state.serialize_field("mtime", &mtime.map(|(secs, _)| secs))?;
+        state.serialize_field("mtime_nsec", &mtime.map(|(_, nanos)| nanos))?;
+        state.end()
+    }
+}
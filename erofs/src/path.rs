@@ -0,0 +1,115 @@
+use alloc::{borrow::Cow, string::String, vec::Vec};
+
+use typed_path::{UnixPath, UnixPathBuf};
+
+/// A path to look up in an EROFS image.
+///
+/// EROFS names are byte strings, so paths are resolved byte for byte and
+/// need not be valid UTF-8. This is implemented for strings and byte
+/// strings, for [`UnixPath`] and [`UnixPathBuf`], and with the `std`
+/// feature on Unix for [`std::path::Path`], [`std::path::PathBuf`],
+/// [`std::ffi::OsStr`] and [`std::ffi::OsString`], whose bytes are used as
+/// they are.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::Path;
+/// use erofs_rs::{EroFS, backend::MmapImage};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let fs = EroFS::new(MmapImage::new_from_path("image.erofs")?)?;
+/// let file = fs.open("/etc/passwd")?;
+/// let file = fs.open(Path::new("/etc/passwd"))?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait AsUnixPath {
+    /// Returns the path as a [`UnixPath`].
+    fn as_unix_path(&self) -> &UnixPath;
+}
+
+impl<T: AsUnixPath + ?Sized> AsUnixPath for &T {
+    fn as_unix_path(&self) -> &UnixPath {
+        (**self).as_unix_path()
+    }
+}
+
+impl AsUnixPath for UnixPath {
+    fn as_unix_path(&self) -> &UnixPath {
+        self
+    }
+}
+
+impl AsUnixPath for UnixPathBuf {
+    fn as_unix_path(&self) -> &UnixPath {
+        self
+    }
+}
+
+impl AsUnixPath for str {
+    fn as_unix_path(&self) -> &UnixPath {
+        UnixPath::new(self)
+    }
+}
+
+impl AsUnixPath for String {
+    fn as_unix_path(&self) -> &UnixPath {
+        UnixPath::new(self)
+    }
+}
+
+impl AsUnixPath for Cow<'_, str> {
+    fn as_unix_path(&self) -> &UnixPath {
+        UnixPath::new(self.as_ref())
+    }
+}
+
+impl AsUnixPath for [u8] {
+    fn as_unix_path(&self) -> &UnixPath {
+        UnixPath::new(self)
+    }
+}
+
+impl AsUnixPath for Vec<u8> {
+    fn as_unix_path(&self) -> &UnixPath {
+        UnixPath::new(self)
+    }
+}
+
+#[cfg(all(feature = "std", unix))]
+mod std_paths {
+    use std::{
+        ffi::{OsStr, OsString},
+        os::unix::ffi::OsStrExt,
+        path::{Path, PathBuf},
+    };
+
+    use typed_path::UnixPath;
+
+    use super::AsUnixPath;
+
+    impl AsUnixPath for OsStr {
+        fn as_unix_path(&self) -> &UnixPath {
+            UnixPath::new(self.as_bytes())
+        }
+    }
+
+    impl AsUnixPath for OsString {
+        fn as_unix_path(&self) -> &UnixPath {
+            UnixPath::new(self.as_bytes())
+        }
+    }
+
+    impl AsUnixPath for Path {
+        fn as_unix_path(&self) -> &UnixPath {
+            UnixPath::new(self.as_os_str().as_bytes())
+        }
+    }
+
+    impl AsUnixPath for PathBuf {
+        fn as_unix_path(&self) -> &UnixPath {
+            UnixPath::new(self.as_os_str().as_bytes())
+        }
+    }
+}
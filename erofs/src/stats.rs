@@ -0,0 +1,48 @@
+use crate::types::Inode;
+
+/// Totals over every inode of an image, returned by
+/// [`EroFS::stats`](crate::EroFS::stats).
+///
+/// Comparing [`logical_bytes`](Self::logical_bytes) with
+/// [`physical_bytes`](Self::physical_bytes) gives the space saved by
+/// compression.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FsStats {
+    /// Number of regular files.
+    pub files: u64,
+    /// Number of directories.
+    pub directories: u64,
+    /// Number of symbolic links.
+    pub symlinks: u64,
+    /// Number of device nodes, FIFOs and sockets.
+    pub others: u64,
+    /// Total size of the data of every inode, as read back.
+    pub logical_bytes: u64,
+    /// Total size of the image holding that data: data blocks, inline
+    /// tails and compressed clusters, including the fragments stored in
+    /// the packed inode. Holes and inode metadata are not counted.
+    pub physical_bytes: u64,
+}
+
+impl FsStats {
+    /// Counts `inode`, whose data takes up `physical` bytes of the image.
+    pub(crate) fn add(&mut self, inode: &Inode, physical: u64) {
+        if inode.is_file() {
+            self.files += 1;
+        } else if inode.is_dir() {
+            self.directories += 1;
+        } else if inode.is_symlink() {
+            self.symlinks += 1;
+        } else {
+            self.others += 1;
+        }
+        self.logical_bytes += inode.data_size() as u64;
+        self.physical_bytes += physical;
+    }
+
+    /// Counts the packed inode, which only adds to the physical size since
+    /// its data is already part of the files it was split off.
+    pub(crate) fn add_packed(&mut self, physical: u64) {
+        self.physical_bytes += physical;
+    }
+}
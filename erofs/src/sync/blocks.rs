@@ -0,0 +1,57 @@
+use bytes::Bytes;
+
+use super::EroFS;
+use crate::Result;
+use crate::backend::Image;
+use crate::types::Inode;
+
+/// An iterator over the data blocks of a file, each returned as owned
+/// [`Bytes`].
+///
+/// Created by [`EroFS::blocks`]. The iterator holds its own handle to the
+/// filesystem rather than borrowing it, so it can be moved to another
+/// thread or kept alongside other state. Every block is as long as the
+/// block size except the last, which is cut at the end of the file. After
+/// an error the iterator is exhausted.
+#[derive(Debug, Clone)]
+pub struct Blocks<I: Image> {
+    erofs: EroFS<I>,
+    inode: Inode,
+    offset: usize,
+}
+
+impl<I: Image> Blocks<I> {
+    pub(crate) fn new(erofs: EroFS<I>, inode: Inode) -> Self {
+        Self {
+            erofs,
+            inode,
+            offset: 0,
+        }
+    }
+}
+
+impl<I: Image> Iterator for Blocks<I> {
+    type Item = Result<Bytes>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.inode.data_size() {
+            return None;
+        }
+
+        let block = self.erofs.get_inode_block_owned(&self.inode, self.offset);
+        self.offset = match block {
+            Ok(_) => self.offset + self.erofs.block_size(),
+            Err(_) => self.inode.data_size(),
+        };
+        Some(block)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self
+            .inode
+            .data_size()
+            .saturating_sub(self.offset)
+            .div_ceil(self.erofs.block_size());
+        (0, Some(remaining))
+    }
+}
@@ -0,0 +1,30 @@
+use super::EroFS;
+use crate::backend::Image;
+use crate::filesystem::ChunkScan;
+use crate::{ChunkRef, Result};
+
+/// An iterator over the chunk index table of a file, in file order.
+///
+/// Created by [`EroFS::chunks`]. Item `i` describes chunk `i` of the file.
+pub struct Chunks<'a, I: Image> {
+    erofs: &'a EroFS<I>,
+    scan: ChunkScan,
+}
+
+impl<'a, I: Image> Chunks<'a, I> {
+    pub(crate) fn new(erofs: &'a EroFS<I>, scan: ChunkScan) -> Self {
+        Self { erofs, scan }
+    }
+}
+
+impl<'a, I: Image> Iterator for Chunks<'a, I> {
+    type Item = Result<ChunkRef>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.erofs.next_chunk(&mut self.scan)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.scan.remaining(), Some(self.scan.remaining()))
+    }
+}
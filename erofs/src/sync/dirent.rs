@@ -1,49 +1,89 @@
-use typed_path::{UnixPath, UnixPathBuf};
+use alloc::borrow::Cow;
+use typed_path::UnixPathBuf;
 
 use super::EroFS;
 use crate::backend::Image;
 use crate::dirent::{DirEntry, DirentBlock};
-use crate::{Result, types::Inode};
+use crate::{
+    AsUnixPath, Result,
+    types::{Dirent, Inode},
+};
 
+/// An iterator over the entries of a directory, excluding `.` and `..`.
+///
+/// Created by [`EroFS::read_dir_from`]. Entries are decoded lazily, one
+/// directory block at a time: only the block holding the next entry is
+/// kept, and a name is only copied out when its entry is yielded, so memory
+/// use stays flat however large the directory is. For the same reason,
+/// [`size_hint`](Iterator::size_hint) only counts the entries of the block
+/// being decoded as certain.
 #[derive(Debug)]
 pub struct ReadDir<'a, I: Image> {
     dir: UnixPathBuf,
     inode: Inode,
     erofs: &'a EroFS<I>,
-    dirent_block: DirentBlock<&'a [u8]>,
+    dirent_block: DirentBlock<Cow<'a, [u8]>>,
     offset: usize,
 }
 
 impl<'a, I: Image> ReadDir<'a, I> {
-    pub(crate) fn new<P: AsRef<UnixPath>>(
+    pub(crate) fn new<P: AsUnixPath>(erofs: &'a EroFS<I>, inode: Inode, dir: P) -> Result<Self> {
+        Self::new_at(erofs, inode, dir, 0)
+    }
+
+    pub(crate) fn new_at<P: AsUnixPath>(
         erofs: &'a EroFS<I>,
         inode: Inode,
         dir: P,
+        cookie: u64,
     ) -> Result<Self> {
-        let block = erofs.get_inode_block(&inode, 0)?;
-        let dirent_block = DirentBlock::new(dir.as_ref().to_path_buf(), block)?;
+        let block_size = erofs.block_size();
+        let (offset, index) = match cookie as usize {
+            cookie if cookie < inode.data_size() => (
+                cookie - cookie % block_size,
+                cookie % block_size / Dirent::size(),
+            ),
+            // Past the end: park on the last block with nothing left to yield.
+            _ => (
+                inode.data_size().saturating_sub(1) / block_size * block_size,
+                usize::MAX,
+            ),
+        };
+
+        let block = erofs.get_inode_block(&inode, offset)?;
+        let mut dirent_block = DirentBlock::new(block)?;
+        dirent_block.seek(index)?;
         Ok(Self {
-            dir: dir.as_ref().to_path_buf(),
+            dir: dir.as_unix_path().to_path_buf(),
             inode,
             erofs,
             dirent_block,
-            offset: 0,
+            offset,
         })
     }
 
+    /// Returns an opaque position that resumes listing right after the last
+    /// entry yielded, for use with [`EroFS::read_dir_from`].
+    ///
+    /// The cookie is the byte offset of the next dirent within the directory
+    /// data, so it stays stable across calls for the same directory.
+    pub fn cookie(&self) -> u64 {
+        (self.offset + self.dirent_block.position() * Dirent::size()) as u64
+    }
+
     fn next_entry(&mut self) -> Result<Option<DirEntry>> {
         if self.offset >= self.inode.data_size() {
             return Ok(None);
         }
 
         while self.offset < self.inode.data_size() {
-            match self.dirent_block.next_entry()? {
+            match self.dirent_block.next_entry(&self.dir)? {
                 Some(entry) => return Ok(Some(entry)),
                 None => {
                     self.offset += self.dirent_block.block_size();
                     if self.offset < self.inode.data_size() {
                         let block = self.erofs.get_inode_block(&self.inode, self.offset)?;
-                        self.dirent_block = DirentBlock::new(self.dir.clone(), block)?;
+                        self.dirent_block = DirentBlock::new(block)?;
                     }
                 }
             }
@@ -58,4 +98,17 @@ impl<'a, I: Image> Iterator for ReadDir<'a, I> {
     fn next(&mut self) -> Option<Self::Item> {
         self.next_entry().transpose()
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let in_block = self.dirent_block.remaining();
+        // Every dirent of the later blocks takes up at least its header and
+        // a one-byte name.
+        let later = self
+            .inode
+            .data_size()
+            .saturating_sub(self.offset + self.dirent_block.block_size())
+            / (Dirent::size() + 1);
+        // `.` and `..` may still be ahead in this block, and are skipped.
+        (in_block.saturating_sub(2), Some(in_block + later))
+    }
 }
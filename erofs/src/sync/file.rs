@@ -1,28 +1,94 @@
+use core::cmp;
 #[cfg(feature = "std")]
-use std::{
-    cmp, format,
-    io::{Read, Result},
-};
+use std::io::{Read, Result, Seek, SeekFrom};
 
 #[cfg(not(feature = "std"))]
 use crate::Result;
 
+use alloc::borrow::Cow;
+use alloc::vec;
+use alloc::vec::Vec;
+
 use bytes::Bytes;
 
 use super::EroFS;
 use crate::backend::Image;
-use crate::types::Inode;
+use crate::types::{Inode, Layout};
+use crate::zmap::ZMap;
+use typed_path::{UnixPath, UnixPathBuf};
 
 #[cfg(not(feature = "std"))]
 /// A trait for reading file contents in `no_std` mode.
 pub trait Read {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+    /// Reads all bytes until the end of the file, appending them to `buf`.
+    ///
+    /// Returns the number of bytes read. On error, `buf` keeps the bytes
+    /// read before the failure.
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        const CHUNK_SIZE: usize = 4096;
+
+        let start = buf.len();
+        loop {
+            let len = buf.len();
+            // Let the vec grow at its own pace, but by at least one chunk.
+            buf.reserve(CHUNK_SIZE);
+            buf.resize(buf.capacity(), 0);
+            match self.read(&mut buf[len..]) {
+                Ok(0) => {
+                    buf.truncate(len);
+                    return Ok(len - start);
+                }
+                Ok(n) => buf.truncate(len + n),
+                Err(e) => {
+                    buf.truncate(len);
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+/// Where to seek to in `no_std` mode, mirroring [`std::io::SeekFrom`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekFrom {
+    /// An offset from the start of the file.
+    Start(u64),
+    /// An offset from the end of the file.
+    End(i64),
+    /// An offset from the current position.
+    Current(i64),
+}
+
+#[cfg(not(feature = "std"))]
+/// A trait for moving within file contents in `no_std` mode.
+pub trait Seek {
+    /// Moves to `pos` and returns the new position from the start of the
+    /// file.
+    ///
+    /// Seeking past the end is allowed, reads from there return no bytes.
+    /// Seeking before the start is an error.
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64>;
+
+    /// Moves back to the start of the file.
+    fn rewind(&mut self) -> Result<()> {
+        self.seek(SeekFrom::Start(0)).map(|_| ())
+    }
+
+    /// Returns the current position from the start of the file.
+    fn stream_position(&mut self) -> Result<u64> {
+        self.seek(SeekFrom::Current(0))
+    }
 }
 
 /// A handle to a file within an EROFS filesystem.
 ///
-/// `File` implements [`std::io::Read`], allowing you to read the file's contents
-/// using standard I/O methods like `read`, `read_to_end`, or `read_to_string`.
+/// `File` implements [`std::io::Read`] and [`std::io::Seek`], allowing you to
+/// read the file's contents using standard I/O methods like `read`,
+/// `read_to_end`, or `read_to_string`. In `no_std` mode it implements this
+/// module's `Read` and `Seek` traits instead.
 ///
 /// # Example
 ///
@@ -42,6 +108,7 @@ pub trait Read {
 pub struct File<'a, I: Image> {
     inode: Inode,
     erofs: &'a EroFS<I>,
+    path: Option<UnixPathBuf>,
     offset: usize,
     buf: Option<Bytes>,
 }
@@ -51,15 +118,230 @@ impl<'a, I: Image> File<'a, I> {
         Self {
             inode,
             erofs,
+            path: None,
             offset: 0,
             buf: None,
         }
     }
 
+    pub(crate) fn with_path(mut self, path: UnixPathBuf) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    /// Returns the path the file was opened by, as it was given, or `None`
+    /// if it was opened from an inode.
+    ///
+    /// This is meant for error and log messages; paths through symlinks
+    /// are not resolved.
+    pub fn path(&self) -> Option<&UnixPath> {
+        self.path.as_deref()
+    }
+
+    /// Returns the inode of the file.
+    pub fn inode(&self) -> &Inode {
+        &self.inode
+    }
+
     /// Returns the size of the file in bytes.
     pub fn size(&self) -> usize {
         self.inode.data_size()
     }
+
+    /// Returns the whole contents of the file borrowed from the image, or
+    /// `None` if they aren't stored as one uncompressed piece.
+    ///
+    /// This is the case for flat files whose data is either all in whole
+    /// blocks or all inline after the inode, but not for flat files with
+    /// an inline tail or for chunk-based and compressed files, which have
+    /// to be read. With a memory-mapped image the slice points into the
+    /// mapping, so nothing is copied. Backends that can't borrow from the
+    /// image without keeping a copy, like
+    /// [`FileImage`](crate::backend::FileImage), always return `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::EroFS;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::open_path("image.erofs")?;
+    /// let file = fs.open("/var/lib/data.db")?;
+    /// if let Some(data) = file.as_slice() {
+    ///     println!("{} bytes without copying", data.len());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn as_slice(&self) -> Option<&'a [u8]> {
+        self.erofs.contiguous_data(&self.inode)
+    }
+
+    /// Reads bytes starting at `offset` into `buf` without moving the
+    /// position used by [`Read`].
+    ///
+    /// Reads are clamped to the end of the file, so fewer than `buf.len()`
+    /// bytes are returned only when the range extends past [`size`](Self::size).
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> crate::Result<usize> {
+        let len = buf.len().min(self.size().saturating_sub(offset));
+        self.erofs
+            .read_inode_at(&self.inode, offset, &mut buf[..len])?;
+        Ok(len)
+    }
+
+    /// Returns an iterator over the decoded blocks of the file, paired with
+    /// the logical offset each one starts at.
+    ///
+    /// Every block but the last is [`EroFS::block_size`] bytes long, so
+    /// blocks at the same offset of two files line up. This holds for
+    /// compressed files too: each physical cluster is decoded once, when
+    /// the first block it covers is reached, and yielded in blocks. Holes
+    /// in sparse files are yielded as zeroed blocks. Iteration always
+    /// starts at offset 0 and doesn't move the position used by [`Read`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::EroFS;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::open_path("image.erofs")?;
+    /// let (a, b) = (fs.open("/etc/a")?, fs.open("/etc/b")?);
+    /// for (a, b) in a.block_iter().zip(b.block_iter()) {
+    ///     let ((offset, a), (_, b)) = (a?, b?);
+    ///     if a != b {
+    ///         println!("blocks at {offset} differ");
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn block_iter(&self) -> impl Iterator<Item = crate::Result<(usize, Cow<'a, [u8]>)>> + 'a {
+        let (inode, erofs, size) = (self.inode, self.erofs, self.size());
+        let compressed = matches!(
+            inode.layout(),
+            Ok(Layout::CompressedFull | Layout::CompressedCompact)
+        );
+        let mut window = None;
+        (0..size).step_by(erofs.block_size()).map(move |offset| {
+            if !compressed {
+                return Ok((offset, erofs.get_inode_block(&inode, offset)?));
+            }
+            let window = if let Some(window) = &mut window {
+                window
+            } else {
+                window.insert(ExtentWindow::new(erofs.inode_zmap(&inode)?))
+            };
+            let len = (size - offset).min(erofs.block_size());
+            Ok((offset, Cow::Owned(window.read(erofs, offset, len)?)))
+        })
+    }
+
+    /// Hints that the next `blocks` blocks from the current position will be
+    /// read soon.
+    ///
+    /// Blocks of uncompressed files are touched so that the pages of a
+    /// memory-mapped image are faulted in ahead of the reads. Blocks of
+    /// compressed files are decoded into the extent cache if the filesystem
+    /// has one (see [`EroFS::with_cache`]), and left alone otherwise, as
+    /// the work would be thrown away.
+    ///
+    /// This is a best-effort hint: it never changes what later reads
+    /// return, only how long they take. Errors are ignored and left for
+    /// the reads to report.
+    pub fn prefetch(&self, blocks: usize) {
+        let compressed = self
+            .inode
+            .layout()
+            .is_ok_and(|layout| layout.is_compressed());
+        if compressed && !self.erofs.caches_extents() {
+            return;
+        }
+
+        let block_size = self.erofs.block_size();
+        let page_size = rustix::param::page_size();
+        let first = self.offset / block_size;
+        let end = first
+            .saturating_add(blocks)
+            .min(self.size().div_ceil(block_size));
+        for block in first..end {
+            let Ok(data) = self.erofs.get_inode_block(&self.inode, block * block_size) else {
+                return;
+            };
+            // Reading one byte per page is enough to fault the page in.
+            core::hint::black_box(data.iter().step_by(page_size).fold(0u8, |acc, b| acc ^ b));
+        }
+    }
+}
+
+/// The decoded data of a compressed file from the current block of
+/// [`File::block_iter`] up to the end of the last extent decoded, so
+/// that extents spanning several blocks are decoded only once.
+struct ExtentWindow<'a> {
+    zmap: ZMap<'a>,
+    start: usize,
+    data: Vec<u8>,
+}
+
+impl<'a> ExtentWindow<'a> {
+    const fn new(zmap: ZMap<'a>) -> Self {
+        Self {
+            zmap,
+            start: 0,
+            data: Vec::new(),
+        }
+    }
+
+    /// Returns the `len` bytes at `offset`, decoding the extents past the
+    /// window that they reach into. Offsets must not go backwards.
+    fn read<I: Image>(
+        &mut self,
+        erofs: &EroFS<I>,
+        offset: usize,
+        len: usize,
+    ) -> crate::Result<Vec<u8>> {
+        let end = offset + len;
+        let window_end = self.start + self.data.len();
+        if end > window_end {
+            // Keep what is left of the window and append the extents after
+            // it, each decoded in full.
+            let from = window_end.max(offset);
+            self.data
+                .drain(..(offset - self.start).min(self.data.len()));
+            self.start = offset;
+            let extents = self.zmap.extents(from, end)?;
+            let to = extents.last().map_or(end, |extent| extent.la + extent.llen);
+            let mut tail = vec![0u8; to - from];
+            for extent in &extents {
+                erofs.read_extent(extent, from, &mut tail)?;
+            }
+            self.data.extend_from_slice(&tail);
+        }
+        Ok(self.data[offset - self.start..end - self.start].to_vec())
+    }
+}
+
+impl<'a, I: Image> Seek for File<'a, I> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let (base, delta) = match pos {
+            SeekFrom::Start(offset) => (offset, 0),
+            SeekFrom::End(delta) => (self.size() as u64, delta),
+            SeekFrom::Current(delta) => (self.offset as u64, delta),
+        };
+        let offset = base
+            .checked_add_signed(delta)
+            .and_then(|offset| usize::try_from(offset).ok())
+            .ok_or(crate::Error::InvalidSeek)?;
+
+        // The buffered block stays valid as long as the position doesn't
+        // leave it.
+        let block_size = self.erofs.block_size();
+        if offset / block_size != self.offset / block_size {
+            self.buf = None;
+        }
+        self.offset = offset;
+        Ok(offset as u64)
+    }
 }
 
 impl<'a, I: Image> Read for File<'a, I> {
@@ -68,8 +350,9 @@ impl<'a, I: Image> Read for File<'a, I> {
             return Ok(0);
         }
 
+        let block_mask = self.erofs.block_size() - 1;
         if let Some(ref data) = self.buf {
-            let offset = self.offset % self.erofs.block_size();
+            let offset = self.offset & block_mask;
             let data_remaining = data.len().saturating_sub(offset);
             let n = cmp::min(buf.len(), data_remaining);
             buf[..n].copy_from_slice(&data[offset..offset + n]);
@@ -80,28 +363,19 @@ impl<'a, I: Image> Read for File<'a, I> {
             return Ok(n);
         }
 
-        let block_size = self.erofs.block_size();
         let cur_offset = self.offset;
-        let block = self.erofs.get_inode_block(&self.inode, cur_offset);
+        let block = self.erofs.get_inode_block(&self.inode, cur_offset)?;
 
-        #[cfg(feature = "std")]
-        let block =
-            block.map_err(|e| std::io::Error::other(format!("read block failed: {}", e)))?;
-        #[cfg(not(feature = "std"))]
-        let block = block.map_err(|e| e)?;
-
-        if buf.len() >= block.len() {
-            let n = block.len();
-            buf[..n].copy_from_slice(block);
-            self.offset += n;
-            Ok(n)
-        } else {
-            let offset = cur_offset % block_size;
-            let n = cmp::min(buf.len(), block.len().saturating_sub(offset));
-            buf[..n].copy_from_slice(&block[offset..offset + n]);
-            self.buf = Some(Bytes::copy_from_slice(block));
-            self.offset += n;
-            Ok(n)
+        let offset = cur_offset & block_mask;
+        let n = cmp::min(buf.len(), block.len().saturating_sub(offset));
+        buf[..n].copy_from_slice(&block[offset..offset + n]);
+        if offset + n < block.len() {
+            self.buf = Some(match block {
+                Cow::Borrowed(block) => Bytes::copy_from_slice(block),
+                Cow::Owned(block) => Bytes::from(block),
+            });
         }
+        self.offset += n;
+        Ok(n)
     }
 }
@@ -1,15 +1,39 @@
-use alloc::{format, string::ToString, sync::Arc};
-use bytes::Buf;
-use typed_path::Component;
-use typed_path::{UnixComponent, UnixPath};
+use alloc::{
+    borrow::Cow,
+    collections::BTreeSet,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use bytes::Bytes;
+use core::cmp;
+use typed_path::{UnixPath, UnixPathBuf};
 
+use super::blocks::Blocks;
+use super::chunks::Chunks;
+use super::dirent::ReadDir;
 use super::file::File;
+use super::inodes::Inodes;
 use super::walkdir::WalkDir;
-use crate::backend::Image;
+#[cfg(feature = "std")]
+use crate::backend::MmapImage;
+use crate::backend::{Image, OffsetImage, SliceImage, VecImage};
+#[cfg(feature = "std")]
+use crate::cache::{ExtentCache, PathCache, ReadTrace};
 use crate::dirent;
-use crate::filesystem::{BlockPlan, EroFSCore};
+use crate::filesystem::{
+    BlockPlan, ChunkScan, EroFSCore, InodeScan, MIN_IMAGE_LEN, PathWalk, SharedXattrScan,
+};
+use crate::fsck::LinkCounts;
+use crate::manifest::ManifestBuilder;
 use crate::types::*;
-use crate::{Error, Result};
+use crate::xattr;
+use crate::zmap::{Extent, ExtentFormat, ZMap};
+use crate::{
+    AsUnixPath, CheckError, ChunkRef, DirEntry, Error, FsStats, FsckCode, FsckReport, Index,
+    ManifestEntry, Metadata, PhysicalExtent, Result, WalkError, Xattrs,
+};
 
 /// The main entry point for reading EROFS filesystem images.
 ///
@@ -48,10 +72,54 @@ use crate::{Error, Result};
 ///     // Process directory entry...
 /// }
 /// ```
-#[derive(Debug, Clone)]
+///
+/// # Thread safety
+///
+/// Reads never modify the image, so `EroFS<I>` is [`Send`] and [`Sync`]
+/// whenever the backend `I` is, as every backend of this crate is. The
+/// caches enabled by [`with_cache`](Self::with_cache) and
+/// [`with_path_cache`](Self::with_path_cache) are guarded by a mutex when
+/// shared. Threads can borrow one `EroFS` and read through their own
+/// [`File`]s independently, or take clones, which share the image and the
+/// caches.
+///
+/// ```no_run
+/// use std::io::Read;
+/// use erofs_rs::{EroFS, backend::MmapImage};
+///
+/// let fs = EroFS::new(MmapImage::new_from_path("image.erofs").unwrap()).unwrap();
+/// std::thread::scope(|s| {
+///     for path in ["/etc/passwd", "/etc/group"] {
+///         let fs = &fs;
+///         s.spawn(move || {
+///             let mut content = Vec::new();
+///             fs.open(path).unwrap().read_to_end(&mut content).unwrap();
+///         });
+///     }
+/// });
+/// ```
+#[derive(Debug)]
 pub struct EroFS<I: Image> {
     image: Arc<I>,
     core: EroFSCore,
+    #[cfg(feature = "std")]
+    cache: Option<Arc<ExtentCache>>,
+    #[cfg(feature = "std")]
+    path_cache: Option<Arc<PathCache>>,
+}
+
+// The image is shared, so cloning doesn't need the backend to be `Clone`.
+impl<I: Image> Clone for EroFS<I> {
+    fn clone(&self) -> Self {
+        Self {
+            image: self.image.clone(),
+            core: self.core.clone(),
+            #[cfg(feature = "std")]
+            cache: self.cache.clone(),
+            #[cfg(feature = "std")]
+            path_cache: self.path_cache.clone(),
+        }
+    }
 }
 
 impl<I: Image> EroFS<I> {
@@ -66,7 +134,11 @@ impl<I: Image> EroFS<I> {
     /// Returns an error if:
     /// - The superblock cannot be read
     /// - The magic number doesn't match EROFS format (0xE0F5E1E2)
-    /// - The block size is invalid (must be 2^n where 9 ≤ n ≤ 24)
+    /// - The block size is unsupported (must be 2^n where 9 ≤ n ≤ 16)
+    /// - The image is too short to hold the superblock, the root inode or
+    ///   the start of the shared xattr pool
+    ///   ([`Error::TruncatedImage`](crate::Error::TruncatedImage)), as with
+    ///   a partial download
     ///
     /// # Examples
     ///
@@ -80,137 +152,1971 @@ impl<I: Image> EroFS<I> {
     /// # }
     /// ```
     pub fn new(image: I) -> Result<Self> {
-        let sb_data = image
-            .get(SUPER_BLOCK_OFFSET..)
-            .ok_or_else(|| Error::InvalidSuperblock("failed to read super block".to_string()))?;
-        let core = EroFSCore::new(sb_data)?;
+        if image.len() < MIN_IMAGE_LEN {
+            return Err(Error::TruncatedImage {
+                expected_min: MIN_IMAGE_LEN,
+                actual: image.len(),
+            });
+        }
+        let sb_data = image.read_exact_at(SUPER_BLOCK_OFFSET, SuperBlock::size())?;
+        let mut core = EroFSCore::new(sb_data)?;
+        core.check_image_len(image.len())?;
+        if let Some((offset, len)) = core.compr_configs_range() {
+            core.parse_compr_configs(image.read_exact_at(offset, len)?)?;
+        }
+        if let Some((offset, len)) = core.device_table_range() {
+            core.parse_devices(image.read_exact_at(offset, len)?)?;
+        }
         Ok(Self {
             image: image.into(),
             core,
+            #[cfg(feature = "std")]
+            cache: None,
+            #[cfg(feature = "std")]
+            path_cache: None,
         })
     }
 
+    /// Creates a new `EroFS` instance that keeps up to `capacity` bytes of
+    /// decompressed data in an LRU cache.
+    ///
+    /// Compressed data is decoded a whole physical cluster at a time, so
+    /// reading a compressed file block by block without the cache decodes
+    /// each cluster once per block it spans. Uncompressed data is always
+    /// read from the image directly, so a `capacity` of `0` disables the
+    /// cache and behaves like [`new`](Self::new).
+    ///
+    /// Clones of the returned instance share the cache.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{EroFS, backend::MmapImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let image = MmapImage::new_from_path("image.erofs")?;
+    /// let fs = EroFS::with_cache(image, 16 << 20)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn with_cache(image: I, capacity: usize) -> Result<Self> {
+        let mut fs = Self::new(image)?;
+        if capacity > 0 {
+            fs.cache = Some(Arc::new(ExtentCache::new(capacity)));
+        }
+        Ok(fs)
+    }
+
+    /// Keeps up to `capacity` resolved paths in an LRU cache, so that
+    /// opening the same paths again skips walking their directories.
+    ///
+    /// Every method taking a path goes through the cache. Paths are cached
+    /// as given, so `/etc/passwd` and `/etc/./passwd` take separate entries.
+    /// A `capacity` of `0` disables the cache. Clones of the returned
+    /// instance share the cache.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{EroFS, backend::MmapImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let image = MmapImage::new_from_path("image.erofs")?;
+    /// let fs = EroFS::with_cache(image, 16 << 20)?.with_path_cache(1024);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn with_path_cache(mut self, capacity: usize) -> Self {
+        self.path_cache = (capacity > 0).then(|| Arc::new(PathCache::new(capacity)));
+        self
+    }
+
+    /// Sets whether file reads verify each decoded block against a data
+    /// checksum stored in the image, failing with
+    /// [`Error::ChecksumMismatch`] on a mismatch.
+    ///
+    /// The EROFS on-disk format only checksums the superblock (see
+    /// [`verify_superblock`](Self::verify_superblock)): neither inodes nor
+    /// compressed indexes carry checksums for file data, whose integrity is
+    /// left to dm-verity or fs-verity. On such images, which are all the
+    /// images this crate reads today, the toggle is a no-op and reads
+    /// behave the same either way. It is off by default.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{EroFS, backend::MmapImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let image = MmapImage::new_from_path("image.erofs")?;
+    /// let fs = EroFS::new(image)?.verify_data(true);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn verify_data(mut self, verify: bool) -> Self {
+        self.core.verify_data = verify;
+        self
+    }
+
+    /// Returns whether data verification was requested with
+    /// [`verify_data`](Self::verify_data).
+    pub fn verifies_data(&self) -> bool {
+        self.core.verify_data
+    }
+
     /// Recursively walks a directory tree starting from the given path.
     ///
     /// Returns an iterator that yields all entries (files and directories)
-    /// under the specified root path.
-    pub fn walk_dir<P: AsRef<UnixPath>>(&self, root: P) -> Result<WalkDir<'_, I>> {
+    /// under the specified root path. A directory that leads back to one of
+    /// its ancestors is reported as [`WalkError::Cycle`] instead of being
+    /// entered again.
+    pub fn walk_dir<P: AsUnixPath>(&self, root: P) -> Result<WalkDir<'_, I>> {
         WalkDir::new(self, root)
     }
 
+    /// Returns an iterator over every inode of the image, together with
+    /// its nid.
+    ///
+    /// Unlike [`walk_dir`](Self::walk_dir), this doesn't follow the directory
+    /// tree: it scans the metadata area in on-disk nid order, so inodes that
+    /// no directory entry refers to are found as well and no paths are built.
+    /// Slots are recognized by checking that they decode to an inode
+    /// consistent with the image, and the scan stops once the number of
+    /// inodes recorded in the superblock has been found. Images that don't
+    /// record it are scanned to the end, and data blocks of compressed or
+    /// chunk-based files may then be mistaken for inodes.
+    pub fn inodes(&self) -> Inodes<'_, I> {
+        Inodes::new(self, InodeScan::new(&self.core))
+    }
+
+    /// Returns totals over every inode of the image, see [`FsStats`].
+    ///
+    /// This is a full metadata walk in `O(inodes)`: every inode found by
+    /// [`inodes`](Self::inodes) is read, along with the chunk table or
+    /// compression map of inodes that have one. File data is neither read
+    /// nor decompressed. Hard-linked files are counted once.
+    pub fn stats(&self) -> Result<FsStats> {
+        let mut stats = FsStats::default();
+        for inode in self.inodes() {
+            let (nid, inode) = inode?;
+            let physical = self.physical_size(&inode)?;
+            if self.core.is_packed_inode(nid) {
+                stats.add_packed(physical);
+            } else {
+                stats.add(&inode, physical);
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Walks the whole image once and returns an [`Index`] of every path.
+    ///
+    /// Subsequent lookups through the index answer in `O(1)` (`O(log n)`
+    /// without the `std` feature) without touching the image, which pays
+    /// off when the same image is queried many times.
+    pub fn index(&self) -> Result<Index> {
+        let mut index = Index::default();
+        index.insert(
+            UnixPathBuf::from("/"),
+            self.core.super_block.root_nid as u64,
+            DirentFileType::Directory,
+        );
+        for entry in self.walk_dir("/")? {
+            let entry = entry?;
+            index.insert(
+                entry.dir_entry.path(),
+                entry.dir_entry.nid(),
+                entry.dir_entry.file_type(),
+            );
+        }
+        Ok(index)
+    }
+
+    /// Checks the whole image for consistency in a single walk.
+    ///
+    /// Unlike the other methods, problems don't abort the check: every one
+    /// found is recorded in the returned [`FsckReport`], which makes this
+    /// suitable for gating generated images in CI.
+    ///
+    /// Every directory entry must refer to a readable inode of the type it
+    /// records, the data of every inode reached must lie within the image
+    /// with a chunk table or compressed indexes that decode, and link
+    /// counts must match the entries found.
+    ///
+    /// # Errors
+    ///
+    /// Only fails if the root directory itself cannot be read.
+    pub fn fsck(&self) -> Result<FsckReport> {
+        let mut report = FsckReport::default();
+        report.check_features(&self.core.super_block);
+        match self.verify_superblock() {
+            Ok(()) => {}
+            Err(e @ Error::ChecksumMismatch { .. }) => {
+                report.push(FsckCode::ChecksumMismatch, "/", None, e.to_string())
+            }
+            Err(e) => report.push(FsckCode::Unreadable, "/", None, e.to_string()),
+        }
+
+        let root = self.root_inode()?;
+        let root_nid = root.id();
+        let mut links = LinkCounts::default();
+        links.visit("/", &root);
+        let ranges = self.data_ranges(&root);
+        report.check_data(&self.core, "/", &root, ranges);
+        if let Some(packed_nid) = self.core.packed_nid() {
+            match self.get_inode(packed_nid) {
+                Ok(packed) => {
+                    let ranges = self.data_ranges(&packed);
+                    report.check_data(&self.core, "/", &packed, ranges);
+                }
+                Err(e) => report.push(FsckCode::Unreadable, "/", Some(packed_nid), e.to_string()),
+            }
+        }
+
+        let mut stack = vec![(root, root_nid, UnixPathBuf::from("/"))];
+        while let Some((dir, parent_nid, dir_path)) = stack.pop() {
+            let dir_path_str = dir_path.to_string_lossy();
+            let block_count = dir.data_size().div_ceil(self.core.block_size);
+            for i in 0..block_count {
+                let dirents = self
+                    .get_inode_block(&dir, i * self.core.block_size)
+                    .and_then(|block| dirent::read_owned_dirents(&block));
+                let dirents = match dirents {
+                    Ok(dirents) => dirents,
+                    Err(e) => {
+                        report.push(
+                            FsckCode::Unreadable,
+                            &dir_path_str,
+                            Some(dir.id()),
+                            format!("directory block {}: {}", i, e),
+                        );
+                        continue;
+                    }
+                };
+
+                for (dirent, name) in dirents {
+                    let nid = dirent.nid;
+                    links.reference(nid);
+                    if report.check_dot_entry(&dir_path_str, &name, nid, dir.id(), parent_nid) {
+                        continue;
+                    }
+
+                    let path = dir_path.join(&name);
+                    let path_str = path.to_string_lossy();
+                    let inode = match self.get_inode(nid) {
+                        Ok(inode) => inode,
+                        Err(e) => {
+                            report.push(FsckCode::Unreadable, &path_str, Some(nid), e.to_string());
+                            continue;
+                        }
+                    };
+                    report.check_inode(&path_str, dirent.file_type, &inode);
+                    if !links.visit(&path_str, &inode) {
+                        if inode.is_dir() {
+                            report.push(
+                                FsckCode::DirectoryCycle,
+                                &path_str,
+                                Some(nid),
+                                "directory already reached through another path".to_string(),
+                            );
+                        }
+                        continue;
+                    }
+
+                    let ranges = self.data_ranges(&inode);
+                    report.check_data(&self.core, &path_str, &inode, ranges);
+                    if inode.is_dir() {
+                        stack.push((inode, dir.id(), path.clone()));
+                    }
+                }
+            }
+        }
+
+        links.check(&mut report);
+        Ok(report)
+    }
+
+    /// Checks the whole image for consistency, returning every problem
+    /// found.
+    ///
+    /// This is [`fsck`](Self::fsck) for callers that only need the list of
+    /// problems: an empty list means the image is consistent.
+    pub fn check(&self) -> Result<Vec<CheckError>> {
+        Ok(self.fsck()?.problems)
+    }
+
+    /// Lists every entry extracting the image would write, flagging those
+    /// that would be unsafe to write as they are.
+    ///
+    /// Only metadata is read: the walk goes through every directory from
+    /// the root, which is not listed itself, and reads symlink targets but
+    /// no file data. Entries with a name that isn't a single path
+    /// component, symlinks pointing outside of the tree, special files,
+    /// duplicate paths and directory cycles are reported in
+    /// [`ManifestEntry::issues`] instead of failing the walk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a directory or inode can't be read.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{EroFS, backend::MmapImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::new(MmapImage::new_from_path("image.erofs")?)?;
+    /// for entry in fs.manifest()? {
+    ///     if !entry.is_safe() {
+    ///         println!("{}: {:?}", entry.path.to_string_lossy(), entry.issues);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn manifest(&self) -> Result<Vec<ManifestEntry>> {
+        let mut manifest = ManifestBuilder::default();
+        for entry in self.walk_dir("/")?.resolve_symlinks(true) {
+            match entry {
+                Ok(entry) => {
+                    let path = entry.dir_entry.path();
+                    let escapes = match &entry.link_target {
+                        Some(target) => self.symlink_escapes(&path, target)?,
+                        None => false,
+                    };
+                    manifest.push(
+                        path,
+                        entry.dir_entry.file_name_bytes(),
+                        &entry.inode,
+                        entry.link_target,
+                        escapes,
+                        false,
+                    );
+                }
+                Err(Error::Walk(WalkError::Cycle { path, nid })) => {
+                    let path = UnixPathBuf::from(path);
+                    let name = path.file_name().unwrap_or_default().to_vec();
+                    let inode = self.get_inode(nid)?;
+                    manifest.push(path, &name, &inode, None, false, true);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(manifest.finish())
+    }
+
     /// Lists the immediate contents of a directory.
     ///
-    /// This is equivalent to `walk_dir` with `max_depth(1)`.
-    pub fn read_dir<P: AsRef<UnixPath>>(&self, path: P) -> Result<WalkDir<'_, I>> {
+    /// This is equivalent to `walk_dir` with `max_depth(1)`. Entries are
+    /// read from the image as they are yielded, see [`ReadDir`].
+    pub fn read_dir<P: AsUnixPath>(&self, path: P) -> Result<WalkDir<'_, I>> {
         Ok(WalkDir::new(self, path)?.max_depth(1))
     }
 
-    /// Opens a file at the given path for reading.
+    /// Lists the immediate subdirectories of a directory.
     ///
-    /// The returned [`File`] implements [`std::io::Read`].
+    /// Entries are picked by the file type recorded in the directory
+    /// itself, so unlike [`read_dir`](Self::read_dir) no inode is read for
+    /// any entry. Symlinks to directories are not included.
     ///
     /// # Errors
     ///
-    /// Returns an error if the path doesn't exist or is not a regular file.
-    pub fn open<P: AsRef<UnixPath>>(&self, path: P) -> Result<File<'_, I>> {
-        let inode = self
-            .get_path_inode(&path)?
-            .ok_or_else(|| Error::PathNotFound(path.as_ref().to_string_lossy().into_owned()))?;
+    /// Returns an error if the path doesn't exist or is not a directory.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::EroFS;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::open_path("image.erofs")?;
+    /// for dir in fs.read_subdirs("/usr")? {
+    ///     println!("{}", dir?.path().to_string_lossy());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn read_subdirs<P: AsUnixPath>(
+        &self,
+        path: P,
+    ) -> Result<impl Iterator<Item = Result<DirEntry>> + '_> {
+        let inode = self.get_path_inode(&path)?;
+        if !inode.is_dir() {
+            return Err(Error::not_a_directory(path.as_unix_path()));
+        }
 
-        self.open_inode_file(inode)
+        Ok(ReadDir::new(self, inode, path)?
+            .filter(|entry| entry.as_ref().map_or(true, DirEntry::is_dir)))
     }
 
-    /// Opens a file from an inode directly.
+    /// Lists a directory starting at `cookie`, as returned by [`ReadDir::cookie`].
     ///
-    /// This is useful when you already have an inode from directory traversal.
-    pub fn open_inode_file(&self, inode: Inode) -> Result<File<'_, I>> {
-        if !inode.is_file() {
-            return Err(Error::NotAFile(format!(
-                "inode {} is not a regular file",
-                inode.id()
-            )));
+    /// A cookie of `0` starts from the beginning. Since only the inode is
+    /// known, entry paths are relative to the listed directory. This lets paginated callers
+    /// such as FUSE `readdir` resume without rescanning the directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `dir` is not a directory.
+    pub fn read_dir_from(&self, dir: Inode, cookie: u64) -> Result<ReadDir<'_, I>> {
+        if !dir.is_dir() {
+            return Err(Error::inode_not_a_directory(dir.id()));
         }
 
-        Ok(File::new(inode, self))
+        ReadDir::new_at(self, dir, "", cookie)
     }
 
-    /// Returns a reference to the filesystem superblock.
-    pub fn super_block(&self) -> &SuperBlock {
-        &self.core.super_block
+    /// Looks up the entry called `name` in the directory with node id
+    /// `parent_nid` and returns its inode, like a FUSE `lookup`.
+    ///
+    /// `name` is a single component compared byte for byte, and symlinks
+    /// are not followed. Directories store `.` and `..` entries, so those
+    /// resolve to the directory itself and its parent.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NotADirectory`] if `parent_nid` is not a directory
+    /// and [`Error::NotFound`] if it has no entry called `name`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{EroFS, backend::MmapImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::new(MmapImage::new_from_path("image.erofs")?)?;
+    /// let etc = fs.lookup(fs.root_inode()?.id(), "etc")?;
+    /// let passwd = fs.lookup(etc.id(), "passwd")?;
+    /// for entry in fs.readdir_at(etc.id())? {
+    ///     println!("{} {}", entry?.nid(), passwd.data_size());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn lookup<N: AsRef<[u8]>>(&self, parent_nid: u64, name: N) -> Result<Inode> {
+        let name = name.as_ref();
+        let dir = self.inode_at(parent_nid)?;
+        if !dir.is_dir() {
+            return Err(Error::inode_not_a_directory(parent_nid));
+        }
+        let dirent = self
+            .lookup_child(&dir, name)?
+            .ok_or_else(|| Error::child_not_found(parent_nid, name))?;
+        self.inode_at(dirent.nid)
     }
 
-    pub(crate) fn block_size(&self) -> usize {
-        self.core.block_size
+    /// Lists the directory with node id `nid`, like a FUSE `readdir`.
+    ///
+    /// This is [`read_dir_from`](Self::read_dir_from) from the start, so
+    /// entry paths are relative to the directory. To resume a listing, pass
+    /// the [`ReadDir::cookie`] to `read_dir_from` instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `nid` is not a directory.
+    pub fn readdir_at(&self, nid: u64) -> Result<ReadDir<'_, I>> {
+        self.read_dir_from(self.inode_at(nid)?, 0)
     }
 
-    pub fn get_inode(&self, nid: u64) -> Result<Inode> {
-        let offset = self.core.get_inode_offset(nid) as usize;
-        let data = self
-            .image
-            .get(offset..)
-            .ok_or_else(|| Error::OutOfBounds("failed to read inode format".to_string()))?;
-        self.core.parse_inode(data, nid)
+    /// Returns the dirent array of block `block_index` of the directory
+    /// `dir`, as stored on disk.
+    ///
+    /// This is a lower-level view than [`read_dir`](Self::read_dir), meant
+    /// for diagnosing the layout of a directory: every dirent of the block
+    /// is returned in order, including `.` and `..`, with its raw
+    /// [`name_off`](Dirent::name_off), nid and file type. The number of
+    /// entries is the first name offset divided by [`Dirent::size`]. Names
+    /// are neither read nor validated, so this also works on blocks that
+    /// `read_dir` rejects as corrupt.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `dir` is not a directory, or if `block_index` is
+    /// past its last block.
+    pub fn dir_block_entries(&self, dir: &Inode, block_index: usize) -> Result<Vec<Dirent>> {
+        if !dir.is_dir() {
+            return Err(Error::inode_not_a_directory(dir.id()));
+        }
+
+        let block = self.get_inode_block(dir, block_index * self.core.block_size)?;
+        dirent::read_raw_dirents(&block)
     }
 
-    pub(crate) fn get_inode_block(&self, inode: &Inode, offset: usize) -> Result<&[u8]> {
-        match self.core.plan_inode_block_read(inode, offset)? {
-            BlockPlan::Direct { offset, size } => self
-                .image
-                .get(offset..offset + size)
-                .ok_or_else(|| Error::OutOfBounds("failed to get inode data".to_string())),
-            BlockPlan::Chunked {
-                addr_offset,
-                chunk_fixed,
-                chunk_size,
-                data_size,
-                chunk_index,
-            } => {
-                let chunk_addr = self
-                    .image
-                    .get(addr_offset..addr_offset + 4)
-                    .ok_or_else(|| Error::OutOfBounds("failed to get chunk address".to_string()))?
-                    .get_i32_le();
+    /// Opens a file at the given path for reading.
+    ///
+    /// Symlinks are followed, including the last component. The returned
+    /// [`File`] implements [`std::io::Read`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path doesn't exist, a symlink on it is
+    /// dangling, it goes through more than [`MAX_SYMLINK_FOLLOWS`] symlinks,
+    /// or it is not a regular file.
+    ///
+    /// [`MAX_SYMLINK_FOLLOWS`]: crate::MAX_SYMLINK_FOLLOWS
+    pub fn open<P: AsUnixPath>(&self, path: P) -> Result<File<'_, I>> {
+        let inode = self.get_path_inode(&path)?;
 
-                let (offset, size) = self.core.resolve_chunk_read(
-                    chunk_addr,
-                    chunk_fixed,
-                    chunk_size,
-                    data_size,
-                    chunk_index,
-                )?;
-                self.image
-                    .get(offset..offset + size)
-                    .ok_or_else(|| Error::OutOfBounds("failed to get inode data".to_string()))
-            }
-        }
+        let file = self.open_inode_file(inode)?;
+        Ok(file.with_path(path.as_unix_path().to_path_buf()))
     }
 
-    pub(crate) fn get_path_inode<P: AsRef<UnixPath>>(&self, path: P) -> Result<Option<Inode>> {
-        let mut nid = self.core.super_block.root_nid as u64;
+    /// Opens a file like [`open`](Self::open), without following a symlink
+    /// in the last component.
+    pub fn open_nofollow<P: AsUnixPath>(&self, path: P) -> Result<File<'_, I>> {
+        let (nid, _) = self.lookup_path(&path, false)?;
 
-        let path = path.as_ref().normalize();
-        'outer: for part in path.components() {
-            if part == UnixComponent::RootDir {
-                continue;
-            }
+        let file = self.open_inode_file(self.get_inode(nid)?)?;
+        Ok(file.with_path(path.as_unix_path().to_path_buf()))
+    }
 
-            let inode = self.get_inode(nid)?;
-            let block_count = inode.data_size().div_ceil(self.core.block_size);
-            if block_count == 0 {
-                return Ok(None);
-            }
+    /// Opens a file like [`open`](Self::open), comparing the names on
+    /// `path` case-insensitively.
+    ///
+    /// This is meant for images made from case-insensitive filesystems.
+    /// Names are compared by the lowercase mapping of their characters, or
+    /// of their ASCII letters if either one isn't valid UTF-8. Symlink
+    /// targets are resolved the same way.
+    ///
+    /// Directory entries are sorted by their exact names, so instead of
+    /// binary-searching each directory on the way, this reads all of its
+    /// entries. Lookups take time linear in the size of the directories,
+    /// and the path cache isn't used.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::AmbiguousPath`] if a name on `path` matches several
+    /// entries of its directory, and otherwise the same errors as
+    /// [`open`](Self::open).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::EroFS;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::open_path("image.erofs")?;
+    /// let file = fs.open_ci("/Windows/System32/DRIVERS/etc/HOSTS")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn open_ci<P: AsUnixPath>(&self, path: P) -> Result<File<'_, I>> {
+        let inode = self.get_path_inode_ci(&path)?;
+        let file = self.open_inode_file(inode)?;
+        Ok(file.with_path(path.as_unix_path().to_path_buf()))
+    }
 
-            for i in 0..block_count {
-                let block = self.get_inode_block(&inode, i * self.core.block_size)?;
-                if let Some(found_nid) = dirent::find_nodeid_by_name(part.as_bytes(), block)? {
-                    nid = found_nid;
-                    continue 'outer;
-                }
-            }
-            return Ok(None);
-        }
+    /// Reads the whole file at `path` into memory, following symlinks like
+    /// [`open`](Self::open).
+    ///
+    /// The buffer is sized up front from the inode, and the file's data is
+    /// decoded straight into it, each compressed physical cluster once.
+    /// Sizes that can't be allocated fail with [`Error::OutOfMemory`]
+    /// instead of aborting.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`open`](Self::open), and any error hit
+    /// while reading the data.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{EroFS, backend::MmapImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::new(MmapImage::new_from_path("image.erofs")?)?;
+    /// let passwd = fs.read("/etc/passwd")?;
+    /// let os_release = fs.read_to_string("/etc/os-release")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn read<P: AsUnixPath>(&self, path: P) -> Result<Bytes> {
+        let file = self.open(path)?;
+        let mut data = EroFSCore::data_buffer(file.size())?;
+        self.read_inode_at(file.inode(), 0, &mut data)?;
+        Ok(Bytes::from(data))
+    }
 
-        let inode = self.get_inode(nid)?;
-        Ok(Some(inode))
+    /// Reads the first `n` bytes of the file at `path`, or all of it if it
+    /// is shorter, following symlinks like [`open`](Self::open).
+    ///
+    /// Only the blocks holding those bytes are read or decompressed, so
+    /// this is cheap enough to sniff the magic numbers of every file of an
+    /// image. The head of a file stored inline is read from right after
+    /// its inode.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`read`](Self::read).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{EroFS, backend::MmapImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::new(MmapImage::new_from_path("image.erofs")?)?;
+    /// for file in fs.walk_dir("/")? {
+    ///     let entry = file?;
+    ///     if entry.inode.is_file() && fs.read_head(entry.dir_entry.path(), 4)? == b"\x7fELF"[..] {
+    ///         println!("{}", entry.dir_entry.path().to_string_lossy());
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn read_head<P: AsUnixPath>(&self, path: P, n: usize) -> Result<Bytes> {
+        let file = self.open(path)?;
+        let mut data = EroFSCore::data_buffer(n.min(file.size()))?;
+        self.read_inode_at(file.inode(), 0, &mut data)?;
+        Ok(Bytes::from(data))
+    }
+
+    /// Returns an iterator over the data blocks of `inode`, each as owned
+    /// [`Bytes`].
+    ///
+    /// The iterator keeps its own handle to the filesystem instead of
+    /// borrowing `self`, so blocks can be streamed from another thread while
+    /// they are decoded. To get the whole file at once, use
+    /// [`read`](Self::read).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{EroFS, backend::MmapImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::new(MmapImage::new_from_path("image.erofs")?)?;
+    /// let inode = *fs.metadata("/usr/bin/bash")?.inode();
+    /// let blocks = fs.blocks(&inode);
+    /// let handle = std::thread::spawn(move || {
+    ///     blocks.map(|block| block.map(|block| block.len())).sum::<Result<usize, _>>()
+    /// });
+    /// assert_eq!(handle.join().unwrap()?, inode.data_size());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn blocks(&self, inode: &Inode) -> Blocks<I> {
+        Blocks::new(self.clone(), *inode)
+    }
+
+    /// Reads the whole file at `path` into a string, like
+    /// [`read`](Self::read).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidUtf8`] if the contents are not valid UTF-8,
+    /// in addition to the errors of [`read`](Self::read).
+    pub fn read_to_string<P: AsUnixPath>(&self, path: P) -> Result<String> {
+        let data = Vec::from(self.read(path)?);
+        String::from_utf8(data).map_err(|e| Error::InvalidUtf8(e.utf8_error()))
+    }
+
+    /// Returns the absolute path `path` resolves to, with `.` and `..`
+    /// applied and every symlink on it followed, including the last
+    /// component.
+    ///
+    /// Relative paths and symlink targets are taken from the root, and the
+    /// result only goes through directories that exist in the image, so it
+    /// can't point outside of it. This makes it suitable for checking where
+    /// an entry of an untrusted image would be written when extracting it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NotFound`] if a component doesn't exist,
+    /// [`Error::NotADirectory`] if a component other than the last is not a
+    /// directory, [`Error::PathEscapesRoot`] if a `..` goes above the root,
+    /// and [`Error::SymlinkLoop`] if more than [`MAX_SYMLINK_FOLLOWS`]
+    /// symlinks are followed.
+    ///
+    /// [`MAX_SYMLINK_FOLLOWS`]: crate::MAX_SYMLINK_FOLLOWS
+    pub fn canonicalize<P: AsUnixPath>(&self, path: P) -> Result<UnixPathBuf> {
+        self.walk_path(path.as_unix_path(), true, false)?
+            .canonical_path()
+    }
+
+    /// Returns the target of the symlink at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path doesn't exist or is not a symlink.
+    pub fn read_link<P: AsUnixPath>(&self, path: P) -> Result<UnixPathBuf> {
+        let (nid, _) = self.lookup_path(&path, false)?;
+
+        let inode = self.get_inode(nid)?;
+        if !inode.is_symlink() {
+            return Err(Error::NotASymlink(
+                path.as_unix_path().to_string_lossy().into_owned(),
+            ));
+        }
+        self.read_inode_link(&inode)
+    }
+
+    /// Returns the target of a symlink inode.
+    ///
+    /// The target is stored as the inode's data, so this is cheap for the
+    /// usual short, inline targets.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::CorruptedData`] if the target is longer than
+    /// `PATH_MAX` (4096 bytes), which no valid image stores.
+    pub fn read_inode_link(&self, inode: &Inode) -> Result<UnixPathBuf> {
+        if !inode.is_symlink() {
+            return Err(Error::NotASymlink(format!("inode {}", inode.id())));
+        }
+        self.core.check_symlink_size(inode)?;
+
+        let mut target = Vec::with_capacity(inode.data_size());
+        let block_count = inode.data_size().div_ceil(self.core.block_size);
+        for i in 0..block_count {
+            target.extend_from_slice(&self.get_inode_block(inode, i * self.core.block_size)?);
+        }
+        Ok(UnixPathBuf::from(target))
+    }
+
+    /// Returns the metadata of the entry at `path`, following symlinks.
+    ///
+    /// Unlike [`open`](Self::open) this works for every kind of entry.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path doesn't exist.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{EroFS, backend::MmapImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::new(MmapImage::new_from_path("image.erofs")?)?;
+    /// let metadata = fs.metadata("/dev/null")?;
+    /// println!("{:?} {:o} {}:{}", metadata.file_type(), metadata.mode(), metadata.uid(), metadata.gid());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn metadata<P: AsUnixPath>(&self, path: P) -> Result<Metadata> {
+        let inode = self.get_path_inode(&path)?;
+        Ok(Metadata::new(inode))
+    }
+
+    /// Returns the metadata of the entry at `path` without following a
+    /// symlink in the last component.
+    pub fn symlink_metadata<P: AsUnixPath>(&self, path: P) -> Result<Metadata> {
+        let (nid, _) = self.lookup_path(&path, false)?;
+        Ok(Metadata::new(self.get_inode(nid)?))
+    }
+
+    /// Returns the metadata of the entry at `path` like
+    /// [`metadata`](Self::metadata), comparing the names on `path`
+    /// case-insensitively as [`open_ci`](Self::open_ci) does.
+    pub fn metadata_ci<P: AsUnixPath>(&self, path: P) -> Result<Metadata> {
+        Ok(Metadata::new(self.get_path_inode_ci(&path)?))
+    }
+
+    /// Returns the file type of the entry at `path`.
+    ///
+    /// The type is taken from the parent's directory entry, so the target
+    /// inode is only parsed for the root or when the entry type is unknown.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path doesn't exist.
+    pub fn file_type<P: AsUnixPath>(&self, path: P) -> Result<DirentFileType> {
+        let (nid, file_type) = self.lookup_path(&path, false)?;
+
+        match file_type {
+            Some(file_type) if file_type != DirentFileType::Unknown => Ok(file_type),
+            _ => Ok(self.get_inode(nid)?.file_type().into()),
+        }
+    }
+
+    /// Returns `true` if `path` points at an existing entry, following
+    /// symlinks like [`open`](Self::open).
+    ///
+    /// Errors are treated as the entry not existing, the same as
+    /// [`std::path::Path::exists`]. Use [`metadata`](Self::metadata) to tell
+    /// them apart.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{EroFS, backend::MmapImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::new(MmapImage::new_from_path("image.erofs")?)?;
+    /// if fs.exists("/etc/os-release") && !fs.is_dir("/etc/os-release") {
+    ///     println!("found os-release");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn exists<P: AsUnixPath>(&self, path: P) -> bool {
+        self.metadata(path).is_ok()
+    }
+
+    /// Returns `true` if `path` points at a directory, following symlinks.
+    ///
+    /// Errors are treated as `false`, like [`exists`](Self::exists).
+    pub fn is_dir<P: AsUnixPath>(&self, path: P) -> bool {
+        self.metadata(path).is_ok_and(|metadata| metadata.is_dir())
+    }
+
+    /// Returns `true` if `path` points at a regular file, following
+    /// symlinks.
+    ///
+    /// Errors are treated as `false`, like [`exists`](Self::exists).
+    pub fn is_file<P: AsUnixPath>(&self, path: P) -> bool {
+        self.metadata(path).is_ok_and(|metadata| metadata.is_file())
+    }
+
+    /// Opens the regular file with node id `nid`.
+    ///
+    /// See [`inode_at`](Self::inode_at) for how `nid` is validated.
+    pub fn open_inode_by_nid(&self, nid: u64) -> Result<File<'_, I>> {
+        self.open_inode_file(self.inode_at(nid)?)
+    }
+
+    /// Opens a file from an inode directly.
+    ///
+    /// This is useful when you already have an inode from directory traversal.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::IsASymlink`] for a symlink, whose target is read
+    /// with [`read_inode_link`](Self::read_inode_link) instead, and
+    /// [`Error::NotARegularFile`] for any other inode that isn't a regular
+    /// file.
+    pub fn open_inode_file(&self, inode: Inode) -> Result<File<'_, I>> {
+        if inode.is_symlink() {
+            return Err(Error::IsASymlink(format!("inode {}", inode.id())));
+        } else if !inode.is_file() {
+            return Err(Error::NotARegularFile(format!("inode {}", inode.id())));
+        }
+
+        Ok(File::new(inode, self))
+    }
+
+    /// Returns a reference to the filesystem superblock.
+    pub fn super_block(&self) -> &SuperBlock {
+        &self.core.super_block
+    }
+
+    /// Returns the time the image was built, as recorded in the superblock.
+    ///
+    /// Compact inodes carry no timestamp of their own, and the kernel reports
+    /// this time for them instead. Returns `None` if the time can't be
+    /// represented as a `SystemTime`.
+    #[cfg(feature = "std")]
+    pub fn build_time(&self) -> Option<std::time::SystemTime> {
+        let sb = &self.core.super_block;
+        system_time(sb.build_time, sb.build_time_ns)
+    }
+
+    /// Returns the time the image was built as `(seconds, nanoseconds)`
+    /// since the Unix epoch, as recorded in the superblock.
+    ///
+    /// Compact inodes carry no timestamp of their own, and the kernel reports
+    /// this time for them instead.
+    #[cfg(not(feature = "std"))]
+    pub fn build_time(&self) -> (u64, u32) {
+        let sb = &self.core.super_block;
+        (sb.build_time, sb.build_time_ns)
+    }
+
+    /// Verifies the superblock checksum.
+    ///
+    /// Images without the `sb_csum` feature carry no checksum and always
+    /// pass. Verification is opt-in so opening an image stays cheap.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ChecksumMismatch`] if the checksum doesn't match.
+    pub fn verify_superblock(&self) -> Result<()> {
+        let Some((offset, len)) = self.core.superblock_checksum_range() else {
+            return Ok(());
+        };
+        let data = self.image.read_exact_at(offset, len)?;
+        self.core.verify_superblock_checksum(data)
+    }
+
+    /// Returns every compression algorithm needed to fully read this image.
+    ///
+    /// The set is derived from the superblock, so it is cheap enough to be
+    /// used as a preflight check right after opening the image. An empty set
+    /// means the image contains no compressed data.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the superblock declares an unknown algorithm.
+    pub fn required_algorithms(&self) -> Result<BTreeSet<CompressionAlgo>> {
+        self.core.required_algorithms()
+    }
+
+    /// Returns the compression algorithm and logical cluster size of
+    /// `inode`, or `None` if its data is not compressed.
+    ///
+    /// Only the inode's map header is read, so this works whether or not
+    /// the decompressor for the algorithm is enabled. A file stored as a
+    /// whole in the packed inode reports the compression of the packed
+    /// inode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the map header can't be read or names an unknown
+    /// algorithm.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{EroFS, backend::MmapImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::new(MmapImage::new_from_path("image.erofs")?)?;
+    /// for entry in fs.walk_dir("/")? {
+    ///     let entry = entry?;
+    ///     if let Some(compression) = fs.compression(&entry.inode)? {
+    ///         println!(
+    ///             "{}: {} in {}-byte clusters",
+    ///             entry.dir_entry.path().to_string_lossy(),
+    ///             compression.algorithm.name(),
+    ///             compression.lcluster_size()
+    ///         );
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn compression(&self, inode: &Inode) -> Result<Option<Compression>> {
+        let Some(map_offset) = self.core.map_header_offset(inode) else {
+            return Ok(None);
+        };
+        let header = self.image.read_exact_at(map_offset, MapHeader::size())?;
+        if let Some(compression) = ZMap::compression(&self.core, header)? {
+            return Ok(Some(compression));
+        }
+
+        let packed_nid = self.core.super_block.packed_nid;
+        if inode.id() == packed_nid {
+            return Err(Error::CorruptedData(format!(
+                "packed inode {} is stored in itself",
+                packed_nid
+            )));
+        }
+        self.compression(&self.inode_at(packed_nid)?)
+    }
+
+    /// Returns where the image stores the data of `inode`, in file order.
+    ///
+    /// This is the mapping reads go through, without decoding any data:
+    /// one extent per run of blocks of a flat inode and its inline tail,
+    /// per chunk of a chunk-based one and per physical cluster of a
+    /// compressed one. Files sharing on-disk data have extents starting at
+    /// the same block. Holes have no extent, and neither have tails stored
+    /// as fragments of the packed inode.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the chunk or compressed mapping can't be decoded.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{EroFS, backend::MmapImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::new(MmapImage::new_from_path("image.erofs")?)?;
+    /// let inode = fs.get_inode(fs.metadata("/etc/passwd")?.nid())?;
+    /// for extent in fs.extents(&inode)? {
+    ///     println!(
+    ///         "{}+{} -> device {} block {}",
+    ///         extent.logical_offset, extent.logical_len, extent.device, extent.phys_block
+    ///     );
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extents(&self, inode: &Inode) -> Result<Vec<PhysicalExtent>> {
+        match inode.layout()? {
+            Layout::FlatPlain | Layout::FlatInline => self.core.flat_extents(inode),
+            Layout::ChunkBased => {
+                self.core.check_chunk_format(inode)?;
+                let (table_offset, addr_len, chunk_size) = self.core.chunk_table(inode);
+                let mut extents = Vec::new();
+                for i in 0..inode.data_size().div_ceil(chunk_size) {
+                    let addr = self
+                        .image
+                        .read_exact_at(table_offset + i * addr_len, addr_len)?;
+                    extents.extend(self.core.chunk_extent(inode, i, addr, self.image.len())?);
+                }
+                Ok(extents)
+            }
+            Layout::CompressedFull | Layout::CompressedCompact => {
+                let zmap = self.inode_zmap(inode)?;
+                Ok(zmap
+                    .extents(0, inode.data_size())?
+                    .iter()
+                    .filter(|extent| extent.format != ExtentFormat::Fragment)
+                    .map(|extent| self.core.zmap_extent(extent))
+                    .collect())
+            }
+        }
+    }
+
+    /// Returns the chunk index table of a chunk-based `inode`, one entry per
+    /// chunk in file order.
+    ///
+    /// Entries are reported as stored, without checking that their blocks
+    /// lie within the image or its devices; use
+    /// [`extents`](Self::extents) for the mapping reads go through. Chunk
+    /// `i` covers the file bytes starting at `i` times the chunk size, and
+    /// chunks shared by deduplicated files have equal entries. Inodes with
+    /// any other layout have no chunk index and yield no entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the chunk format of `inode` isn't supported.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{ChunkRef, EroFS, backend::MmapImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::new(MmapImage::new_from_path("image.erofs")?)?;
+    /// let inode = fs.get_inode(fs.metadata("/data.bin")?.nid())?;
+    /// for (i, chunk) in fs.chunks(&inode)?.enumerate() {
+    ///     match chunk? {
+    ///         ChunkRef::Hole => println!("chunk {i}: hole"),
+    ///         ChunkRef::Mapped { device, block } => {
+    ///             println!("chunk {i}: device {device} block {block}")
+    ///         }
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn chunks(&self, inode: &Inode) -> Result<Chunks<'_, I>> {
+        Ok(Chunks::new(self, ChunkScan::new(&self.core, inode)?))
+    }
+
+    /// Returns the extra devices (blobs) this image expects besides itself,
+    /// as recorded in its device table.
+    ///
+    /// The list is empty for single-device images. Chunks stored on an extra
+    /// device can only be read when the device is mapped into the image's
+    /// block address space; otherwise reading them fails with
+    /// [`Error::UnsupportedFeature`].
+    pub fn devices(&self) -> &[DeviceSlot] {
+        &self.core.devices
+    }
+
+    /// Returns the extended attributes of `inode`.
+    ///
+    /// Both inline and shared attributes are decoded; an inode without
+    /// xattrs yields an empty iterator.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the xattr region is corrupted or uses long name
+    /// prefixes, which are not supported yet.
+    pub fn xattrs(&self, inode: &Inode) -> Result<Xattrs> {
+        let Some((offset, size)) = self.core.xattr_range(inode) else {
+            return Ok(Xattrs::default());
+        };
+        let data = self.image.read_exact_at(offset, size)?;
+        let (shared, mut xattrs) = xattr::parse_inline(data)?;
+
+        for id in shared {
+            xattrs.push(self.shared_xattr(id)?);
+        }
+        Ok(Xattrs::new(xattrs))
+    }
+
+    /// Returns the IDs of the shared extended attributes `inode` refers to,
+    /// in the order they are stored.
+    ///
+    /// Inodes with identical attributes, such as the same SELinux label,
+    /// refer to the same entry of the shared xattr pool, which
+    /// [`shared_xattr`](Self::shared_xattr) decodes.
+    pub fn shared_xattr_ids(&self, inode: &Inode) -> Result<Vec<u32>> {
+        let Some((offset, size)) = self.core.xattr_range(inode) else {
+            return Ok(Vec::new());
+        };
+        xattr::parse_shared_ids(self.image.read_exact_at(offset, size)?)
+    }
+
+    /// Decodes the entry `id` of the shared xattr pool into a
+    /// `(name, value)` pair.
+    pub fn shared_xattr(&self, id: u32) -> Result<(String, Bytes)> {
+        let offset = self.core.shared_xattr_offset(id);
+        let header = self.image.read_exact_at(offset, XattrEntry::size())?;
+        let len = xattr::entry_len(header)?;
+        xattr::parse_entry(self.image.read_exact_at(offset, len)?)
+    }
+
+    /// Returns an iterator over the entries of the shared xattr pool, by ID.
+    ///
+    /// Entries are decoded lazily, in the order they are stored. The
+    /// superblock doesn't record where the pool ends, so the iterator stops
+    /// at the zeros padding it to the end of its block, at the start of the
+    /// inodes if they follow it, or at the end of the image. An entry that
+    /// can't be read ends the iteration after its error, while one that
+    /// can't be decoded is skipped over after its own.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::EroFS;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::open_path("system.erofs")?;
+    /// for entry in fs.shared_xattrs() {
+    ///     let (id, (name, value)) = entry?;
+    ///     println!("{id}: {name}={}", String::from_utf8_lossy(&value));
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn shared_xattrs(&self) -> impl Iterator<Item = Result<(u32, (String, Bytes))>> + '_ {
+        let mut scan = SharedXattrScan::new(&self.core);
+        core::iter::from_fn(move || {
+            let (id, offset) = scan.next_entry(&self.core)?;
+            let entry = self
+                .image
+                .read_exact_at(offset, XattrEntry::size())
+                .and_then(|header| scan.accept(header))
+                .and_then(|len| {
+                    len.map(|len| self.image.read_exact_at(offset, len))
+                        .transpose()
+                });
+            match entry {
+                Ok(Some(entry)) => Some(xattr::parse_entry(entry).map(|xattr| (id, xattr))),
+                Ok(None) => None,
+                Err(e) => {
+                    scan.finish();
+                    Some(Err(e))
+                }
+            }
+        })
+    }
+
+    /// Returns the value of the extended attribute `name` of `inode`, or
+    /// `None` if it is not set.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{EroFS, backend::MmapImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::new(MmapImage::new_from_path("system.erofs")?)?;
+    /// for entry in fs.walk_dir("/system/bin")? {
+    ///     let entry = entry?;
+    ///     if let Some(label) = fs.get_xattr(&entry.inode, "security.selinux")? {
+    ///         println!("{} {}", entry.dir_entry.file_name(), String::from_utf8_lossy(&label));
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_xattr(&self, inode: &Inode, name: &str) -> Result<Option<Bytes>> {
+        Ok(self
+            .xattrs(inode)?
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value))
+    }
+
+    /// Returns the block size of the image in bytes, between 512 bytes and
+    /// 64 KiB.
+    pub fn block_size(&self) -> usize {
+        self.core.block_size
+    }
+
+    /// Returns the base-2 logarithm of the [block size](Self::block_size),
+    /// between 9 and 16.
+    pub fn block_size_bits(&self) -> u8 {
+        self.core.super_block.blk_size_bits
+    }
+
+    /// Returns the length of the image in bytes, as reported by the
+    /// backend.
+    pub fn image_len(&self) -> u64 {
+        self.image.len()
+    }
+
+    /// Returns the number of blocks in the image, counting a partial last
+    /// block.
+    pub fn block_count(&self) -> u64 {
+        self.core.block_count(self.image.len())
+    }
+
+    /// Returns `true` if block `blkaddr` starts within the image.
+    ///
+    /// Block addresses found in the image, such as chunk indexes or the
+    /// addresses in the superblock, are validated with this before they
+    /// are read. Blocks of extra devices are addressed after they are
+    /// mapped into the image.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::EroFS;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::open_path("image.erofs")?;
+    /// assert!(fs.block_address_valid(0));
+    /// assert!(!fs.block_address_valid(fs.block_count()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn block_address_valid(&self, blkaddr: u64) -> bool {
+        self.core.block_address_valid(blkaddr, self.image.len())
+    }
+
+    /// Reads the inode of the root directory, whose nid is recorded in the
+    /// superblock.
+    ///
+    /// This is the entry point for traversing the image by hand with
+    /// [`inode_at`](Self::inode_at) and [`read_dir_from`](Self::read_dir_from).
+    pub fn root_inode(&self) -> Result<Inode> {
+        self.inode_at(self.core.super_block.root_nid as u64)
+    }
+
+    /// Lists the root directory, like `read_dir_from(root_inode, 0)`.
+    ///
+    /// Entry paths are relative to the root.
+    pub fn root_dir(&self) -> Result<ReadDir<'_, I>> {
+        let root = self.root_inode()?;
+        self.read_dir_from(root, 0)
+    }
+
+    /// Returns the packed inode, which holds the tails of files stored as
+    /// fragments, or `None` if the image has none.
+    ///
+    /// The packed inode is a regular file that no directory refers to. Its
+    /// node id is recorded in the superblock, and is only meaningful when
+    /// the image uses the fragments feature.
+    pub fn fragment_inode(&self) -> Result<Option<Inode>> {
+        self.core
+            .packed_nid()
+            .map(|nid| self.inode_at(nid))
+            .transpose()
+    }
+
+    /// Reads `len` bytes at `offset` of the data of the packed inode, or
+    /// returns `None` if the image has none, see
+    /// [`fragment_inode`](Self::fragment_inode).
+    ///
+    /// Offsets are those fragment references use, so this reads back the
+    /// tail of a file stored as a fragment without going through the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::OutOfRange`] if the range extends past the end of
+    /// the packed inode, and otherwise fails like reading a file.
+    pub fn read_packed(&self, offset: usize, len: usize) -> Result<Option<Vec<u8>>> {
+        let Some(packed) = self.fragment_inode()? else {
+            return Ok(None);
+        };
+        self.core.check_packed_range(&packed, offset, len)?;
+        let mut data = vec![0u8; len];
+        File::new(packed, self).read_at(offset, &mut data)?;
+        Ok(Some(data))
+    }
+
+    /// Same as [`inode_at`](Self::inode_at).
+    pub fn get_inode(&self, nid: u64) -> Result<Inode> {
+        self.inode_at(nid)
+    }
+
+    /// Reads the inode with node id `nid` directly, bypassing path lookup.
+    ///
+    /// This allows following on-disk references such as
+    /// [`DirEntry::nid`](crate::DirEntry::nid) by hand.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidInode`] if `nid` does not point to an inode
+    /// within the image.
+    pub fn inode_at(&self, nid: u64) -> Result<Inode> {
+        let offset = self.core.inode_offset(nid)?;
+        let data = self
+            .get_inode_data(offset)
+            .ok_or(Error::InvalidInode(nid))?;
+        self.core.parse_inode(data, nid)
+    }
+
+    /// Gets the bytes at `offset` that may hold an inode, which are fewer
+    /// than an extended inode needs at the very end of the image.
+    fn get_inode_data(&self, offset: usize) -> Option<&[u8]> {
+        if let Some(data) = self.image.get_array::<{ InodeExtended::size() }>(offset) {
+            return Some(data);
+        }
+        let image_len = usize::try_from(self.image.len()).unwrap_or(usize::MAX);
+        self.image.get(offset..image_len)
+    }
+
+    /// Returns the next inode found by `scan`, see [`inodes`](Self::inodes).
+    pub(crate) fn next_scanned_inode(&self, scan: &mut InodeScan) -> Option<Result<(u64, Inode)>> {
+        while let Some((nid, offset)) = scan.next_slot(&self.core) {
+            let inode = self
+                .get_inode_data(offset)
+                .and_then(|data| self.core.parse_inode(data, nid).ok())
+                .filter(|inode| self.core.is_plausible_inode(inode));
+            let Some(inode) = inode else {
+                scan.skip();
+                continue;
+            };
+
+            let map_header = self
+                .core
+                .map_header_offset(&inode)
+                .and_then(|offset| self.image.get_array::<{ MapHeader::size() }>(offset))
+                .map_or(&[][..], |header| header);
+            let meta_len = self
+                .core
+                .inode_meta_len(&inode, map_header)
+                // Resume right after the inode and its xattrs if the data
+                // following them can't be sized, e.g. a corrupted map header.
+                .unwrap_or_else(|_| inode.size() + inode.xattr_len());
+            scan.accept(&self.core, &inode, meta_len);
+            return Some(Ok((nid, inode)));
+        }
+        None
+    }
+
+    /// Returns the next entry walked by `scan`, see [`chunks`](Self::chunks).
+    pub(crate) fn next_chunk(&self, scan: &mut ChunkScan) -> Option<Result<ChunkRef>> {
+        let (offset, len) = scan.next_entry()?;
+        Some(
+            self.image
+                .read_exact_at(offset, len)
+                .and_then(|addr| self.core.chunk_ref(addr)),
+        )
+    }
+
+    /// Bytes of the image taken up by the data of `inode`, see
+    /// [`FsStats::physical_bytes`].
+    fn physical_size(&self, inode: &Inode) -> Result<u64> {
+        Ok(self.data_ranges(inode)?.iter().map(|(_, len)| len).sum())
+    }
+
+    /// Image byte ranges holding the data of `inode`, as `(offset, len)`
+    /// pairs. Fragments stored in the packed inode are left out. Fails if
+    /// the chunk or compressed mapping can't be decoded.
+    fn data_ranges(&self, inode: &Inode) -> Result<Vec<(u64, u64)>> {
+        match inode.layout()? {
+            Layout::FlatPlain | Layout::FlatInline => self.core.flat_data_ranges(inode),
+            Layout::ChunkBased => {
+                self.core.check_chunk_format(inode)?;
+                let (table_offset, addr_len, chunk_size) = self.core.chunk_table(inode);
+                let mut ranges = Vec::new();
+                for (i, start) in (0..inode.data_size()).step_by(chunk_size).enumerate() {
+                    let addr = self
+                        .image
+                        .read_exact_at(table_offset + i * addr_len, addr_len)?;
+                    if let Some(block) =
+                        self.core
+                            .chunk_block(inode.id(), i, addr, 0, self.image.len())?
+                    {
+                        let len = (inode.data_size() - start).min(chunk_size);
+                        ranges.push((
+                            block << self.core.super_block.blk_size_bits,
+                            len.next_multiple_of(self.core.block_size) as u64,
+                        ));
+                    }
+                }
+                Ok(ranges)
+            }
+            Layout::CompressedFull | Layout::CompressedCompact => {
+                let zmap = self.inode_zmap(inode)?;
+                Ok(zmap
+                    .extents(0, inode.data_size())?
+                    .iter()
+                    .filter(|extent| extent.format != ExtentFormat::Fragment)
+                    .map(|extent| (extent.pa as u64, extent.plen as u64))
+                    .collect())
+            }
+        }
+    }
+
+    pub(crate) fn get_inode_block(&self, inode: &Inode, offset: usize) -> Result<Cow<'_, [u8]>> {
+        match self.core.plan_inode_block_read(inode, offset)? {
+            BlockPlan::Direct { offset, size } => self.read_data(offset, size),
+            BlockPlan::Chunked {
+                addr_offset,
+                addr_len,
+                chunk_fixed,
+                chunk_size,
+                data_size,
+                chunk_index,
+            } => {
+                let chunk_addr = self.image.read_exact_at(addr_offset, addr_len)?;
+                let chunk_block = self.core.chunk_block(
+                    inode.id(),
+                    chunk_index,
+                    chunk_addr,
+                    chunk_fixed,
+                    self.image.len(),
+                )?;
+
+                let (offset, size) = self.core.resolve_chunk_read(
+                    chunk_block,
+                    chunk_fixed,
+                    chunk_size,
+                    data_size,
+                    chunk_index,
+                )?;
+                let Some(offset) = offset else {
+                    return Ok(Cow::Owned(vec![0u8; size]));
+                };
+                self.read_data(offset, size)
+            }
+            BlockPlan::Compressed {
+                map_offset,
+                start,
+                size,
+            } => {
+                let zmap = self.zmap(inode, map_offset)?;
+                let mut block = vec![0u8; size];
+                for extent in zmap.extents(start, start + size)? {
+                    self.read_extent(&extent, start, &mut block)?;
+                }
+                Ok(Cow::Owned(block))
+            }
+        }
+    }
+
+    /// Reads the data of `inode` starting at `offset` into `buf` like
+    /// [`File::read_at`], and reports how the extent cache served it.
+    ///
+    /// Every compressed extent the read touches counts as a hit if its
+    /// decoded data was cached and as a miss if it had to be decoded,
+    /// including those of the packed inode holding a fragment. Uncompressed
+    /// data never goes through the cache and counts as neither.
+    /// This is meant for sizing the cache given to
+    /// [`with_cache`](Self::with_cache); [`File::read_at`] and the other
+    /// reads keep no such counts.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`File::read_at`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{EroFS, backend::MmapImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::with_cache(MmapImage::new_from_path("image.erofs")?, 16 << 20)?;
+    /// let inode = *fs.metadata("/usr/lib/libc.so.6")?.inode();
+    /// let mut buf = vec![0u8; 64 << 10];
+    /// let trace = fs.read_at_traced(&inode, 0, &mut buf)?;
+    /// println!("{} bytes, {} hits, {} misses", trace.bytes, trace.cache_hits, trace.cache_misses);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn read_at_traced(
+        &self,
+        inode: &Inode,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<ReadTrace> {
+        let len = buf.len().min(inode.data_size().saturating_sub(offset));
+        let buf = &mut buf[..len];
+        let mut trace = ReadTrace {
+            bytes: len,
+            ..ReadTrace::default()
+        };
+        if len == 0 || !inode.layout()?.is_compressed() {
+            self.read_inode_at(inode, offset, buf)?;
+            return Ok(trace);
+        }
+
+        for extent in self.inode_zmap(inode)?.extents(offset, offset + len)? {
+            if extent.format == ExtentFormat::Fragment {
+                let Some((from, to)) = EroFSCore::extent_overlap(&extent, offset, len) else {
+                    continue;
+                };
+                let packed = self.inode_at(self.core.super_block.packed_nid)?;
+                let packed_offset = EroFSCore::fragment_offset(&extent, from)?;
+                let fragment = &mut buf[from - offset..to - offset];
+                let packed_trace = self.read_at_traced(&packed, packed_offset, fragment)?;
+                if packed_trace.bytes != fragment.len() {
+                    return Err(Error::CorruptedData(format!(
+                        "fragment of {} bytes at {} exceeds the packed inode",
+                        extent.llen, extent.pa
+                    )));
+                }
+                trace.cache_hits += packed_trace.cache_hits;
+                trace.cache_misses += packed_trace.cache_misses;
+                continue;
+            }
+
+            let data = match self.cache.as_ref().and_then(|cache| cache.get(&extent)) {
+                Some(data) => {
+                    trace.cache_hits += 1;
+                    data
+                }
+                None => {
+                    trace.cache_misses += 1;
+                    let data = Bytes::from(self.decode_extent(&extent)?.into_owned());
+                    if let Some(cache) = &self.cache {
+                        cache.insert(extent, data.clone());
+                    }
+                    data
+                }
+            };
+            self.core.copy_extent(&extent, &data, offset, buf);
+        }
+        Ok(trace)
+    }
+
+    /// Builds the logical to physical map of a compressed inode whose map
+    /// header sits at `map_offset`.
+    fn zmap(&self, inode: &Inode, map_offset: usize) -> Result<ZMap<'_>> {
+        let header = self.image.read_exact_at(map_offset, MapHeader::size())?;
+        let map_len = ZMap::data_len(&self.core, inode, header)?;
+        let map_data = self.image.read_exact_at(map_offset, map_len)?;
+        ZMap::new(&self.core, inode, map_offset, map_data)
+    }
+
+    /// Builds the logical to physical map of the compressed `inode`.
+    pub(crate) fn inode_zmap(&self, inode: &Inode) -> Result<ZMap<'_>> {
+        self.zmap(inode, ZMap::header_offset(&self.core, inode))
+    }
+
+    /// Reads the data of `inode` starting at `offset` into all of `buf`,
+    /// which must not extend past the end of the file.
+    ///
+    /// Compressed data is mapped once for the whole range, so each physical
+    /// cluster is decoded once however many blocks of `buf` it fills.
+    pub(crate) fn read_inode_at(&self, inode: &Inode, offset: usize, buf: &mut [u8]) -> Result<()> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+        if let Layout::CompressedFull | Layout::CompressedCompact = inode.layout()? {
+            let zmap = self.inode_zmap(inode)?;
+            for extent in zmap.extents(offset, offset + buf.len())? {
+                self.read_extent(&extent, offset, buf)?;
+            }
+            return Ok(());
+        }
+
+        // Block sizes are powers of two, so masking finds the offset within
+        // a block.
+        let block_mask = self.block_size() - 1;
+        let mut n = 0;
+        while n < buf.len() {
+            let pos = offset + n;
+            let block = self.get_inode_block(inode, pos)?;
+            let start = pos & block_mask;
+            let m = (buf.len() - n).min(block.len().saturating_sub(start));
+            if m == 0 {
+                return Err(Error::CorruptedData(format!(
+                    "short block at offset {}",
+                    pos
+                )));
+            }
+            buf[n..n + m].copy_from_slice(&block[start..start + m]);
+            n += m;
+        }
+        Ok(())
+    }
+
+    /// Returns all of the data of `inode` borrowed from the image, if it is
+    /// stored uncompressed in one piece, see [`File::as_slice`].
+    pub(crate) fn contiguous_data(&self, inode: &Inode) -> Option<&[u8]> {
+        let (offset, len) = self.core.contiguous_data_range(inode)?;
+        self.image.get_borrowed(offset..offset.checked_add(len)?)
+    }
+
+    /// Reads the `len` bytes of file data at `offset`, borrowed from the
+    /// image if the backend lends them out and owned otherwise, so that
+    /// backends like [`FileImage`](crate::backend::FileImage) don't keep
+    /// every block read.
+    fn read_data(&self, offset: usize, len: usize) -> Result<Cow<'_, [u8]>> {
+        let end = offset.checked_add(len);
+        if let Some(data) = end.and_then(|end| self.image.get_borrowed(offset..end)) {
+            return Ok(Cow::Borrowed(data));
+        }
+        end.and_then(|end| self.image.get_owned(offset..end))
+            .map(|data| Cow::Owned(Vec::from(data)))
+            .ok_or(Error::OutOfBounds {
+                offset,
+                len,
+                image_len: self.image.len(),
+            })
+    }
+
+    /// Reads the block of `inode` containing `offset` into an owned buffer.
+    pub(crate) fn get_inode_block_owned(&self, inode: &Inode, offset: usize) -> Result<Bytes> {
+        Ok(match self.get_inode_block(inode, offset)? {
+            Cow::Borrowed(block) => Bytes::copy_from_slice(block),
+            Cow::Owned(block) => Bytes::from(block),
+        })
+    }
+
+    /// Returns `true` if decoded extents are kept in a cache.
+    pub(crate) fn caches_extents(&self) -> bool {
+        #[cfg(feature = "std")]
+        return self.cache.is_some();
+        #[cfg(not(feature = "std"))]
+        false
+    }
+
+    /// Decodes `extent` and copies its part of the logical range starting at
+    /// `start` into `buf`, going through the extent cache if there is one.
+    pub(crate) fn read_extent(&self, extent: &Extent, start: usize, buf: &mut [u8]) -> Result<()> {
+        if extent.format == ExtentFormat::Fragment {
+            return self.read_fragment(extent, start, buf);
+        }
+        #[cfg(feature = "std")]
+        if let Some(cache) = &self.cache {
+            let data = match cache.get(extent) {
+                Some(data) => data,
+                None => {
+                    let data = Bytes::from(self.decode_extent(extent)?.into_owned());
+                    cache.insert(*extent, data.clone());
+                    data
+                }
+            };
+            self.core.copy_extent(extent, &data, start, buf);
+            return Ok(());
+        }
+
+        let data = self.decode_extent(extent)?;
+        self.core.copy_extent(extent, &data, start, buf);
+        Ok(())
+    }
+
+    fn decode_extent(&self, extent: &Extent) -> Result<Cow<'_, [u8]>> {
+        match self.read_data(extent.pa, extent.plen)? {
+            Cow::Borrowed(input) => self.core.decode_extent(extent, input),
+            Cow::Owned(input) => Ok(Cow::Owned(
+                self.core.decode_extent(extent, &input)?.into_owned(),
+            )),
+        }
+    }
+
+    /// Reads the part of the fragment `extent` overlapping the logical range
+    /// starting at `start` into `buf`, straight from the packed inode.
+    fn read_fragment(&self, extent: &Extent, start: usize, buf: &mut [u8]) -> Result<()> {
+        let Some((from, to)) = EroFSCore::extent_overlap(extent, start, buf.len()) else {
+            return Ok(());
+        };
+        let packed = self.inode_at(self.core.super_block.packed_nid)?;
+        let offset = EroFSCore::fragment_offset(extent, from)?;
+        let buf = &mut buf[from - start..to - start];
+        if File::new(packed, self).read_at(offset, buf)? != buf.len() {
+            return Err(Error::CorruptedData(format!(
+                "fragment of {} bytes at {} exceeds the packed inode",
+                extent.llen, extent.pa
+            )));
+        }
+        Ok(())
+    }
+
+    /// Resolves `path` to its inode, following symlinks.
+    pub(crate) fn get_path_inode<P: AsUnixPath>(&self, path: P) -> Result<Inode> {
+        let (nid, _) = self.lookup_path(path, true)?;
+        self.get_inode(nid)
+    }
+
+    /// Resolves `path` to its inode with case-insensitive lookups, following
+    /// symlinks.
+    fn get_path_inode_ci<P: AsUnixPath>(&self, path: P) -> Result<Inode> {
+        let (nid, _) = self.walk_path(path.as_unix_path(), true, true)?.finish();
+        self.get_inode(nid)
+    }
+
+    /// Finds the entries of the directory `dir` whose names match `name`
+    /// case-insensitively, together with those names.
+    fn find_children_ci(&self, dir: &Inode, name: &[u8]) -> Result<Vec<(Dirent, Vec<u8>)>> {
+        let mut found = Vec::new();
+        for block_index in 0..dir.data_size().div_ceil(self.core.block_size) {
+            let block = self.get_inode_block(dir, block_index * self.core.block_size)?;
+            found.extend(dirent::find_dirents_ignore_case(name, &block)?);
+        }
+        Ok(found)
+    }
+
+    /// Looks up the entry called `name` in the directory `dir`.
+    ///
+    /// Directory entries are sorted by name across blocks, so this
+    /// binary-searches the blocks by their first name and then the entries
+    /// within the only block that can hold `name`.
+    fn lookup_child(&self, dir: &Inode, name: &[u8]) -> Result<Option<Dirent>> {
+        let mut lo = 0;
+        let mut hi = dir.data_size().div_ceil(self.core.block_size);
+        let mut candidate = None;
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let block = self.get_inode_block(dir, mid * self.core.block_size)?;
+            if dirent::cmp_first_name(&block, name)? == cmp::Ordering::Greater {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+                candidate = Some(block);
+            }
+        }
+
+        let Some(block) = candidate else {
+            return Ok(None);
+        };
+        dirent::find_dirent_by_name(name, &block)
+    }
+
+    /// Resolves `path` to the nid of its last component, together with the
+    /// file type recorded in the parent directory entry (`None` for the root).
+    ///
+    /// Symlinks met along the way are followed; the last component is only
+    /// followed if `follow` is set.
+    fn lookup_path<P: AsUnixPath>(
+        &self,
+        path: P,
+        follow: bool,
+    ) -> Result<(u64, Option<DirentFileType>)> {
+        let path = path.as_unix_path();
+        #[cfg(feature = "std")]
+        if let Some(cache) = &self.path_cache {
+            if let Some(target) = cache.get(path, follow) {
+                return Ok(target);
+            }
+            let target = self.resolve_path(path, follow)?;
+            cache.insert(path, follow, target);
+            return Ok(target);
+        }
+        self.resolve_path(path, follow)
+    }
+
+    /// Walks the directories on `path` for [`lookup_path`](Self::lookup_path).
+    fn resolve_path(&self, path: &UnixPath, follow: bool) -> Result<(u64, Option<DirentFileType>)> {
+        Ok(self.walk_path(path, follow, false)?.finish())
+    }
+
+    /// Walks the directories on `path`, following symlinks like
+    /// [`lookup_path`](Self::lookup_path).
+    ///
+    /// With `ignore_case`, names are compared case-insensitively and the
+    /// walk enters the entries under their stored names.
+    fn walk_path(&self, path: &UnixPath, follow: bool, ignore_case: bool) -> Result<PathWalk> {
+        let mut walk = PathWalk::new(self.core.super_block.root_nid as u64, path);
+        while let Some(name) = walk.next_name() {
+            let inode = self.get_inode(walk.dir())?;
+            if !inode.is_dir() {
+                return Err(walk.not_a_directory());
+            }
+            let (dirent, name) = if ignore_case {
+                let mut found = self.find_children_ci(&inode, &name)?;
+                if found.len() > 1 {
+                    return Err(walk.ambiguous());
+                }
+                found.pop().ok_or_else(|| walk.not_found(&name))?
+            } else {
+                let dirent = self
+                    .lookup_child(&inode, &name)?
+                    .ok_or_else(|| walk.not_found(&name))?;
+                (dirent, name)
+            };
+
+            let nid = dirent.nid;
+            let file_type = dirent.file_type.try_into()?;
+            if (follow || !walk.is_last())
+                && matches!(file_type, DirentFileType::Symlink | DirentFileType::Unknown)
+            {
+                let inode = self.get_inode(nid)?;
+                if inode.is_symlink() {
+                    walk.follow(&self.read_inode_link(&inode)?)?;
+                    continue;
+                }
+            }
+            walk.enter(name, nid, file_type);
+        }
+
+        Ok(walk)
+    }
+
+    /// Whether the symlink at `path` points outside of the tree once the
+    /// image is extracted, resolving `target` through the symlinks of the
+    /// image like the host would, see [`manifest`](Self::manifest).
+    fn symlink_escapes(&self, path: &UnixPath, target: &UnixPath) -> Result<bool> {
+        if target.is_absolute() {
+            return Ok(true);
+        }
+        let dir = path.parent().unwrap_or_else(|| UnixPath::new("/"));
+        let mut walk = PathWalk::new(self.core.super_block.root_nid as u64, &dir.join(target));
+        let mut stopped_at = None;
+        while let Some(name) = walk.next_name() {
+            // The host stops resolving at a name looked up in something
+            // that isn't a directory or missing from it.
+            let inode = self.get_inode(walk.dir())?;
+            if !inode.is_dir() {
+                stopped_at = Some(name);
+                break;
+            }
+            let Some(dirent) = self.lookup_child(&inode, &name)? else {
+                stopped_at = Some(name);
+                break;
+            };
+
+            let file_type = dirent.file_type.try_into()?;
+            if matches!(file_type, DirentFileType::Symlink | DirentFileType::Unknown) {
+                let inode = self.get_inode(dirent.nid)?;
+                if inode.is_symlink() {
+                    // Symlink loops don't resolve on the host either.
+                    if walk.follow(&self.read_inode_link(&inode)?).is_err() {
+                        break;
+                    }
+                    continue;
+                }
+            }
+            walk.enter(name, dirent.nid, file_type);
+        }
+        Ok(walk.leaves_root(stopped_at.as_deref()))
+    }
+}
+
+impl<I: Image> EroFS<OffsetImage<I>> {
+    /// Creates a new `EroFS` instance for an image embedded `offset` bytes
+    /// into `image`, e.g. a partition inside an Android super image.
+    ///
+    /// This is a shorthand for wrapping `image` in an [`OffsetImage`]; every
+    /// read, including the superblock at `offset + 1024`, is relative to
+    /// where the embedded image starts.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{EroFS, backend::MmapImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let container = MmapImage::new_from_path("super.img")?;
+    /// let fs = EroFS::new_at_offset(container, 0x10_0000)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_at_offset(image: I, offset: usize) -> Result<Self> {
+        Self::new(OffsetImage::new(image, offset))
+    }
+}
+
+#[cfg(feature = "std")]
+impl EroFS<MmapImage> {
+    /// Opens the image file at `path`, memory-mapped.
+    ///
+    /// This is a shorthand for [`new`](Self::new) with
+    /// [`MmapImage::new_from_path`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] if the file can't be opened or mapped, and
+    /// otherwise fails like [`new`](Self::new).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::EroFS;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::open_path("image.erofs")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn open_path<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        Self::new(MmapImage::new_from_path(path)?)
+    }
+}
+
+impl EroFS<VecImage> {
+    /// Creates a new `EroFS` instance owning the image `data`.
+    ///
+    /// This is a shorthand for [`new`](Self::new) with a [`VecImage`], which
+    /// takes a `Vec<u8>` or [`Bytes`] without copying it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::EroFS;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::from_bytes(std::fs::read("image.erofs")?)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_bytes<B: Into<Bytes>>(data: B) -> Result<Self> {
+        Self::new(VecImage::new(data))
+    }
+
+    /// Creates a new `EroFS` instance from the whole of `reader`, read into
+    /// memory.
+    ///
+    /// This is meant for sources that can't be memory-mapped, like an entry
+    /// of an archive. The stream is read from its start up to the end it
+    /// had when the call began, so it should not be too large to keep in
+    /// memory; `max_bytes` bounds how much is read, guarding against
+    /// untrusted streams claiming a huge length.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ImageTooLarge`] if the stream is longer than
+    /// `max_bytes`, [`Error::Io`] if it can't be read, and otherwise fails
+    /// like [`new`](Self::new).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::EroFS;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let file = std::fs::File::open("image.erofs")?;
+    /// let fs = EroFS::from_reader(file, Some(64 << 20))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn from_reader<R>(mut reader: R, max_bytes: Option<u64>) -> Result<Self>
+    where
+        R: std::io::Read + std::io::Seek,
+    {
+        use std::io::{ErrorKind, Read, SeekFrom};
+
+        let len = reader.seek(SeekFrom::End(0))?;
+        if let Some(max) = max_bytes.filter(|&max| len > max) {
+            return Err(Error::ImageTooLarge { len, max });
+        }
+        reader.rewind()?;
+
+        let mut data = Vec::new();
+        data.try_reserve_exact(usize::try_from(len).unwrap_or(usize::MAX))
+            .map_err(|_| std::io::Error::from(ErrorKind::OutOfMemory))?;
+        reader.take(len).read_to_end(&mut data)?;
+        Self::from_bytes(data)
+    }
+}
+
+impl<'a> EroFS<SliceImage<'a>> {
+    /// Creates a new `EroFS` instance borrowing the image `data`.
+    ///
+    /// This is a shorthand for [`new`](Self::new) with a [`SliceImage`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::EroFS;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let data = std::fs::read("image.erofs")?;
+    /// let fs = EroFS::from_slice(&data)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_slice(data: &'a [u8]) -> Result<Self> {
+        Self::new(SliceImage::new(data))
     }
 }
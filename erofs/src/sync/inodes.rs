@@ -0,0 +1,27 @@
+use super::EroFS;
+use crate::Result;
+use crate::backend::Image;
+use crate::filesystem::InodeScan;
+use crate::types::Inode;
+
+/// An iterator over every inode of the image, in on-disk nid order.
+///
+/// Created by [`EroFS::inodes`]. Each item is a `(nid, inode)` pair.
+pub struct Inodes<'a, I: Image> {
+    erofs: &'a EroFS<I>,
+    scan: InodeScan,
+}
+
+impl<'a, I: Image> Inodes<'a, I> {
+    pub(crate) fn new(erofs: &'a EroFS<I>, scan: InodeScan) -> Self {
+        Self { erofs, scan }
+    }
+}
+
+impl<'a, I: Image> Iterator for Inodes<'a, I> {
+    type Item = Result<(u64, Inode)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.erofs.next_scanned_inode(&mut self.scan)
+    }
+}
@@ -1,8 +1,14 @@
+mod blocks;
+mod chunks;
 mod dirent;
 pub mod file;
 pub mod filesystem;
+mod inodes;
 pub mod walkdir;
 
+pub use blocks::Blocks;
+pub use chunks::Chunks;
 pub use dirent::ReadDir;
 pub use filesystem::EroFS;
-pub use walkdir::{WalkDir, WalkDirEntry};
+pub use inodes::Inodes;
+pub use walkdir::{Files, WalkDir, WalkDirEntry};
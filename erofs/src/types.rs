@@ -5,18 +5,70 @@ use std::{
     time::{Duration, SystemTime},
 };
 
+use alloc::{format, string::String};
+use core::fmt;
+
 use binrw::BinRead;
 use rustix::fs::FileType;
 
 use crate::Error;
+use crate::filesystem::SUPPORTED_FEATURE_INCOMPAT;
 
 pub const MAGIC_NUMBER: u32 = 0xe0f5e1e2;
 pub const SUPER_BLOCK_OFFSET: usize = 1024;
+/// Smallest and largest supported `blk_size_bits`, i.e. 512-byte to 64 KiB
+/// blocks.
+pub const MIN_BLOCK_SIZE_BITS: u8 = 9;
+pub const MAX_BLOCK_SIZE_BITS: u8 = 16;
 
 pub const LAYOUT_CHUNK_FORMAT_BITS: u16 = 0x001F;
 pub const LAYOUT_CHUNK_FORMAT_INDEXES: u16 = 0x0020;
 
 pub const SB_EXTSLOT_SIZE: usize = 16;
+pub const DEVT_SLOT_SIZE: usize = 128;
+/// Block address of chunks that aren't backed by any data.
+pub const NULL_ADDR: u32 = u32::MAX;
+
+pub const FEATURE_COMPAT_SB_CHKSUM: u32 = 0x0000_0001;
+
+pub const FEATURE_INCOMPAT_ZERO_PADDING: u32 = 0x0000_0001;
+pub const FEATURE_INCOMPAT_COMPR_CFGS: u32 = 0x0000_0002;
+pub const FEATURE_INCOMPAT_BIG_PCLUSTER: u32 = 0x0000_0002;
+pub const FEATURE_INCOMPAT_CHUNKED_FILE: u32 = 0x0000_0004;
+pub const FEATURE_INCOMPAT_DEVICE_TABLE: u32 = 0x0000_0008;
+pub const FEATURE_INCOMPAT_ZTAILPACKING: u32 = 0x0000_0010;
+pub const FEATURE_INCOMPAT_FRAGMENTS: u32 = 0x0000_0020;
+
+pub const XATTR_INDEX_USER: u8 = 1;
+pub const XATTR_INDEX_POSIX_ACL_ACCESS: u8 = 2;
+pub const XATTR_INDEX_POSIX_ACL_DEFAULT: u8 = 3;
+pub const XATTR_INDEX_TRUSTED: u8 = 4;
+pub const XATTR_INDEX_LUSTRE: u8 = 5;
+pub const XATTR_INDEX_SECURITY: u8 = 6;
+pub const XATTR_LONG_PREFIX: u8 = 0x80;
+
+pub const LZMA_MAX_DICT_SIZE: u32 = 8 << 20;
+pub const ZSTD_MAX_DICT_SIZE: u32 = 1 << 20;
+pub const DEFLATE_MAX_WINDOWBITS: u8 = 15;
+/// The smallest window log zstd allows, which `ZstdConfig::windowlog` is
+/// relative to.
+pub const ZSTD_WINDOWLOG_ABSOLUTEMIN: u8 = 10;
+
+pub const MAP_ADVISE_COMPACTED_2B: u16 = 0x0001;
+pub const MAP_ADVISE_BIG_PCLUSTER_1: u16 = 0x0002;
+pub const MAP_ADVISE_BIG_PCLUSTER_2: u16 = 0x0004;
+pub const MAP_ADVISE_INLINE_PCLUSTER: u16 = 0x0008;
+pub const MAP_ADVISE_INTERLACED_PCLUSTER: u16 = 0x0010;
+pub const MAP_ADVISE_FRAGMENT_PCLUSTER: u16 = 0x0020;
+pub const MAP_FRAGMENT_INODE_BIT: u8 = 7;
+
+pub const LCLUSTER_TYPE_PLAIN: u8 = 0;
+pub const LCLUSTER_TYPE_HEAD1: u8 = 1;
+pub const LCLUSTER_TYPE_NONHEAD: u8 = 2;
+pub const LCLUSTER_TYPE_HEAD2: u8 = 3;
+pub const LCLUSTER_TYPE_MASK: u16 = 0x0003;
+pub const LI_PARTIAL_REF: u16 = 0x8000;
+pub const LI_D0_CBLKCNT: u16 = 0x0800;
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, BinRead)]
@@ -53,18 +105,233 @@ impl SuperBlock {
     pub const fn size() -> usize {
         size_of::<Self>()
     }
+
+    /// Returns the block size in bytes.
+    pub fn block_size(&self) -> usize {
+        1 << self.blk_size_bits
+    }
+
+    /// Returns the compatible features the image uses, including bits
+    /// without a name.
+    pub fn compat_features(&self) -> CompatFeatures {
+        CompatFeatures::from_bits_retain(self.feature_compat)
+    }
+
+    /// Returns the incompatible features the image uses, including bits
+    /// without a name.
+    ///
+    /// EROFS has no read-only compatible features, as every image is read
+    /// only.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{EroFS, types::IncompatFeatures};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::open_path("image.erofs")?;
+    /// let features = fs.super_block().incompat_features();
+    /// if features.contains(IncompatFeatures::FRAGMENTS) {
+    ///     println!("file tails are packed into the packed inode");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn incompat_features(&self) -> IncompatFeatures {
+        IncompatFeatures::from_bits_retain(self.feature_incompat)
+    }
+
+    /// Returns the incompatible features the image uses that this crate
+    /// can't read, which explains why reading some files fails.
+    pub fn unsupported_features(&self) -> IncompatFeatures {
+        IncompatFeatures::from_bits_retain(self.feature_incompat & !SUPPORTED_FEATURE_INCOMPAT)
+    }
+
+    /// Formats the UUID in its canonical hyphenated form, e.g.
+    /// `71bd9ab4-fb8c-47b4-986c-5c901ad547c7`.
+    pub fn uuid_string(&self) -> String {
+        let mut out = String::with_capacity(36);
+        for (i, b) in self.uuid.iter().enumerate() {
+            if matches!(i, 4 | 6 | 8 | 10) {
+                out.push('-');
+            }
+            out.push_str(&format!("{:02x}", b));
+        }
+        out
+    }
+
+    /// Returns the volume label with trailing NUL padding removed.
+    ///
+    /// A label that is not valid UTF-8 is cut at the first invalid byte.
+    pub fn volume_name(&self) -> &str {
+        let len = self
+            .volume_name
+            .iter()
+            .rposition(|&b| b != 0)
+            .map_or(0, |i| i + 1);
+        let name = &self.volume_name[..len];
+        core::str::from_utf8(name)
+            .unwrap_or_else(|e| core::str::from_utf8(&name[..e.valid_up_to()]).unwrap_or_default())
+    }
+}
+
+/// An entry of the device table describing an extra device (blob).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, BinRead)]
+#[br(little)]
+pub struct DeviceSlot {
+    pub tag: [u8; 64],
+    pub blocks: u32,
+    pub mapped_blkaddr: u32,
+    pub reserved: [u8; 56],
 }
 
-#[derive(Debug, PartialEq, Eq)]
+impl DeviceSlot {
+    #[inline]
+    pub const fn size() -> usize {
+        size_of::<Self>()
+    }
+
+    /// Returns the device tag with trailing NUL padding removed.
+    pub fn tag(&self) -> &[u8] {
+        let len = self
+            .tag
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(self.tag.len());
+        &self.tag[..len]
+    }
+}
+
+/// Compression algorithms defined by the EROFS on-disk format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum CompressionAlgo {
+    Lz4 = 0,
+    Lzma = 1,
+    Deflate = 2,
+    Zstd = 3,
+}
+
+impl CompressionAlgo {
+    pub const ALL: [Self; 4] = [Self::Lz4, Self::Lzma, Self::Deflate, Self::Zstd];
+
+    /// Returns the lowercase name used by `mkfs.erofs -z`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Lz4 => "lz4",
+            Self::Lzma => "lzma",
+            Self::Deflate => "deflate",
+            Self::Zstd => "zstd",
+        }
+    }
+}
+
+impl TryFrom<u8> for CompressionAlgo {
+    type Error = Error;
+    fn try_from(x: u8) -> Result<Self, Error> {
+        use CompressionAlgo::*;
+        match x {
+            0 => Ok(Lz4),
+            1 => Ok(Lzma),
+            2 => Ok(Deflate),
+            3 => Ok(Zstd),
+            x => Err(Error::UnsupportedCompression(format!("algorithm {}", x))),
+        }
+    }
+}
+
+/// Compression parameters of a compressed inode, read from its map header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compression {
+    /// Algorithm the inode's compressed clusters are encoded with.
+    pub algorithm: CompressionAlgo,
+    /// Logical cluster size in bits: the file is mapped to physical
+    /// clusters in units of `1 << lcluster_bits` bytes.
+    pub lcluster_bits: u8,
+}
+
+impl Compression {
+    /// Returns the logical cluster size in bytes.
+    pub fn lcluster_size(&self) -> usize {
+        1 << self.lcluster_bits
+    }
+}
+
+/// LZMA parameters from the compression configs following the superblock.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, BinRead)]
+#[br(little)]
+pub struct LzmaConfig {
+    pub dict_size: u32,
+    pub format: u16,
+    pub reserved: [u8; 8],
+}
+
+/// DEFLATE parameters from the compression configs following the
+/// superblock.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, BinRead)]
+#[br(little)]
+pub struct DeflateConfig {
+    pub windowbits: u8,
+    pub reserved: [u8; 5],
+}
+
+/// Zstandard parameters from the compression configs following the
+/// superblock.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, BinRead)]
+#[br(little)]
+pub struct ZstdConfig {
+    pub format: u8,
+    /// The window log minus [`ZSTD_WINDOWLOG_ABSOLUTEMIN`].
+    pub windowlog: u8,
+    pub reserved: [u8; 4],
+}
+
+impl ZstdConfig {
+    /// Returns the largest window a zstd frame of this image may use.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::CorruptedData`] if the window log is too large for
+    /// the window size to fit in a `u64`.
+    pub fn window_size(&self) -> Result<u64, Error> {
+        let log = self.windowlog as u32 + ZSTD_WINDOWLOG_ABSOLUTEMIN as u32;
+        1u64.checked_shl(log)
+            .ok_or_else(|| Error::CorruptedData(format!("zstd window log {} is too large", log)))
+    }
+}
+
+/// How the data of an inode is stored, as decoded from the `datalayout`
+/// bits of its format field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum Layout {
+    /// Uncompressed, in consecutive blocks starting at the inode's block
+    /// address.
     FlatPlain = 0,
+    /// Compressed, with a full 8-byte index for every logical cluster.
     CompressedFull = 1,
+    /// Like [`FlatPlain`](Self::FlatPlain), except that the last partial
+    /// block is stored inline right after the inode.
     FlatInline = 2,
+    /// Compressed, with compacted 2- or 4-byte indexes.
     CompressedCompact = 3,
+    /// Uncompressed, in chunks located through a table following the
+    /// inode, which allows holes and data shared between files.
     ChunkBased = 4,
 }
 
+impl Layout {
+    /// Returns `true` for the compressed layouts, whose data can only be
+    /// read through a decompressor.
+    pub fn is_compressed(&self) -> bool {
+        matches!(self, Self::CompressedFull | Self::CompressedCompact)
+    }
+}
+
 impl TryFrom<u8> for Layout {
     type Error = Error;
     fn try_from(x: u8) -> Result<Self, Error> {
@@ -80,6 +347,71 @@ impl TryFrom<u8> for Layout {
     }
 }
 
+bitflags::bitflags! {
+    /// Compatible feature bits of the superblock. Readers may ignore the ones
+    /// they don't know.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct CompatFeatures: u32 {
+        /// The superblock carries a CRC32C checksum.
+        const SB_CHKSUM = FEATURE_COMPAT_SB_CHKSUM;
+        /// Inodes carry modification times.
+        const MTIME = 0x0000_0002;
+        /// Inodes carry a bloom filter of their xattr names.
+        const XATTR_FILTER = 0x0000_0004;
+    }
+}
+
+bitflags::bitflags! {
+    /// Incompatible feature bits of the superblock. Readers must refuse
+    /// images using any they don't know.
+    ///
+    /// Some bits were given a second meaning as the format grew, which is
+    /// why several flags share a value.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct IncompatFeatures: u32 {
+        /// Compressed data is padded with zeros at the start of its block.
+        const ZERO_PADDING = FEATURE_INCOMPAT_ZERO_PADDING;
+        /// Per-algorithm configurations follow the superblock.
+        const COMPR_CFGS = FEATURE_INCOMPAT_COMPR_CFGS;
+        /// Physical clusters may span more than one block.
+        const BIG_PCLUSTER = FEATURE_INCOMPAT_BIG_PCLUSTER;
+        /// Files may use the chunk-based layout.
+        const CHUNKED_FILE = FEATURE_INCOMPAT_CHUNKED_FILE;
+        /// A device table describes extra devices.
+        const DEVICE_TABLE = FEATURE_INCOMPAT_DEVICE_TABLE;
+        /// Compressed files may use a second compression algorithm.
+        const COMPR_HEAD2 = FEATURE_INCOMPAT_DEVICE_TABLE;
+        /// The tail of compressed data may be stored inline.
+        const ZTAILPACKING = FEATURE_INCOMPAT_ZTAILPACKING;
+        /// The tails of compressed files may be packed into the packed inode.
+        const FRAGMENTS = FEATURE_INCOMPAT_FRAGMENTS;
+        /// Compressed extents may be shared between files.
+        const DEDUPE = FEATURE_INCOMPAT_FRAGMENTS;
+        /// Long xattr name prefixes are stored in the packed inode.
+        const XATTR_PREFIXES = 0x0000_0040;
+        /// Block addresses and sizes are 48 bits wide.
+        const BLKADDR_48BIT = 0x0000_0080;
+        /// Metadata lives in a separate metabox inode.
+        const METABOX = 0x0000_0100;
+    }
+}
+
+/// Formats the features as their names joined by ` | `, with any bits
+/// without a name as a trailing hex number, e.g. `SB_CHKSUM | 0x80`.
+impl fmt::Display for CompatFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        bitflags::parser::to_writer(self, f)
+    }
+}
+
+/// Formats the features as their names joined by ` | `, with any bits
+/// without a name as a trailing hex number, e.g. `FRAGMENTS | 0x200`.
+impl fmt::Display for IncompatFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        bitflags::parser::to_writer(self, f)
+    }
+}
+
 bitflags::bitflags! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct FileMode: u16 {
@@ -134,23 +466,77 @@ impl Inode {
         (layout & 0x01) == 0
     }
 
-    pub fn id(&self) -> u64 {
+    /// Returns the node ID this inode was read from.
+    ///
+    /// The nid is the inode's slot in the metadata area, so it reflects the
+    /// order in which the image builder allocated inodes. All hard links to
+    /// a file share the same nid, which makes it the stable identifier to
+    /// detect them with.
+    pub fn nid(&self) -> u64 {
         match self {
             Self::Compact((nid, _)) => *nid,
             Self::Extended((nid, _)) => *nid,
         }
     }
 
+    /// Same as [`Inode::nid`].
+    pub fn id(&self) -> u64 {
+        self.nid()
+    }
+
+    /// Returns the inode number recorded on disk (`i_ino`).
+    ///
+    /// This is assigned by the image builder and only used by the kernel for
+    /// 32-bit `stat` compatibility; prefer [`Inode::nid`] to identify inodes.
+    pub fn ino(&self) -> u32 {
+        match self {
+            Self::Compact((_, n)) => n.inode,
+            Self::Extended((_, n)) => n.inode,
+        }
+    }
+
+    /// Returns the data layout of the inode.
+    ///
+    /// This only decodes the inode's format field and doesn't need any
+    /// decompressor.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidLayout`] if the layout bits hold a value this
+    /// crate doesn't know.
     pub fn layout(&self) -> Result<Layout, Error> {
-        let format_layout = match self {
+        let layout = ((self.raw_format() & 0x0E) >> 1) as u8;
+        layout.try_into()
+    }
+
+    /// Returns the raw `i_format` field, holding the inode version in bit 0
+    /// and the data layout in bits 1 to 3.
+    pub fn raw_format(&self) -> u16 {
+        match self {
             Self::Compact((_, n)) => n.format,
             Self::Extended((_, n)) => n.format,
-        };
+        }
+    }
 
-        let layout = ((format_layout & 0x0E) >> 1) as u8;
-        layout.try_into()
+    /// Returns `true` if the tail of the inode's data is stored inline,
+    /// right after the inode and its xattrs, rather than in a block of its
+    /// own.
+    pub fn is_inline_data(&self) -> bool {
+        matches!(self.layout(), Ok(Layout::FlatInline))
+    }
+
+    /// Returns `true` for a 64-byte extended inode, `false` for a 32-byte
+    /// compact one.
+    ///
+    /// Only extended inodes hold 64-bit sizes, 32-bit uids and gids, a link
+    /// count above 65535 and their own modification time; the accessors
+    /// read whichever fields the inode's format has.
+    pub fn is_extended(&self) -> bool {
+        matches!(self, Self::Extended(_))
     }
 
+    /// Returns the size of the on-disk inode in bytes: 32 for a compact
+    /// inode, 64 for an extended one.
     pub fn size(&self) -> usize {
         match self {
             Self::Compact(_) => size_of::<InodeCompact>(),
@@ -158,6 +544,36 @@ impl Inode {
         }
     }
 
+    /// Returns the size of the inode's data in bytes.
+    ///
+    /// Compact inodes store a 32-bit size, extended inodes a 64-bit one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use erofs_rs::types::{Inode, InodeExtended};
+    ///
+    /// let inode = Inode::Extended((
+    ///     0,
+    ///     InodeExtended {
+    ///         format: 1,
+    ///         xattr_count: 0,
+    ///         mode: 0o100644,
+    ///         reserved: 0,
+    ///         size: 5 << 30,
+    ///         inode_data: 0,
+    ///         inode: 0,
+    ///         uid: 0,
+    ///         gid: 0,
+    ///         mtime: 0,
+    ///         mtime_ns: 0,
+    ///         nlink: 1,
+    ///         reserved2: [0; 16],
+    ///     },
+    /// ));
+    /// assert!(inode.is_extended());
+    /// assert_eq!(inode.data_size() as u64, 5 << 30);
+    /// ```
     #[inline]
     pub fn data_size(&self) -> usize {
         match self {
@@ -173,15 +589,52 @@ impl Inode {
         }
     }
 
-    pub fn xattr_size(&self) -> usize {
-        let count = match self {
+    /// Returns the raw `i_xattr_icount` field.
+    ///
+    /// This is the size of the inline xattr region in 4-byte slots, with the
+    /// 12-byte header counted as one slot; it is not the number of attributes,
+    /// which takes decoding the region to find. Zero means the inode has no
+    /// xattrs.
+    pub fn xattr_icount(&self) -> u16 {
+        match self {
             Self::Compact((_, n)) => n.xattr_count,
             Self::Extended((_, n)) => n.xattr_count,
-        };
-        if count == 0 {
+        }
+    }
+
+    /// Returns the size in bytes of the inline xattr region following the
+    /// inode, computed from [`xattr_icount`](Self::xattr_icount) without
+    /// decoding any entry.
+    pub fn xattr_size(&self) -> u64 {
+        self.xattr_len() as u64
+    }
+
+    /// Like [`xattr_size`](Self::xattr_size), as an offset within the image.
+    pub(crate) fn xattr_len(&self) -> usize {
+        let icount = self.xattr_icount();
+        if icount == 0 {
             0
         } else {
-            (count - 1) as usize * size_of::<XattrEntry>() + size_of::<XattrHeader>()
+            (icount - 1) as usize * size_of::<XattrEntry>() + size_of::<XattrHeader>()
+        }
+    }
+
+    /// Returns the raw `i_mode` field: file type and permission bits.
+    pub fn mode(&self) -> u16 {
+        match self {
+            Self::Compact((_, n)) => n.mode,
+            Self::Extended((_, n)) => n.mode,
+        }
+    }
+
+    /// Returns the number of hard links to this inode.
+    ///
+    /// Compact inodes store it in 16 bits and extended inodes in 32 bits,
+    /// so either is widened to a `u32`.
+    pub fn nlink(&self) -> u32 {
+        match self {
+            Self::Compact((_, n)) => n.nlink as u32,
+            Self::Extended((_, n)) => n.nlink,
         }
     }
 
@@ -204,6 +657,82 @@ impl Inode {
         self.file_type().is_symlink()
     }
 
+    pub fn is_block_device(&self) -> bool {
+        self.file_type() == FileType::BlockDevice
+    }
+
+    pub fn is_char_device(&self) -> bool {
+        self.file_type() == FileType::CharacterDevice
+    }
+
+    pub fn is_fifo(&self) -> bool {
+        self.file_type() == FileType::Fifo
+    }
+
+    pub fn is_socket(&self) -> bool {
+        self.file_type() == FileType::Socket
+    }
+
+    /// Returns the `(major, minor)` device number of a block or character
+    /// device, or `None` for any other kind of inode.
+    pub fn device_id(&self) -> Option<(u32, u32)> {
+        if !self.is_block_device() && !self.is_char_device() {
+            return None;
+        }
+
+        // The union field holds the device number in the kernel's
+        // `new_encode_dev()` format.
+        let rdev = self.raw_block_addr();
+        let major = (rdev & 0xfff00) >> 8;
+        let minor = (rdev & 0xff) | ((rdev >> 12) & 0xfff00);
+        Some((major, minor))
+    }
+
+    /// Returns `true` if this is an overlayfs whiteout: a character device
+    /// with device number 0/0, which hides the same path in lower layers of
+    /// a layered image.
+    pub fn is_whiteout(&self) -> bool {
+        self.device_id() == Some((0, 0)) && self.is_char_device()
+    }
+
+    /// Returns the mode in the style of `ls -l`: the file type character
+    /// followed by the read, write and execute bits of the owner, group and
+    /// others, with setuid, setgid and the sticky bit shown in place of the
+    /// execute bits.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use erofs_rs::{EroFS, backend::MmapImage};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let fs = EroFS::new(MmapImage::new_from_path("image.erofs")?)?;
+    /// let tmp = fs.metadata("/tmp")?;
+    /// assert_eq!(tmp.inode().mode_string(), "drwxrwxrwt");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn mode_string(&self) -> String {
+        let mode = self.mode();
+        let bit = |mask: u16, c: char| if mode & mask != 0 { c } else { '-' };
+        // An execute bit overlaid with a special bit shows as the lowercase
+        // letter when set and the uppercase one when not.
+        let special =
+            |exec: u16, special: u16, c: char| match (mode & exec != 0, mode & special != 0) {
+                (true, true) => c,
+                (false, true) => c.to_ascii_uppercase(),
+                (true, false) => 'x',
+                (false, false) => '-',
+            };
+        let mut s = format!("{}", DirentFileType::from(self.file_type()));
+        for (shift, special_mask, c) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+            s.push(bit(0o4 << shift, 'r'));
+            s.push(bit(0o2 << shift, 'w'));
+            s.push(special(0o1 << shift, special_mask, c));
+        }
+        s
+    }
+
     #[cfg(feature = "std")]
     pub fn permissions(&self) -> Permissions {
         match self {
@@ -220,34 +749,71 @@ impl Inode {
         }
     }
 
-    #[cfg(feature = "std")]
-    pub fn modified(&self) -> Option<SystemTime> {
+    /// Returns the modification time as `(seconds, nanoseconds)` since the
+    /// Unix epoch, or `None` for compact inodes, which carry no timestamp.
+    pub(crate) fn mtime(&self) -> Option<(u64, u32)> {
         match self {
-            Self::Compact((_, _)) => None,
-            Self::Extended((_, n)) => {
-                let secs = n.mtime;
-                let nanos = n.mtime_ns;
-                Some(
-                    SystemTime::UNIX_EPOCH
-                        + Duration::from_secs(secs)
-                        + Duration::from_nanos(nanos as u64),
-                )
-            }
+            Self::Compact(_) => None,
+            Self::Extended((_, n)) => Some((n.mtime, n.mtime_ns)),
         }
     }
 
+    /// Returns the modification time, which only extended inodes carry.
+    ///
+    /// Returns `None` for compact inodes, or if the time can't be
+    /// represented as a `SystemTime`.
+    #[cfg(feature = "std")]
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.mtime()
+            .and_then(|(secs, nanos)| system_time(secs, nanos))
+    }
+
+    /// Returns the last access time, which is the modification time.
+    ///
+    /// EROFS records a single timestamp per inode, which the kernel reports
+    /// for every timestamp.
+    #[cfg(feature = "std")]
+    pub fn accessed(&self) -> Option<SystemTime> {
+        self.modified()
+    }
+
+    /// Returns the creation time, which is the modification time.
+    ///
+    /// EROFS records a single timestamp per inode, which the kernel reports
+    /// for every timestamp.
+    #[cfg(feature = "std")]
+    pub fn created(&self) -> Option<SystemTime> {
+        self.modified()
+    }
+
+    /// Returns the modification time as `(seconds, nanoseconds)` since the
+    /// Unix epoch, which only extended inodes carry.
     #[cfg(not(feature = "std"))]
     pub fn modified(&self) -> Option<(u64, u32)> {
-        match self {
-            Self::Compact((_, _)) => None,
-            Self::Extended((_, n)) => {
-                let secs = n.mtime;
-                let nanos = n.mtime_ns;
-                Some((secs, nanos))
-            }
-        }
+        self.mtime()
+    }
+
+    /// Returns the last access time, which is the modification time.
+    ///
+    /// EROFS records a single timestamp per inode, which the kernel reports
+    /// for every timestamp.
+    #[cfg(not(feature = "std"))]
+    pub fn accessed(&self) -> Option<(u64, u32)> {
+        self.mtime()
     }
 
+    /// Returns the creation time, which is the modification time.
+    ///
+    /// EROFS records a single timestamp per inode, which the kernel reports
+    /// for every timestamp.
+    #[cfg(not(feature = "std"))]
+    pub fn created(&self) -> Option<(u64, u32)> {
+        self.mtime()
+    }
+
+    /// Returns the group ID of the owner.
+    ///
+    /// Compact inodes store a 16-bit ID, which is zero-extended.
     pub fn gid(&self) -> u32 {
         match self {
             Self::Compact((_, n)) => n.gid as u32,
@@ -255,6 +821,9 @@ impl Inode {
         }
     }
 
+    /// Returns the user ID of the owner.
+    ///
+    /// Compact inodes store a 16-bit ID, which is zero-extended.
     pub fn uid(&self) -> u32 {
         match self {
             Self::Compact((_, n)) => n.uid as u32,
@@ -263,6 +832,15 @@ impl Inode {
     }
 }
 
+/// Converts a timestamp since the Unix epoch to a `SystemTime`, or `None`
+/// if it is out of range.
+#[cfg(feature = "std")]
+pub(crate) fn system_time(secs: u64, nanos: u32) -> Option<SystemTime> {
+    SystemTime::UNIX_EPOCH
+        .checked_add(Duration::from_secs(secs))?
+        .checked_add(Duration::from_nanos(nanos as u64))
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy, BinRead)]
 #[br(little)]
@@ -313,7 +891,10 @@ impl InodeExtended {
     }
 }
 
+/// The file type recorded in a directory entry, which mirrors the type of
+/// the inode it points to.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum DirentFileType {
     Unknown = 0,
     RegularFile = 1,
@@ -337,6 +918,55 @@ impl DirentFileType {
     pub fn is_symlink(&self) -> bool {
         matches!(self, Self::Symlink)
     }
+
+    pub fn is_block_device(&self) -> bool {
+        matches!(self, Self::BlockDevice)
+    }
+
+    pub fn is_char_device(&self) -> bool {
+        matches!(self, Self::CharacterDevice)
+    }
+
+    pub fn is_fifo(&self) -> bool {
+        matches!(self, Self::Fifo)
+    }
+
+    pub fn is_socket(&self) -> bool {
+        matches!(self, Self::Socket)
+    }
+}
+
+/// Formats the file type as the character `ls -l` shows for it: `-`, `d`,
+/// `c`, `b`, `p`, `s` or `l`, and `?` for an unknown type.
+impl fmt::Display for DirentFileType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let c = match self {
+            Self::Unknown => '?',
+            Self::RegularFile => '-',
+            Self::Directory => 'd',
+            Self::CharacterDevice => 'c',
+            Self::BlockDevice => 'b',
+            Self::Fifo => 'p',
+            Self::Socket => 's',
+            Self::Symlink => 'l',
+        };
+        fmt::Write::write_char(f, c)
+    }
+}
+
+impl From<FileType> for DirentFileType {
+    fn from(file_type: FileType) -> Self {
+        match file_type {
+            FileType::RegularFile => Self::RegularFile,
+            FileType::Directory => Self::Directory,
+            FileType::CharacterDevice => Self::CharacterDevice,
+            FileType::BlockDevice => Self::BlockDevice,
+            FileType::Fifo => Self::Fifo,
+            FileType::Socket => Self::Socket,
+            FileType::Symlink => Self::Symlink,
+            _ => Self::Unknown,
+        }
+    }
 }
 
 impl TryFrom<u8> for DirentFileType {
@@ -357,12 +987,17 @@ impl TryFrom<u8> for DirentFileType {
     }
 }
 
+/// An on-disk directory entry, as stored at the start of a directory block.
 #[repr(C, packed)]
 #[derive(Debug, Clone, Copy, Default, BinRead)]
 #[br(little)]
 pub struct Dirent {
+    /// Node id of the entry's inode.
     pub nid: u64,
+    /// Offset of the entry's name within the block. Names are stored back
+    /// to back, so each one ends where the next one starts.
     pub name_off: u16,
+    /// Raw file type, see [`DirentFileType`].
     pub file_type: u8,
     pub reserved: u8,
 }
@@ -395,6 +1030,26 @@ impl ChunkBasedFormat {
     }
 }
 
+/// An entry of the chunk index array that follows chunk-based inodes using
+/// [`LAYOUT_CHUNK_FORMAT_INDEXES`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, BinRead)]
+#[br(little)]
+pub struct ChunkIndex {
+    /// High bits of the start block, only used by 48-bit block addresses.
+    pub startblk_hi: u16,
+    /// The device holding the chunk, 0 being the primary image.
+    pub device_id: u16,
+    pub blkaddr: u32,
+}
+
+impl ChunkIndex {
+    #[inline]
+    pub const fn size() -> usize {
+        size_of::<Self>()
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy, BinRead)]
 #[br(little)]
@@ -404,6 +1059,13 @@ pub struct XattrHeader {
     pub reserved: [u8; 7],
 }
 
+impl XattrHeader {
+    #[inline]
+    pub const fn size() -> usize {
+        size_of::<Self>()
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy, BinRead)]
 #[br(little)]
@@ -413,6 +1075,13 @@ pub struct XattrEntry {
     pub value_len: u16,
 }
 
+impl XattrEntry {
+    #[inline]
+    pub const fn size() -> usize {
+        size_of::<Self>()
+    }
+}
+
 #[repr(C, packed)]
 #[derive(Debug, Clone, Copy, BinRead)]
 #[br(little)]
@@ -451,7 +1120,45 @@ impl MapHeader {
         size_of::<Self>()
     }
 
+    /// Offset of the tail fragment in the packed inode, which overlays the
+    /// first four bytes of the header.
     pub fn fragmentoff(&self) -> u32 {
-        u32::from_le((self._reserved as u32) << 16 | u32::from(self.data_size))
+        (self.data_size as u32) << 16 | self._reserved as u32
+    }
+
+    /// Offset in the packed inode of a file packed there as a whole, which
+    /// takes up the entire header apart from the flag bit.
+    pub fn fragment_inode_offset(&self) -> u64 {
+        let header = (self.clusterbits as u64) << 56
+            | (self.algorithmtype as u64) << 48
+            | (self.advise as u64) << 32
+            | self.fragmentoff() as u64;
+        header & !(1 << 63)
+    }
+}
+
+/// A full (non-compacted) logical cluster index of a compressed inode.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, BinRead)]
+#[br(little)]
+pub struct LclusterIndex {
+    pub advise: u16,
+    pub clusterofs: u16,
+    // blkaddr for HEAD lclusters, delta[0] and delta[1] for NONHEAD ones
+    pub data: u32,
+}
+
+impl LclusterIndex {
+    #[inline]
+    pub const fn size() -> usize {
+        size_of::<Self>()
+    }
+
+    pub fn cluster_type(&self) -> u8 {
+        (self.advise & LCLUSTER_TYPE_MASK) as u8
+    }
+
+    pub fn delta(&self) -> [u16; 2] {
+        [self.data as u16, (self.data >> 16) as u16]
     }
 }
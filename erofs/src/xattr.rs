@@ -0,0 +1,125 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use binrw::BinRead;
+use binrw::io::Cursor;
+use bytes::Bytes;
+
+use crate::types::*;
+use crate::{Error, Result};
+
+/// A decoded `(name, value)` attribute pair.
+type Xattr = (String, Bytes);
+
+/// An iterator over the extended attributes of an inode.
+///
+/// Created by [`EroFS::xattrs`](crate::EroFS::xattrs). Each item is a
+/// `(name, value)` pair whose name carries its full prefix, e.g.
+/// `security.selinux`. Inline attributes are yielded before shared ones.
+#[derive(Debug, Clone, Default)]
+pub struct Xattrs {
+    inner: alloc::vec::IntoIter<Xattr>,
+}
+
+impl Xattrs {
+    pub(crate) fn new(xattrs: Vec<Xattr>) -> Self {
+        Self {
+            inner: xattrs.into_iter(),
+        }
+    }
+}
+
+impl Iterator for Xattrs {
+    type Item = (String, Bytes);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Xattrs {}
+
+/// Parses the inline xattr region following an inode.
+///
+/// Returns the IDs of the shared attributes it references, followed by the
+/// attributes stored inline.
+pub fn parse_inline(data: &[u8]) -> Result<(Vec<u32>, Vec<Xattr>)> {
+    let shared = parse_shared_ids(data)?;
+    let ids_end = XattrHeader::size() + shared.len() * 4;
+
+    let mut inline = Vec::new();
+    let mut pos = ids_end;
+    while pos + XattrEntry::size() <= data.len() {
+        let len = entry_len(&data[pos..])?;
+        let entry = data
+            .get(pos..pos + len)
+            .ok_or_else(|| Error::CorruptedData("xattr entry exceeds xattr region".to_string()))?;
+        inline.push(parse_entry(entry)?);
+        pos += len.next_multiple_of(4);
+    }
+    Ok((shared, inline))
+}
+
+/// Parses the IDs of the shared attributes referenced by the inline xattr
+/// region following an inode.
+pub fn parse_shared_ids(data: &[u8]) -> Result<Vec<u32>> {
+    let header = XattrHeader::read(&mut Cursor::new(data))?;
+    let ids_end = XattrHeader::size() + header.shared_count as usize * 4;
+    let ids = data
+        .get(XattrHeader::size()..ids_end)
+        .ok_or_else(|| Error::CorruptedData("shared xattr ids exceed xattr region".to_string()))?;
+    Ok(ids
+        .chunks_exact(4)
+        .map(|id| u32::from_le_bytes([id[0], id[1], id[2], id[3]]))
+        .collect())
+}
+
+/// Returns the total length of the xattr entry starting at `data`.
+pub fn entry_len(data: &[u8]) -> Result<usize> {
+    let entry = XattrEntry::read(&mut Cursor::new(data))?;
+    Ok(XattrEntry::size() + entry.name_len as usize + entry.value_len as usize)
+}
+
+/// Decodes a single xattr entry into its full name and value.
+pub fn parse_entry(data: &[u8]) -> Result<Xattr> {
+    let entry = XattrEntry::read(&mut Cursor::new(data))?;
+    let name_end = XattrEntry::size() + entry.name_len as usize;
+    let value_end = name_end + entry.value_len as usize;
+    let (Some(name), Some(value)) = (
+        data.get(XattrEntry::size()..name_end),
+        data.get(name_end..value_end),
+    ) else {
+        return Err(Error::CorruptedData("truncated xattr entry".to_string()));
+    };
+
+    let mut full_name = prefix(entry.name_index)?.to_string();
+    full_name.push_str(&String::from_utf8_lossy(name));
+    Ok((full_name, Bytes::copy_from_slice(value)))
+}
+
+/// Maps an xattr name index to the prefix it stands for.
+fn prefix(name_index: u8) -> Result<&'static str> {
+    if name_index & XATTR_LONG_PREFIX != 0 {
+        return Err(Error::NotSupported("long xattr name prefixes".to_string()));
+    }
+
+    match name_index {
+        XATTR_INDEX_USER => Ok("user."),
+        XATTR_INDEX_POSIX_ACL_ACCESS => Ok("system.posix_acl_access"),
+        XATTR_INDEX_POSIX_ACL_DEFAULT => Ok("system.posix_acl_default"),
+        XATTR_INDEX_TRUSTED => Ok("trusted."),
+        XATTR_INDEX_LUSTRE => Ok("lustre."),
+        XATTR_INDEX_SECURITY => Ok("security."),
+        _ => Err(Error::CorruptedData(format!(
+            "invalid xattr name index {}",
+            name_index
+        ))),
+    }
+}
@@ -0,0 +1,958 @@
+use alloc::{format, string::ToString, vec::Vec};
+
+use binrw::BinRead;
+use binrw::io::Cursor;
+
+use crate::filesystem::EroFSCore;
+use crate::types::*;
+use crate::{Error, Result};
+
+/// How the physical cluster of an [`Extent`] is encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ExtentFormat {
+    /// Uncompressed, stored from the start of the physical cluster.
+    Shifted,
+    /// Uncompressed, rotated by the logical offset within the block.
+    Interlaced,
+    /// Compressed with the given algorithm.
+    Compressed(CompressionAlgo),
+    /// Uncompressed, stored in the packed inode shared by the tails of
+    /// many files.
+    Fragment,
+}
+
+/// A range of file data decoded from a single physical cluster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Extent {
+    /// Logical start offset within the file.
+    pub la: usize,
+    /// Logical (decompressed) length.
+    pub llen: usize,
+    /// Absolute image offset of the physical cluster, or the offset within
+    /// the packed inode for [`ExtentFormat::Fragment`].
+    pub pa: usize,
+    /// Physical (compressed) length.
+    pub plen: usize,
+    pub format: ExtentFormat,
+    /// The physical cluster decodes to more data than this extent uses.
+    pub partial: bool,
+}
+
+/// A decoded logical cluster index.
+#[derive(Debug, Clone, Copy, Default)]
+struct Lcluster {
+    lcn: usize,
+    cluster_type: u8,
+    clusterofs: usize,
+    delta: [usize; 2],
+    pblk: u32,
+    // Size in blocks of the big pcluster headed by the previous lcluster,
+    // if this is the first non-head lcluster following it.
+    compressed_blocks: usize,
+    partial: bool,
+    // Offset right after the index pack holding this lcluster, relative to
+    // the map header.
+    next_pack: usize,
+}
+
+/// Logical to physical mapping of a compressed inode.
+///
+/// `data` holds the map header, the lcluster indexes and the inline tail
+/// pcluster (if any), starting at the absolute image offset `base`.
+pub struct ZMap<'a> {
+    core: &'a EroFSCore,
+    data: &'a [u8],
+    base: usize,
+    compact: bool,
+    header: MapHeader,
+    lclusterbits: usize,
+    size: usize,
+    // Head lcn and relative offset of the inline tail pcluster.
+    tail: Option<(usize, usize)>,
+    // Head lcn of the tail extent stored in the packed inode and the
+    // offset of that data in the packed inode.
+    fragment: Option<(usize, usize)>,
+}
+
+impl<'a> ZMap<'a> {
+    /// Absolute image offset of the map header of a compressed inode.
+    pub(crate) fn header_offset(core: &EroFSCore, inode: &Inode) -> usize {
+        let end = core.get_inode_offset(inode.id()) as usize + inode.size() + inode.xattr_len();
+        end.next_multiple_of(8)
+    }
+
+    /// Reads the compression parameters from the raw map header, or `None`
+    /// if the whole file is stored in the packed inode.
+    ///
+    /// Unlike [`ZMap::new`] this accepts any header, so it also works for
+    /// inodes whose data can't be read yet.
+    pub(crate) fn compression(core: &EroFSCore, header: &[u8]) -> Result<Option<Compression>> {
+        let header = MapHeader::read(&mut Cursor::new(header))?;
+        if Self::is_fragment_inode(&header) {
+            return Ok(None);
+        }
+        Ok(Some(Compression {
+            algorithm: (header.algorithmtype & 0x0f).try_into()?,
+            lcluster_bits: core.super_block.blk_size_bits + (header.clusterbits & 0x0f),
+        }))
+    }
+
+    /// Number of bytes starting at the map header that [`ZMap::new`] needs,
+    /// computed from the raw map header.
+    pub(crate) fn data_len(core: &EroFSCore, inode: &Inode, header: &[u8]) -> Result<usize> {
+        let header = MapHeader::read(&mut Cursor::new(header))?;
+        let lclusterbits = Self::check_header(core, inode, &header)?;
+        if Self::is_fragment_inode(&header) {
+            return Ok(MapHeader::size());
+        }
+
+        let index_len = match inode.layout()? {
+            Layout::CompressedFull => {
+                let count = inode.data_size().div_ceil(1 << lclusterbits);
+                MapHeader::size() + 8 + count * LclusterIndex::size()
+            }
+            _ => {
+                let base = Self::header_offset(core, inode);
+                let (initial, compacted_2b, rest) =
+                    Self::compacted_counts(core, inode.data_size(), &header, base);
+                MapHeader::size() + initial * 4 + compacted_2b * 2 + rest.next_multiple_of(2) * 4
+            }
+        };
+
+        if header.advise & MAP_ADVISE_INLINE_PCLUSTER != 0 {
+            Ok(index_len + header.data_size as usize)
+        } else {
+            Ok(index_len)
+        }
+    }
+
+    pub(crate) fn new(
+        core: &'a EroFSCore,
+        inode: &Inode,
+        base: usize,
+        data: &'a [u8],
+    ) -> Result<Self> {
+        let header = MapHeader::read(&mut Cursor::new(data))?;
+        let lclusterbits = Self::check_header(core, inode, &header)?;
+
+        let mut zmap = Self {
+            core,
+            data,
+            base,
+            compact: inode.layout()? == Layout::CompressedCompact,
+            header,
+            lclusterbits,
+            size: inode.data_size(),
+            tail: None,
+            fragment: None,
+        };
+
+        if Self::is_fragment_inode(&header) {
+            let offset = usize::try_from(header.fragment_inode_offset()).map_err(|_| {
+                Error::CorruptedData(format!(
+                    "fragment offset out of range for nid {}",
+                    inode.id()
+                ))
+            })?;
+            zmap.fragment = Some((0, offset));
+            return Ok(zmap);
+        }
+
+        if header.advise & MAP_ADVISE_INLINE_PCLUSTER != 0 && zmap.size != 0 {
+            let last = zmap.size - 1;
+            let offset = zmap.load(last >> lclusterbits, false)?.next_pack;
+            let (head, _) = zmap.locate(last)?;
+
+            let idata_size = header.data_size as usize;
+            let block_offset = (base + offset) % core.block_size;
+            if idata_size == 0 || block_offset + idata_size > core.block_size {
+                return Err(Error::CorruptedData(format!(
+                    "invalid inline pcluster of {} bytes for nid {}",
+                    idata_size,
+                    inode.id()
+                )));
+            }
+            zmap.tail = Some((head.lcn, offset));
+        }
+
+        if header.advise & MAP_ADVISE_FRAGMENT_PCLUSTER != 0 && zmap.size != 0 {
+            let (head, _) = zmap.locate(zmap.size - 1)?;
+            zmap.fragment = Some((head.lcn, header.fragmentoff() as usize));
+        }
+
+        Ok(zmap)
+    }
+
+    /// Whether the whole file is stored in the packed inode, in which case
+    /// the rest of the map header holds its offset there instead of flags.
+    fn is_fragment_inode(header: &MapHeader) -> bool {
+        header.clusterbits >> MAP_FRAGMENT_INODE_BIT != 0
+    }
+
+    /// Validates the map header and returns the logical cluster bits.
+    fn check_header(core: &EroFSCore, inode: &Inode, header: &MapHeader) -> Result<usize> {
+        let fragment_inode = Self::is_fragment_inode(header);
+        if fragment_inode || header.advise & MAP_ADVISE_FRAGMENT_PCLUSTER != 0 {
+            if core.super_block.feature_incompat & FEATURE_INCOMPAT_FRAGMENTS == 0 {
+                return Err(Error::CorruptedData(format!(
+                    "nid {} references a fragment without the fragments feature",
+                    inode.id()
+                )));
+            }
+            if inode.id() == core.super_block.packed_nid {
+                return Err(Error::UnsupportedFeature(
+                    "fragments within the packed inode".to_string(),
+                ));
+            }
+        }
+        if fragment_inode {
+            return Ok(core.super_block.blk_size_bits as usize);
+        }
+        if header.advise & MAP_ADVISE_FRAGMENT_PCLUSTER != 0
+            && header.advise & MAP_ADVISE_INLINE_PCLUSTER != 0
+        {
+            return Err(Error::CorruptedData(format!(
+                "nid {} has both an inline and a fragment tail",
+                inode.id()
+            )));
+        }
+        let big_pcluster = header.advise & (MAP_ADVISE_BIG_PCLUSTER_1 | MAP_ADVISE_BIG_PCLUSTER_2);
+        if big_pcluster != 0
+            && core.super_block.feature_incompat & FEATURE_INCOMPAT_BIG_PCLUSTER == 0
+        {
+            return Err(Error::CorruptedData(format!(
+                "nid {} uses big pclusters without the big pcluster feature",
+                inode.id()
+            )));
+        }
+        // Compacted indexes only locate the blocks of big pclusters when
+        // both head types may have them.
+        if inode.layout()? == Layout::CompressedCompact
+            && big_pcluster != 0
+            && big_pcluster != MAP_ADVISE_BIG_PCLUSTER_1 | MAP_ADVISE_BIG_PCLUSTER_2
+        {
+            return Err(Error::UnsupportedFeature(format!(
+                "big pclusters for one head type only with compacted indexes (nid {})",
+                inode.id()
+            )));
+        }
+
+        let lclusterbits =
+            core.super_block.blk_size_bits as usize + (header.clusterbits & 0x0f) as usize;
+        if inode.layout()? == Layout::CompressedCompact && lclusterbits > 14 {
+            return Err(Error::CorruptedData(format!(
+                "invalid logical cluster bits {} for compacted indexes of nid {}",
+                lclusterbits,
+                inode.id()
+            )));
+        }
+        Ok(lclusterbits)
+    }
+
+    /// Returns the extents overlapping the logical range `start..end`.
+    pub(crate) fn extents(&self, start: usize, end: usize) -> Result<Vec<Extent>> {
+        let mut extents = Vec::new();
+        let mut pos = start;
+        while pos < end {
+            let extent = self.map(pos)?;
+            pos = extent.la + extent.llen;
+            extents.push(extent);
+        }
+        Ok(extents)
+    }
+
+    /// Maps the logical offset `offset` to the extent containing it.
+    pub(crate) fn map(&self, offset: usize) -> Result<Extent> {
+        if let Some((_, pa)) = self.fragment
+            && Self::is_fragment_inode(&self.header)
+        {
+            return Ok(Self::fragment_extent(0, self.size, pa));
+        }
+
+        let (head, la) = self.locate(offset)?;
+        let llen = self.decompressed_len(head.lcn, la)?;
+        if la + llen <= offset {
+            return Err(Error::CorruptedData(format!(
+                "extent at {} does not cover offset {}",
+                la, offset
+            )));
+        }
+        if let Some((lcn, pa)) = self.fragment
+            && lcn == head.lcn
+        {
+            return Ok(Self::fragment_extent(la, llen, pa));
+        }
+
+        let (pa, plen) = match self.tail {
+            Some((lcn, idata_offset)) if lcn == head.lcn => {
+                (self.base + idata_offset, self.header.data_size as usize)
+            }
+            _ => (
+                self.core.block_offset(head.pblk) as usize,
+                self.compressed_len(&head)?,
+            ),
+        };
+
+        let format = match head.cluster_type {
+            LCLUSTER_TYPE_PLAIN => {
+                if llen > plen {
+                    return Err(Error::CorruptedData(format!(
+                        "plain extent of {} bytes exceeds its {}-byte pcluster",
+                        llen, plen
+                    )));
+                }
+                if self.header.advise & MAP_ADVISE_INTERLACED_PCLUSTER != 0 {
+                    ExtentFormat::Interlaced
+                } else {
+                    ExtentFormat::Shifted
+                }
+            }
+            LCLUSTER_TYPE_HEAD1 => {
+                ExtentFormat::Compressed((self.header.algorithmtype & 0x0f).try_into()?)
+            }
+            _ => ExtentFormat::Compressed((self.header.algorithmtype >> 4).try_into()?),
+        };
+
+        Ok(Extent {
+            la,
+            llen,
+            pa,
+            plen,
+            format,
+            partial: head.partial,
+        })
+    }
+
+    fn fragment_extent(la: usize, llen: usize, pa: usize) -> Extent {
+        Extent {
+            la,
+            llen,
+            pa,
+            plen: llen,
+            format: ExtentFormat::Fragment,
+            partial: false,
+        }
+    }
+
+    /// Finds the head lcluster of the extent containing `offset`, together
+    /// with the extent's logical start.
+    fn locate(&self, offset: usize) -> Result<(Lcluster, usize)> {
+        let lclusterbits = self.lclusterbits;
+        let mut m = self.load(offset >> lclusterbits, false)?;
+        let endoff = offset & ((1 << lclusterbits) - 1);
+
+        let mut distance = match m.cluster_type {
+            LCLUSTER_TYPE_NONHEAD => m.delta[0],
+            _ if endoff >= m.clusterofs => return Ok((m, (m.lcn << lclusterbits) | m.clusterofs)),
+            // The offset belongs to the extent ending in this lcluster.
+            _ => 1,
+        };
+
+        loop {
+            if distance == 0 || distance > m.lcn {
+                return Err(Error::CorruptedData(format!(
+                    "bogus lookback distance {} at lcn {}",
+                    distance, m.lcn
+                )));
+            }
+
+            m = self.load(m.lcn - distance, false)?;
+            if m.cluster_type != LCLUSTER_TYPE_NONHEAD {
+                return Ok((m, (m.lcn << lclusterbits) | m.clusterofs));
+            }
+            distance = m.delta[0];
+        }
+    }
+
+    /// Returns the physical length of the pcluster headed by `head`, which
+    /// spans a single block unless big pclusters are enabled for its type.
+    fn compressed_len(&self, head: &Lcluster) -> Result<usize> {
+        let big = match head.cluster_type {
+            LCLUSTER_TYPE_HEAD1 => MAP_ADVISE_BIG_PCLUSTER_1,
+            _ => MAP_ADVISE_BIG_PCLUSTER_2,
+        };
+        let lcn = head.lcn + 1;
+        if self.header.advise & big == 0 || lcn << self.lclusterbits >= self.size {
+            return Ok(self.core.block_size);
+        }
+
+        // The size is stored in the first non-head lcluster following the
+        // head; a head right after it means a single-block pcluster.
+        let m = self.load(lcn, false)?;
+        match m.cluster_type {
+            LCLUSTER_TYPE_NONHEAD if m.delta[0] == 1 && m.compressed_blocks != 0 => {
+                Ok(m.compressed_blocks << self.core.super_block.blk_size_bits)
+            }
+            LCLUSTER_TYPE_NONHEAD => Err(Error::CorruptedData(format!(
+                "missing compressed block count at lcn {}",
+                lcn
+            ))),
+            _ => Ok(self.core.block_size),
+        }
+    }
+
+    /// Walks forward from the head lcluster to the next head (or EOF) to
+    /// find the full decompressed length of the extent starting at `la`.
+    fn decompressed_len(&self, head_lcn: usize, la: usize) -> Result<usize> {
+        let lclusterbits = self.lclusterbits;
+        let mut lcn = head_lcn;
+        let end = loop {
+            if lcn << lclusterbits >= self.size {
+                break self.size;
+            }
+
+            let m = self.load(lcn, true)?;
+            if m.cluster_type == LCLUSTER_TYPE_NONHEAD {
+                lcn += m.delta[1].max(1);
+            } else if lcn != head_lcn {
+                break (lcn << lclusterbits) + m.clusterofs;
+            } else {
+                lcn += 1;
+            }
+        };
+
+        end.checked_sub(la)
+            .filter(|&llen| llen > 0)
+            .ok_or_else(|| Error::CorruptedData(format!("empty extent at {}", la)))
+    }
+
+    fn load(&self, lcn: usize, lookahead: bool) -> Result<Lcluster> {
+        if self.compact {
+            self.load_compact(lcn, lookahead)
+        } else {
+            self.load_full(lcn)
+        }
+    }
+
+    fn load_full(&self, lcn: usize) -> Result<Lcluster> {
+        let pos = MapHeader::size() + 8 + lcn * LclusterIndex::size();
+        let data = self
+            .data
+            .get(pos..pos + LclusterIndex::size())
+            .ok_or_else(|| {
+                Error::CorruptedData(format!("lcluster index {} is outside the map", lcn))
+            })?;
+        let index = LclusterIndex::read(&mut Cursor::new(data))?;
+
+        let mut m = Lcluster {
+            lcn,
+            cluster_type: index.cluster_type(),
+            next_pack: pos + LclusterIndex::size(),
+            ..Default::default()
+        };
+        if m.cluster_type == LCLUSTER_TYPE_NONHEAD {
+            let delta = index.delta();
+            m.clusterofs = 1 << self.lclusterbits;
+            m.delta = [delta[0] as usize, delta[1] as usize];
+            if delta[0] & LI_D0_CBLKCNT != 0 {
+                if !self.has_big_pcluster() {
+                    return Err(Error::CorruptedData(format!(
+                        "unexpected compressed block count at lcn {}",
+                        lcn
+                    )));
+                }
+                m.compressed_blocks = (delta[0] & !LI_D0_CBLKCNT) as usize;
+                m.delta[0] = 1;
+            }
+        } else {
+            m.partial = index.advise & LI_PARTIAL_REF != 0;
+            m.clusterofs = index.clusterofs as usize;
+            m.pblk = index.data;
+            if m.clusterofs >= 1 << self.lclusterbits {
+                return Err(Error::CorruptedData(format!(
+                    "invalid cluster offset {} at lcn {}",
+                    m.clusterofs, lcn
+                )));
+            }
+        }
+        Ok(m)
+    }
+
+    /// Whether some pclusters of the inode may span more than one block.
+    fn has_big_pcluster(&self) -> bool {
+        self.header.advise & (MAP_ADVISE_BIG_PCLUSTER_1 | MAP_ADVISE_BIG_PCLUSTER_2) != 0
+    }
+
+    fn load_compact(&self, lcn: usize, lookahead: bool) -> Result<Lcluster> {
+        let (initial, compacted_2b, rest) =
+            Self::compacted_counts(self.core, self.size, &self.header, self.base);
+        if lcn >= initial + compacted_2b + rest {
+            return Err(Error::OutOfRange(lcn, initial + compacted_2b + rest));
+        }
+
+        let mut pos = MapHeader::size();
+        let (amortizedshift, index) = if lcn < initial {
+            (2, lcn)
+        } else if lcn - initial < compacted_2b {
+            pos += initial * 4;
+            (1, lcn - initial)
+        } else {
+            pos += initial * 4 + compacted_2b * 2;
+            (2, lcn - initial - compacted_2b)
+        };
+        pos += index << amortizedshift;
+
+        self.unpack_compacted_index(lcn, amortizedshift, pos, lookahead)
+    }
+
+    /// Number of 4B-initial, 2B and 4B-trailing compacted indexes.
+    fn compacted_counts(
+        core: &EroFSCore,
+        size: usize,
+        header: &MapHeader,
+        base: usize,
+    ) -> (usize, usize, usize) {
+        let total = size.div_ceil(core.block_size);
+        let ebase = base + MapHeader::size();
+
+        // 4B indexes pad the start of the 2B area to 32-byte alignment.
+        let initial = (32 - ebase % 32) / 4 % 8;
+        if initial > total {
+            return (0, 0, total);
+        }
+        let compacted_2b = if header.advise & MAP_ADVISE_COMPACTED_2B != 0 && initial < total {
+            (total - initial) / 16 * 16
+        } else {
+            0
+        };
+        (initial, compacted_2b, total - initial - compacted_2b)
+    }
+
+    fn unpack_compacted_index(
+        &self,
+        lcn: usize,
+        amortizedshift: usize,
+        pos: usize,
+        lookahead: bool,
+    ) -> Result<Lcluster> {
+        let lclusterbits = self.lclusterbits;
+        let vcnt: usize = match amortizedshift {
+            2 if lclusterbits <= 14 => 2,
+            1 if lclusterbits <= 12 => 16,
+            _ => {
+                return Err(Error::CorruptedData(format!(
+                    "invalid compacted index at lcn {}",
+                    lcn
+                )));
+            }
+        };
+
+        // Packs are aligned on the absolute image offset.
+        let pack_size = vcnt << amortizedshift;
+        let abs = self.base + pos;
+        let pack_start = pos - abs % pack_size;
+        let pack = self
+            .data
+            .get(pack_start..pack_start + pack_size)
+            .ok_or_else(|| {
+                Error::CorruptedData(format!(
+                    "compacted index pack of lcn {} is outside the map",
+                    lcn
+                ))
+            })?;
+
+        let lobits = lclusterbits.max(LI_D0_CBLKCNT.ilog2() as usize + 1);
+        let encodebits = (pack_size - 4) * 8 / vcnt;
+        let i = (abs % pack_size) >> amortizedshift;
+        let decode = |i: usize| decode_compacted_bits(pack, lobits, encodebits * i);
+
+        let mut m = Lcluster {
+            lcn,
+            next_pack: pack_start + pack_size,
+            ..Default::default()
+        };
+        let (lo, cluster_type) = decode(i);
+        m.cluster_type = cluster_type;
+
+        if cluster_type == LCLUSTER_TYPE_NONHEAD {
+            m.clusterofs = 1 << lclusterbits;
+            if lookahead {
+                m.delta[1] = compacted_lookahead_distance(pack, lobits, encodebits, vcnt, i);
+            }
+            if lo & LI_D0_CBLKCNT as usize != 0 {
+                if !self.has_big_pcluster() {
+                    return Err(Error::CorruptedData(format!(
+                        "unexpected compressed block count at lcn {}",
+                        lcn
+                    )));
+                }
+                m.compressed_blocks = lo & !(LI_D0_CBLKCNT as usize);
+                m.delta[0] = 1;
+                return Ok(m);
+            }
+            m.delta[0] = if i + 1 != vcnt {
+                lo
+            } else {
+                // The last lcluster of a pack stores delta[1] instead, so
+                // derive delta[0] from the previous one.
+                match decode(i - 1) {
+                    (lo, LCLUSTER_TYPE_NONHEAD) if lo & LI_D0_CBLKCNT as usize != 0 => 2,
+                    (lo, LCLUSTER_TYPE_NONHEAD) => lo + 1,
+                    _ => 1,
+                }
+            };
+            return Ok(m);
+        }
+
+        m.clusterofs = lo;
+        let nblk = if self.has_big_pcluster() {
+            compacted_big_pcluster_blocks(pack, lobits, encodebits, i, lcn)?
+        } else {
+            // Every head lcluster before this one in the pack owns one block.
+            let mut nblk = 1;
+            let mut j = i as isize;
+            while j > 0 {
+                j -= 1;
+                let (lo, cluster_type) = decode(j as usize);
+                if cluster_type == LCLUSTER_TYPE_NONHEAD {
+                    j -= lo as isize;
+                }
+                if j >= 0 {
+                    nblk += 1;
+                }
+            }
+            nblk
+        };
+
+        let blkaddr = u32::from_le_bytes(pack[pack_size - 4..].try_into().unwrap());
+        m.pblk = blkaddr.wrapping_add(nblk);
+        Ok(m)
+    }
+}
+
+/// Decodes the compacted index at bit offset `pos` into its low bits and
+/// lcluster type.
+fn decode_compacted_bits(pack: &[u8], lobits: usize, pos: usize) -> (usize, u8) {
+    let mut buf = [0u8; 4];
+    let start = pos / 8;
+    let end = pack.len().min(start + 4);
+    buf[..end - start].copy_from_slice(&pack[start..end]);
+    let v = u32::from_le_bytes(buf) >> (pos & 7);
+    (
+        (v & ((1 << lobits) - 1)) as usize,
+        ((v >> lobits) & 3) as u8,
+    )
+}
+
+/// Number of blocks taken by the pclusters headed before the `i`-th
+/// lcluster of a pack, for inodes with big pclusters.
+///
+/// Heads of single-block pclusters count one block each, while big ones
+/// are skipped over and counted by the block count stored in the
+/// lcluster following them.
+fn compacted_big_pcluster_blocks(
+    pack: &[u8],
+    lobits: usize,
+    encodebits: usize,
+    i: usize,
+    lcn: usize,
+) -> Result<u32> {
+    let mut nblk = 0u32;
+    let mut j = i as isize;
+    while j > 0 {
+        j -= 1;
+        let (lo, cluster_type) = decode_compacted_bits(pack, lobits, encodebits * j as usize);
+        if cluster_type != LCLUSTER_TYPE_NONHEAD {
+            nblk = nblk.wrapping_add(1);
+        } else if lo & LI_D0_CBLKCNT as usize != 0 {
+            // Skip the head this block count belongs to.
+            j -= 1;
+            nblk = nblk.wrapping_add((lo & !(LI_D0_CBLKCNT as usize)) as u32);
+        } else if lo <= 1 {
+            // The lcluster right after a big pcluster head holds its block
+            // count rather than a distance of 1.
+            return Err(Error::CorruptedData(format!(
+                "bogus lookback distance {} before lcn {}",
+                lo, lcn
+            )));
+        } else {
+            // Jump to the lcluster holding the block count.
+            j -= lo as isize - 2;
+        }
+    }
+    Ok(nblk)
+}
+
+/// Distance from the `i`-th lcluster of a pack to the next head lcluster.
+fn compacted_lookahead_distance(
+    pack: &[u8],
+    lobits: usize,
+    encodebits: usize,
+    vcnt: usize,
+    mut i: usize,
+) -> usize {
+    let mut distance = 0;
+    let mut lo;
+    loop {
+        let (bits, cluster_type) = decode_compacted_bits(pack, lobits, encodebits * i);
+        if cluster_type != LCLUSTER_TYPE_NONHEAD {
+            return distance;
+        }
+        lo = bits;
+        distance += 1;
+        i += 1;
+        if i >= vcnt {
+            break;
+        }
+    }
+
+    // The last lcluster of the pack holds the remaining distance.
+    if lo & LI_D0_CBLKCNT as usize == 0 {
+        distance += lo.saturating_sub(1);
+    }
+    distance
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    const BLOCK_SIZE: usize = 4096;
+
+    /// A core for a 4 KiB-block image with the given incompatible features.
+    fn core(feature_incompat: u32) -> EroFSCore {
+        let mut sb = vec![0u8; SuperBlock::size()];
+        sb[..4].copy_from_slice(&MAGIC_NUMBER.to_le_bytes());
+        sb[12] = BLOCK_SIZE.ilog2() as u8;
+        sb[36..40].copy_from_slice(&1024u32.to_le_bytes());
+        sb[80..84].copy_from_slice(&feature_incompat.to_le_bytes());
+        EroFSCore::new(&sb).unwrap()
+    }
+
+    fn inode(layout: Layout, lclusters: usize) -> Inode {
+        Inode::Compact((
+            0,
+            InodeCompact {
+                format: (layout as u16) << 1,
+                xattr_count: 0,
+                mode: 0o100644,
+                nlink: 1,
+                size: (lclusters * BLOCK_SIZE) as u32,
+                reserved: 0,
+                inode_data: 0,
+                inode: 0,
+                uid: 0,
+                gid: 0,
+                reserved2: 0,
+            },
+        ))
+    }
+
+    /// A map header for LZ4 HEAD1 and DEFLATE HEAD2 pclusters with one
+    /// block per lcluster.
+    fn header(advise: u16) -> Vec<u8> {
+        let mut header = vec![0u8; MapHeader::size()];
+        header[4..6].copy_from_slice(&advise.to_le_bytes());
+        header[6] = (CompressionAlgo::Deflate as u8) << 4 | CompressionAlgo::Lz4 as u8;
+        header
+    }
+
+    fn head_index(cluster_type: u8, clusterofs: u16, blkaddr: u32) -> Vec<u8> {
+        let mut index = Vec::new();
+        index.extend_from_slice(&u16::from(cluster_type).to_le_bytes());
+        index.extend_from_slice(&clusterofs.to_le_bytes());
+        index.extend_from_slice(&blkaddr.to_le_bytes());
+        index
+    }
+
+    fn nonhead_index(delta0: u16, delta1: u16) -> Vec<u8> {
+        let mut index = Vec::new();
+        index.extend_from_slice(&u16::from(LCLUSTER_TYPE_NONHEAD).to_le_bytes());
+        index.extend_from_slice(&0u16.to_le_bytes());
+        index.extend_from_slice(&delta0.to_le_bytes());
+        index.extend_from_slice(&delta1.to_le_bytes());
+        index
+    }
+
+    /// Packs the compacted `(type, lo)` indexes of 4 KiB lclusters into a
+    /// `pack_size`-byte pack ending with the block address `base`.
+    fn compact_pack(entries: &[(u8, u16)], pack_size: usize, base: u32) -> Vec<u8> {
+        let encodebits = (pack_size - 4) * 8 / entries.len();
+        let mut pack = vec![0u8; pack_size];
+        for (i, &(cluster_type, lo)) in entries.iter().enumerate() {
+            let value = u32::from(cluster_type) << 12 | u32::from(lo);
+            for bit in 0..14 {
+                let pos = encodebits * i + bit;
+                pack[pos / 8] |= (((value >> bit) & 1) as u8) << (pos % 8);
+            }
+        }
+        pack[pack_size - 4..].copy_from_slice(&base.to_le_bytes());
+        pack
+    }
+
+    fn extent(la: usize, llen: usize, pblk: usize, blocks: usize, format: ExtentFormat) -> Extent {
+        Extent {
+            la,
+            llen,
+            pa: pblk * BLOCK_SIZE,
+            plen: blocks * BLOCK_SIZE,
+            format,
+            partial: false,
+        }
+    }
+
+    const LZ4: ExtentFormat = ExtentFormat::Compressed(CompressionAlgo::Lz4);
+    const DEFLATE: ExtentFormat = ExtentFormat::Compressed(CompressionAlgo::Deflate);
+
+    #[test]
+    fn full_indexes() {
+        let core = core(0);
+        let inode = inode(Layout::CompressedFull, 4);
+        let data = [
+            header(0),
+            vec![0; 8],
+            head_index(LCLUSTER_TYPE_HEAD1, 0, 10),
+            nonhead_index(1, 1),
+            head_index(LCLUSTER_TYPE_PLAIN, 100, 11),
+            head_index(LCLUSTER_TYPE_HEAD2, 0, 12),
+        ]
+        .concat();
+        let zmap = ZMap::new(&core, &inode, 0x2020, &data).unwrap();
+
+        let expected = [
+            extent(0, 2 * BLOCK_SIZE + 100, 10, 1, LZ4),
+            extent(
+                2 * BLOCK_SIZE + 100,
+                BLOCK_SIZE - 100,
+                11,
+                1,
+                ExtentFormat::Shifted,
+            ),
+            extent(3 * BLOCK_SIZE, BLOCK_SIZE, 12, 1, DEFLATE),
+        ];
+        assert_eq!(zmap.extents(0, 4 * BLOCK_SIZE).unwrap(), expected);
+        // Offsets in a non-head lcluster, and before the cluster offset of
+        // a head, belong to the extent started earlier.
+        assert_eq!(zmap.map(BLOCK_SIZE + 1).unwrap(), expected[0]);
+        assert_eq!(zmap.map(2 * BLOCK_SIZE + 99).unwrap(), expected[0]);
+        assert_eq!(zmap.map(2 * BLOCK_SIZE + 100).unwrap(), expected[1]);
+    }
+
+    #[test]
+    fn full_indexes_big_pcluster() {
+        let core = core(FEATURE_INCOMPAT_BIG_PCLUSTER);
+        let inode = inode(Layout::CompressedFull, 4);
+        let data = [
+            header(MAP_ADVISE_BIG_PCLUSTER_1),
+            vec![0; 8],
+            head_index(LCLUSTER_TYPE_HEAD1, 0, 20),
+            nonhead_index(LI_D0_CBLKCNT | 3, 2),
+            nonhead_index(2, 1),
+            head_index(LCLUSTER_TYPE_HEAD1, 0, 23),
+        ]
+        .concat();
+        let zmap = ZMap::new(&core, &inode, 0x2020, &data).unwrap();
+        assert_eq!(
+            zmap.extents(0, 4 * BLOCK_SIZE).unwrap(),
+            [
+                extent(0, 3 * BLOCK_SIZE, 20, 3, LZ4),
+                extent(3 * BLOCK_SIZE, BLOCK_SIZE, 23, 1, LZ4),
+            ]
+        );
+        assert_eq!(zmap.map(2 * BLOCK_SIZE).unwrap().la, 0);
+
+        // A block count is only valid with big pclusters.
+        let data = [&header(0)[..], &data[MapHeader::size()..]].concat();
+        let zmap = ZMap::new(&core, &inode, 0x2020, &data).unwrap();
+        assert!(matches!(zmap.map(0), Err(Error::CorruptedData(_))));
+    }
+
+    #[test]
+    fn compacted_4b_indexes() {
+        let core = core(0);
+        let inode = inode(Layout::CompressedCompact, 6);
+        // The last lcluster of each pack stores the distance to the next
+        // head rather than to the previous one.
+        let data = [
+            header(0),
+            compact_pack(
+                &[(LCLUSTER_TYPE_HEAD1, 0), (LCLUSTER_TYPE_NONHEAD, 3)],
+                8,
+                29,
+            ),
+            compact_pack(
+                &[(LCLUSTER_TYPE_NONHEAD, 2), (LCLUSTER_TYPE_NONHEAD, 1)],
+                8,
+                0,
+            ),
+            compact_pack(
+                &[(LCLUSTER_TYPE_HEAD2, 300), (LCLUSTER_TYPE_PLAIN, 0)],
+                8,
+                40,
+            ),
+        ]
+        .concat();
+        let zmap = ZMap::new(&core, &inode, 0x2020, &data).unwrap();
+
+        let expected = [
+            extent(0, 4 * BLOCK_SIZE + 300, 30, 1, LZ4),
+            extent(4 * BLOCK_SIZE + 300, BLOCK_SIZE - 300, 41, 1, DEFLATE),
+            extent(5 * BLOCK_SIZE, BLOCK_SIZE, 42, 1, ExtentFormat::Shifted),
+        ];
+        assert_eq!(zmap.extents(0, 6 * BLOCK_SIZE).unwrap(), expected);
+        assert_eq!(zmap.map(BLOCK_SIZE).unwrap(), expected[0]);
+        assert_eq!(zmap.map(3 * BLOCK_SIZE).unwrap(), expected[0]);
+        assert_eq!(zmap.map(4 * BLOCK_SIZE + 299).unwrap(), expected[0]);
+    }
+
+    #[test]
+    fn compacted_2b_indexes() {
+        let core = core(0);
+        let inode = inode(Layout::CompressedCompact, 16);
+        let mut entries = vec![(LCLUSTER_TYPE_HEAD1, 0)];
+        entries.extend((1..5).map(|d| (LCLUSTER_TYPE_NONHEAD, d)));
+        entries.push((LCLUSTER_TYPE_HEAD2, 500));
+        entries.push((LCLUSTER_TYPE_HEAD1, 0));
+        entries.extend((1..9).map(|d| (LCLUSTER_TYPE_NONHEAD, d)));
+        // The last one holds the distance to the end of the file.
+        entries.push((LCLUSTER_TYPE_NONHEAD, 1));
+        // With the indexes starting 32-byte aligned, all of them are 2B.
+        let data = [
+            header(MAP_ADVISE_COMPACTED_2B),
+            compact_pack(&entries, 32, 40),
+        ]
+        .concat();
+        let zmap = ZMap::new(&core, &inode, 0x2018, &data).unwrap();
+
+        let expected = [
+            extent(0, 5 * BLOCK_SIZE + 500, 41, 1, LZ4),
+            extent(5 * BLOCK_SIZE + 500, BLOCK_SIZE - 500, 42, 1, DEFLATE),
+            extent(6 * BLOCK_SIZE, 10 * BLOCK_SIZE, 43, 1, LZ4),
+        ];
+        assert_eq!(zmap.extents(0, 16 * BLOCK_SIZE).unwrap(), expected);
+        assert_eq!(zmap.map(4 * BLOCK_SIZE).unwrap(), expected[0]);
+        assert_eq!(zmap.map(15 * BLOCK_SIZE + 7).unwrap(), expected[2]);
+    }
+
+    #[test]
+    fn compacted_indexes_big_pcluster() {
+        let core = core(FEATURE_INCOMPAT_BIG_PCLUSTER);
+        let inode = inode(Layout::CompressedCompact, 4);
+        // Heads of big pclusters are located from the block counts of the
+        // pclusters before them in the pack.
+        let data = [
+            header(MAP_ADVISE_BIG_PCLUSTER_1 | MAP_ADVISE_BIG_PCLUSTER_2),
+            compact_pack(
+                &[
+                    (LCLUSTER_TYPE_HEAD1, 0),
+                    (LCLUSTER_TYPE_NONHEAD, LI_D0_CBLKCNT | 2),
+                ],
+                8,
+                50,
+            ),
+            compact_pack(&[(LCLUSTER_TYPE_HEAD1, 0), (LCLUSTER_TYPE_HEAD1, 0)], 8, 52),
+        ]
+        .concat();
+        let zmap = ZMap::new(&core, &inode, 0x2020, &data).unwrap();
+        assert_eq!(
+            zmap.extents(0, 4 * BLOCK_SIZE).unwrap(),
+            [
+                extent(0, 2 * BLOCK_SIZE, 50, 2, LZ4),
+                extent(2 * BLOCK_SIZE, BLOCK_SIZE, 52, 1, LZ4),
+                extent(3 * BLOCK_SIZE, BLOCK_SIZE, 53, 1, LZ4),
+            ]
+        );
+    }
+}
@@ -0,0 +1,274 @@
+mod common;
+
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+
+use bytes::Bytes;
+use erofs_rs::EroFS;
+use erofs_rs::backend::{
+    BytesImage, ChainImage, FileImage, Image, MmapImage, OffsetImage, SliceImage, VecImage,
+};
+
+/// Writes `img` to a scratch file named `name`.
+fn write_image(name: &str, img: &[u8]) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::write(&path, img).unwrap();
+    path
+}
+
+#[test]
+fn file_image_reads_like_a_slice() {
+    let img = common::sample();
+    let image = FileImage::new_from_path(write_image("file_image.erofs", &img)).unwrap();
+
+    assert_eq!(image.get(1024..1028), Some(&img[1024..1028]));
+    assert_eq!(image.get(..), Some(&img[..]));
+    assert_eq!(image.get(img.len() - 1..img.len() + 1), None);
+    assert_eq!(
+        image.get_owned(4000..5000).as_deref(),
+        Some(&img[4000..5000])
+    );
+    assert_eq!(image.get_owned(img.len()..=img.len()), None);
+
+    let fs = EroFS::new(image).unwrap();
+    let mut motd = Vec::new();
+    fs.open("/etc/motd")
+        .unwrap()
+        .read_to_end(&mut motd)
+        .unwrap();
+    assert_eq!(motd, common::motd());
+    assert_eq!(fs.walk_dir("/").unwrap().count(), 5);
+}
+
+#[test]
+fn vec_image_owns_its_data() {
+    let img = common::sample();
+    let image = VecImage::new(img.clone());
+    assert_eq!(image.len(), img.len() as u64);
+    assert_eq!(image.get(1024..1028), Some(&img[1024..1028]));
+    assert_eq!(image.get(img.len()..=img.len()), None);
+    // Owned reads are views into the image, not copies.
+    let owned = image.get_owned(4000..5000).unwrap();
+    assert_eq!(owned, img[4000..5000]);
+    assert_eq!(owned.as_ptr(), image.get(4000..).unwrap().as_ptr());
+
+    // Nothing borrows `img`, so the filesystem can outlive it.
+    let fs: EroFS<VecImage> = EroFS::new(img.into()).unwrap();
+    let read = std::thread::spawn(move || {
+        let mut motd = Vec::new();
+        fs.open("/etc/motd")
+            .unwrap()
+            .read_to_end(&mut motd)
+            .unwrap();
+        motd
+    });
+    assert_eq!(read.join().unwrap(), common::motd());
+}
+
+#[test]
+fn get_array_checks_bounds_on_every_backend() {
+    let img = common::sample();
+    let magic = |image: &dyn Fn(usize) -> Option<[u8; 4]>| {
+        assert_eq!(image(1024), Some([0xe2, 0xe1, 0xf5, 0xe0]));
+        assert_eq!(image(img.len() - 4), Some([0; 4]));
+        assert_eq!(image(img.len() - 3), None);
+        assert_eq!(image(usize::MAX - 1), None);
+    };
+
+    let vec = VecImage::new(img.clone());
+    magic(&|offset| vec.get_array(offset).copied());
+    let file = FileImage::new_from_path(write_image("get_array.erofs", &img)).unwrap();
+    magic(&|offset| file.get_array(offset).copied());
+}
+
+#[test]
+fn offset_image_reads_an_embedded_image() {
+    let img = common::sample();
+    // An unaligned header before the image and trailing data after it.
+    let mut container = vec![0xa5; 12345];
+    container.extend_from_slice(&img);
+    container.extend_from_slice(&[0x5a; 100]);
+
+    let image = OffsetImage::new(SliceImage::new(&container), 12345);
+    assert_eq!(image.offset(), 12345);
+    assert_eq!(image.len(), img.len() as u64 + 100);
+    assert_eq!(image.get(1024..1028), Some(&img[1024..1028]));
+    assert_eq!(image.get(..4).unwrap(), &img[..4]);
+    assert_eq!(
+        image.get_owned(4000..=4999).as_deref(),
+        Some(&img[4000..5000])
+    );
+    assert_eq!(image.get(usize::MAX..), None);
+
+    let fs = EroFS::new_at_offset(SliceImage::new(&container), 12345).unwrap();
+    assert_eq!(fs.read("/etc/motd").unwrap(), common::motd());
+    assert_eq!(fs.walk_dir("/").unwrap().count(), 5);
+
+    // The superblock isn't where the container starts.
+    assert!(EroFS::new(SliceImage::new(&container)).is_err());
+    assert!(EroFS::new_at_offset(SliceImage::new(&container), container.len()).is_err());
+}
+
+#[test]
+fn populated_mmap_image_reads_like_a_slice() {
+    let img = common::sample();
+    let path = write_image("populated_mmap_image.erofs", &img);
+    let image = MmapImage::new_from_path_populated(&path).unwrap();
+    assert_eq!(image.get(..), Some(&img[..]));
+
+    let image = MmapImage::new_from_file_populated(&fs::File::open(&path).unwrap()).unwrap();
+    #[cfg(unix)]
+    image.advise_sequential().unwrap();
+    let fs = EroFS::new(image).unwrap();
+    let mut motd = Vec::new();
+    fs.open("/etc/motd")
+        .unwrap()
+        .read_to_end(&mut motd)
+        .unwrap();
+    assert_eq!(motd, common::motd());
+}
+
+#[test]
+fn bytes_image_shares_its_buffer() {
+    let data = Bytes::from(common::sample());
+    let image = BytesImage::from(data.clone());
+    let fs = EroFS::new(image.clone()).unwrap();
+    // The backend reads straight from the shared buffer.
+    assert_eq!(image.get(..).unwrap().as_ptr(), data.as_ptr());
+
+    let mut motd = Vec::new();
+    fs.open("/etc/motd")
+        .unwrap()
+        .read_to_end(&mut motd)
+        .unwrap();
+    assert_eq!(motd, common::motd());
+}
+
+#[test]
+fn constructor_shorthands() {
+    let img = common::sample();
+    let path = write_image("constructor_shorthands.erofs", &img);
+    let read_motd = |motd: erofs_rs::Result<bytes::Bytes>| {
+        assert_eq!(motd.unwrap(), common::motd());
+    };
+
+    read_motd(EroFS::open_path(&path).unwrap().read("/etc/motd"));
+    read_motd(EroFS::from_slice(&img).unwrap().read("/etc/motd"));
+    read_motd(EroFS::from_bytes(img.clone()).unwrap().read("/etc/motd"));
+    read_motd(
+        EroFS::from_bytes(Bytes::from(img))
+            .unwrap()
+            .read("/etc/motd"),
+    );
+
+    let missing = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("missing.erofs");
+    assert!(matches!(
+        EroFS::open_path(missing),
+        Err(erofs_rs::Error::Io(_))
+    ));
+}
+
+#[test]
+fn chain_image_joins_its_parts() {
+    let img = common::sample();
+    // Parts split off the block grid, so some reads straddle them.
+    let parts = [
+        &img[..1000],
+        &img[1000..1030],
+        &img[1030..5000],
+        &img[5000..],
+    ];
+    let image = ChainImage::new(parts.map(SliceImage::new));
+    assert_eq!(image.len(), img.len() as u64);
+    assert_eq!(image.parts().len(), 4);
+    // A read within one part borrows it directly.
+    assert_eq!(
+        image.get(1030..1040).unwrap().as_ptr(),
+        img[1030..].as_ptr()
+    );
+    assert_eq!(image.get(990..1040), Some(&img[990..1040]));
+    assert_eq!(image.get(..), Some(&img[..]));
+    assert_eq!(
+        image.get_owned(900..=5099).as_deref(),
+        Some(&img[900..5100])
+    );
+    assert_eq!(image.get(img.len() - 1..img.len() + 1), None);
+    assert_eq!(image.get(img.len()..), Some(&[][..]));
+    // Straddling ranges aren't lent out, so file data is read owned.
+    assert_eq!(image.get_borrowed(1030..1040), Some(&img[1030..1040]));
+    assert_eq!(image.get_borrowed(990..1040), None);
+
+    let fs = EroFS::new(image).unwrap();
+    assert_eq!(fs.read("/etc/motd").unwrap(), common::motd());
+    assert_eq!(fs.walk_dir("/").unwrap().count(), 5);
+}
+
+#[test]
+fn from_reader_reads_the_whole_stream() {
+    let img = common::sample();
+    let fs = EroFS::from_reader(std::io::Cursor::new(img.clone()), None).unwrap();
+    assert_eq!(fs.read("/etc/motd").unwrap(), common::motd());
+
+    // The position the stream was left at doesn't matter.
+    let mut reader = std::io::Cursor::new(img.clone());
+    reader.set_position(5000);
+    let len = img.len() as u64;
+    let fs = EroFS::from_reader(reader, Some(len)).unwrap();
+    assert_eq!(fs.image_len(), len);
+
+    assert!(matches!(
+        EroFS::from_reader(std::io::Cursor::new(img), Some(len - 1)),
+        Err(erofs_rs::Error::ImageTooLarge { len: l, max }) if l == len && max == len - 1
+    ));
+}
+
+#[test]
+fn filesystems_are_shared_across_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<EroFS<SliceImage<'static>>>();
+    assert_send_sync::<EroFS<VecImage>>();
+    assert_send_sync::<EroFS<BytesImage>>();
+    assert_send_sync::<EroFS<FileImage>>();
+    assert_send_sync::<EroFS<MmapImage>>();
+    assert_send_sync::<EroFS<OffsetImage<VecImage>>>();
+
+    let img = common::sample();
+    let fs = EroFS::with_cache(SliceImage::new(&img), 1 << 20).unwrap();
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                let mut content = Vec::new();
+                fs.open("/etc/motd")
+                    .unwrap()
+                    .read_to_end(&mut content)
+                    .unwrap();
+                assert_eq!(content, common::motd());
+            });
+        }
+    });
+}
+
+#[test]
+fn file_image_reads_file_data_owned() {
+    let img = common::sample();
+    let path = write_image("owned.erofs", &img);
+    let image = FileImage::new_from_path(&path).unwrap();
+    assert_eq!(image.get_borrowed(0..10), None);
+
+    let fs = EroFS::new(image).unwrap();
+    let file = fs.open("/etc/motd").unwrap();
+    assert_eq!(file.as_slice(), None);
+    let mut content = Vec::new();
+    fs.open("/etc/motd")
+        .unwrap()
+        .read_to_end(&mut content)
+        .unwrap();
+    assert_eq!(content, common::motd());
+    assert_eq!(fs.read("/etc/hostname").unwrap(), &b"erofs\n"[..]);
+
+    let slice = SliceImage::new(&img);
+    assert_eq!(slice.get_borrowed(10..20), Some(&img[10..20]));
+    let fs = EroFS::new(OffsetImage::new(slice, 0)).unwrap();
+    assert!(fs.open("/etc/motd").unwrap().as_slice().is_some());
+}
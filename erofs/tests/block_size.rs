@@ -0,0 +1,96 @@
+mod common;
+
+use std::io::Read;
+
+use common::{Data, ImageBuilder, random_bytes};
+use erofs_rs::{EroFS, Error, backend::SliceImage};
+
+/// Builds an image holding plain and tail-packed files two and a half
+/// blocks long, and a plain one exactly two blocks long, and reads them back
+/// whole and in pieces that straddle block boundaries.
+fn check_reads(blkbits: u8) {
+    let mut builder = ImageBuilder::new(blkbits);
+    let bs = builder.block_size();
+    let plain: fn(Vec<u8>) -> Data = Data::Plain;
+    let files = [
+        ("/plain", random_bytes(bs * 5 / 2, 1), plain),
+        ("/inline", random_bytes(bs * 5 / 2, 2), Data::Inline),
+        ("/plain-aligned", random_bytes(bs * 2, 3), plain),
+    ];
+    let root = builder.reserve();
+    let entries: Vec<_> = files
+        .iter()
+        .map(|(path, data, layout)| (&path[1..], builder.file(layout(data.clone()))))
+        .collect();
+    builder.dir(root, root, &entries);
+    let img = builder.build();
+
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    assert_eq!(fs.block_size(), bs);
+    assert_eq!(fs.block_size_bits(), blkbits);
+    for (path, data, _) in &files {
+        let mut content = Vec::new();
+        fs.open(path).unwrap().read_to_end(&mut content).unwrap();
+        assert_eq!(content, data[..], "{path}");
+
+        // An odd buffer size makes every read after the first start in the
+        // middle of a block.
+        let mut file = fs.open(path).unwrap();
+        let mut content = Vec::new();
+        let mut buf = [0; 1000];
+        loop {
+            let n = file.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            content.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(content, data[..], "{path}");
+
+        let mut buf = vec![0; bs];
+        let offset = bs * 3 / 2 + 7;
+        let n = fs.open(path).unwrap().read_at(offset, &mut buf).unwrap();
+        assert_eq!(
+            buf[..n],
+            data[offset..(offset + bs).min(data.len())],
+            "{path}"
+        );
+    }
+}
+
+#[test]
+fn block_size_512() {
+    check_reads(9);
+}
+
+#[test]
+fn block_size_4k() {
+    check_reads(12);
+}
+
+#[test]
+fn block_size_16k() {
+    check_reads(14);
+}
+
+#[test]
+fn block_size_64k() {
+    check_reads(16);
+}
+
+#[test]
+fn unsupported_block_sizes() {
+    let mut img = common::empty_image(&mut ImageBuilder::new(12));
+
+    // Block sizes of 256 bytes and 128 KiB, just outside the valid range.
+    for blkbits in [8, 17] {
+        img[1024 + 12] = blkbits;
+        assert!(
+            matches!(
+                EroFS::new(SliceImage::new(&img)),
+                Err(Error::UnsupportedBlockSize(bits)) if bits == blkbits
+            ),
+            "{blkbits}"
+        );
+    }
+}
@@ -0,0 +1,217 @@
+mod common;
+
+use common::{Data, ImageBuilder, check_file, random_bytes, single_file};
+use erofs_rs::backend::SliceImage;
+use erofs_rs::{ChunkRef, EroFS, Error};
+
+/// A file of four and a half chunks of two blocks each.
+fn chunked(builder: &ImageBuilder, device: Option<u16>) -> (Data, Vec<u8>) {
+    let data = random_bytes(builder.block_size() * 9, 1);
+    let file = Data::Chunked {
+        data: data.clone(),
+        chunk_bits: 1,
+        holes: Vec::new(),
+        device,
+    };
+    (file, data)
+}
+
+#[test]
+fn chunk_block_addresses() {
+    let mut builder = ImageBuilder::new(12);
+    let (file, data) = chunked(&builder, None);
+    check_file(&single_file(&mut builder, file), &data);
+}
+
+#[test]
+fn chunk_indexes_on_the_primary_device() {
+    let mut builder = ImageBuilder::new(12);
+    let (file, data) = chunked(&builder, Some(0));
+    check_file(&single_file(&mut builder, file), &data);
+}
+
+#[test]
+fn chunk_indexes_on_a_mapped_device() {
+    // The device covers the image from block 1, so its chunks are read
+    // relative to there.
+    let mut builder = ImageBuilder::new(12);
+    builder.device("blob", 1024, 1);
+    let (file, data) = chunked(&builder, Some(1));
+    check_file(&single_file(&mut builder, file), &data);
+}
+
+#[test]
+fn chunk_indexes_on_an_unmapped_device() {
+    let mut builder = ImageBuilder::new(12);
+    builder.device("blob", 1024, 0);
+    let (file, _) = chunked(&builder, Some(1));
+    let img = single_file(&mut builder, file);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    assert!(matches!(
+        fs.read("/file"),
+        Err(Error::UnsupportedFeature(msg)) if msg.contains("blob")
+    ));
+
+    // Like the kernel, ids are masked to the bits the device count needs,
+    // and those past the table are corrupted.
+    let mut builder = ImageBuilder::new(12);
+    builder.device("blob0", 1024, 1).device("blob1", 1024, 1025);
+    let (file, _) = chunked(&builder, Some(3));
+    let img = single_file(&mut builder, file);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    assert!(matches!(fs.read("/file"), Err(Error::CorruptedData(_))));
+}
+
+#[test]
+fn chunk_holes_read_as_zeros() {
+    for device in [None, Some(0)] {
+        let mut builder = ImageBuilder::new(12);
+        let chunk_size = builder.block_size() * 2;
+
+        // The second and the partial last chunk are left as holes.
+        let mut data = random_bytes(chunk_size * 9 / 2, 1);
+        let holes = vec![1, 4];
+        for &hole in &holes {
+            let start = hole * chunk_size;
+            let end = data.len().min(start + chunk_size);
+            data[start..end].fill(0);
+        }
+        let file = Data::Chunked {
+            data: data.clone(),
+            chunk_bits: 1,
+            holes,
+            device,
+        };
+        let img = single_file(&mut builder, file);
+        check_file(&img, &data);
+
+        // Reads running into and out of the first hole, and into the last.
+        let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+        let file = fs.open("/file").unwrap();
+        for offset in [chunk_size - 32, chunk_size * 2 - 32, chunk_size * 4 - 32] {
+            let mut buf = [0xff; 64];
+            assert_eq!(file.read_at(offset, &mut buf).unwrap(), 64);
+            assert_eq!(buf, data[offset..offset + 64], "at {offset}");
+        }
+    }
+}
+
+#[test]
+fn chunk_addresses_past_the_image_are_corrupt() {
+    let mut builder = ImageBuilder::new(12);
+    let (file, _) = chunked(&builder, None);
+    let mut img = single_file(&mut builder, file);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let nid = fs.metadata("/file").unwrap().nid();
+    let sb = fs.super_block();
+    let table = ((sb.meta_blk_addr as usize) << sb.blk_size_bits) + nid as usize * 32 + 32;
+
+    // Point the third chunk past the end of the image.
+    img[table + 8..table + 12].copy_from_slice(&0xffffu32.to_le_bytes());
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    assert!(matches!(
+        fs.read("/file"),
+        Err(Error::CorruptChunkIndex {
+            inode_nid,
+            chunk_index: 2,
+            blkaddr: 0xffff,
+        }) if inode_nid == nid
+    ));
+    let file = fs.open("/file").unwrap();
+    let mut buf = [0; 16];
+    assert_eq!(file.read_at(0, &mut buf).unwrap(), 16);
+    assert!(file.read_at(4 * 4096, &mut buf).is_err());
+}
+
+#[test]
+fn chunks_running_past_the_image_are_corrupt() {
+    let mut builder = ImageBuilder::new(12);
+    let (file, _) = chunked(&builder, None);
+    let mut img = single_file(&mut builder, file);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let nid = fs.metadata("/file").unwrap().nid();
+    let sb = fs.super_block();
+    let table = ((sb.meta_blk_addr as usize) << sb.blk_size_bits) + nid as usize * 32 + 32;
+
+    // Start the third chunk of two blocks at the last block of the image.
+    let last = (img.len() / 4096 - 1) as u32;
+    img[table + 8..table + 12].copy_from_slice(&last.to_le_bytes());
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let file = fs.open("/file").unwrap();
+    let mut buf = [0; 16];
+    assert_eq!(file.read_at(4 * 4096, &mut buf).unwrap(), 16);
+    assert!(matches!(
+        file.read_at(5 * 4096, &mut buf),
+        Err(Error::CorruptChunkIndex {
+            inode_nid,
+            chunk_index: 2,
+            blkaddr,
+        }) if inode_nid == nid && blkaddr == last as u64 + 1
+    ));
+}
+
+#[test]
+fn chunks_list_the_index_table() {
+    let mut builder = ImageBuilder::new(12);
+    let chunk_size = builder.block_size() * 2;
+    let mut data = random_bytes(chunk_size * 9 / 2, 1);
+    data[chunk_size..chunk_size * 2].fill(0);
+    let file = Data::Chunked {
+        data: data.clone(),
+        chunk_bits: 1,
+        holes: vec![1],
+        device: None,
+    };
+    let img = single_file(&mut builder, file);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let inode = *fs.metadata("/file").unwrap().inode();
+
+    let chunks = fs.chunks(&inode).unwrap();
+    assert_eq!(chunks.size_hint(), (5, Some(5)));
+    let chunks: Vec<_> = chunks.map(Result::unwrap).collect();
+    assert_eq!(chunks.len(), 5);
+    assert_eq!(chunks[1], ChunkRef::Hole);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let ChunkRef::Mapped { device, block } = *chunk else {
+            assert_eq!(i, 1);
+            continue;
+        };
+        assert_eq!(device, 0);
+        let start = i * chunk_size;
+        let len = chunk_size.min(data.len() - start);
+        let stored = &img[block as usize * 4096..][..len];
+        assert_eq!(stored, &data[start..start + len], "chunk {i}");
+    }
+
+    // Entries naming a device that isn't mapped are reported as stored.
+    let mut builder = ImageBuilder::new(12);
+    builder.device("blob", 1024, 0);
+    let (file, _) = chunked(&builder, Some(1));
+    let img = single_file(&mut builder, file);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let inode = *fs.metadata("/file").unwrap().inode();
+    let chunks: Vec<_> = fs.chunks(&inode).unwrap().map(Result::unwrap).collect();
+    assert_eq!(chunks.len(), 5);
+    assert!(
+        chunks
+            .iter()
+            .all(|chunk| matches!(chunk, ChunkRef::Mapped { device: 1, .. }))
+    );
+
+    // Other layouts have no chunk index.
+    let img = single_file(&mut ImageBuilder::new(12), Data::Plain(data));
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let inode = *fs.metadata("/file").unwrap().inode();
+    assert_eq!(fs.chunks(&inode).unwrap().count(), 0);
+}
+
+#[test]
+fn device_ids_are_masked_with_many_devices() {
+    // With 32768 extra devices, the mask covers every bit of a device id.
+    let mut builder = ImageBuilder::new(12);
+    for i in 0..32768 {
+        builder.device(&format!("blob{i}"), 1, 1);
+    }
+    let (file, data) = chunked(&builder, Some(0));
+    check_file(&single_file(&mut builder, file), &data);
+}
@@ -0,0 +1,1015 @@
+//! A minimal EROFS image writer for building test images in memory.
+//!
+//! Every inode takes up a metadata block of its own, so nids follow from
+//! the order inodes are added in: the `k`-th one has nid `k * block_size /
+//! 32`, and the first one is the root directory. Data blocks follow the
+//! metadata blocks in the same order.
+
+// Each test binary uses its own subset of the writer.
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use erofs_rs::EroFS;
+use erofs_rs::backend::SliceImage;
+use erofs_rs::types::{
+    CompressionAlgo, FEATURE_INCOMPAT_BIG_PCLUSTER, FEATURE_INCOMPAT_CHUNKED_FILE,
+    FEATURE_INCOMPAT_COMPR_CFGS, FEATURE_INCOMPAT_FRAGMENTS, FEATURE_INCOMPAT_ZERO_PADDING,
+    FEATURE_INCOMPAT_ZTAILPACKING, LAYOUT_CHUNK_FORMAT_INDEXES, LCLUSTER_TYPE_HEAD1,
+    LCLUSTER_TYPE_NONHEAD, LCLUSTER_TYPE_PLAIN, LI_D0_CBLKCNT, MAP_ADVISE_BIG_PCLUSTER_1,
+    MAP_ADVISE_FRAGMENT_PCLUSTER, MAP_ADVISE_INLINE_PCLUSTER, MAP_FRAGMENT_INODE_BIT, NULL_ADDR,
+};
+
+pub const S_IFDIR: u16 = 0o040000;
+pub const S_IFREG: u16 = 0o100000;
+pub const S_IFLNK: u16 = 0o120000;
+pub const S_IFCHR: u16 = 0o020000;
+pub const S_IFBLK: u16 = 0o060000;
+pub const S_IFIFO: u16 = 0o010000;
+pub const S_IFSOCK: u16 = 0o140000;
+
+const MAGIC: u32 = 0xe0f5_e1e2;
+const SUPER_BLOCK_OFFSET: usize = 1024;
+const SUPER_BLOCK_SIZE: usize = 128;
+const INODE_SIZE: usize = 32;
+const EXTENDED_INODE_SIZE: usize = 64;
+const DIRENT_SIZE: usize = 12;
+const DEVT_SLOT_SIZE: usize = 128;
+
+const LAYOUT_FLAT_PLAIN: u16 = 0;
+const LAYOUT_COMPRESSED_FULL: u16 = 1;
+const LAYOUT_FLAT_INLINE: u16 = 2;
+const LAYOUT_CHUNK_BASED: u16 = 4;
+
+/// How the data of a regular file is stored.
+#[derive(Debug, Clone)]
+pub enum Data {
+    /// In whole blocks following the metadata.
+    Plain(Vec<u8>),
+    /// In whole blocks, with the last partial block right after the inode.
+    Inline(Vec<u8>),
+    /// In chunks of `2^chunk_bits` blocks, located through a table following
+    /// the inode. The chunks listed in `holes` aren't stored, and must be
+    /// zeroed in `data`.
+    Chunked {
+        data: Vec<u8>,
+        chunk_bits: u8,
+        holes: Vec<usize>,
+        /// `None` for a table of 4-byte block addresses. Otherwise the
+        /// table holds 8-byte chunk indexes naming this device, with block
+        /// addresses relative to where the device is mapped.
+        device: Option<u16>,
+    },
+    /// In physical clusters described by full lcluster indexes, with one
+    /// lcluster per block.
+    Compressed {
+        /// The decompressed size of the file.
+        size: usize,
+        algorithm: CompressionAlgo,
+        /// The pclusters in logical order, no two starting in the same
+        /// lcluster.
+        pclusters: Vec<Pcluster>,
+        /// Whether the last pcluster is stored right after the indexes
+        /// instead of in a block of its own.
+        inline_tail: bool,
+    },
+    /// Entirely in the packed inode, starting at `offset`.
+    Fragment { size: usize, offset: u32 },
+}
+
+/// A physical cluster of a compressed file.
+#[derive(Debug, Clone)]
+pub struct Pcluster {
+    /// Logical offset of the first byte the pcluster decodes to.
+    pub la: usize,
+    /// The compressed stream, or the data itself for a plain pcluster.
+    pub payload: Vec<u8>,
+    /// Whether `payload` is stored uncompressed.
+    pub plain: bool,
+    /// Size in blocks.
+    pub blocks: usize,
+    /// Offset of the data in the packed inode, for a tail fragment.
+    pub fragment: Option<u32>,
+}
+
+impl Pcluster {
+    /// A single-block pcluster holding the compressed stream `payload`.
+    pub const fn compressed(la: usize, payload: Vec<u8>) -> Self {
+        Self {
+            la,
+            payload,
+            plain: false,
+            blocks: 1,
+            fragment: None,
+        }
+    }
+
+    /// A single-block pcluster holding `data` uncompressed.
+    pub const fn plain(la: usize, data: Vec<u8>) -> Self {
+        Self {
+            la,
+            payload: data,
+            plain: true,
+            blocks: 1,
+            fragment: None,
+        }
+    }
+
+    /// Makes the pcluster span `blocks` blocks.
+    pub const fn blocks(mut self, blocks: usize) -> Self {
+        self.blocks = blocks;
+        self
+    }
+
+    /// The tail of the file starting at `la`, stored in the packed inode at
+    /// `offset`. It must be the last pcluster.
+    pub const fn fragment(la: usize, offset: u32) -> Self {
+        Self {
+            la,
+            payload: Vec::new(),
+            plain: false,
+            blocks: 0,
+            fragment: Some(offset),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Dir {
+        parent: u64,
+        entries: Vec<(Vec<u8>, u64)>,
+    },
+    File(Data),
+    Symlink(Vec<u8>),
+    Special {
+        mode: u16,
+        rdev: u32,
+    },
+}
+
+/// Builds an image out of directories, files and symlinks added by nid.
+#[derive(Debug)]
+pub struct ImageBuilder {
+    blkbits: u8,
+    feature_compat: u32,
+    feature_incompat: u32,
+    compr_algs: u16,
+    compr_configs: Vec<u8>,
+    packed_nid: u64,
+    uuid: [u8; 16],
+    volume_name: [u8; 16],
+    devices: Vec<(String, u32, u32)>,
+    nodes: Vec<Option<Node>>,
+    xattrs: BTreeMap<u64, Vec<u8>>,
+    shared_xattrs: Vec<u8>,
+    build_time: (u64, u32),
+    mtimes: BTreeMap<u64, (u64, u32)>,
+    owners: BTreeMap<u64, (u32, u32)>,
+    permissions: BTreeMap<u64, u16>,
+}
+
+impl ImageBuilder {
+    pub fn new(blkbits: u8) -> Self {
+        Self {
+            blkbits,
+            feature_compat: 0,
+            feature_incompat: 0,
+            compr_algs: 0,
+            compr_configs: Vec::new(),
+            packed_nid: 0,
+            uuid: [0; 16],
+            volume_name: [0; 16],
+            devices: Vec::new(),
+            nodes: Vec::new(),
+            xattrs: BTreeMap::new(),
+            shared_xattrs: Vec::new(),
+            build_time: (0, 0),
+            mtimes: BTreeMap::new(),
+            owners: BTreeMap::new(),
+            permissions: BTreeMap::new(),
+        }
+    }
+
+    pub const fn block_size(&self) -> usize {
+        1 << self.blkbits
+    }
+
+    /// Sets compatible feature bits.
+    pub fn feature_compat(&mut self, bits: u32) -> &mut Self {
+        self.feature_compat |= bits;
+        self
+    }
+
+    /// Sets incompatible feature bits on top of those the inodes need.
+    pub fn feature_incompat(&mut self, bits: u32) -> &mut Self {
+        self.feature_incompat |= bits;
+        self
+    }
+
+    /// Sets the raw `compr_algs` superblock field.
+    pub fn compr_algs(&mut self, bits: u16) -> &mut Self {
+        self.compr_algs = bits;
+        self
+    }
+
+    /// Records the compression config `payload` of `algorithm`, setting its
+    /// `compr_algs` bit. Configs must be added in algorithm order.
+    pub fn compr_config(&mut self, algorithm: CompressionAlgo, payload: &[u8]) -> &mut Self {
+        self.feature_incompat |= FEATURE_INCOMPAT_COMPR_CFGS;
+        self.compr_algs |= 1 << algorithm as u16;
+        self.compr_configs
+            .resize(self.compr_configs.len().next_multiple_of(4), 0);
+        self.compr_configs
+            .extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        self.compr_configs.extend_from_slice(payload);
+        self
+    }
+
+    /// Makes `nid` the packed inode holding fragments.
+    pub fn packed(&mut self, nid: u64) -> &mut Self {
+        self.packed_nid = nid;
+        self
+    }
+
+    /// Sets the superblock UUID.
+    pub fn uuid(&mut self, uuid: [u8; 16]) -> &mut Self {
+        self.uuid = uuid;
+        self
+    }
+
+    /// Sets the volume label, NUL padded to 16 bytes.
+    pub fn volume_name(&mut self, name: &[u8]) -> &mut Self {
+        self.volume_name = [0; 16];
+        self.volume_name[..name.len()].copy_from_slice(name);
+        self
+    }
+
+    /// Sets the build time in the superblock, as seconds and nanoseconds.
+    pub fn build_time(&mut self, secs: u64, nanos: u32) -> &mut Self {
+        self.build_time = (secs, nanos);
+        self
+    }
+
+    /// Writes `nid` as an extended inode with the modification time `secs`
+    /// and `nanos`.
+    pub fn mtime(&mut self, nid: u64, secs: u64, nanos: u32) -> &mut Self {
+        self.mtimes.insert(nid, (secs, nanos));
+        self
+    }
+
+    /// Sets the owner of `nid`. Compact inodes keep only the low 16 bits of
+    /// each ID.
+    pub fn owner(&mut self, nid: u64, uid: u32, gid: u32) -> &mut Self {
+        self.owners.insert(nid, (uid, gid));
+        self
+    }
+
+    /// Replaces the permission bits of `nid`, setuid, setgid and sticky
+    /// included, with `bits`.
+    pub fn permissions(&mut self, nid: u64, bits: u16) -> &mut Self {
+        self.permissions.insert(nid, bits);
+        self
+    }
+
+    /// Adds an extra device to the device table.
+    pub fn device(&mut self, tag: &str, blocks: u32, mapped_blkaddr: u32) -> &mut Self {
+        self.devices.push((tag.to_string(), blocks, mapped_blkaddr));
+        self
+    }
+
+    /// Allocates the nid of an inode to be filled in later, so that
+    /// directories can refer to inodes added after them.
+    pub fn reserve(&mut self) -> u64 {
+        self.nodes.push(None);
+        self.nid(self.nodes.len() - 1)
+    }
+
+    /// Fills in the reserved `nid` as a directory holding `entries`, each
+    /// a name and the nid it points to, besides `.` and `..`.
+    pub fn dir(&mut self, nid: u64, parent: u64, entries: &[(&str, u64)]) {
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|&(name, nid)| (name.as_bytes(), nid))
+            .collect();
+        self.dir_bytes(nid, parent, &entries);
+    }
+
+    /// Like [`dir`](Self::dir), with names given as bytes that need not be
+    /// valid UTF-8.
+    pub fn dir_bytes(&mut self, nid: u64, parent: u64, entries: &[(&[u8], u64)]) {
+        let entries = entries
+            .iter()
+            .map(|&(name, nid)| (name.to_vec(), nid))
+            .collect();
+        self.set(nid, Node::Dir { parent, entries });
+    }
+
+    /// Adds a regular file and returns its nid.
+    pub fn file(&mut self, data: Data) -> u64 {
+        let nid = self.reserve();
+        self.set(nid, Node::File(data));
+        nid
+    }
+
+    /// Adds a symlink to `target` and returns its nid.
+    pub fn symlink(&mut self, target: &str) -> u64 {
+        let nid = self.reserve();
+        self.set(nid, Node::Symlink(target.as_bytes().to_vec()));
+        nid
+    }
+
+    /// Adds a device node, fifo or socket of type `mode`, with the device
+    /// number `rdev` in the kernel's `new_encode_dev()` format.
+    pub fn special(&mut self, mode: u16, rdev: u32) -> u64 {
+        let nid = self.reserve();
+        self.set(nid, Node::Special { mode, rdev });
+        nid
+    }
+
+    /// Stores `region` as the inline xattrs of `nid`, between the inode and
+    /// its tail-packed data. It must start with the 12-byte xattr header and
+    /// be a multiple of 4 bytes long.
+    pub fn inline_xattrs(&mut self, nid: u64, region: &[u8]) -> &mut Self {
+        assert!(region.len() >= 12 && region.len().is_multiple_of(4));
+        self.xattrs.insert(nid, region.to_vec());
+        self
+    }
+
+    /// Adds an entry built by [`xattr_entry`] to the shared xattr area and
+    /// returns its id.
+    pub fn shared_xattr(&mut self, entry: &[u8]) -> u32 {
+        let id = self.shared_xattrs.len() / 4;
+        self.shared_xattrs.extend_from_slice(entry);
+        id as u32
+    }
+
+    /// Writes out the image, with the first inode added as its root.
+    pub fn build(&self) -> Vec<u8> {
+        let bs = self.block_size();
+        let devt = self.device_table();
+        // Compression configs follow the superblock, then the device table.
+        let configs_offset = SUPER_BLOCK_OFFSET + SUPER_BLOCK_SIZE;
+        let devt_offset =
+            (configs_offset + self.compr_configs.len()).next_multiple_of(DEVT_SLOT_SIZE);
+        let meta_blk = (devt_offset + devt.len()).div_ceil(bs);
+        let mut img = vec![0u8; (meta_blk + self.nodes.len()) * bs];
+        let mut feature_incompat = self.feature_incompat;
+        // The shared xattr area comes right after the metadata blocks, and
+        // its address is 0 without one.
+        let xattr_blk = Self::append_blocks(&mut img, bs, &self.shared_xattrs) as usize;
+
+        for (k, node) in self.nodes.iter().enumerate() {
+            let node = node.as_ref().expect("reserved nid left unset");
+            let inode_offset = (meta_blk + k) * bs;
+            let xattrs = self.xattrs.get(&self.nid(k)).map_or(&[][..], Vec::as_slice);
+            let inode_size = if self.mtimes.contains_key(&self.nid(k)) {
+                EXTENDED_INODE_SIZE
+            } else {
+                INODE_SIZE
+            };
+            let data_offset = inode_offset + inode_size + xattrs.len();
+            let (mode, nlink, data) = match node {
+                Node::Dir { parent, entries } => {
+                    let subdirs = entries
+                        .iter()
+                        .filter(|(_, nid)| self.file_type(*nid) == 2)
+                        .count();
+                    let data = self.dir_data(self.nid(k), *parent, entries);
+                    (S_IFDIR | 0o755, 2 + subdirs, Data::Inline(data))
+                }
+                Node::File(data) => (S_IFREG | 0o644, self.links(k), data.clone()),
+                Node::Symlink(target) => {
+                    (S_IFLNK | 0o777, self.links(k), Data::Inline(target.clone()))
+                }
+                Node::Special { mode, rdev } => {
+                    let nlink = self.links(k);
+                    let inode = self.inode(LAYOUT_FLAT_PLAIN, 0, mode | 0o644, nlink, 0, *rdev, k);
+                    img[inode_offset..inode_offset + inode_size].copy_from_slice(&inode);
+                    continue;
+                }
+            };
+
+            let (layout, size, i_u) = match &data {
+                Data::Plain(data) => {
+                    let blkaddr = Self::append_blocks(&mut img, bs, data);
+                    (LAYOUT_FLAT_PLAIN, data.len(), blkaddr)
+                }
+                Data::Inline(data) => {
+                    let tail_len = data.len() % bs;
+                    let (blocks, tail) = data.split_at(data.len() - tail_len);
+                    assert!(
+                        data_offset + tail.len() <= inode_offset + bs,
+                        "inline tail too long"
+                    );
+                    let blkaddr = Self::append_blocks(&mut img, bs, blocks);
+                    img[data_offset..data_offset + tail.len()].copy_from_slice(tail);
+                    (LAYOUT_FLAT_INLINE, data.len(), blkaddr)
+                }
+                Data::Chunked {
+                    data,
+                    chunk_bits,
+                    holes,
+                    device,
+                } => {
+                    feature_incompat |= FEATURE_INCOMPAT_CHUNKED_FILE;
+                    // Unknown devices are stored as if mapped at block 0.
+                    let base = device
+                        .and_then(|id| self.devices.get(usize::from(id).checked_sub(1)?))
+                        .map_or(0, |(_, _, mapped_blkaddr)| *mapped_blkaddr);
+                    let mut table = Vec::new();
+                    for (i, chunk) in data.chunks(bs << chunk_bits).enumerate() {
+                        let blkaddr = if holes.contains(&i) {
+                            assert!(chunk.iter().all(|&b| b == 0), "hole {i} not zeroed");
+                            NULL_ADDR
+                        } else {
+                            Self::append_blocks(&mut img, bs, chunk) - base
+                        };
+                        if let Some(id) = device {
+                            table.extend_from_slice(&0u16.to_le_bytes()); // startblk_hi
+                            table.extend_from_slice(&id.to_le_bytes());
+                        }
+                        table.extend_from_slice(&blkaddr.to_le_bytes());
+                    }
+                    // Chunk indexes are aligned to their size.
+                    let (table_offset, format) = match device {
+                        Some(_) => (
+                            data_offset.next_multiple_of(8),
+                            LAYOUT_CHUNK_FORMAT_INDEXES as u32,
+                        ),
+                        None => (data_offset, 0),
+                    };
+                    assert!(
+                        table_offset + table.len() <= inode_offset + bs,
+                        "chunk table too long"
+                    );
+                    img[table_offset..table_offset + table.len()].copy_from_slice(&table);
+                    (
+                        LAYOUT_CHUNK_BASED,
+                        data.len(),
+                        format | u32::from(*chunk_bits),
+                    )
+                }
+                Data::Compressed {
+                    size,
+                    algorithm,
+                    pclusters,
+                    inline_tail,
+                } => {
+                    if *inline_tail {
+                        feature_incompat |= FEATURE_INCOMPAT_ZTAILPACKING;
+                    }
+                    if pclusters.iter().any(|p| p.fragment.is_some()) {
+                        feature_incompat |= FEATURE_INCOMPAT_FRAGMENTS;
+                    }
+                    if pclusters.iter().any(|p| p.blocks > 1) {
+                        feature_incompat |= FEATURE_INCOMPAT_BIG_PCLUSTER;
+                    }
+                    let zero_padding = feature_incompat & FEATURE_INCOMPAT_ZERO_PADDING != 0;
+                    let map = Self::compressed_map(
+                        &mut img,
+                        bs,
+                        zero_padding,
+                        *size,
+                        *algorithm,
+                        pclusters,
+                        *inline_tail,
+                    );
+                    // The indexes start 8-byte aligned after the inline xattrs.
+                    let map_offset = data_offset.next_multiple_of(8);
+                    assert!(
+                        map_offset + map.len() <= inode_offset + bs,
+                        "indexes too long"
+                    );
+                    img[map_offset..map_offset + map.len()].copy_from_slice(&map);
+                    let stored = pclusters.len() - usize::from(*inline_tail);
+                    let blocks = pclusters[..stored].iter().map(|p| p.blocks).sum::<usize>();
+                    (LAYOUT_COMPRESSED_FULL, *size, blocks as u32)
+                }
+                Data::Fragment { size, offset } => {
+                    feature_incompat |= FEATURE_INCOMPAT_FRAGMENTS;
+                    // The header holds the offset, with the top bit of
+                    // `clusterbits` marking the whole file as a fragment.
+                    let mut map = u64::from(*offset).to_le_bytes();
+                    map[7] |= 1 << MAP_FRAGMENT_INODE_BIT;
+                    let map_offset = data_offset.next_multiple_of(8);
+                    img[map_offset..map_offset + 8].copy_from_slice(&map);
+                    (LAYOUT_COMPRESSED_FULL, *size, 0)
+                }
+            };
+            let xattr_icount = xattrs.len().checked_sub(12).map_or(0, |len| len / 4 + 1);
+            let inode = self.inode(layout, xattr_icount, mode, nlink, size, i_u, k);
+            img[inode_offset..inode_offset + inode_size].copy_from_slice(&inode);
+            img[inode_offset + inode_size..data_offset].copy_from_slice(xattrs);
+        }
+
+        let blocks = img.len() / bs;
+        let sb = self.super_block(
+            meta_blk,
+            blocks,
+            xattr_blk,
+            devt_offset / DEVT_SLOT_SIZE,
+            feature_incompat,
+        );
+        img[SUPER_BLOCK_OFFSET..SUPER_BLOCK_OFFSET + SUPER_BLOCK_SIZE].copy_from_slice(&sb);
+        img[configs_offset..configs_offset + self.compr_configs.len()]
+            .copy_from_slice(&self.compr_configs);
+        img[devt_offset..devt_offset + devt.len()].copy_from_slice(&devt);
+        img
+    }
+
+    /// The device table, a slot per extra device.
+    fn device_table(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (tag, blocks, mapped_blkaddr) in &self.devices {
+            let mut slot = tag.as_bytes().to_vec();
+            slot.resize(64, 0);
+            slot.extend_from_slice(&blocks.to_le_bytes());
+            slot.extend_from_slice(&mapped_blkaddr.to_le_bytes());
+            slot.resize(DEVT_SLOT_SIZE, 0);
+            data.extend_from_slice(&slot);
+        }
+        data
+    }
+
+    fn nid(&self, index: usize) -> u64 {
+        (index * self.block_size() / INODE_SIZE) as u64
+    }
+
+    fn set(&mut self, nid: u64, node: Node) {
+        let index = nid as usize * INODE_SIZE / self.block_size();
+        self.nodes[index] = Some(node);
+    }
+
+    fn node(&self, nid: u64) -> &Node {
+        let index = nid as usize * INODE_SIZE / self.block_size();
+        self.nodes[index].as_ref().expect("reserved nid left unset")
+    }
+
+    /// Number of directory entries linking to the non-directory in slot `k`.
+    fn links(&self, k: usize) -> usize {
+        let nid = self.nid(k);
+        let links = self
+            .nodes
+            .iter()
+            .flatten()
+            .filter_map(|node| match node {
+                Node::Dir { entries, .. } => Some(entries),
+                _ => None,
+            })
+            .flatten()
+            .filter(|(_, entry)| *entry == nid)
+            .count();
+        links.max(1)
+    }
+
+    /// The dirent file type of the inode `nid`.
+    fn file_type(&self, nid: u64) -> u8 {
+        match self.node(nid) {
+            Node::File(_) => 1,
+            Node::Dir { .. } => 2,
+            Node::Symlink(_) => 7,
+            Node::Special { mode, .. } => match *mode {
+                S_IFCHR => 3,
+                S_IFBLK => 4,
+                S_IFIFO => 5,
+                S_IFSOCK => 6,
+                _ => unreachable!("not a special file type"),
+            },
+        }
+    }
+
+    /// Directory blocks holding `.`, `..` and `entries`, sorted by name,
+    /// with the last block cut short.
+    fn dir_data(&self, nid: u64, parent: u64, entries: &[(Vec<u8>, u64)]) -> Vec<u8> {
+        let bs = self.block_size();
+        let mut all = vec![(b".".to_vec(), nid, 2), (b"..".to_vec(), parent, 2)];
+        all.extend(
+            entries
+                .iter()
+                .map(|(name, nid)| (name.clone(), *nid, self.file_type(*nid))),
+        );
+        all.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut blocks: Vec<Vec<(Vec<u8>, u64, u8)>> = vec![Vec::new()];
+        let mut used = 0;
+        for entry in all {
+            let len = DIRENT_SIZE + entry.0.len();
+            if used + len > bs {
+                blocks.push(Vec::new());
+                used = 0;
+            }
+            used += len;
+            blocks.last_mut().unwrap().push(entry);
+        }
+
+        let mut data = Vec::new();
+        for (i, block) in blocks.iter().enumerate() {
+            let start = data.len();
+            let mut name_offset = block.len() * DIRENT_SIZE;
+            for (name, nid, file_type) in block {
+                data.extend_from_slice(&nid.to_le_bytes());
+                data.extend_from_slice(&(name_offset as u16).to_le_bytes());
+                data.extend_from_slice(&[*file_type, 0]);
+                name_offset += name.len();
+            }
+            for (name, _, _) in block {
+                data.extend_from_slice(name);
+            }
+            if i + 1 != blocks.len() {
+                data.resize(start + bs, 0);
+            }
+        }
+        data
+    }
+
+    /// Appends `data` to the image in whole blocks and returns the first
+    /// block's address, or 0 for no data.
+    fn append_blocks(img: &mut Vec<u8>, bs: usize, data: &[u8]) -> u32 {
+        if data.is_empty() {
+            return 0;
+        }
+        let blkaddr = (img.len() / bs) as u32;
+        img.extend_from_slice(data);
+        img.resize(img.len().next_multiple_of(bs), 0);
+        blkaddr
+    }
+
+    /// Stores the pclusters of a compressed file that don't go inline and
+    /// returns the map header, the full indexes and the inline tail, which
+    /// follow the inode.
+    fn compressed_map(
+        img: &mut Vec<u8>,
+        bs: usize,
+        zero_padding: bool,
+        size: usize,
+        algorithm: CompressionAlgo,
+        pclusters: &[Pcluster],
+        inline_tail: bool,
+    ) -> Vec<u8> {
+        let fragment = pclusters.last().and_then(|p| p.fragment);
+        let stored = pclusters.len() - usize::from(inline_tail) - usize::from(fragment.is_some());
+        let blkaddrs: Vec<_> = pclusters[..stored]
+            .iter()
+            .map(|p| {
+                let mut blocks = vec![0; p.blocks * bs];
+                assert!(p.payload.len() <= blocks.len(), "pcluster too long");
+                // Compressed data ends at the end of the pcluster with zero
+                // padding, and starts at its start otherwise.
+                let start = if zero_padding && !p.plain {
+                    blocks.len() - p.payload.len()
+                } else {
+                    0
+                };
+                blocks[start..start + p.payload.len()].copy_from_slice(&p.payload);
+                Self::append_blocks(img, bs, &blocks)
+            })
+            .collect();
+        let tail = inline_tail.then(|| &pclusters[stored].payload[..]);
+
+        let big_pcluster = pclusters.iter().any(|p| p.blocks > 1);
+        let mut map = Vec::new();
+        let mut advise = if let Some(offset) = fragment {
+            // The fragment offset overlays the reserved and data size fields.
+            map.extend_from_slice(&offset.to_le_bytes());
+            MAP_ADVISE_FRAGMENT_PCLUSTER
+        } else if let Some(tail) = tail {
+            map.extend_from_slice(&0u16.to_le_bytes()); // reserved
+            map.extend_from_slice(&(tail.len() as u16).to_le_bytes());
+            MAP_ADVISE_INLINE_PCLUSTER
+        } else {
+            map.extend_from_slice(&[0; 4]);
+            0
+        };
+        if big_pcluster {
+            advise |= MAP_ADVISE_BIG_PCLUSTER_1;
+        }
+        map.extend_from_slice(&advise.to_le_bytes());
+        map.extend_from_slice(&[algorithm as u8, 0]);
+        map.extend_from_slice(&[0; 8]);
+
+        let head_lcns: Vec<_> = pclusters.iter().map(|p| p.la / bs).collect();
+        let lclusters = size.div_ceil(bs);
+        for lcn in 0..lclusters {
+            if let Some(k) = head_lcns.iter().position(|&head| head == lcn) {
+                let p = &pclusters[k];
+                let cluster_type = if p.plain {
+                    LCLUSTER_TYPE_PLAIN
+                } else {
+                    LCLUSTER_TYPE_HEAD1
+                };
+                map.extend_from_slice(&u16::from(cluster_type).to_le_bytes());
+                map.extend_from_slice(&((p.la % bs) as u16).to_le_bytes());
+                map.extend_from_slice(&blkaddrs.get(k).copied().unwrap_or(0).to_le_bytes());
+            } else {
+                let k = head_lcns.iter().rposition(|&head| head < lcn).unwrap();
+                let next = head_lcns.get(k + 1).copied().unwrap_or(lclusters);
+                let mut delta0 = (lcn - head_lcns[k]) as u16;
+                // With big pclusters, the first non-head lcluster of every
+                // pcluster records its size instead.
+                if delta0 == 1 && big_pcluster {
+                    delta0 = LI_D0_CBLKCNT | pclusters[k].blocks as u16;
+                }
+                map.extend_from_slice(&u16::from(LCLUSTER_TYPE_NONHEAD).to_le_bytes());
+                map.extend_from_slice(&0u16.to_le_bytes());
+                map.extend_from_slice(&delta0.to_le_bytes());
+                map.extend_from_slice(&((next - lcn) as u16).to_le_bytes());
+            }
+        }
+        if let Some(tail) = tail {
+            map.extend_from_slice(tail);
+        }
+        map
+    }
+
+    /// The inode in slot `ino`, extended if it has a modification time.
+    #[allow(clippy::too_many_arguments)]
+    fn inode(
+        &self,
+        layout: u16,
+        xattr_icount: usize,
+        mode: u16,
+        nlink: usize,
+        size: usize,
+        i_u: u32,
+        ino: usize,
+    ) -> Vec<u8> {
+        let (uid, gid) = self.owners.get(&self.nid(ino)).copied().unwrap_or_default();
+        let mode = match self.permissions.get(&self.nid(ino)) {
+            Some(&bits) => mode & !0o7777 | bits,
+            None => mode,
+        };
+        if let Some(&(secs, nanos)) = self.mtimes.get(&self.nid(ino)) {
+            let mut inode = Vec::with_capacity(EXTENDED_INODE_SIZE);
+            inode.extend_from_slice(&(1 | layout << 1).to_le_bytes());
+            inode.extend_from_slice(&(xattr_icount as u16).to_le_bytes());
+            inode.extend_from_slice(&mode.to_le_bytes());
+            inode.extend_from_slice(&0u16.to_le_bytes());
+            inode.extend_from_slice(&(size as u64).to_le_bytes());
+            inode.extend_from_slice(&i_u.to_le_bytes());
+            inode.extend_from_slice(&(ino as u32).to_le_bytes());
+            inode.extend_from_slice(&uid.to_le_bytes());
+            inode.extend_from_slice(&gid.to_le_bytes());
+            inode.extend_from_slice(&secs.to_le_bytes());
+            inode.extend_from_slice(&nanos.to_le_bytes());
+            inode.extend_from_slice(&(nlink as u32).to_le_bytes());
+            inode.resize(EXTENDED_INODE_SIZE, 0);
+            return inode;
+        }
+
+        let mut inode = Vec::with_capacity(INODE_SIZE);
+        inode.extend_from_slice(&(layout << 1).to_le_bytes());
+        inode.extend_from_slice(&(xattr_icount as u16).to_le_bytes());
+        inode.extend_from_slice(&mode.to_le_bytes());
+        inode.extend_from_slice(&(nlink as u16).to_le_bytes());
+        inode.extend_from_slice(&(size as u32).to_le_bytes());
+        inode.extend_from_slice(&0u32.to_le_bytes());
+        inode.extend_from_slice(&i_u.to_le_bytes());
+        inode.extend_from_slice(&(ino as u32).to_le_bytes());
+        inode.extend_from_slice(&(uid as u16).to_le_bytes());
+        inode.extend_from_slice(&(gid as u16).to_le_bytes());
+        inode.resize(INODE_SIZE, 0);
+        inode
+    }
+
+    fn super_block(
+        &self,
+        meta_blk: usize,
+        blocks: usize,
+        xattr_blk: usize,
+        devt_slot_off: usize,
+        feature_incompat: u32,
+    ) -> Vec<u8> {
+        let mut sb = Vec::with_capacity(SUPER_BLOCK_SIZE);
+        sb.extend_from_slice(&MAGIC.to_le_bytes());
+        sb.extend_from_slice(&0u32.to_le_bytes()); // checksum
+        sb.extend_from_slice(&self.feature_compat.to_le_bytes());
+        sb.extend_from_slice(&[self.blkbits, 0]);
+        sb.extend_from_slice(&0u16.to_le_bytes()); // root nid
+        sb.extend_from_slice(&(self.nodes.len() as u64).to_le_bytes());
+        sb.extend_from_slice(&self.build_time.0.to_le_bytes());
+        sb.extend_from_slice(&self.build_time.1.to_le_bytes());
+        sb.extend_from_slice(&(blocks as u32).to_le_bytes());
+        sb.extend_from_slice(&(meta_blk as u32).to_le_bytes());
+        sb.extend_from_slice(&(xattr_blk as u32).to_le_bytes());
+        sb.extend_from_slice(&self.uuid);
+        sb.extend_from_slice(&self.volume_name);
+        sb.extend_from_slice(&feature_incompat.to_le_bytes());
+        sb.extend_from_slice(&self.compr_algs.to_le_bytes());
+        sb.extend_from_slice(&(self.devices.len() as u16).to_le_bytes());
+        sb.extend_from_slice(&(devt_slot_off as u16).to_le_bytes());
+        sb.extend_from_slice(&[0; 6]); // dir_blkbits, xattr prefixes
+        sb.extend_from_slice(&self.packed_nid.to_le_bytes());
+        sb.resize(SUPER_BLOCK_SIZE, 0);
+        sb
+    }
+}
+
+/// The LZMA compression config for a dictionary of `dict_size` bytes.
+pub fn lzma_config(dict_size: u32) -> Vec<u8> {
+    let mut config = dict_size.to_le_bytes().to_vec();
+    config.resize(14, 0); // format, reserved
+    config
+}
+
+/// Builds an image whose root holds the compressed file `/file`.
+pub fn single_file(builder: &mut ImageBuilder, file: Data) -> Vec<u8> {
+    let root = builder.reserve();
+    let nid = builder.file(file);
+    builder.dir(root, root, &[("file", nid)]);
+    builder.build()
+}
+
+/// Checks that `/file` reads back as `expected`, both at once and in
+/// reads of an odd size that straddle every block and extent boundary,
+/// with and without the extent cache.
+pub fn check_file(img: &[u8], expected: &[u8]) {
+    check_reads(&EroFS::new(SliceImage::new(img)).unwrap(), expected);
+    check_reads(
+        &EroFS::with_cache(SliceImage::new(img), 1 << 20).unwrap(),
+        expected,
+    );
+}
+
+fn check_reads(fs: &EroFS<SliceImage<'_>>, expected: &[u8]) {
+    let mut content = Vec::new();
+    fs.open("/file").unwrap().read_to_end(&mut content).unwrap();
+    assert_eq!(content, expected);
+
+    let mut file = fs.open("/file").unwrap();
+    let mut content = Vec::new();
+    let mut buf = [0; 1000];
+    loop {
+        let n = file.read(&mut buf).unwrap();
+        if n == 0 {
+            break;
+        }
+        content.extend_from_slice(&buf[..n]);
+    }
+    assert_eq!(content, expected);
+}
+
+/// The deflate compression config for windows of `1 << windowbits` bytes.
+pub fn deflate_config(windowbits: u8) -> Vec<u8> {
+    vec![windowbits, 0, 0, 0, 0, 0]
+}
+
+/// The zstd compression config for windows of up to `1 << (10 + windowlog)`
+/// bytes.
+pub fn zstd_config(windowlog: u8) -> Vec<u8> {
+    vec![0, windowlog, 0, 0, 0, 0]
+}
+
+/// An image holding an empty root directory.
+pub fn empty_image(builder: &mut ImageBuilder) -> Vec<u8> {
+    let root = builder.reserve();
+    builder.dir(root, root, &[]);
+    builder.build()
+}
+
+/// Encodes an xattr entry named `name` under the prefix `name_index`,
+/// padded to 4 bytes.
+pub fn xattr_entry(name_index: u8, name: &str, value: &[u8]) -> Vec<u8> {
+    let mut entry = vec![name.len() as u8, name_index];
+    entry.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    entry.extend_from_slice(name.as_bytes());
+    entry.extend_from_slice(value);
+    entry.resize(entry.len().next_multiple_of(4), 0);
+    entry
+}
+
+/// Encodes an inline xattr region referencing the shared entries `shared`
+/// and holding the `inline` entries built by [`xattr_entry`].
+pub fn xattr_region(shared: &[u32], inline: &[Vec<u8>]) -> Vec<u8> {
+    let mut region = vec![0; 4]; // name filter
+    region.push(shared.len() as u8);
+    region.resize(12, 0);
+    for id in shared {
+        region.extend_from_slice(&id.to_le_bytes());
+    }
+    region.extend(inline.concat());
+    region
+}
+
+/// Returns `len` pseudo-random bytes, the same for the same `seed`.
+pub fn random_bytes(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (state >> 56) as u8
+        })
+        .collect()
+}
+
+/// Returns `pattern` repeated up to `len` bytes.
+pub fn repeat(pattern: &[u8], len: usize) -> Vec<u8> {
+    pattern.iter().copied().cycle().take(len).collect()
+}
+
+/// Compresses [`repeat(pattern, len)`](repeat) into an LZ4 block: the
+/// pattern as literals, one match copying it over and over, and the five
+/// literals a block has to end with.
+pub fn lz4_repeat(pattern: &[u8], len: usize) -> Vec<u8> {
+    assert!(!pattern.is_empty() && len >= pattern.len() + 12);
+    let data = repeat(pattern, len);
+    let match_len = len - pattern.len() - 5;
+
+    let mut out = Vec::new();
+    out.push(((pattern.len().min(15) as u8) << 4) | (match_len - 4).min(15) as u8);
+    lz4_length(&mut out, pattern.len());
+    out.extend_from_slice(pattern);
+    out.extend_from_slice(&(pattern.len() as u16).to_le_bytes());
+    lz4_length(&mut out, match_len - 4);
+    out.push(5 << 4);
+    out.extend_from_slice(&data[len - 5..]);
+    out
+}
+
+/// Returns `len` bytes of data along with an LZ4 block of exactly
+/// `stream_len` bytes decoding to them: random literals, a run of the last
+/// one, and a few more literals, sized so that the block fills a pcluster
+/// without any padding.
+pub fn lz4_exact(len: usize, stream_len: usize, seed: u64) -> (Vec<u8>, Vec<u8>) {
+    for literals in 1..len {
+        for tail in 5..15 {
+            let Some(run) = len.checked_sub(literals + tail).filter(|&run| run >= 4) else {
+                continue;
+            };
+            let data = random_bytes(literals, seed);
+            let last = data[literals - 1];
+            let tail_data = random_bytes(tail, !seed);
+
+            let mut out = vec![((literals.min(15) as u8) << 4) | (run - 4).min(15) as u8];
+            lz4_length(&mut out, literals);
+            out.extend_from_slice(&data);
+            out.extend_from_slice(&1u16.to_le_bytes());
+            lz4_length(&mut out, run - 4);
+            out.push((tail as u8) << 4);
+            out.extend_from_slice(&tail_data);
+            if out.len() == stream_len {
+                let data = [data, vec![last; run], tail_data].concat();
+                return (data, out);
+            }
+        }
+    }
+    panic!("no {stream_len}-byte LZ4 block decodes to {len} bytes");
+}
+
+/// Encodes `data` as an LZ4 block of literals only, which is slightly
+/// longer than `data`.
+pub fn lz4_literals(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![(data.len().min(15) as u8) << 4];
+    lz4_length(&mut out, data.len());
+    out.extend_from_slice(data);
+    out
+}
+
+/// Appends the extra bytes of an LZ4 length that doesn't fit in its token.
+fn lz4_length(out: &mut Vec<u8>, len: usize) {
+    if len < 15 {
+        return;
+    }
+    let mut rest = len - 15;
+    while rest >= 255 {
+        out.push(255);
+        rest -= 255;
+    }
+    out.push(rest as u8);
+}
+
+/// The contents of `/etc/motd` in [`sample`].
+pub fn motd() -> Vec<u8> {
+    random_bytes(10_000, 7)
+}
+
+/// A 4K-block image holding a small tree:
+///
+/// ```text
+/// /
+/// ├── empty/
+/// ├── etc/
+/// │   ├── hostname   "erofs\n", tail-packed
+/// │   └── motd       motd(), in plain blocks
+/// └── hostname -> etc/hostname
+/// ```
+pub fn sample() -> Vec<u8> {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let empty = builder.reserve();
+    let etc = builder.reserve();
+    let hostname = builder.file(Data::Inline(b"erofs\n".to_vec()));
+    let motd = builder.file(Data::Plain(motd()));
+    let link = builder.symlink("etc/hostname");
+    builder.dir(
+        root,
+        root,
+        &[("empty", empty), ("etc", etc), ("hostname", link)],
+    );
+    builder.dir(empty, root, &[]);
+    builder.dir(etc, root, &[("hostname", hostname), ("motd", motd)]);
+    builder.build()
+}
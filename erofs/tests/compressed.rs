@@ -0,0 +1,346 @@
+#![cfg(feature = "lz4")]
+
+mod common;
+
+use std::io::Read;
+
+use common::{
+    Data, ImageBuilder, Pcluster, check_file, lz4_exact, lz4_literals, lz4_repeat, random_bytes,
+    repeat, single_file,
+};
+use erofs_rs::backend::SliceImage;
+use erofs_rs::types::{Compression, CompressionAlgo, FEATURE_INCOMPAT_ZERO_PADDING};
+use erofs_rs::{EroFS, Error, ReadTrace};
+
+#[test]
+fn lz4_pclusters() {
+    let mut builder = ImageBuilder::new(12);
+    let bs = builder.block_size();
+
+    // Without zero padding, compressed data fills its pcluster from the
+    // start. The second pcluster starts halfway through an lcluster, and
+    // the last one is stored uncompressed.
+    let (a, a_lz4) = lz4_exact(bs * 3 / 2, bs, 1);
+    let (b, b_lz4) = lz4_exact(bs * 3 / 2, bs, 2);
+    let c = random_bytes(bs, 3);
+    let file = Data::Compressed {
+        size: a.len() + b.len() + c.len(),
+        algorithm: CompressionAlgo::Lz4,
+        pclusters: vec![
+            Pcluster::compressed(0, a_lz4),
+            Pcluster::compressed(a.len(), b_lz4),
+            Pcluster::plain(a.len() + b.len(), c.clone()),
+        ],
+        inline_tail: false,
+    };
+    let img = single_file(&mut builder, file);
+    check_file(&img, &[a, b, c].concat());
+}
+
+#[test]
+fn zero_padded_lz4_pclusters() {
+    let mut builder = ImageBuilder::new(12);
+    builder.feature_incompat(FEATURE_INCOMPAT_ZERO_PADDING);
+    let bs = builder.block_size();
+
+    // Each stream ends where its pcluster ends, behind leading zeros.
+    let a = repeat(b"alpha-", bs * 3 / 2);
+    let b = repeat(b"bravo!!", bs * 3 / 2);
+    let c = random_bytes(bs, 3);
+    let file = Data::Compressed {
+        size: a.len() + b.len() + c.len(),
+        algorithm: CompressionAlgo::Lz4,
+        pclusters: vec![
+            Pcluster::compressed(0, lz4_repeat(b"alpha-", a.len())),
+            Pcluster::compressed(a.len(), lz4_repeat(b"bravo!!", b.len())),
+            Pcluster::plain(a.len() + b.len(), c.clone()),
+        ],
+        inline_tail: false,
+    };
+    let img = single_file(&mut builder, file);
+    check_file(&img, &[a, b, c].concat());
+}
+
+#[test]
+fn big_lz4_pcluster() {
+    let mut builder = ImageBuilder::new(12);
+    // Big pclusters come with compression configs: an LZ4 one with the
+    // default match distance and pclusters of up to 3 blocks.
+    let mut lz4_config = [0; 14];
+    lz4_config[2] = 3;
+    builder
+        .feature_incompat(FEATURE_INCOMPAT_ZERO_PADDING)
+        .compr_config(CompressionAlgo::Lz4, &lz4_config);
+    let bs = builder.block_size();
+
+    // Literals don't compress, so the first pcluster takes three blocks
+    // with its padding inside the first one. The second pcluster starts
+    // partway through the lcluster after the block count.
+    let a = random_bytes(bs * 2 + 808, 1);
+    let b = repeat(b"after-big", bs * 4 - a.len());
+    let file = Data::Compressed {
+        size: a.len() + b.len(),
+        algorithm: CompressionAlgo::Lz4,
+        pclusters: vec![
+            Pcluster::compressed(0, lz4_literals(&a)).blocks(3),
+            Pcluster::compressed(a.len(), lz4_repeat(b"after-big", b.len())),
+        ],
+        inline_tail: false,
+    };
+    let img = single_file(&mut builder, file);
+    let expected = [a.clone(), b].concat();
+    check_file(&img, &expected);
+
+    // A read straddling both pclusters.
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let mut buf = vec![0; 2000];
+    let file = fs.open("/file").unwrap();
+    assert_eq!(file.read_at(a.len() - 1000, &mut buf).unwrap(), 2000);
+    assert_eq!(buf, expected[a.len() - 1000..a.len() + 1000]);
+}
+
+#[test]
+fn inline_tail_pcluster_after_partial_lcluster() {
+    let mut builder = ImageBuilder::new(12);
+    builder.feature_incompat(FEATURE_INCOMPAT_ZERO_PADDING);
+    let bs = builder.block_size();
+
+    // The tail pcluster starts a quarter into the second lcluster, past
+    // the end of the last full one, and runs to the end of the file.
+    let head = repeat(b"head", bs * 5 / 4);
+    let tail = repeat(b"tail-data", bs * 5 / 4);
+    let file = Data::Compressed {
+        size: head.len() + tail.len(),
+        algorithm: CompressionAlgo::Lz4,
+        pclusters: vec![
+            Pcluster::compressed(0, lz4_repeat(b"head", head.len())),
+            Pcluster::compressed(head.len(), lz4_repeat(b"tail-data", tail.len())),
+        ],
+        inline_tail: true,
+    };
+    let img = single_file(&mut builder, file);
+    check_file(&img, &[head, tail].concat());
+}
+
+#[test]
+fn read_at_traced_counts_cache_hits() {
+    let mut builder = ImageBuilder::new(12);
+    let bs = builder.block_size();
+    let (a, a_lz4) = lz4_exact(bs * 2, bs, 1);
+    let (b, b_lz4) = lz4_exact(bs * 2, bs, 2);
+    let root = builder.reserve();
+    let plain = builder.file(Data::Plain(random_bytes(bs, 3)));
+    let file = Data::Compressed {
+        size: a.len() + b.len(),
+        algorithm: CompressionAlgo::Lz4,
+        pclusters: vec![
+            Pcluster::compressed(0, a_lz4),
+            Pcluster::compressed(a.len(), b_lz4),
+        ],
+        inline_tail: false,
+    };
+    let nid = builder.file(file);
+    builder.dir(root, root, &[("file", nid), ("plain", plain)]);
+    let img = builder.build();
+    let expected = [a, b].concat();
+
+    let fs = EroFS::with_cache(SliceImage::new(&img), 1 << 20).unwrap();
+    let inode = *fs.metadata("/file").unwrap().inode();
+    let mut buf = vec![0; bs * 2];
+    let trace = fs.read_at_traced(&inode, bs, &mut buf).unwrap();
+    assert_eq!(buf, expected[bs..bs * 3]);
+    assert_eq!(
+        trace,
+        ReadTrace {
+            bytes: bs * 2,
+            cache_hits: 0,
+            cache_misses: 2
+        }
+    );
+
+    let mut buf = vec![0; bs * 8];
+    let trace = fs.read_at_traced(&inode, 0, &mut buf).unwrap();
+    assert_eq!(buf[..trace.bytes], expected);
+    assert_eq!((trace.cache_hits, trace.cache_misses), (2, 0));
+
+    // Without a cache every extent is a miss, and plain data counts as
+    // neither.
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let trace = fs.read_at_traced(&inode, 0, &mut buf).unwrap();
+    assert_eq!((trace.cache_hits, trace.cache_misses), (0, 2));
+    let plain = *fs.metadata("/plain").unwrap().inode();
+    let trace = fs.read_at_traced(&plain, 0, &mut buf).unwrap();
+    assert_eq!(trace.bytes, bs);
+    assert_eq!((trace.cache_hits, trace.cache_misses), (0, 0));
+}
+
+/// Builds an image whose packed inode stores `packed` uncompressed, with
+/// `files` in the root.
+fn with_packed_inode(
+    builder: &mut ImageBuilder,
+    packed: &[u8],
+    files: Vec<(&str, Data)>,
+) -> Vec<u8> {
+    let root = builder.reserve();
+    let packed_nid = builder.file(Data::Plain(packed.to_vec()));
+    builder.packed(packed_nid);
+    let entries: Vec<_> = files
+        .into_iter()
+        .map(|(name, data)| (name, builder.file(data)))
+        .collect();
+    builder.dir(root, root, &entries);
+    builder.build()
+}
+
+#[test]
+fn fragments_in_the_packed_inode() {
+    let mut builder = ImageBuilder::new(12);
+    builder.feature_incompat(FEATURE_INCOMPAT_ZERO_PADDING);
+    let bs = builder.block_size();
+    let packed = [repeat(b"whole file; ", 300), repeat(b"tail!", 500)].concat();
+
+    // One file packed as a whole, one with a compressed head and its tail
+    // in the packed inode.
+    let head = repeat(b"head", bs);
+    let mixed = Data::Compressed {
+        size: bs + 500,
+        algorithm: CompressionAlgo::Lz4,
+        pclusters: vec![
+            Pcluster::compressed(0, lz4_repeat(b"head", bs)),
+            Pcluster::fragment(bs, 300),
+        ],
+        inline_tail: false,
+    };
+    let img = with_packed_inode(
+        &mut builder,
+        &packed,
+        vec![
+            ("mixed", mixed),
+            (
+                "whole",
+                Data::Fragment {
+                    size: 300,
+                    offset: 0,
+                },
+            ),
+        ],
+    );
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let read = |path: &str| {
+        let mut content = Vec::new();
+        fs.open(path).unwrap().read_to_end(&mut content).unwrap();
+        content
+    };
+    assert_eq!(read("/whole"), packed[..300]);
+    assert_eq!(read("/mixed"), [&head[..], &packed[300..]].concat());
+
+    let mut buf = [0; 100];
+    let inode = *fs.metadata("/mixed").unwrap().inode();
+    let trace = fs.read_at_traced(&inode, bs - 50, &mut buf).unwrap();
+    assert_eq!(trace.bytes, 100);
+    assert_eq!(buf[..50], head[bs - 50..]);
+    assert_eq!(buf[50..], packed[300..350]);
+    // Only the head pcluster is decoded: the packed inode is not compressed.
+    assert_eq!((trace.cache_hits, trace.cache_misses), (0, 1));
+}
+
+#[test]
+fn fragments_are_read_only_where_requested() {
+    let mut builder = ImageBuilder::new(12);
+    let packed = random_bytes(10_000, 6);
+    // The fragment claims 6000 bytes, but only 5000 are left in the packed
+    // inode, so only its first block can be read.
+    let short = Data::Fragment {
+        size: 6000,
+        offset: 5000,
+    };
+    let img = with_packed_inode(&mut builder, &packed, vec![("short", short)]);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let file = fs.open("/short").unwrap();
+
+    let mut buf = [0; 50];
+    assert_eq!(file.read_at(20, &mut buf).unwrap(), 50);
+    assert_eq!(buf, packed[5020..5070]);
+    let err = file.read_at(5500, &mut [0; 100]).unwrap_err();
+    assert!(
+        err.to_string().contains("exceeds the packed inode"),
+        "{err}"
+    );
+}
+
+#[test]
+fn reading_the_packed_inode() {
+    let mut builder = ImageBuilder::new(12);
+    let packed = random_bytes(5000, 4);
+    let whole = Data::Fragment {
+        size: 100,
+        offset: 4000,
+    };
+    let img = with_packed_inode(&mut builder, &packed, vec![("whole", whole)]);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let inode = fs.fragment_inode().unwrap().unwrap();
+    assert!(inode.is_file());
+    assert_eq!(inode.data_size(), packed.len());
+    // The tail of `whole` read back through the offset it refers to.
+    assert_eq!(
+        fs.read_packed(4000, 100).unwrap().unwrap(),
+        packed[4000..4100]
+    );
+    assert_eq!(fs.read_packed(0, 5000).unwrap().unwrap(), packed);
+    assert!(matches!(
+        fs.read_packed(4990, 11),
+        Err(Error::OutOfRange(5001, 5000))
+    ));
+
+    let img = single_file(&mut ImageBuilder::new(12), Data::Plain(packed));
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    assert!(fs.fragment_inode().unwrap().is_none());
+    assert!(fs.read_packed(0, 1).unwrap().is_none());
+}
+
+#[test]
+fn compression_reports_algorithm_and_cluster_size() {
+    let mut builder = ImageBuilder::new(12);
+    builder.feature_incompat(FEATURE_INCOMPAT_ZERO_PADDING);
+    let bs = builder.block_size();
+    let lz4 = Data::Compressed {
+        size: bs,
+        algorithm: CompressionAlgo::Lz4,
+        pclusters: vec![Pcluster::compressed(0, lz4_repeat(b"lz4", bs))],
+        inline_tail: false,
+    };
+    let img = with_packed_inode(
+        &mut builder,
+        &repeat(b"packed", 100),
+        vec![
+            ("lz4", lz4),
+            ("plain", Data::Plain(random_bytes(bs, 1))),
+            (
+                "whole",
+                Data::Fragment {
+                    size: 100,
+                    offset: 0,
+                },
+            ),
+        ],
+    );
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let compression = |path: &str| {
+        let inode = *fs.metadata(path).unwrap().inode();
+        fs.compression(&inode).unwrap()
+    };
+
+    let lz4 = compression("/lz4").unwrap();
+    assert_eq!(
+        lz4,
+        Compression {
+            algorithm: CompressionAlgo::Lz4,
+            lcluster_bits: 12,
+        }
+    );
+    assert_eq!(lz4.lcluster_size(), bs);
+    assert_eq!(compression("/plain"), None);
+    // A whole-file fragment reports the packed inode, which is plain here.
+    assert_eq!(compression("/whole"), None);
+}
@@ -0,0 +1,52 @@
+#![cfg(feature = "deflate")]
+
+mod common;
+
+use common::{Data, ImageBuilder, Pcluster, check_file, deflate_config, repeat, single_file};
+use erofs_rs::backend::SliceImage;
+use erofs_rs::types::{CompressionAlgo, FEATURE_INCOMPAT_ZERO_PADDING};
+use erofs_rs::{EroFS, Error};
+use miniz_oxide::deflate::compress_to_vec;
+
+fn deflate_image(windowbits: u8, inline_tail: bool) -> (Vec<u8>, Vec<u8>) {
+    let mut builder = ImageBuilder::new(12);
+    builder
+        .feature_incompat(FEATURE_INCOMPAT_ZERO_PADDING)
+        .compr_config(CompressionAlgo::Deflate, &deflate_config(windowbits));
+    let bs = builder.block_size();
+    // The second pcluster starts partway through the third lcluster.
+    let a = repeat(b"deflate pcluster a ", bs * 2 + 808);
+    let b = repeat(b"and b; ", bs / 2);
+    let file = Data::Compressed {
+        size: a.len() + b.len(),
+        algorithm: CompressionAlgo::Deflate,
+        pclusters: vec![
+            Pcluster::compressed(0, compress_to_vec(&a, 6)),
+            Pcluster::compressed(a.len(), compress_to_vec(&b, 6)),
+        ],
+        inline_tail,
+    };
+    (single_file(&mut builder, file), [a, b].concat())
+}
+
+#[test]
+fn deflate_pclusters() {
+    let (img, expected) = deflate_image(15, false);
+    check_file(&img, &expected);
+}
+
+#[test]
+fn deflate_inline_tail_pcluster() {
+    let (img, expected) = deflate_image(15, true);
+    check_file(&img, &expected);
+}
+
+#[test]
+fn deflate_config_is_validated() {
+    // Raw DEFLATE windows are at most 32 KiB.
+    let (img, _) = deflate_image(16, false);
+    assert!(matches!(
+        EroFS::new(SliceImage::new(&img)),
+        Err(Error::CorruptedData(_))
+    ));
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use std::io::{ErrorKind, Read};
+
+use erofs_rs::backend::SliceImage;
+use erofs_rs::{EroFS, Error};
+
+#[test]
+fn io_error_kind_matches_error() {
+    let cases = [
+        (
+            Error::NotFound {
+                path: "/x".into(),
+                component: "x".into(),
+            },
+            ErrorKind::NotFound,
+        ),
+        (
+            Error::NotADirectory {
+                path: "/x".into(),
+                component: "x".into(),
+            },
+            ErrorKind::NotADirectory,
+        ),
+        (Error::NotARegularFile("/x".into()), ErrorKind::InvalidInput),
+        (Error::IsASymlink("/x".into()), ErrorKind::InvalidInput),
+        (
+            Error::OutOfBounds {
+                offset: 4096,
+                len: 8,
+                image_len: 4100,
+            },
+            ErrorKind::UnexpectedEof,
+        ),
+        (Error::NotSupported("x".into()), ErrorKind::Unsupported),
+        (Error::CorruptedData("x".into()), ErrorKind::InvalidData),
+        (Error::InvalidInode(7), ErrorKind::InvalidData),
+    ];
+    for (err, kind) in cases {
+        let message = err.to_string();
+        let io = std::io::Error::from(err);
+        assert_eq!(io.kind(), kind, "{message}");
+        assert_eq!(io.to_string(), message);
+        assert!(io.get_ref().unwrap().downcast_ref::<Error>().is_some());
+    }
+}
+
+#[test]
+fn read_errors_keep_their_source() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let motd = fs.metadata("/etc/motd").unwrap();
+
+    // Cut the image in the middle of the file's data.
+    let truncated = &img[..img.len() - 4096];
+    let fs = EroFS::new(SliceImage::new(truncated)).unwrap();
+    let mut file = fs.open_inode_file(*motd.inode()).unwrap();
+    let err = file.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    let source = err.into_inner().unwrap().downcast::<Error>().unwrap();
+    // The error tells where the read landed in the truncated image.
+    match *source {
+        Error::OutOfBounds {
+            offset,
+            len,
+            image_len,
+        } => {
+            assert_eq!(image_len, truncated.len() as u64);
+            assert!(offset + len > truncated.len(), "{offset} + {len}");
+        }
+        ref err => panic!("unexpected error {err}"),
+    }
+}
+
+#[test]
+fn path_errors_name_the_failing_component() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let Err(Error::NotFound { path, component }) = fs.metadata("/etc/missing/file") else {
+        panic!("/etc/missing/file must not be found");
+    };
+    assert_eq!(
+        (path.as_str(), component.as_str()),
+        ("/etc/missing/file", "missing")
+    );
+
+    let err = fs.metadata("/etc/motd/file").unwrap_err();
+    let Error::NotADirectory { path, component } = &err else {
+        panic!("unexpected error {err}");
+    };
+    assert_eq!(
+        (path.as_str(), component.as_str()),
+        ("/etc/motd/file", "motd")
+    );
+    assert_eq!(
+        err.to_string(),
+        "not a directory: /etc/motd/file (motd is not a directory)"
+    );
+
+    // Opening a file as a directory names nothing beyond the path itself.
+    let err = fs.read_dir("/etc/motd").unwrap_err();
+    assert!(matches!(err, Error::NotADirectory { .. }), "{err}");
+}
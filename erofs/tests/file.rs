@@ -0,0 +1,350 @@
+mod common;
+
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::RangeBounds;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use common::{Data, ImageBuilder, Pcluster, random_bytes};
+use erofs_rs::backend::{Image, SliceImage, VecImage};
+use erofs_rs::types::CompressionAlgo;
+use erofs_rs::{EroFS, Error};
+
+#[test]
+fn read_at_does_not_move_the_position() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let motd = common::motd();
+    let mut file = fs.open("/etc/motd").unwrap();
+
+    let mut head = [0; 10];
+    file.read_exact(&mut head).unwrap();
+
+    let mut buf = vec![0; 5000];
+    assert_eq!(file.read_at(4090, &mut buf).unwrap(), 5000);
+    assert_eq!(buf, motd[4090..9090]);
+
+    assert_eq!(file.read_at(9000, &mut buf).unwrap(), 1000);
+    assert_eq!(buf[..1000], motd[9000..]);
+    assert_eq!(file.read_at(motd.len(), &mut buf).unwrap(), 0);
+
+    let mut rest = Vec::new();
+    file.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, motd[10..]);
+}
+
+#[test]
+fn read_whole_files() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    assert_eq!(fs.read("/etc/motd").unwrap(), common::motd());
+    assert_eq!(fs.read_to_string("/hostname").unwrap(), "erofs\n");
+    assert!(matches!(fs.read("/etc"), Err(Error::NotARegularFile(_))));
+    assert!(matches!(
+        fs.read_to_string("/missing"),
+        Err(Error::NotFound { .. })
+    ));
+}
+
+#[test]
+fn read_to_string_rejects_invalid_utf8() {
+    let img = common::single_file(
+        &mut common::ImageBuilder::new(12),
+        common::Data::Inline(b"ok\xff".to_vec()),
+    );
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    assert_eq!(fs.read("/file").unwrap(), &b"ok\xff"[..]);
+    assert!(matches!(
+        fs.read_to_string("/file"),
+        Err(Error::InvalidUtf8(e)) if e.valid_up_to() == 2
+    ));
+}
+
+#[test]
+fn seek_moves_the_position() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let motd = common::motd();
+    let mut file = fs.open("/etc/motd").unwrap();
+
+    // Within the buffered block, across blocks and back.
+    let mut buf = [0; 100];
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(file.seek(SeekFrom::Current(-50)).unwrap(), 50);
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, motd[50..150]);
+    assert_eq!(file.seek(SeekFrom::Start(8000)).unwrap(), 8000);
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, motd[8000..8100]);
+    assert_eq!(file.seek(SeekFrom::End(-10)).unwrap(), 9990);
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail).unwrap();
+    assert_eq!(tail, motd[9990..]);
+    file.rewind().unwrap();
+    file.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, motd[..100]);
+
+    // Past the end reads nothing, before the start is an error.
+    assert_eq!(file.seek(SeekFrom::End(10)).unwrap(), 10_010);
+    assert_eq!(file.read(&mut buf).unwrap(), 0);
+    let err = file.seek(SeekFrom::Current(-20_000)).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(file.stream_position().unwrap(), 10_010);
+}
+
+#[test]
+fn blocks_are_owned_and_outlive_the_borrow() {
+    let fs = EroFS::new(VecImage::new(common::sample())).unwrap();
+    let inode = *fs.metadata("/etc/motd").unwrap().inode();
+    let blocks = fs.blocks(&inode);
+    drop(fs);
+
+    let blocks: Vec<_> = std::thread::spawn(move || blocks.map(Result::unwrap).collect())
+        .join()
+        .unwrap();
+    let lens: Vec<_> = blocks.iter().map(|block| block.len()).collect();
+    assert_eq!(lens, [4096, 4096, 10_000 - 2 * 4096]);
+    assert_eq!(blocks.concat(), common::motd());
+}
+
+/// An image counting how often it is read.
+struct CountingImage<'a> {
+    inner: SliceImage<'a>,
+    reads: Arc<AtomicUsize>,
+}
+
+impl Image for CountingImage<'_> {
+    fn get<R: RangeBounds<usize>>(&self, range: R) -> Option<&[u8]> {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        self.inner.get(range)
+    }
+
+    fn len(&self) -> u64 {
+        self.inner.len()
+    }
+}
+
+#[test]
+fn prefetch_on_16k_blocks() {
+    let mut builder = ImageBuilder::new(14);
+    let bs = builder.block_size();
+    let root = builder.reserve();
+    let data = random_bytes(bs * 5 / 2, 1);
+    let plain = builder.file(Data::Plain(data.clone()));
+    let compressed = builder.file(Data::Compressed {
+        size: bs * 2,
+        algorithm: CompressionAlgo::Lz4,
+        pclusters: vec![
+            Pcluster::plain(0, data[..bs].to_vec()),
+            Pcluster::plain(bs, data[bs..bs * 2].to_vec()),
+        ],
+        inline_tail: false,
+    });
+    builder.dir(root, root, &[("compressed", compressed), ("plain", plain)]);
+    let img = builder.build();
+
+    let counter = Arc::new(AtomicUsize::new(0));
+    let image = CountingImage {
+        inner: SliceImage::new(&img),
+        reads: counter.clone(),
+    };
+    let fs = EroFS::new(image).unwrap();
+    let reads = || counter.load(Ordering::Relaxed);
+
+    let mut file = fs.open("/plain").unwrap();
+    file.seek(SeekFrom::Start(bs as u64 + 10)).unwrap();
+    let before = reads();
+    file.prefetch(2);
+    let two = reads() - before;
+    assert!(two > 0);
+    // Only two blocks are left in the file.
+    let before = reads();
+    file.prefetch(usize::MAX);
+    assert_eq!(reads() - before, two);
+    let mut rest = Vec::new();
+    file.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, data[bs + 10..]);
+
+    // Decoding without a cache to keep the result in is wasted work.
+    let file = fs.open("/compressed").unwrap();
+    let before = reads();
+    file.prefetch(2);
+    assert_eq!(reads(), before);
+
+    let fs = EroFS::with_cache(SliceImage::new(&img), 1 << 20).unwrap();
+    let mut file = fs.open("/compressed").unwrap();
+    file.prefetch(2);
+    let mut content = Vec::new();
+    file.read_to_end(&mut content).unwrap();
+    assert_eq!(content, data[..bs * 2]);
+}
+
+#[test]
+fn files_know_their_path_and_inode() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    // The path is kept as given, through the symlink.
+    let file = fs.open("/hostname").unwrap();
+    assert_eq!(file.path().unwrap().as_bytes(), b"/hostname");
+    let target = fs.metadata("/etc/hostname").unwrap();
+    assert_eq!(file.inode().id(), target.nid());
+
+    let file = fs.open_inode_by_nid(target.nid()).unwrap();
+    assert!(file.path().is_none());
+
+    let paths: Vec<_> = fs
+        .walk_dir("/")
+        .unwrap()
+        .files()
+        .map(|file| {
+            let (path, file) = file.unwrap();
+            assert_eq!(file.path(), Some(path.as_path()));
+            path.to_string_lossy().into_owned()
+        })
+        .collect();
+    assert_eq!(paths, ["/etc/hostname", "/etc/motd"]);
+}
+
+#[test]
+fn block_iter_yields_aligned_blocks() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let data = random_bytes(4096 * 2 + 100, 7);
+    let plain = builder.file(Data::Plain(data.clone()));
+    let compressed = builder.file(Data::Compressed {
+        size: data.len(),
+        algorithm: CompressionAlgo::Lz4,
+        pclusters: vec![
+            Pcluster::plain(0, data[..4096].to_vec()),
+            Pcluster::plain(4096, data[4096..8192].to_vec()),
+            Pcluster::plain(8192, data[8192..].to_vec()),
+        ],
+        inline_tail: false,
+    });
+    let sparse = builder.file(Data::Chunked {
+        data: [vec![0; 4096], data[4096..].to_vec()].concat(),
+        chunk_bits: 0,
+        holes: vec![0],
+        device: None,
+    });
+    builder.dir(
+        root,
+        root,
+        &[
+            ("compressed", compressed),
+            ("plain", plain),
+            ("sparse", sparse),
+        ],
+    );
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let blocks = |path: &str| -> Vec<(usize, Vec<u8>)> {
+        let mut file = fs.open(path).unwrap();
+        file.seek(SeekFrom::Start(10)).unwrap();
+        file.block_iter()
+            .map(|block| {
+                let (offset, block) = block.unwrap();
+                (offset, block.into_owned())
+            })
+            .collect()
+    };
+    let expected: Vec<_> = data
+        .chunks(4096)
+        .enumerate()
+        .map(|(i, block)| (i * 4096, block.to_vec()))
+        .collect();
+    assert_eq!(blocks("/plain"), expected);
+    assert_eq!(blocks("/compressed"), expected);
+
+    let sparse = blocks("/sparse");
+    assert_eq!(sparse[0], (0, vec![0; 4096]));
+    assert_eq!(sparse[1..], expected[1..]);
+}
+
+#[test]
+fn as_slice_borrows_files_stored_in_one_piece() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let data = random_bytes(4096 * 2 + 100, 3);
+    let plain = builder.file(Data::Plain(data.clone()));
+    let small = builder.file(Data::Inline(data[..100].to_vec()));
+    let tail = builder.file(Data::Inline(data.clone()));
+    let empty = builder.file(Data::Inline(Vec::new()));
+    let chunked = builder.file(Data::Chunked {
+        data: data.clone(),
+        chunk_bits: 0,
+        holes: Vec::new(),
+        device: None,
+    });
+    builder.dir(
+        root,
+        root,
+        &[
+            ("chunked", chunked),
+            ("empty", empty),
+            ("plain", plain),
+            ("small", small),
+            ("tail", tail),
+        ],
+    );
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let slice = |path: &str| fs.open(path).unwrap().as_slice();
+
+    let plain = slice("/plain").unwrap();
+    assert_eq!(plain, data);
+    assert!(img.as_ptr_range().contains(&plain.as_ptr()));
+    assert_eq!(slice("/small").unwrap(), &data[..100]);
+    assert_eq!(slice("/empty").unwrap(), b"");
+    // Split between blocks and the inode, or between chunks.
+    assert_eq!(slice("/tail"), None);
+    assert_eq!(slice("/chunked"), None);
+}
+
+#[test]
+fn read_head_reads_only_the_first_blocks() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let data = random_bytes(4096 * 3, 9);
+    let compressed = builder.file(Data::Compressed {
+        size: data.len(),
+        algorithm: CompressionAlgo::Lz4,
+        pclusters: vec![
+            Pcluster::plain(0, data[..4096].to_vec()),
+            Pcluster::plain(4096, data[4096..8192].to_vec()),
+            Pcluster::plain(8192, data[8192..].to_vec()),
+        ],
+        inline_tail: false,
+    });
+    let small = builder.file(Data::Inline(b"#!/bin/sh".to_vec()));
+    let link = builder.symlink("small");
+    builder.dir(
+        root,
+        root,
+        &[("compressed", compressed), ("link", link), ("small", small)],
+    );
+    let img = builder.build();
+    let counter = Arc::new(AtomicUsize::new(0));
+    let image = CountingImage {
+        inner: SliceImage::new(&img),
+        reads: counter.clone(),
+    };
+    let fs = EroFS::new(image).unwrap();
+    let reads = |f: &dyn Fn()| {
+        let before = counter.load(Ordering::Relaxed);
+        f();
+        counter.load(Ordering::Relaxed) - before
+    };
+
+    let head = reads(&|| assert_eq!(fs.read_head("/compressed", 4).unwrap(), data[..4]));
+    let whole = reads(&|| assert_eq!(fs.read("/compressed").unwrap(), data));
+    assert!(head < whole, "{head} >= {whole}");
+
+    assert_eq!(fs.read_head("/link", 2).unwrap(), b"#!"[..]);
+    assert_eq!(fs.read_head("/small", 100).unwrap(), b"#!/bin/sh"[..]);
+    assert!(fs.read_head("/", 1).is_err());
+}
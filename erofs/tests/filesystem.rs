@@ -0,0 +1,591 @@
+mod common;
+
+use std::collections::BTreeSet;
+use std::io::Read;
+use std::ops::RangeBounds;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use common::{Data, ImageBuilder, Pcluster, empty_image, lzma_config, zstd_config};
+use erofs_rs::backend::{Image, SliceImage};
+use erofs_rs::types::{
+    CompatFeatures, CompressionAlgo, DirentFileType, FEATURE_COMPAT_SB_CHKSUM,
+    FEATURE_INCOMPAT_COMPR_CFGS, FEATURE_INCOMPAT_ZERO_PADDING, IncompatFeatures, Layout,
+};
+use erofs_rs::{EroFS, Error};
+
+fn required_algorithms(builder: &mut ImageBuilder) -> BTreeSet<CompressionAlgo> {
+    let img = empty_image(builder);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    fs.required_algorithms().unwrap()
+}
+
+#[test]
+fn required_algorithms_from_superblock() {
+    let uncompressed = required_algorithms(&mut ImageBuilder::new(12));
+    assert!(uncompressed.is_empty());
+
+    // Without compression configs `compr_algs` is the LZ4 distance, and the
+    // zero padding bit alone marks LZ4 data.
+    let mut builder = ImageBuilder::new(12);
+    builder
+        .feature_incompat(FEATURE_INCOMPAT_ZERO_PADDING)
+        .compr_algs(0xffff);
+    assert_eq!(
+        required_algorithms(&mut builder),
+        BTreeSet::from([CompressionAlgo::Lz4])
+    );
+
+    let mut builder = ImageBuilder::new(12);
+    builder
+        .compr_config(CompressionAlgo::Lzma, &lzma_config(1 << 16))
+        .compr_config(CompressionAlgo::Zstd, &zstd_config(10));
+    assert_eq!(
+        required_algorithms(&mut builder),
+        BTreeSet::from([CompressionAlgo::Lzma, CompressionAlgo::Zstd])
+    );
+}
+
+#[test]
+fn required_algorithms_rejects_unknown_algorithm() {
+    let mut builder = ImageBuilder::new(12);
+    builder
+        .feature_incompat(FEATURE_INCOMPAT_COMPR_CFGS)
+        .compr_algs(1 << 7);
+    let img = empty_image(&mut builder);
+    assert!(matches!(
+        EroFS::new(SliceImage::new(&img)),
+        Err(Error::UnsupportedCompression(_))
+    ));
+}
+
+#[test]
+fn file_type_from_parent_dirent() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    assert_eq!(fs.file_type("/").unwrap(), DirentFileType::Directory);
+    assert_eq!(fs.file_type("/etc").unwrap(), DirentFileType::Directory);
+    assert_eq!(
+        fs.file_type("/etc/motd").unwrap(),
+        DirentFileType::RegularFile
+    );
+    assert_eq!(fs.file_type("/hostname").unwrap(), DirentFileType::Symlink);
+    assert!(matches!(
+        fs.file_type("/etc/missing"),
+        Err(Error::NotFound { .. })
+    ));
+}
+
+#[test]
+fn devices_from_device_table() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    assert!(fs.devices().is_empty());
+
+    let mut builder = ImageBuilder::new(12);
+    builder.device("blob0", 16, 1024).device("blob1", 32, 1040);
+    let img = empty_image(&mut builder);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let devices: Vec<_> = fs
+        .devices()
+        .iter()
+        .map(|d| (d.tag().to_vec(), d.blocks, d.mapped_blkaddr))
+        .collect();
+    assert_eq!(
+        devices,
+        [(b"blob0".to_vec(), 16, 1024), (b"blob1".to_vec(), 32, 1040)]
+    );
+}
+
+#[test]
+fn inline_xattr_size_from_icount() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let plain = builder.file(Data::Inline(b"no xattrs".to_vec()));
+    let tagged = builder.file(Data::Inline(b"tail after xattrs".to_vec()));
+    builder.dir(root, root, &[("plain", plain), ("tagged", tagged)]);
+    // The header and two 4-byte slots: an icount of 3.
+    builder.inline_xattrs(tagged, &[0; 20]);
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let inode = fs.get_inode(plain).unwrap();
+    assert_eq!((inode.xattr_icount(), inode.xattr_size()), (0, 0));
+    let inode = fs.get_inode(tagged).unwrap();
+    assert_eq!((inode.xattr_icount(), inode.xattr_size()), (3, 20));
+
+    let mut content = String::new();
+    fs.open("/tagged")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, "tail after xattrs");
+}
+
+#[test]
+fn inode_nid_is_its_slot() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let file = builder.file(Data::Inline(Vec::new()));
+    builder.dir(root, root, &[("file", file)]);
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    // Each inode of the test image takes a 4K block, or 128 32-byte slots.
+    assert_eq!((root, file), (0, 128));
+    let inode = fs.get_inode(file).unwrap();
+    assert_eq!(inode.nid(), file);
+    assert_eq!(inode.id(), inode.nid());
+}
+
+#[test]
+fn super_block_uuid_volume_name_and_block_size() {
+    let mut builder = ImageBuilder::new(12);
+    builder
+        .uuid([
+            0x71, 0xbd, 0x9a, 0xb4, 0xfb, 0x8c, 0x47, 0xb4, 0x98, 0x6c, 0x5c, 0x90, 0x1a, 0xd5,
+            0x47, 0xc7,
+        ])
+        .volume_name(b"rootfs");
+    let img = empty_image(&mut builder);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let sb = fs.super_block();
+
+    assert_eq!(sb.block_size(), 4096);
+    assert_eq!(sb.uuid_string(), "71bd9ab4-fb8c-47b4-986c-5c901ad547c7");
+    assert_eq!(sb.volume_name(), "rootfs");
+}
+
+#[test]
+fn super_block_volume_name_cut_at_invalid_utf8() {
+    let mut builder = ImageBuilder::new(12);
+    builder.volume_name(b"data\xffjunk");
+    let img = empty_image(&mut builder);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    assert_eq!(fs.super_block().volume_name(), "data");
+}
+
+#[test]
+fn super_block_feature_flags() {
+    let mut builder = ImageBuilder::new(12);
+    builder
+        .feature_compat(FEATURE_COMPAT_SB_CHKSUM | 0x2 | 0x100)
+        .feature_incompat(FEATURE_INCOMPAT_ZERO_PADDING | 0x8000_0000);
+    let img = empty_image(&mut builder);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let sb = fs.super_block();
+
+    let compat = sb.compat_features();
+    assert!(compat.contains(CompatFeatures::SB_CHKSUM | CompatFeatures::MTIME));
+    assert!(!compat.contains(CompatFeatures::XATTR_FILTER));
+    assert_eq!(compat.to_string(), "SB_CHKSUM | MTIME | 0x100");
+
+    let incompat = sb.incompat_features();
+    assert!(incompat.contains(IncompatFeatures::ZERO_PADDING));
+    assert_eq!(
+        sb.unsupported_features(),
+        IncompatFeatures::from_bits_retain(0x8000_0000)
+    );
+    assert_eq!(sb.unsupported_features().to_string(), "0x80000000");
+}
+
+#[test]
+fn verify_superblock_checks_crc32c() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    fs.verify_superblock().unwrap();
+
+    let mut builder = ImageBuilder::new(12);
+    builder.feature_compat(FEATURE_COMPAT_SB_CHKSUM);
+    let mut img = empty_image(&mut builder);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let Err(Error::ChecksumMismatch { expected, actual }) = fs.verify_superblock() else {
+        panic!("a zero checksum must not verify");
+    };
+    assert_eq!(expected, 0);
+
+    img[1028..1032].copy_from_slice(&actual.to_le_bytes());
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    fs.verify_superblock().unwrap();
+
+    img[1024 + 64] ^= 1;
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    assert!(matches!(
+        fs.verify_superblock(),
+        Err(Error::ChecksumMismatch { .. })
+    ));
+}
+
+#[test]
+fn truncated_images_are_rejected_up_front() {
+    let img = common::sample();
+    let truncated = |len: usize| match EroFS::new(SliceImage::new(&img[..len])) {
+        Err(Error::TruncatedImage {
+            expected_min,
+            actual,
+        }) => {
+            assert_eq!(actual, len as u64);
+            expected_min
+        }
+        other => panic!("{len} bytes: {other:?}"),
+    };
+
+    // Too short for the superblock itself.
+    assert_eq!(truncated(1000), 1024 + 128);
+    // The superblock is there, but the root inode in the next block isn't.
+    assert_eq!(truncated(4096), 4096 + 32);
+    assert!(EroFS::new(SliceImage::new(&img[..4096 + 32])).is_ok());
+
+    // Metadata can't start past the blocks the image claims to have.
+    let mut img = img.clone();
+    img[1024 + 40..1024 + 44].copy_from_slice(&1000u32.to_le_bytes());
+    assert!(matches!(
+        EroFS::new(SliceImage::new(&img)),
+        Err(Error::InvalidSuperblock(_))
+    ));
+}
+
+#[test]
+fn verify_data_is_a_no_op_without_data_checksums() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    assert!(!fs.verifies_data());
+
+    let fs = fs.verify_data(true);
+    assert!(fs.verifies_data());
+    assert_eq!(fs.read("/etc/motd").unwrap(), common::motd());
+    assert!(!fs.verify_data(false).verifies_data());
+}
+
+#[test]
+fn inode_at_validates_nid() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let motd = fs.metadata("/etc/motd").unwrap().nid();
+    let mut content = Vec::new();
+    fs.open_inode_by_nid(motd)
+        .unwrap()
+        .read_to_end(&mut content)
+        .unwrap();
+    assert_eq!(content, common::motd());
+    assert!(fs.inode_at(motd).unwrap().is_file());
+
+    let etc = fs.metadata("/etc").unwrap().nid();
+    assert!(matches!(
+        fs.open_inode_by_nid(etc),
+        Err(Error::NotARegularFile(_))
+    ));
+
+    let past_end = (img.len() / 32) as u64;
+    assert!(matches!(
+        fs.inode_at(past_end),
+        Err(Error::InvalidInode(nid)) if nid == past_end
+    ));
+    assert!(matches!(
+        fs.inode_at(u64::MAX),
+        Err(Error::InvalidInode(u64::MAX))
+    ));
+}
+
+#[test]
+fn inode_at_stops_at_the_end_of_the_image() {
+    // Trailing bytes beyond the block count in the superblock hold no inodes.
+    let mut img = common::sample();
+    let past_end = (img.len() / 32) as u64;
+    img.resize(img.len() * 2, 0);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    assert!(matches!(
+        fs.inode_at(past_end),
+        Err(Error::InvalidInode(nid)) if nid == past_end
+    ));
+    assert!(fs.root_inode().unwrap().is_dir());
+}
+
+/// An image counting how often it is read.
+struct CountingImage<'a> {
+    inner: SliceImage<'a>,
+    reads: Arc<AtomicUsize>,
+}
+
+impl Image for CountingImage<'_> {
+    fn get<R: RangeBounds<usize>>(&self, range: R) -> Option<&[u8]> {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        self.inner.get(range)
+    }
+
+    fn len(&self) -> u64 {
+        self.inner.len()
+    }
+}
+
+#[test]
+fn path_cache_skips_directory_walks() {
+    let img = common::sample();
+    let reads = Arc::new(AtomicUsize::new(0));
+    let image = CountingImage {
+        inner: SliceImage::new(&img),
+        reads: reads.clone(),
+    };
+    let fs = EroFS::new(image).unwrap().with_path_cache(16);
+    let count = |path: &str| {
+        let before = reads.load(Ordering::Relaxed);
+        let nid = fs.metadata(path).unwrap().nid();
+        (nid, reads.load(Ordering::Relaxed) - before)
+    };
+
+    let (nid, walked) = count("/etc/motd");
+    let (cached_nid, cached) = count("/etc/motd");
+    assert_eq!(cached_nid, nid);
+    // A cached path only reads its inode, not the directories on the way.
+    assert!(cached < walked, "{cached} reads cached, {walked} walked");
+
+    // Following the last component is part of the key.
+    assert!(fs.symlink_metadata("/hostname").unwrap().is_symlink());
+    assert!(fs.metadata("/hostname").unwrap().is_file());
+    assert!(fs.symlink_metadata("/hostname").unwrap().is_symlink());
+    assert!(matches!(
+        fs.metadata("/missing"),
+        Err(Error::NotFound { .. })
+    ));
+}
+
+#[test]
+fn stats_count_inodes_and_sizes() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let stats = fs.stats().unwrap();
+    assert_eq!(
+        (stats.files, stats.directories, stats.symlinks, stats.others),
+        (2, 3, 1, 0)
+    );
+
+    // Directories and the symlink are tail-packed, so only the plain
+    // blocks of motd take up more than their size.
+    let dirs: u64 = ["/", "/empty", "/etc"]
+        .iter()
+        .map(|path| fs.metadata(path).unwrap().size() as u64)
+        .sum();
+    let logical = dirs + "etc/hostname".len() as u64 + 6 + 10_000;
+    assert_eq!(stats.logical_bytes, logical);
+    assert_eq!(stats.physical_bytes, logical + 3 * 4096 - 10_000);
+}
+
+#[test]
+fn stats_skip_holes_of_chunked_files() {
+    let mut data = common::random_bytes(3 * 4096 - 1000, 3);
+    data[4096..2 * 4096].fill(0);
+    let mut builder = ImageBuilder::new(12);
+    let img = common::single_file(
+        &mut builder,
+        Data::Chunked {
+            data,
+            chunk_bits: 0,
+            holes: vec![1],
+            device: None,
+        },
+    );
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let stats = fs.stats().unwrap();
+    let root = fs.metadata("/").unwrap().size() as u64;
+    assert_eq!(stats.files, 1);
+    assert_eq!(stats.logical_bytes, root + 3 * 4096 - 1000);
+    assert_eq!(stats.physical_bytes, root + 2 * 4096);
+}
+
+#[test]
+fn layout_of_each_kind_of_file() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let data = vec![1; 5000];
+    let plain = builder.file(Data::Plain(data.clone()));
+    let inline = builder.file(Data::Inline(data.clone()));
+    let chunked = builder.file(Data::Chunked {
+        data: data.clone(),
+        chunk_bits: 0,
+        holes: Vec::new(),
+        device: None,
+    });
+    let compressed = builder.file(Data::Compressed {
+        size: data.len(),
+        algorithm: CompressionAlgo::Lz4,
+        pclusters: vec![
+            Pcluster::plain(0, data[..4096].to_vec()),
+            Pcluster::plain(4096, data[4096..].to_vec()),
+        ],
+        inline_tail: false,
+    });
+    builder.dir(
+        root,
+        root,
+        &[
+            ("chunked", chunked),
+            ("compressed", compressed),
+            ("inline", inline),
+            ("plain", plain),
+        ],
+    );
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    for (path, layout, inline, compressed) in [
+        ("/plain", Layout::FlatPlain, false, false),
+        ("/inline", Layout::FlatInline, true, false),
+        ("/chunked", Layout::ChunkBased, false, false),
+        ("/compressed", Layout::CompressedFull, false, true),
+    ] {
+        let inode = *fs.metadata(path).unwrap().inode();
+        assert_eq!(inode.layout().unwrap(), layout, "{path}");
+        assert_eq!(inode.is_inline_data(), inline, "{path}");
+        assert_eq!(layout.is_compressed(), compressed, "{path}");
+    }
+    assert!(Layout::CompressedCompact.is_compressed());
+}
+
+#[test]
+fn image_len_and_block_addresses() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    assert_eq!(fs.image_len(), img.len() as u64);
+    let blocks = img.len() as u64 / 4096;
+    assert_eq!(fs.block_count(), blocks);
+    assert!(fs.block_address_valid(0));
+    assert!(fs.block_address_valid(blocks - 1));
+    assert!(!fs.block_address_valid(blocks));
+    assert!(!fs.block_address_valid(u64::MAX));
+
+    // A partial last block counts.
+    let mut img = img.clone();
+    img.extend_from_slice(&[0; 100]);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    assert_eq!(fs.block_count(), blocks + 1);
+    assert!(fs.block_address_valid(blocks));
+}
+
+#[test]
+fn extents_locate_file_data() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let data = common::random_bytes(3 * 4096 - 1000, 5);
+    let plain = builder.file(Data::Plain(data.clone()));
+    let inline = builder.file(Data::Inline(data.clone()));
+    let mut sparse_data = data.clone();
+    sparse_data[4096..2 * 4096].fill(0);
+    let sparse = builder.file(Data::Chunked {
+        data: sparse_data,
+        chunk_bits: 0,
+        holes: vec![1],
+        device: None,
+    });
+    let compressed = builder.file(Data::Compressed {
+        size: 4096 + 100,
+        algorithm: CompressionAlgo::Lz4,
+        pclusters: vec![
+            Pcluster::plain(0, data[..4096].to_vec()),
+            Pcluster::compressed(4096, common::lz4_repeat(b"ab", 100)),
+        ],
+        inline_tail: false,
+    });
+    builder.dir(
+        root,
+        root,
+        &[
+            ("compressed", compressed),
+            ("inline", inline),
+            ("plain", plain),
+            ("sparse", sparse),
+        ],
+    );
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let extents = |path: &str| fs.extents(fs.metadata(path).unwrap().inode()).unwrap();
+    let stored = |block: u64, len: u64| &img[block as usize * 4096..][..len as usize];
+
+    let plain = extents("/plain");
+    assert_eq!(plain.len(), 1);
+    assert_eq!((plain[0].device, plain[0].logical_offset), (0, 0));
+    assert_eq!(plain[0].logical_len, data.len() as u64);
+    assert_eq!(plain[0].len, 3 * 4096);
+    assert_eq!(stored(plain[0].phys_block, plain[0].logical_len), data);
+
+    // The inline tail starts partway through the block holding the inode.
+    let inline = extents("/inline");
+    assert_eq!(inline.len(), 2);
+    assert_eq!(inline[0].logical_len, 2 * 4096);
+    assert_eq!(stored(inline[0].phys_block, 2 * 4096), &data[..2 * 4096]);
+    assert_eq!(inline[1].logical_offset, 2 * 4096);
+    assert_eq!(inline[1].logical_len, data.len() as u64 - 2 * 4096);
+
+    let sparse = extents("/sparse");
+    let offsets: Vec<_> = sparse.iter().map(|e| e.logical_offset).collect();
+    assert_eq!(offsets, [0, 2 * 4096]);
+    for extent in &sparse {
+        let start = extent.logical_offset as usize;
+        assert_eq!(
+            stored(extent.phys_block, extent.logical_len),
+            &data[start..start + extent.logical_len as usize]
+        );
+        assert_eq!(extent.len, 4096);
+    }
+
+    let compressed = extents("/compressed");
+    assert_eq!(compressed.len(), 2);
+    assert_eq!(stored(compressed[0].phys_block, 4096), &data[..4096]);
+    assert_eq!(
+        (compressed[1].logical_offset, compressed[1].logical_len),
+        (4096, 100)
+    );
+    assert_eq!(compressed[1].len, 4096);
+}
+
+#[cfg(feature = "lz4")]
+#[test]
+fn read_decodes_each_pcluster_once() {
+    let mut builder = ImageBuilder::new(12);
+    builder.feature_incompat(FEATURE_INCOMPAT_ZERO_PADDING);
+    let bs = builder.block_size();
+    // One pcluster decoding to three blocks.
+    let img = common::single_file(
+        &mut builder,
+        Data::Compressed {
+            size: bs * 3,
+            algorithm: CompressionAlgo::Lz4,
+            pclusters: vec![Pcluster::compressed(0, common::lz4_repeat(b"abc", bs * 3))],
+            inline_tail: false,
+        },
+    );
+    let reads = Arc::new(AtomicUsize::new(0));
+    let image = CountingImage {
+        inner: SliceImage::new(&img),
+        reads: reads.clone(),
+    };
+    let fs = EroFS::new(image).unwrap();
+    let count = |f: &dyn Fn()| {
+        let before = reads.load(Ordering::Relaxed);
+        f();
+        reads.load(Ordering::Relaxed) - before
+    };
+
+    let head = count(&|| assert_eq!(fs.read_head("/file", 1).unwrap().len(), 1));
+    let whole = count(&|| assert_eq!(fs.read("/file").unwrap(), common::repeat(b"abc", bs * 3)));
+    assert_eq!(whole, head);
+}
+
+#[test]
+fn read_reports_sizes_it_cannot_allocate() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let file = builder.file(Data::Plain(vec![1; 100]));
+    builder.mtime(file, 0, 0);
+    builder.dir(root, root, &[("file", file)]);
+    let mut img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let offset = fs.super_block().meta_blk_addr as usize * 4096 + file as usize * 32;
+    assert!(fs.metadata("/file").unwrap().inode().is_extended());
+
+    // `i_size` of extended inodes is 64 bits wide, at offset 8.
+    img[offset + 8..offset + 16].copy_from_slice(&(u64::MAX >> 2).to_le_bytes());
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    assert!(matches!(fs.read("/file"), Err(Error::OutOfMemory(_))));
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use common::{Data, ImageBuilder};
+use erofs_rs::backend::SliceImage;
+use erofs_rs::{EroFS, FsckCode};
+
+/// Offset of the compact inode `nid` in `img`.
+fn inode_offset(fs: &EroFS<SliceImage<'_>>, nid: u64) -> usize {
+    let sb = fs.super_block();
+    ((sb.meta_blk_addr as usize) << sb.blk_size_bits) + nid as usize * 32
+}
+
+#[test]
+fn fsck_sample_is_clean() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let report = fs.fsck().unwrap();
+    assert!(report.is_clean(), "{:?}", report.problems);
+}
+
+#[test]
+fn fsck_reports_every_problem() {
+    // `/b/..` points at `/a` instead of the root, and `/b/again` reaches
+    // `/a` a second time, which also throws off the link counts of all
+    // three directories.
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let a = builder.reserve();
+    let b = builder.reserve();
+    let file = builder.file(Data::Inline(b"data".to_vec()));
+    builder.dir(root, root, &[("a", a), ("b", b)]);
+    builder.dir(a, root, &[("file", file)]);
+    builder.dir(b, a, &[("again", a)]);
+    builder.feature_incompat(0x8000_0000);
+    let img = builder.build();
+
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let report = fs.fsck().unwrap();
+    let mut problems: Vec<_> = report
+        .problems
+        .iter()
+        .map(|p| (p.code, p.path.as_str(), p.nid))
+        .collect();
+    problems.sort_unstable();
+    assert_eq!(
+        problems,
+        [
+            (FsckCode::UnsupportedFeature, "/", None),
+            (FsckCode::ParentMismatch, "/b", Some(b)),
+            (FsckCode::DirectoryCycle, "/b/again", Some(a)),
+            (FsckCode::LinkCountMismatch, "/", Some(root)),
+            (FsckCode::LinkCountMismatch, "/a", Some(a)),
+            (FsckCode::LinkCountMismatch, "/b", Some(b)),
+        ]
+    );
+    assert!(!report.is_clean());
+}
+
+#[test]
+fn check_lists_data_and_link_count_problems() {
+    let mut img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    assert!(fs.check().unwrap().is_empty());
+    let motd = fs.metadata("/etc/motd").unwrap().nid();
+    let hostname = fs.symlink_metadata("/hostname").unwrap().nid();
+    let blocks = fs.super_block().blocks;
+    let (motd_offset, hostname_offset) = (inode_offset(&fs, motd), inode_offset(&fs, hostname));
+
+    // Move the blocks of motd past the end of the image, and give the
+    // symlink a second link nothing refers to.
+    img[motd_offset + 16..motd_offset + 20].copy_from_slice(&(blocks - 1).to_le_bytes());
+    img[hostname_offset + 6..hostname_offset + 8].copy_from_slice(&2u16.to_le_bytes());
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let problems: Vec<_> = fs
+        .check()
+        .unwrap()
+        .iter()
+        .map(|p| (p.code, p.path.clone(), p.nid))
+        .collect();
+    assert_eq!(
+        problems,
+        [
+            (
+                FsckCode::DataOutOfRange,
+                "/etc/motd".to_string(),
+                Some(motd)
+            ),
+            (
+                FsckCode::LinkCountMismatch,
+                "/hostname".to_string(),
+                Some(hostname)
+            ),
+        ]
+    );
+}
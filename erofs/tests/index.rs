@@ -0,0 +1,48 @@
+mod common;
+
+use erofs_rs::EroFS;
+use erofs_rs::backend::SliceImage;
+use erofs_rs::types::DirentFileType;
+
+#[test]
+fn index_answers_path_queries() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let index = fs.index().unwrap();
+
+    let mut paths: Vec<_> = index
+        .iter()
+        .map(|(path, _, _)| path.to_str().unwrap())
+        .collect();
+    paths.sort_unstable();
+    assert_eq!(
+        paths,
+        [
+            "/",
+            "/empty",
+            "/etc",
+            "/etc/hostname",
+            "/etc/motd",
+            "/hostname"
+        ]
+    );
+    assert_eq!(index.len(), 6);
+
+    assert_eq!(
+        index.lookup("/"),
+        Some(u64::from(fs.super_block().root_nid))
+    );
+    for entry in fs.read_dir("/etc").unwrap() {
+        let entry = entry.unwrap().dir_entry;
+        let path = entry.path();
+        assert_eq!(index.lookup(&path), Some(entry.nid()), "{path:?}");
+    }
+    assert_eq!(
+        index.file_type("/etc/./motd"),
+        Some(DirentFileType::RegularFile)
+    );
+    assert_eq!(index.file_type("/hostname"), Some(DirentFileType::Symlink));
+    assert!(index.exists("/empty"));
+    assert!(!index.exists("/etc/missing"));
+    assert_eq!(index.lookup("/etc/missing"), None);
+}
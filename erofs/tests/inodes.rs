@@ -0,0 +1,28 @@
+mod common;
+
+use common::{Data, ImageBuilder};
+use erofs_rs::EroFS;
+use erofs_rs::backend::SliceImage;
+
+#[test]
+fn inodes_in_nid_order_including_unreferenced_ones() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let etc = builder.reserve();
+    let orphan = builder.file(Data::Inline(b"nobody links here".to_vec()));
+    let motd = builder.file(Data::Plain(common::motd()));
+    builder.dir(root, root, &[("etc", etc)]);
+    builder.dir(etc, root, &[("motd", motd)]);
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let inodes: Vec<_> = fs.inodes().map(Result::unwrap).collect();
+    let nids: Vec<_> = inodes.iter().map(|(nid, _)| *nid).collect();
+    assert_eq!(nids, [root, etc, orphan, motd]);
+    for (nid, inode) in &inodes {
+        assert_eq!(inode.nid(), *nid);
+    }
+    assert!(inodes[1].1.is_dir());
+    assert_eq!(inodes[2].1.data_size(), 17);
+    assert_eq!(inodes[3].1.data_size(), common::motd().len());
+}
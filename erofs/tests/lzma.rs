@@ -0,0 +1,65 @@
+#![cfg(feature = "lzma")]
+
+mod common;
+
+use std::io::Write;
+
+use common::{Data, ImageBuilder, Pcluster, check_file, lzma_config, repeat, single_file};
+use erofs_rs::backend::SliceImage;
+use erofs_rs::types::{CompressionAlgo, FEATURE_INCOMPAT_ZERO_PADDING};
+use erofs_rs::{EroFS, Error};
+use lzma_rust2::{LzmaOptions, LzmaWriter};
+
+const DICT_SIZE: u32 = 1 << 16;
+
+/// Compresses `data` into a MicroLZMA stream as mkfs.erofs does: a raw
+/// LZMA1 stream without an end marker whose first byte holds the inverted
+/// properties byte.
+fn microlzma(data: &[u8]) -> Vec<u8> {
+    let options = LzmaOptions {
+        dict_size: DICT_SIZE,
+        ..LzmaOptions::with_preset(6)
+    };
+    let mut writer = LzmaWriter::new_no_header(Vec::new(), &options, false).unwrap();
+    writer.write_all(data).unwrap();
+    let props = writer.props();
+    let mut stream = writer.finish().unwrap();
+    stream[0] = !props;
+    stream
+}
+
+fn lzma_image(builder: &mut ImageBuilder) -> (Vec<u8>, Vec<u8>) {
+    builder.feature_incompat(FEATURE_INCOMPAT_ZERO_PADDING);
+    let bs = builder.block_size();
+    let a = repeat(b"lzma pcluster a ", bs * 2);
+    let b = repeat(b"and then b; ", bs + 100);
+    let file = Data::Compressed {
+        size: a.len() + b.len(),
+        algorithm: CompressionAlgo::Lzma,
+        pclusters: vec![
+            Pcluster::compressed(0, microlzma(&a)),
+            Pcluster::compressed(a.len(), microlzma(&b)),
+        ],
+        inline_tail: false,
+    };
+    (single_file(builder, file), [a, b].concat())
+}
+
+#[test]
+fn lzma_pclusters() {
+    let mut builder = ImageBuilder::new(12);
+    builder.compr_config(CompressionAlgo::Lzma, &lzma_config(DICT_SIZE));
+    let (img, expected) = lzma_image(&mut builder);
+    check_file(&img, &expected);
+}
+
+#[test]
+fn lzma_config_is_validated() {
+    let mut builder = ImageBuilder::new(12);
+    builder.compr_config(CompressionAlgo::Lzma, &lzma_config(16 << 20));
+    let (img, _) = lzma_image(&mut builder);
+    assert!(matches!(
+        EroFS::new(SliceImage::new(&img)),
+        Err(Error::CorruptedData(_))
+    ));
+}
@@ -0,0 +1,236 @@
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::time::{Duration, UNIX_EPOCH};
+
+use common::{ImageBuilder, S_IFBLK, S_IFCHR, S_IFIFO, S_IFSOCK};
+use erofs_rs::backend::SliceImage;
+use erofs_rs::types::DirentFileType;
+use erofs_rs::{EroFS, Error};
+
+#[test]
+fn metadata_follows_symlinks() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let target = fs.metadata("/etc/hostname").unwrap();
+    let link = fs.metadata("/hostname").unwrap();
+    assert!(link.is_file());
+    assert_eq!(link.nid(), target.nid());
+    assert_eq!(link.size(), 6);
+    assert_eq!(link.mode(), 0o100644);
+    assert_eq!(link.permissions().mode(), 0o100644);
+    assert_eq!((link.uid(), link.gid(), link.nlink()), (0, 0, 1));
+    assert_eq!(link.modified(), None);
+
+    let etc = fs.metadata("/etc").unwrap();
+    assert!(etc.is_dir());
+    assert_eq!(etc.file_type(), DirentFileType::Directory);
+    assert_eq!(etc.nlink(), 2);
+
+    assert!(matches!(
+        fs.metadata("/missing"),
+        Err(Error::NotFound { .. })
+    ));
+}
+
+#[test]
+fn symlink_metadata_describes_the_link() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let link = fs.symlink_metadata("/hostname").unwrap();
+    assert!(link.is_symlink());
+    assert_eq!(link.file_type(), DirentFileType::Symlink);
+    assert_eq!(link.size(), "etc/hostname".len());
+    assert_eq!(link.mode(), 0o120777);
+    assert_ne!(link.nid(), fs.metadata("/hostname").unwrap().nid());
+}
+
+#[test]
+fn special_files_and_device_numbers() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    // 8:1 and 259:65536, encoded like the kernel's new_encode_dev().
+    let sda1 = builder.special(S_IFBLK, 0x801);
+    let nvme = builder.special(S_IFCHR, (259 << 8) | (65536 << 12));
+    let fifo = builder.special(S_IFIFO, 0);
+    let socket = builder.special(S_IFSOCK, 0);
+    builder.dir(
+        root,
+        root,
+        &[
+            ("fifo", fifo),
+            ("nvme", nvme),
+            ("sda1", sda1),
+            ("socket", socket),
+        ],
+    );
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let sda1 = fs.metadata("/sda1").unwrap();
+    assert!(sda1.is_block_device() && !sda1.is_char_device());
+    assert_eq!(sda1.device_id(), Some((8, 1)));
+
+    let nvme = fs.metadata("/nvme").unwrap();
+    assert!(nvme.is_char_device());
+    assert_eq!(nvme.device_id(), Some((259, 65536)));
+
+    let fifo = fs.metadata("/fifo").unwrap();
+    assert!(fifo.is_fifo());
+    assert_eq!(fifo.device_id(), None);
+    assert!(fs.metadata("/socket").unwrap().is_socket());
+    assert_eq!(fs.metadata("/").unwrap().device_id(), None);
+}
+
+#[test]
+fn hard_links_share_nid_and_ino() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let bin = builder.reserve();
+    let busybox = builder.file(common::Data::Inline(b"#!busybox".to_vec()));
+    builder.dir(root, root, &[("bin", bin), ("sh", busybox)]);
+    builder.dir(bin, root, &[("busybox", busybox), ("ls", busybox)]);
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let sh = fs.metadata("/sh").unwrap();
+    let ls = fs.metadata("/bin/ls").unwrap();
+    assert_eq!(sh.nlink(), 3);
+    assert_eq!(sh.nid(), ls.nid());
+    assert_eq!(sh.inode().ino(), ls.inode().ino());
+    // The builder numbers inodes by slot, which is not the nid.
+    assert_eq!(sh.inode().ino(), 2);
+    assert_ne!(u64::from(sh.inode().ino()), sh.nid());
+}
+
+#[test]
+fn nlink_uses_the_full_width_of_compact_inodes() {
+    let mut img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let nid = fs.metadata("/etc/motd").unwrap().nid();
+    let offset = fs.super_block().meta_blk_addr as usize * 4096 + nid as usize * 32;
+    drop(fs);
+
+    // `i_nlink` follows the format, xattr count and mode fields.
+    img[offset + 6..offset + 8].copy_from_slice(&u16::MAX.to_le_bytes());
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let motd = fs.metadata("/etc/motd").unwrap();
+    assert!(!motd.inode().is_extended());
+    assert_eq!(motd.nlink(), 65535);
+    assert_eq!(motd.inode().nlink(), 65535);
+}
+
+#[test]
+fn path_predicates_follow_symlinks() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    assert!(fs.exists("/") && fs.is_dir("/") && !fs.is_file("/"));
+    assert!(fs.is_dir("/etc") && !fs.is_file("/etc"));
+    assert!(fs.is_file("/etc/motd") && !fs.is_dir("/etc/motd"));
+    assert!(fs.exists("/hostname") && fs.is_file("/hostname"));
+
+    assert!(!fs.exists("/missing"));
+    assert!(!fs.is_dir("/missing") && !fs.is_file("/missing"));
+    // A lookup through a regular file is an error, not a panic.
+    assert!(!fs.exists("/etc/motd/x"));
+}
+
+#[test]
+fn timestamps_of_extended_inodes_and_build_time() {
+    let mut builder = ImageBuilder::new(12);
+    builder.build_time(1_700_000_000, 5);
+    let root = builder.reserve();
+    let old = builder.file(common::Data::Inline(b"old".to_vec()));
+    let compact = builder.file(common::Data::Inline(b"compact".to_vec()));
+    builder.mtime(old, 1_000_000_000, 123_456_789);
+    builder.dir(root, root, &[("compact", compact), ("old", old)]);
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let build_time = UNIX_EPOCH + Duration::new(1_700_000_000, 5);
+    assert_eq!(fs.build_time(), Some(build_time));
+
+    let old = fs.metadata("/old").unwrap();
+    let mtime = UNIX_EPOCH + Duration::new(1_000_000_000, 123_456_789);
+    assert_eq!(old.modified(), Some(mtime));
+    assert_eq!(old.accessed(), Some(mtime));
+    assert_eq!(old.created(), Some(mtime));
+    assert_eq!(fs.read("/old").unwrap(), &b"old"[..]);
+
+    // Compact inodes have no time of their own.
+    let compact = fs.metadata("/compact").unwrap();
+    assert_eq!(compact.modified(), None);
+    assert_eq!(compact.accessed(), None);
+}
+
+#[test]
+fn mode_strings_like_ls() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let tmp = builder.reserve();
+    let setuid = builder.file(common::Data::Inline(Vec::new()));
+    let setgid = builder.file(common::Data::Inline(Vec::new()));
+    let link = builder.symlink("tmp");
+    let fifo = builder.special(S_IFIFO, 0);
+    builder.dir(
+        root,
+        root,
+        &[
+            ("fifo", fifo),
+            ("link", link),
+            ("setgid", setgid),
+            ("setuid", setuid),
+            ("tmp", tmp),
+        ],
+    );
+    builder.dir(tmp, root, &[]);
+    builder
+        .permissions(tmp, 0o1777)
+        .permissions(setuid, 0o4755)
+        .permissions(setgid, 0o2640);
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    for (path, mode) in [
+        ("/", "drwxr-xr-x"),
+        ("/fifo", "prw-r--r--"),
+        ("/link", "lrwxrwxrwx"),
+        ("/setgid", "-rw-r-S---"),
+        ("/setuid", "-rwsr-xr-x"),
+        ("/tmp", "drwxrwxrwt"),
+    ] {
+        let metadata = fs.symlink_metadata(path).unwrap();
+        assert_eq!(metadata.mode_string(), mode, "{path}");
+        assert_eq!(metadata.inode().mode_string(), mode, "{path}");
+        assert_eq!(metadata.file_type().to_string(), &mode[..1], "{path}");
+    }
+    assert_eq!(DirentFileType::Unknown.to_string(), "?");
+}
+
+#[test]
+fn compact_and_extended_inodes() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let compact = builder.file(common::Data::Inline(b"compact".to_vec()));
+    let extended = builder.file(common::Data::Inline(b"extended".to_vec()));
+    builder.dir(root, root, &[("compact", compact), ("extended", extended)]);
+    builder
+        .mtime(extended, 1_700_000_000, 0)
+        .owner(extended, 100_000, 100_001);
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let compact = *fs.metadata("/compact").unwrap().inode();
+    assert!(!compact.is_extended());
+    assert_eq!(compact.size(), 32);
+    assert_eq!(compact.data_size(), 7);
+
+    let extended = *fs.metadata("/extended").unwrap().inode();
+    assert!(extended.is_extended());
+    assert_eq!(extended.size(), 64);
+    assert_eq!(extended.data_size(), 8);
+    assert_eq!((extended.uid(), extended.gid()), (100_000, 100_001));
+}
@@ -0,0 +1,426 @@
+mod common;
+
+use common::{Data, ImageBuilder, S_IFBLK, S_IFCHR, S_IFIFO, S_IFSOCK};
+use erofs_rs::backend::SliceImage;
+use erofs_rs::{EroFS, Error};
+
+/// A 512-byte-block image whose root holds `count` files, enough to spread
+/// the directory over several blocks.
+fn big_dir(count: usize) -> (Vec<u8>, Vec<String>) {
+    let mut builder = ImageBuilder::new(9);
+    let root = builder.reserve();
+    let names: Vec<_> = (0..count).map(|i| format!("file-{i:03}")).collect();
+    let entries: Vec<_> = names
+        .iter()
+        .map(|name| (name.as_str(), builder.file(Data::Inline(Vec::new()))))
+        .collect();
+    builder.dir(root, root, &entries);
+    (builder.build(), names)
+}
+
+#[test]
+fn read_dir_from_resumes_at_cookie() {
+    let (img, names) = big_dir(100);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let root = fs.get_inode(fs.super_block().root_nid.into()).unwrap();
+    assert!(root.data_size() > 2 * 512);
+
+    // Pages of 7 entries, each listed from the cookie the previous one left.
+    let mut listed = Vec::new();
+    let mut cookie = 0;
+    loop {
+        let mut dir = fs.read_dir_from(root, cookie).unwrap();
+        let page: Vec<_> = dir
+            .by_ref()
+            .take(7)
+            .map(|entry| entry.unwrap().file_name().to_string())
+            .collect();
+        if page.is_empty() {
+            break;
+        }
+        listed.extend(page);
+        cookie = dir.cookie();
+    }
+    assert_eq!(listed, names);
+
+    // A cookie past the end yields nothing.
+    let end = root.data_size() as u64;
+    assert_eq!(fs.read_dir_from(root, end).unwrap().count(), 0);
+}
+
+#[test]
+fn read_dir_size_hint_bounds_the_entries_left() {
+    let (img, _) = big_dir(100);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let root = fs.get_inode(fs.super_block().root_nid.into()).unwrap();
+
+    let mut dir = fs.read_dir_from(root, 0).unwrap();
+    // The entries of the first block are certain.
+    assert!(dir.size_hint().0 > 0);
+    for left in (0..=100).rev() {
+        let (lower, upper) = dir.size_hint();
+        assert!(lower <= left, "{lower} > {left}");
+        assert!(upper.unwrap() >= left, "{upper:?} < {left}");
+        assert_eq!(dir.next().is_some(), left > 0);
+    }
+    assert_eq!(dir.size_hint(), (0, Some(0)));
+}
+
+#[test]
+fn read_dir_from_rejects_files() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let inode = fs.get_inode(file_nid(&fs, "/etc", "motd")).unwrap();
+    assert!(matches!(
+        fs.read_dir_from(inode, 0),
+        Err(Error::NotADirectory { .. })
+    ));
+}
+
+fn file_nid(fs: &EroFS<SliceImage<'_>>, dir: &str, name: &str) -> u64 {
+    fs.read_dir(dir)
+        .unwrap()
+        .map(Result::unwrap)
+        .find(|entry| entry.dir_entry.file_name() == name)
+        .unwrap()
+        .dir_entry
+        .nid()
+}
+
+#[test]
+fn lookup_finds_every_entry_of_a_multi_block_directory() {
+    let (img, names) = big_dir(100);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    for name in &names {
+        assert!(fs.metadata(format!("/{name}")).unwrap().is_file(), "{name}");
+    }
+    for missing in [
+        "/file-",
+        "/file-0000",
+        "/file-049a",
+        "/file-100",
+        "/zzz",
+        "/a",
+    ] {
+        assert!(
+            matches!(fs.metadata(missing), Err(Error::NotFound { .. })),
+            "{missing}"
+        );
+    }
+}
+
+#[test]
+fn lookup_names_sorted_around_dot_entries() {
+    // "+" and "-" sort before "." and "..", so they are not the first
+    // entries after them.
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let entries: Vec<_> = ["+plus", "-dash", ".hidden", "0", "~tilde"]
+        .into_iter()
+        .map(|name| (name, builder.file(Data::Inline(name.as_bytes().to_vec()))))
+        .collect();
+    builder.dir(root, root, &entries);
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    for (name, _) in &entries {
+        let path = format!("/{name}");
+        assert_eq!(fs.metadata(&path).unwrap().size(), name.len(), "{name}");
+    }
+    assert!(matches!(fs.metadata("/+"), Err(Error::NotFound { .. })));
+    assert!(matches!(fs.metadata("/-"), Err(Error::NotFound { .. })));
+}
+
+#[test]
+fn dir_entry_file_type_predicates() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let dir = builder.reserve();
+    let file = builder.file(Data::Inline(Vec::new()));
+    let link = builder.symlink("file");
+    let blk = builder.special(S_IFBLK, 0x801);
+    let chr = builder.special(S_IFCHR, 0x501);
+    let fifo = builder.special(S_IFIFO, 0);
+    let sock = builder.special(S_IFSOCK, 0);
+    builder.dir(
+        root,
+        root,
+        &[
+            ("blk", blk),
+            ("chr", chr),
+            ("dir", dir),
+            ("fifo", fifo),
+            ("file", file),
+            ("link", link),
+            ("sock", sock),
+        ],
+    );
+    builder.dir(dir, root, &[]);
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    for entry in fs.read_dir("/").unwrap() {
+        let entry = entry.unwrap().dir_entry;
+        let kinds = [
+            entry.is_block_device(),
+            entry.is_char_device(),
+            entry.is_dir(),
+            entry.is_fifo(),
+            entry.is_file(),
+            entry.is_symlink(),
+            entry.is_socket(),
+        ];
+        let expected = ["blk", "chr", "dir", "fifo", "file", "link", "sock"]
+            .map(|name| entry.file_name() == name);
+        assert_eq!(kinds, expected, "{}", entry.file_name());
+    }
+}
+
+#[test]
+fn read_dir_decodes_one_block_at_a_time() {
+    let (mut img, names) = big_dir(100);
+    // Corrupt the name offset of the first dirent in the block holding
+    // `file-080`, which is not the first block of the directory.
+    let name = img.windows(8).position(|w| w == b"file-080").unwrap();
+    let block = name / 512 * 512;
+    img[block + 8..block + 10].copy_from_slice(&u16::MAX.to_le_bytes());
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    // Every entry of the earlier blocks is still yielded before the error.
+    let mut listed = Vec::new();
+    let mut entries = fs.read_dir("/").unwrap();
+    let err = loop {
+        match entries.next().unwrap() {
+            Ok(entry) => listed.push(entry.dir_entry.file_name().to_string()),
+            Err(err) => break err,
+        }
+    };
+    assert!(matches!(err, Error::CorruptDirectory(_)), "{err}");
+    assert!(!listed.is_empty());
+    assert_eq!(listed, names[..listed.len()]);
+}
+
+#[test]
+fn read_dir_rejects_malformed_names() {
+    for bad in ["a/b", "a\0b", ""] {
+        let mut builder = ImageBuilder::new(12);
+        let root = builder.reserve();
+        let ok = builder.file(Data::Inline(Vec::new()));
+        let file = builder.file(Data::Inline(Vec::new()));
+        builder.dir(root, root, &[(bad, file), ("z", ok)]);
+        let img = builder.build();
+        let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+        let err = fs
+            .read_dir("/")
+            .unwrap()
+            .find_map(Result::err)
+            .unwrap_or_else(|| panic!("{bad:?} was accepted"));
+        assert!(matches!(err, Error::CorruptDirectory(_)), "{bad:?}: {err}");
+        assert!(
+            matches!(fs.metadata("/z"), Err(Error::CorruptDirectory(_))),
+            "{bad:?}"
+        );
+    }
+}
+
+#[test]
+fn dir_block_entries_returns_raw_dirents() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let file = builder.file(Data::Inline(Vec::new()));
+    let other = builder.file(Data::Inline(Vec::new()));
+    builder.dir(root, root, &[("a/b", file), ("z", other)]);
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let dir = fs.get_inode(root).unwrap();
+
+    // Names are not checked, so the block `read_dir` rejects still decodes.
+    let dirents = fs.dir_block_entries(&dir, 0).unwrap();
+    let summary: Vec<_> = dirents
+        .iter()
+        .map(|d| ({ d.nid }, { d.name_off }, d.file_type))
+        .collect();
+    assert_eq!(
+        summary,
+        [(root, 48, 2), (root, 49, 2), (file, 51, 1), (other, 54, 1)]
+    );
+
+    assert!(fs.dir_block_entries(&dir, 1).is_err());
+    let file = fs.get_inode(file).unwrap();
+    assert!(matches!(
+        fs.dir_block_entries(&file, 0),
+        Err(Error::NotADirectory { .. })
+    ));
+}
+
+#[test]
+fn names_that_are_not_utf8_are_kept_as_stored() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let dir = builder.reserve();
+    let file = builder.file(Data::Inline(b"latin-1\n".to_vec()));
+    builder.dir_bytes(root, root, &[(b"caf\xe9", dir)]);
+    builder.dir(dir, root, &[("menu", file)]);
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let entry = fs.read_dir("/").unwrap().next().unwrap().unwrap().dir_entry;
+    assert_eq!(entry.file_name(), "caf\u{fffd}");
+    assert_eq!(entry.file_name_bytes(), b"caf\xe9");
+    assert_eq!(entry.path().as_bytes(), b"/caf\xe9");
+
+    // The lossy name doesn't resolve, the stored bytes do, whichever type
+    // carries them.
+    assert!(matches!(
+        fs.metadata("/caf\u{fffd}"),
+        Err(Error::NotFound { .. })
+    ));
+    let raw = b"/caf\xe9/menu";
+    assert_eq!(fs.read(&raw[..]).unwrap(), b"latin-1\n"[..]);
+    assert_eq!(fs.read(raw.to_vec()).unwrap(), b"latin-1\n"[..]);
+    assert_eq!(fs.read(OsStr::from_bytes(raw)).unwrap(), b"latin-1\n"[..]);
+    let path = Path::new(OsStr::from_bytes(raw));
+    assert_eq!(fs.read(path).unwrap(), b"latin-1\n"[..]);
+    assert_eq!(fs.read(path.to_path_buf()).unwrap(), b"latin-1\n"[..]);
+
+    let walked: Vec<_> = fs
+        .walk_dir("/")
+        .unwrap()
+        .map(|entry| entry.unwrap().dir_entry.path().into_vec())
+        .collect();
+    assert_eq!(walked, [b"/caf\xe9".to_vec(), raw.to_vec()]);
+
+    let index = fs.index().unwrap();
+    assert_eq!(index.lookup(path), Some(file));
+    assert_eq!(index.lookup("/caf\u{fffd}/menu"), None);
+}
+
+#[test]
+fn root_dir_lists_the_root_inode() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let root = fs.root_inode().unwrap();
+    assert!(root.is_dir());
+    assert_eq!(root.id(), u64::from(fs.super_block().root_nid));
+
+    let names: Vec<_> = fs
+        .root_dir()
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string())
+        .collect();
+    assert_eq!(names, ["empty", "etc", "hostname"]);
+}
+
+#[test]
+fn read_subdirs_skips_other_entries() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    // `/hostname` is a symlink to a file, and isn't followed.
+    let paths: Vec<_> = fs
+        .read_subdirs("/")
+        .unwrap()
+        .map(|entry| entry.unwrap().path().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(paths, ["/empty", "/etc"]);
+    assert_eq!(fs.read_subdirs("/etc").unwrap().count(), 0);
+
+    assert!(matches!(
+        fs.read_subdirs("/hostname"),
+        Err(Error::NotADirectory { .. })
+    ));
+    assert!(matches!(
+        fs.read_subdirs("/missing"),
+        Err(Error::NotFound { .. })
+    ));
+}
+
+#[test]
+fn case_insensitive_lookups() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let docs = builder.reserve();
+    let readme = builder.file(Data::Inline(b"readme\n".to_vec()));
+    let upper = builder.file(Data::Inline(b"README\n".to_vec()));
+    let latin1 = builder.file(Data::Inline(b"latin-1\n".to_vec()));
+    let link = builder.symlink("DOCS/Readme");
+    builder.dir(root, root, &[("Docs", docs), ("link", link)]);
+    builder.dir_bytes(
+        docs,
+        root,
+        &[
+            (b"README", upper),
+            (b"Readme", readme),
+            (b"Stra\xdfe", latin1),
+        ],
+    );
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    // Exact lookups are still case-sensitive.
+    assert!(matches!(fs.open("/docs"), Err(Error::NotFound { .. })));
+    let meta = fs.metadata_ci("/docs").unwrap();
+    assert!(meta.is_dir());
+    assert_eq!(meta.nid(), docs);
+
+    // Names that aren't UTF-8 compare their ASCII letters.
+    let mut content = Vec::new();
+    let mut file = fs.open_ci(&b"/DOCS/STRA\xdfE"[..]).unwrap();
+    std::io::Read::read_to_end(&mut file, &mut content).unwrap();
+    assert_eq!(content, b"latin-1\n");
+
+    // The symlink target is resolved case-insensitively too, and is
+    // ambiguous like the name itself.
+    assert!(matches!(
+        fs.metadata_ci("/link"),
+        Err(Error::AmbiguousPath(_))
+    ));
+    assert!(matches!(
+        fs.open_ci("/docs/readme"),
+        Err(Error::AmbiguousPath(_))
+    ));
+    assert!(matches!(
+        fs.open_ci("/docs/missing"),
+        Err(Error::NotFound { .. })
+    ));
+}
+
+#[test]
+fn lookup_and_readdir_by_nid() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let root = fs.root_inode().unwrap().id();
+
+    let etc = fs.lookup(root, "etc").unwrap();
+    assert!(etc.is_dir());
+    let motd = fs.lookup(etc.id(), b"motd").unwrap();
+    assert_eq!(motd.id(), file_nid(&fs, "/etc", "motd"));
+    assert_eq!(fs.lookup(etc.id(), ".").unwrap().id(), etc.id());
+    assert_eq!(fs.lookup(etc.id(), "..").unwrap().id(), root);
+    // Symlinks are returned as they are.
+    assert!(fs.lookup(root, "hostname").unwrap().is_symlink());
+
+    let err = fs.lookup(etc.id(), "passwd").unwrap_err();
+    assert!(
+        matches!(&err, Error::NotFound { component, .. } if component == "passwd"),
+        "{err:?}"
+    );
+    assert!(matches!(
+        fs.lookup(motd.id(), "x"),
+        Err(Error::NotADirectory { .. })
+    ));
+
+    let names: Vec<_> = fs
+        .readdir_at(etc.id())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string())
+        .collect();
+    assert_eq!(names, ["hostname", "motd"]);
+    assert!(fs.readdir_at(motd.id()).is_err());
+}
@@ -0,0 +1,68 @@
+#![cfg(feature = "serde")]
+
+mod common;
+
+use common::{Data, ImageBuilder};
+use erofs_rs::EroFS;
+use erofs_rs::backend::SliceImage;
+use serde_json::json;
+
+#[test]
+fn metadata_and_dir_entries_serialize() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let file = builder.file(Data::Inline(b"hello".to_vec()));
+    builder.mtime(file, 1_700_000_000, 42);
+    builder.owner(file, 1000, 100);
+    builder.dir_bytes(root, root, &[(b"caf\xe9", file), (b"plain", file)]);
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let metadata = fs.metadata("/plain").unwrap();
+    assert_eq!(
+        serde_json::to_value(&metadata).unwrap(),
+        json!({
+            "file_type": "RegularFile",
+            "size": 5,
+            "mode": 0o100644,
+            "uid": 1000,
+            "gid": 100,
+            "nlink": 2,
+            "nid": file,
+            "device_id": null,
+            "mtime": 1_700_000_000,
+            "mtime_nsec": 42,
+        })
+    );
+    // Compact inodes carry no timestamp.
+    let root = fs.metadata("/").unwrap();
+    let root = serde_json::to_value(&root).unwrap();
+    assert_eq!(root["file_type"], "Directory");
+    assert_eq!(
+        (&root["mtime"], &root["mtime_nsec"]),
+        (&json!(null), &json!(null))
+    );
+
+    let entries: Vec<_> = fs
+        .read_dir("/")
+        .unwrap()
+        .map(|entry| serde_json::to_value(entry.unwrap().dir_entry).unwrap())
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            json!({
+                "path": "/caf\u{fffd}",
+                "file_name": "caf\u{fffd}",
+                "nid": file,
+                "file_type": "RegularFile",
+            }),
+            json!({
+                "path": "/plain",
+                "file_name": "plain",
+                "nid": file,
+                "file_type": "RegularFile",
+            }),
+        ]
+    );
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use std::io::Read;
+
+use common::ImageBuilder;
+use erofs_rs::backend::SliceImage;
+use erofs_rs::{EroFS, Error};
+
+fn links() -> Vec<u8> {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let etc = builder.reserve();
+    let hostname = builder.file(common::Data::Inline(b"erofs\n".to_vec()));
+    let dir_link = builder.symlink("etc");
+    let absolute = builder.symlink("/etc/hostname");
+    let up = builder.symlink("../etc/hostname");
+    let a = builder.symlink("b");
+    let b = builder.symlink("a");
+    let dangling = builder.symlink("missing");
+    builder.dir(
+        root,
+        root,
+        &[
+            ("a", a),
+            ("b", b),
+            ("dangling", dangling),
+            ("etc", etc),
+            ("link", dir_link),
+        ],
+    );
+    builder.dir(
+        etc,
+        root,
+        &[("absolute", absolute), ("hostname", hostname), ("up", up)],
+    );
+    builder.build()
+}
+
+fn read(fs: &EroFS<SliceImage<'_>>, path: &str) -> String {
+    let mut buf = String::new();
+    fs.open(path).unwrap().read_to_string(&mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn read_link_returns_target() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    assert_eq!(
+        fs.read_link("/hostname").unwrap().as_bytes(),
+        b"etc/hostname"
+    );
+    assert!(matches!(
+        fs.read_link("/etc/hostname"),
+        Err(Error::NotASymlink(_))
+    ));
+}
+
+#[test]
+fn open_follows_symlinks() {
+    let img = links();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    assert_eq!(read(&fs, "/link/hostname"), "erofs\n");
+    assert_eq!(read(&fs, "/etc/absolute"), "erofs\n");
+    assert_eq!(read(&fs, "/link/up"), "erofs\n");
+    assert!(matches!(fs.open("/a"), Err(Error::SymlinkLoop(_))));
+    assert!(matches!(fs.open("/dangling"), Err(Error::NotFound { .. })));
+}
+
+#[test]
+fn open_nofollow_does_not_follow_last_component() {
+    let img = links();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let err = fs.open_nofollow("/etc/absolute").unwrap_err();
+    assert!(matches!(err, Error::IsASymlink(_)));
+    assert!(err.to_string().contains("read_link"), "{err}");
+    // Following the link opens its target.
+    assert!(fs.open("/etc/absolute").is_ok());
+    assert_eq!(
+        fs.read_link("/link/up").unwrap().as_bytes(),
+        b"../etc/hostname"
+    );
+}
+
+#[test]
+fn open_reports_what_is_not_a_regular_file() {
+    let img = links();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    assert!(matches!(fs.open("/etc"), Err(Error::NotARegularFile(_))));
+    // A symlink to a directory is followed to the directory.
+    assert!(matches!(fs.open("/link"), Err(Error::NotARegularFile(_))));
+    assert!(matches!(
+        fs.open_nofollow("/link"),
+        Err(Error::IsASymlink(_))
+    ));
+    let link = *fs.symlink_metadata("/link").unwrap().inode();
+    assert!(matches!(
+        fs.open_inode_file(link),
+        Err(Error::IsASymlink(_))
+    ));
+}
+
+#[test]
+fn canonicalize_resolves_to_real_paths() {
+    let img = links();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    for (path, expected) in [
+        ("/", "/"),
+        ("/link/absolute", "/etc/hostname"),
+        ("link/up", "/etc/hostname"),
+        ("/etc/./../link", "/etc"),
+    ] {
+        assert_eq!(
+            fs.canonicalize(path).unwrap().as_bytes(),
+            expected.as_bytes(),
+            "{path}"
+        );
+    }
+
+    assert!(matches!(
+        fs.canonicalize("/etc/../.."),
+        Err(Error::PathEscapesRoot(_))
+    ));
+    assert!(matches!(
+        fs.canonicalize("/dangling"),
+        Err(Error::NotFound { .. })
+    ));
+    assert!(matches!(fs.canonicalize("/a"), Err(Error::SymlinkLoop(_))));
+    for path in ["/etc/hostname/x", "/link/hostname/.."] {
+        assert!(
+            matches!(fs.canonicalize(path), Err(Error::NotADirectory { .. })),
+            "{path}"
+        );
+    }
+}
+
+#[test]
+fn read_link_rejects_targets_longer_than_path_max() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let longest = builder.symlink(&"a".repeat(4096));
+    let too_long = builder.symlink(&"a".repeat(4097));
+    builder.dir(root, root, &[("longest", longest), ("too-long", too_long)]);
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    assert_eq!(fs.read_link("/longest").unwrap().as_bytes().len(), 4096);
+    assert!(matches!(
+        fs.read_link("/too-long"),
+        Err(Error::CorruptedData(msg)) if msg.contains("4097-byte target")
+    ));
+}
@@ -0,0 +1,357 @@
+mod common;
+
+use std::io::Read;
+
+use common::{Data, ImageBuilder, S_IFCHR};
+use erofs_rs::backend::SliceImage;
+use erofs_rs::{EroFS, Error, ManifestIssue, Result, WalkDir, WalkDirEntry, WalkError};
+
+/// An image holding:
+///
+/// ```text
+/// /
+/// ├── a/
+/// │   ├── x/
+/// │   │   └── deep
+/// │   └── y
+/// ├── b/
+/// │   └── z
+/// └── top
+/// ```
+fn tree() -> Vec<u8> {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let a = builder.reserve();
+    let x = builder.reserve();
+    let b = builder.reserve();
+    let mut file = |name: &str| builder.file(Data::Inline(name.as_bytes().to_vec()));
+    let (deep, y, z, top) = (file("deep"), file("y"), file("z"), file("top"));
+    builder.dir(root, root, &[("a", a), ("b", b), ("top", top)]);
+    builder.dir(a, root, &[("x", x), ("y", y)]);
+    builder.dir(x, a, &[("deep", deep)]);
+    builder.dir(b, root, &[("z", z)]);
+    builder.build()
+}
+
+fn paths<I: erofs_rs::backend::Image>(walk: WalkDir<'_, I>) -> Vec<String> {
+    walk.map(|entry: Result<WalkDirEntry>| {
+        entry
+            .unwrap()
+            .dir_entry
+            .path()
+            .to_string_lossy()
+            .into_owned()
+    })
+    .collect()
+}
+
+#[test]
+fn walk_dir_min_depth_still_descends() {
+    let img = tree();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    assert_eq!(
+        paths(fs.walk_dir("/").unwrap().min_depth(2)),
+        ["/a/x", "/a/x/deep", "/a/y", "/b/z"]
+    );
+    assert_eq!(
+        paths(fs.walk_dir("/").unwrap().min_depth(2).max_depth(2)),
+        ["/a/x", "/a/y", "/b/z"]
+    );
+}
+
+#[test]
+fn walk_dir_filter_entry_prunes_subtrees() {
+    let img = tree();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let walk = fs
+        .walk_dir("/")
+        .unwrap()
+        .filter_entry(|entry| entry.dir_entry.file_name() != "x");
+    assert_eq!(paths(walk), ["/a", "/a/y", "/b", "/b/z", "/top"]);
+
+    let walk = fs
+        .walk_dir("/")
+        .unwrap()
+        .filter_entry(|entry| entry.inode.is_dir());
+    assert_eq!(paths(walk), ["/a", "/a/x", "/b"]);
+}
+
+#[test]
+fn walk_dir_filter_entry_prunes_above_min_depth() {
+    let img = tree();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    // `/a` is never yielded, but rejecting it still skips its subtree.
+    let walk = fs
+        .walk_dir("/")
+        .unwrap()
+        .min_depth(2)
+        .filter_entry(|entry| entry.dir_entry.file_name() != "a");
+    assert_eq!(paths(walk), ["/b/z"]);
+}
+
+#[test]
+fn walk_dir_stops_at_directory_cycle() {
+    // / -> a -> b -> loop, where `loop` is a hardlink back to `a`.
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let a = builder.reserve();
+    let b = builder.reserve();
+    let file = builder.file(Data::Inline(b"hello".to_vec()));
+    builder.dir(root, root, &[("a", a)]);
+    builder.dir(a, root, &[("b", b)]);
+    builder.dir(b, a, &[("file", file), ("loop", a)]);
+
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let results: Vec<_> = fs.walk_dir("/").unwrap().take(16).collect();
+    assert!(results.len() < 16, "walk did not terminate");
+
+    let paths: Vec<_> = results
+        .iter()
+        .filter_map(|r| r.as_ref().ok())
+        .map(|entry| entry.dir_entry.path().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(paths, ["/a", "/a/b", "/a/b/file"]);
+
+    let cycles: Vec<_> = results
+        .iter()
+        .filter_map(|r| match r {
+            Err(Error::Walk(WalkError::Cycle { path, nid })) => Some((path.as_str(), *nid)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(cycles, [("/a/b/loop", a)]);
+}
+
+#[test]
+fn walk_dir_enters_a_directory_shared_by_siblings() {
+    // / -> a -> shared and / -> b -> shared: no directory leads back to
+    // one of its ancestors, so this is not a cycle.
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let (a, b, shared) = (builder.reserve(), builder.reserve(), builder.reserve());
+    let file = builder.file(Data::Inline(b"hello".to_vec()));
+    builder.dir(root, root, &[("a", a), ("b", b)]);
+    builder.dir(a, root, &[("shared", shared)]);
+    builder.dir(b, root, &[("shared", shared)]);
+    builder.dir(shared, a, &[("file", file)]);
+
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    assert_eq!(
+        paths(fs.walk_dir("/").unwrap()),
+        [
+            "/a",
+            "/a/shared",
+            "/a/shared/file",
+            "/b",
+            "/b/shared",
+            "/b/shared/file"
+        ]
+    );
+}
+
+#[test]
+fn walk_dir_sort_by_orders_each_directory() {
+    let img = tree();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let walk = fs
+        .walk_dir("/")
+        .unwrap()
+        .sort_by(|a, b| b.file_name().cmp(a.file_name()));
+    assert_eq!(
+        paths(walk),
+        ["/top", "/b", "/b/z", "/a", "/a/y", "/a/x", "/a/x/deep"]
+    );
+
+    // The image stores names sorted, so this matches the unsorted walk.
+    assert_eq!(
+        paths(fs.walk_dir("/").unwrap().sort_by_name()),
+        paths(fs.walk_dir("/").unwrap())
+    );
+
+    let walk = fs
+        .walk_dir("/")
+        .unwrap()
+        .sort_by(|a, b| b.file_name().cmp(a.file_name()))
+        .filter_entry(|entry| entry.dir_entry.file_name() != "a");
+    assert_eq!(paths(walk), ["/top", "/b", "/b/z"]);
+}
+
+#[test]
+fn walk_dir_files_opens_regular_files() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    // The symlink `/hostname` and the directories are skipped.
+    let files: Vec<_> = fs
+        .walk_dir("/")
+        .unwrap()
+        .files()
+        .map(|file| {
+            let (path, mut file) = file.unwrap();
+            let mut content = Vec::new();
+            file.read_to_end(&mut content).unwrap();
+            (path.to_string_lossy().into_owned(), content)
+        })
+        .collect();
+    assert_eq!(
+        files,
+        [
+            ("/etc/hostname".to_string(), b"erofs\n".to_vec()),
+            ("/etc/motd".to_string(), common::motd()),
+        ]
+    );
+
+    let img = tree();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let walk = fs.walk_dir("/").unwrap().max_depth(2);
+    let paths: Vec<_> = walk
+        .files()
+        .map(|file| file.unwrap().0.to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(paths, ["/a/y", "/b/z", "/top"]);
+}
+
+#[test]
+fn walk_dir_skip_whiteouts() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let removed = builder.special(S_IFCHR, 0);
+    let tty = builder.special(S_IFCHR, 0x401);
+    let file = builder.file(Data::Inline(b"kept\n".to_vec()));
+    builder.dir(
+        root,
+        root,
+        &[("file", file), ("removed", removed), ("tty1", tty)],
+    );
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    assert!(fs.metadata("/removed").unwrap().is_whiteout());
+    assert!(!fs.metadata("/tty1").unwrap().is_whiteout());
+    let whiteouts: Vec<_> = fs
+        .walk_dir("/")
+        .unwrap()
+        .map(|entry| {
+            let entry = entry.unwrap();
+            (entry.dir_entry.file_name().to_string(), entry.is_whiteout())
+        })
+        .collect();
+    assert_eq!(
+        whiteouts,
+        [
+            ("file".to_string(), false),
+            ("removed".to_string(), true),
+            ("tty1".to_string(), false),
+        ]
+    );
+
+    let names: Vec<_> = fs
+        .walk_dir("/")
+        .unwrap()
+        .skip_whiteouts(true)
+        .map(|entry| entry.unwrap().dir_entry.file_name().to_string())
+        .collect();
+    assert_eq!(names, ["file", "tty1"]);
+}
+
+#[test]
+fn walk_dir_resolve_symlinks() {
+    let img = common::sample();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let targets = |walk: WalkDir<'_, SliceImage<'_>>| -> Vec<_> {
+        walk.filter_map(|entry| {
+            let entry = entry.unwrap();
+            let target = entry.link_target?;
+            Some((entry.dir_entry.file_name().to_string(), target))
+        })
+        .collect()
+    };
+
+    assert!(targets(fs.walk_dir("/").unwrap()).is_empty());
+    let resolved = targets(fs.walk_dir("/").unwrap().resolve_symlinks(true));
+    assert_eq!(resolved.len(), 1);
+    assert_eq!(resolved[0].0, "hostname");
+    assert_eq!(resolved[0].1.as_bytes(), b"etc/hostname");
+
+    // The filter sees the targets.
+    let kept: Vec<_> = fs
+        .walk_dir("/")
+        .unwrap()
+        .resolve_symlinks(true)
+        .filter_entry(|entry| entry.link_target.is_none())
+        .map(|entry| entry.unwrap().dir_entry.file_name().to_string())
+        .collect();
+    assert_eq!(kept, ["empty", "etc", "hostname", "motd"]);
+}
+
+#[test]
+fn manifest_flags_unsafe_entries() {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let d = builder.reserve();
+    let file = builder.file(Data::Inline(b"hello".to_vec()));
+    let dev = builder.special(S_IFCHR, 0x0103);
+    builder.permissions(dev, 0o600);
+    let (abs, sib) = (builder.symlink("/etc"), builder.symlink("../f"));
+    // `up` leads back to the root, so `trick` only looks like it stays
+    // inside `/d` until the symlink is resolved.
+    let (trick, up) = (builder.symlink("up/../.."), builder.symlink(".."));
+    builder.dir(
+        root,
+        root,
+        &[("d", d), ("dev", dev), ("f", file), ("f", file)],
+    );
+    builder.dir(
+        d,
+        root,
+        &[
+            ("abs", abs),
+            ("again", d),
+            ("sib", sib),
+            ("trick", trick),
+            ("up", up),
+        ],
+    );
+    let img = builder.build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let manifest = fs.manifest().unwrap();
+    let entries: Vec<_> = manifest
+        .iter()
+        .map(|entry| {
+            (
+                entry.path.to_string_lossy().into_owned(),
+                entry.issues.clone(),
+            )
+        })
+        .collect();
+    let entry = |path: &str, issues: &[ManifestIssue]| (path.to_string(), issues.to_vec());
+    assert_eq!(
+        entries,
+        [
+            entry("/d", &[]),
+            entry("/d/abs", &[ManifestIssue::SymlinkEscapes]),
+            entry("/d/again", &[ManifestIssue::DirectoryCycle]),
+            entry("/d/sib", &[]),
+            entry("/d/trick", &[ManifestIssue::SymlinkEscapes]),
+            entry("/d/up", &[]),
+            entry("/dev", &[ManifestIssue::SpecialFile]),
+            entry("/f", &[]),
+            entry("/f", &[ManifestIssue::DuplicatePath]),
+        ]
+    );
+
+    let dev = &manifest[6];
+    assert_eq!(dev.permissions, 0o600);
+    assert!(!dev.is_safe());
+    let sib = &manifest[3];
+    assert_eq!(sib.link_target.as_ref().unwrap().as_bytes(), b"../f");
+    assert!(sib.is_safe());
+    assert_eq!(manifest[7].nid, file);
+}
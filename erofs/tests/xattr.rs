@@ -0,0 +1,142 @@
+mod common;
+
+use std::io::Read;
+
+use common::{Data, ImageBuilder, xattr_entry, xattr_region};
+use erofs_rs::backend::SliceImage;
+use erofs_rs::types::{XATTR_INDEX_SECURITY, XATTR_INDEX_TRUSTED, XATTR_INDEX_USER};
+use erofs_rs::{EroFS, Error};
+
+/// An image whose root holds `/tagged`, with two inline xattrs and a
+/// shared one, `/plain` without xattrs, and `/long` using a long name
+/// prefix. Returns the image and the nids of the three files.
+fn build() -> (Vec<u8>, [u64; 3]) {
+    let mut builder = ImageBuilder::new(12);
+    let root = builder.reserve();
+    let tagged = builder.file(Data::Inline(b"tagged".to_vec()));
+    let plain = builder.file(Data::Inline(b"plain".to_vec()));
+    let long = builder.file(Data::Inline(Vec::new()));
+    builder.dir(
+        root,
+        root,
+        &[("long", long), ("plain", plain), ("tagged", tagged)],
+    );
+
+    builder.shared_xattr(&xattr_entry(XATTR_INDEX_USER, "unused", b""));
+    let opaque = builder.shared_xattr(&xattr_entry(XATTR_INDEX_TRUSTED, "overlay.opaque", b"y"));
+    let region = xattr_region(
+        &[opaque],
+        &[
+            xattr_entry(XATTR_INDEX_USER, "comment", b"hello"),
+            xattr_entry(
+                XATTR_INDEX_SECURITY,
+                "selinux",
+                b"u:object_r:system_file:s0",
+            ),
+        ],
+    );
+    builder.inline_xattrs(tagged, &region);
+    builder.inline_xattrs(long, &xattr_region(&[], &[xattr_entry(0x80, "x", b"")]));
+    (builder.build(), [tagged, plain, long])
+}
+
+#[test]
+fn xattrs_inline_then_shared() {
+    let (img, [tagged, plain, _]) = build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let inode = fs.get_inode(tagged).unwrap();
+    let xattrs: Vec<_> = fs
+        .xattrs(&inode)
+        .unwrap()
+        .map(|(name, value)| (name, value.to_vec()))
+        .collect();
+    assert_eq!(
+        xattrs,
+        [
+            ("user.comment".to_string(), b"hello".to_vec()),
+            (
+                "security.selinux".to_string(),
+                b"u:object_r:system_file:s0".to_vec()
+            ),
+            ("trusted.overlay.opaque".to_string(), b"y".to_vec()),
+        ]
+    );
+    assert_eq!(
+        fs.get_xattr(&inode, "trusted.overlay.opaque")
+            .unwrap()
+            .as_deref(),
+        Some(&b"y"[..])
+    );
+    assert_eq!(fs.get_xattr(&inode, "user.missing").unwrap(), None);
+
+    // Tail-packed data still follows the xattr region.
+    let mut content = String::new();
+    fs.open("/tagged")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, "tagged");
+
+    let inode = fs.get_inode(plain).unwrap();
+    assert_eq!(fs.xattrs(&inode).unwrap().len(), 0);
+}
+
+#[test]
+fn xattrs_long_prefix_not_supported() {
+    let (img, [_, _, long]) = build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let inode = fs.get_inode(long).unwrap();
+    assert!(matches!(fs.xattrs(&inode), Err(Error::NotSupported(_))));
+}
+
+#[test]
+fn shared_xattr_pool_by_id() {
+    let (img, [tagged, plain, _]) = build();
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    // Entries no inode refers to are part of the pool too.
+    let pool: Vec<_> = fs
+        .shared_xattrs()
+        .map(|entry| {
+            let (id, (name, value)) = entry.unwrap();
+            (id, name, value.to_vec())
+        })
+        .collect();
+    assert_eq!(
+        pool,
+        [
+            (0, "user.unused".to_string(), Vec::new()),
+            (3, "trusted.overlay.opaque".to_string(), b"y".to_vec()),
+        ]
+    );
+
+    let inode = fs.get_inode(tagged).unwrap();
+    assert_eq!(fs.shared_xattr_ids(&inode).unwrap(), [3]);
+    let (name, value) = fs.shared_xattr(3).unwrap();
+    assert_eq!(
+        (name.as_str(), &value[..]),
+        ("trusted.overlay.opaque", &b"y"[..])
+    );
+    let inode = fs.get_inode(plain).unwrap();
+    assert!(fs.shared_xattr_ids(&inode).unwrap().is_empty());
+}
+
+#[test]
+fn shared_xattr_pool_skips_entries_it_cannot_decode() {
+    let mut builder = ImageBuilder::new(12);
+    builder.shared_xattr(&xattr_entry(0x80, "x", b""));
+    builder.shared_xattr(&xattr_entry(XATTR_INDEX_USER, "after", b"1"));
+    let img = common::empty_image(&mut builder);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+
+    let mut pool = fs.shared_xattrs();
+    assert!(matches!(pool.next(), Some(Err(Error::NotSupported(_)))));
+    let (id, (name, _)) = pool.next().unwrap().unwrap();
+    assert_eq!((id, name.as_str()), (2, "user.after"));
+    assert!(pool.next().is_none());
+
+    let img = common::empty_image(&mut ImageBuilder::new(12));
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    assert_eq!(fs.shared_xattrs().count(), 0);
+}
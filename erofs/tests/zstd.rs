@@ -0,0 +1,67 @@
+#![cfg(feature = "zstd")]
+
+mod common;
+
+use std::io::Read;
+
+use common::{Data, ImageBuilder, Pcluster, check_file, repeat, single_file, zstd_config};
+use erofs_rs::backend::SliceImage;
+use erofs_rs::types::{CompressionAlgo, FEATURE_INCOMPAT_ZERO_PADDING};
+use erofs_rs::{EroFS, Error};
+use ruzstd::encoding::{CompressionLevel, compress_to_vec};
+
+fn zstd_image(windowlog: u8) -> (Vec<u8>, Vec<u8>) {
+    let mut builder = ImageBuilder::new(12);
+    builder
+        .feature_incompat(FEATURE_INCOMPAT_ZERO_PADDING)
+        .compr_config(CompressionAlgo::Zstd, &zstd_config(windowlog));
+    let bs = builder.block_size();
+    let a = repeat(b"zstd pcluster a ", bs * 2);
+    let b = repeat(b"and then b; ", bs + 100);
+    let compress = |data: &[u8]| compress_to_vec(data, CompressionLevel::Fastest);
+    let file = Data::Compressed {
+        size: a.len() + b.len(),
+        algorithm: CompressionAlgo::Zstd,
+        pclusters: vec![
+            Pcluster::compressed(0, compress(&a)),
+            Pcluster::compressed(a.len(), compress(&b)),
+        ],
+        inline_tail: false,
+    };
+    (single_file(&mut builder, file), [a, b].concat())
+}
+
+#[test]
+fn zstd_pclusters() {
+    let (img, expected) = zstd_image(10);
+    check_file(&img, &expected);
+}
+
+#[test]
+fn zstd_window_larger_than_config_is_rejected() {
+    let (img, _) = zstd_image(0);
+    let fs = EroFS::new(SliceImage::new(&img)).unwrap();
+    let mut content = Vec::new();
+    let err = fs
+        .open("/file")
+        .unwrap()
+        .read_to_end(&mut content)
+        .unwrap_err();
+    assert!(err.to_string().contains("zstd window"), "{err}");
+}
+
+#[test]
+fn zstd_config_is_validated() {
+    // Windows of more than 1 MiB are never valid for EROFS, and some logs
+    // don't even give a window size that fits in 64 bits.
+    for windowlog in [11, 60, 255] {
+        let (img, _) = zstd_image(windowlog);
+        assert!(
+            matches!(
+                EroFS::new(SliceImage::new(&img)),
+                Err(Error::CorruptedData(_))
+            ),
+            "{windowlog}"
+        );
+    }
+}